@@ -0,0 +1,48 @@
+// Copyright © 2021
+// Author: Antonio Caggiano <info@antoniocaggiano.eu>
+// SPDX-License-Identifier: MIT
+
+//! Compares reading a vertex attribute all at once with reading one element at a time.
+//! Run with `cargo bench --features bench`.
+
+use std::hint::black_box;
+
+use rustspot::*;
+
+const COUNT: usize = 1 << 20;
+const RUNS: u32 = 16;
+
+fn read_per_element(data: &[u8], count: usize, stride: usize) -> Vec<[f32; 3]> {
+    let mut positions = Vec::with_capacity(count);
+    for i in 0..count {
+        let mut position = [0.0; 3];
+        for (j, value) in position.iter_mut().enumerate() {
+            let offset = i * stride + j * 4;
+            let mut bytes = [0; 4];
+            bytes.copy_from_slice(&data[offset..offset + 4]);
+            *value = f32::from_le_bytes(bytes);
+        }
+        positions.push(position);
+    }
+    positions
+}
+
+fn bench<F: Fn() -> Vec<[f32; 3]>>(name: &str, read: F) {
+    let mut timer = Timer::new();
+    for _ in 0..RUNS {
+        black_box(read());
+    }
+    let delta = timer.get_delta() / RUNS;
+    println!("{:>12}: {:?}", name, delta);
+}
+
+fn main() {
+    let data: Vec<u8> = (0..COUNT * 3)
+        .flat_map(|i| (i as f32).to_le_bytes().to_vec())
+        .collect();
+
+    bench("per element", || {
+        read_per_element(black_box(&data), COUNT, 12)
+    });
+    bench("bulk", || read_positions(black_box(&data), COUNT, 12));
+}
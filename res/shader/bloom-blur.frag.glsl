@@ -0,0 +1,28 @@
+precision mediump int;
+precision mediump float;
+precision mediump sampler2D;
+
+out vec4 out_color;
+
+in vec3 color;
+in vec2 tex_coords;
+
+uniform sampler2D tex_sampler;
+
+// Extent of the texture, used to step one texel at a time
+uniform vec2 extent;
+// Either (1, 0) for the horizontal pass or (0, 1) for the vertical pass
+uniform vec2 direction;
+
+// Gaussian weights of a 9 taps kernel
+const float weights[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+void main() {
+    vec2 step = direction / extent;
+    vec3 result = texture(tex_sampler, tex_coords).rgb * weights[0];
+    for (int i = 1; i < 5; ++i) {
+        result += texture(tex_sampler, tex_coords + step * float(i)).rgb * weights[i];
+        result += texture(tex_sampler, tex_coords - step * float(i)).rgb * weights[i];
+    }
+    out_color = vec4(result, 1.0);
+}
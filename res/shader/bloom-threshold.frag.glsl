@@ -0,0 +1,19 @@
+precision mediump int;
+precision mediump float;
+precision mediump sampler2D;
+
+out vec4 out_color;
+
+in vec3 color;
+in vec2 tex_coords;
+
+uniform sampler2D tex_sampler;
+
+// Pixels with a luminance below this are discarded
+uniform float threshold;
+
+void main() {
+    vec3 tex_color = texture(tex_sampler, tex_coords).rgb;
+    float luminance = dot(tex_color, vec3(0.2126, 0.7152, 0.0722));
+    out_color = vec4(luminance > threshold ? tex_color : vec3(0.0), 1.0);
+}
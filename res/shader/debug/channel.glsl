@@ -0,0 +1,10 @@
+// Default channel, a constant color for each object
+
+uniform int node_id;
+
+vec4 get_debug_color() {
+    // Golden ratio spreads hues of consecutive ids
+    float hue = fract(float(node_id) * 0.618034);
+    vec3 rgb = clamp(abs(mod(hue * 6.0 + vec3(0.0, 4.0, 2.0), 6.0) - 3.0) - 1.0, 0.0, 1.0);
+    return vec4(rgb, 1.0);
+}
@@ -0,0 +1,5 @@
+// World space normals, from [-1, 1] to [0, 1]
+
+vec4 get_debug_color() {
+    return vec4(normalize(normal) * 0.5 + 0.5, 1.0);
+}
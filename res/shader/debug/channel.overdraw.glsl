@@ -0,0 +1,5 @@
+// Each fragment adds a bit of color, expecting additive blending without depth test
+
+vec4 get_debug_color() {
+    return vec4(0.2, 0.1, 0.05, 1.0);
+}
@@ -0,0 +1,5 @@
+// Texture coordinates as red and green, repeating outside [0, 1]
+
+vec4 get_debug_color() {
+    return vec4(fract(tex_coords), 0.0, 1.0);
+}
@@ -0,0 +1,12 @@
+precision mediump float;
+
+in vec2 tex_coords;
+in vec3 normal;
+
+out vec4 out_color;
+
+#include "channel.glsl"
+
+void main() {
+    out_color = get_debug_color();
+}
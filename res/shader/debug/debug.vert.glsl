@@ -0,0 +1,17 @@
+layout (location = 0) in vec3 in_pos;
+layout (location = 2) in vec2 in_tex_coords;
+layout (location = 3) in vec3 in_normal;
+
+uniform mat4 model;
+uniform mat3 model_intr;
+uniform mat4 view;
+uniform mat4 proj;
+
+out vec2 tex_coords;
+out vec3 normal;
+
+void main() {
+    tex_coords = in_tex_coords;
+    normal = model_intr * in_normal;
+    gl_Position = proj * view * model * vec4(in_pos, 1.0);
+}
@@ -8,19 +8,28 @@ in mediump vec3 normal;
 in mediump vec4 pos_light_space;
 
 uniform sampler2D tex_sampler;
+// Material tint
+uniform vec4 tint;
 
-uniform vec3 light_color;
-uniform vec3 light_direction;
+// Up to 4 directional lights, the first one casting shadows
+layout (std140) uniform Lights {
+    highp mat4 light_space;
+    highp vec4 light_colors[4];
+    highp vec4 light_directions[4];
+    highp int directional_light_count;
+};
 
 void main() {
     float aw = 0.3;
     vec4 ambient = vec4(aw, aw, aw, 1.0);
-    vec4 albedo = texture(tex_sampler, tex_coords);
+    vec4 albedo = vec4(color, 1.0) * texture(tex_sampler, tex_coords) * tint;
     out_color = ambient * albedo;
 
     float dw = 1.0 - aw;
-    float df = max(dot(normalize(normal), normalize(light_direction)), 0.0);
-    vec4 diffuse = vec4(dw * df, dw * df, dw * df, 1.0);
+    for (int i = 0; i < directional_light_count; ++i) {
+        float df = max(dot(normalize(normal), normalize(light_directions[i].xyz)), 0.0);
+        vec4 diffuse = vec4(dw * df, dw * df, dw * df, 1.0);
 
-    out_color += diffuse * vec4(light_color, 1.0) * albedo;
+        out_color += diffuse * vec4(light_colors[i].rgb, 1.0) * albedo;
+    }
 }
@@ -2,6 +2,15 @@ precision mediump float;
 
 out mediump vec4 out_color;
 
+in mediump vec2 tex_coords;
+
+uniform sampler2D tex_sampler;
+// Fragments of cutout materials with a lower alpha do not cast shadows
+uniform float alpha_cutoff;
+
 void main() {
+    if (texture(tex_sampler, tex_coords).a < alpha_cutoff) {
+        discard;
+    }
     out_color = vec4(1.0, 1.0, 1.0, 1.0);
 }
@@ -1,9 +1,19 @@
 layout (location = 0) in vec3 in_pos;
+layout (location = 2) in vec2 in_tex_coords;
+
+#include "skin.glsl"
 
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 proj;
+
+out vec2 tex_coords;
+
+layout (std140) uniform Camera {
+    highp mat4 view;
+    highp mat4 proj;
+    highp vec3 cam_pos;
+};
 
 void main() {
-    gl_Position = proj * view * model * vec4(in_pos, 1.0);
+    tex_coords = in_tex_coords;
+    gl_Position = proj * view * model * get_skin_matrix() * vec4(in_pos, 1.0);
 }
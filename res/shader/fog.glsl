@@ -0,0 +1,12 @@
+// Linear fog, disabled when end is not greater than start
+uniform vec3 fog_color;
+uniform float fog_start;
+uniform float fog_end;
+
+vec3 apply_fog(vec3 color) {
+    if (fog_end <= fog_start) {
+        return color;
+    }
+    float fog = clamp((fog_end - view_depth) / (fog_end - fog_start), 0.0, 1.0);
+    return mix(fog_color, color, fog);
+}
@@ -6,12 +6,22 @@ in mediump vec3 color;
 in mediump vec2 tex_coords;
 in mediump vec3 normal;
 in mediump vec4 pos_light_space;
+in mediump float view_depth;
 
 uniform sampler2D tex_sampler;
+// Material tint
+uniform vec4 tint;
 uniform sampler2D shadow_sampler;
 
-uniform vec3 light_color;
-uniform vec3 light_direction;
+// Up to 4 directional lights, the first one casting shadows
+layout (std140) uniform Lights {
+    highp mat4 light_space;
+    highp vec4 light_colors[4];
+    highp vec4 light_directions[4];
+    highp int directional_light_count;
+};
+
+#include "fog.glsl"
 
 float calculate_shadow(vec4 pos_light_space) {
     // Perspective divide so pos is in range [-1, 1]
@@ -23,7 +33,7 @@ float calculate_shadow(vec4 pos_light_space) {
     float current_depth = pos.z;
 
     vec3 normal = normalize(normal);
-    vec3 light_dir = normalize(light_direction);
+    vec3 light_dir = normalize(light_directions[0].xyz);
     float bias = max(0.005 * (1.0 - dot(normal, light_dir)), 0.0005);
     // Greater depth means it is further away
     float shadow = current_depth - bias > closest_depth ? 0.5 : 1.0;
@@ -33,15 +43,18 @@ float calculate_shadow(vec4 pos_light_space) {
 
 void main() {
     float aw = 0.3;
-    vec4 ambient = vec4(color * aw, 1.0);
-    vec4 albedo = texture(tex_sampler, tex_coords);
+    vec4 ambient = vec4(vec3(aw), 1.0);
+    vec4 albedo = vec4(color, 1.0) * texture(tex_sampler, tex_coords) * tint;
     out_color = ambient * albedo;
 
     float dw = 1.0 - aw;
-    float df = max(dot(normalize(normal), normalize(light_direction)), 0.0);
-    vec4 diffuse = vec4(vec3(dw * df), 1.0);
+    for (int i = 0; i < directional_light_count; ++i) {
+        float df = max(dot(normalize(normal), normalize(light_directions[i].xyz)), 0.0);
+        vec4 diffuse = vec4(vec3(dw * df), 1.0);
 
-    float shadow = calculate_shadow(pos_light_space);
+        float shadow = i == 0 ? calculate_shadow(pos_light_space) : 1.0;
 
-    out_color += shadow * diffuse * vec4(light_color, 1.0) * albedo;
+        out_color += shadow * diffuse * vec4(light_colors[i].rgb, 1.0) * albedo;
+    }
+    out_color.rgb = apply_fog(out_color.rgb);
 }
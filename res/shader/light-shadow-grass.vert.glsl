@@ -7,17 +7,34 @@ uniform int instance_count;
 uniform mat4 model;
 // There is a limit of 256 uniforms per shader
 uniform mat4 models[128];
-uniform mat4 view;
+
+layout (std140) uniform Camera {
+    highp mat4 view;
+    highp mat4 proj;
+    highp vec3 cam_pos;
+};
+
 uniform mat3 billboard;
-uniform mat4 proj;
 uniform mat3 model_intr;
-uniform mat4 light_space;
+
+// Up to 4 directional lights, the first one casting shadows
+layout (std140) uniform Lights {
+    highp mat4 light_space;
+    highp vec4 light_colors[4];
+    highp vec4 light_directions[4];
+    highp int directional_light_count;
+};
+
 uniform float time;
+uniform float wind_strength;
+uniform float wind_frequency;
+uniform vec3 wind_direction;
 
 out vec3 color;
 out vec2 tex_coords;
 out vec3 normal;
 out vec4 pos_light_space;
+out float view_depth;
 
 // Simple noise
 vec2 rand2(highp vec2 p) {
@@ -85,14 +102,11 @@ void main() {
         instance_model[3][2]
     );
 
-    float wind_speed = 1.0 / 32.0;
-    float wind = worley(translation.xz / 32.0 + vec2(time) * wind_speed);
+    float wind = worley(translation.xz / 32.0 + vec2(time) * wind_frequency);
     color.rgb += wind * top_color_weight;
 
     // Rotation will affect only vertex at the top of the blade
-    vec3 wind_direction = normalize(vec3(1.0, 0.0, -1.0));
-    float wind_strength = 1.0;
-    mat3 rotation = rot_from_axis_angle(wind_direction, wind * wind_strength * in_pos.y);
+    mat3 rotation = rot_from_axis_angle(normalize(wind_direction), wind * wind_strength * in_pos.y);
 
     // Rotate to face the camera, like a billboard
     // Rotate first, then translate
@@ -105,6 +119,9 @@ void main() {
 
     pos_light_space = light_space * model_pos;
 
-    gl_Position = proj * view * model_pos;
+    vec4 view_pos = view * model_pos;
+    view_depth = -view_pos.z;
+
+    gl_Position = proj * view_pos;
 }
 
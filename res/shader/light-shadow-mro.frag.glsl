@@ -10,17 +10,31 @@ in mediump vec3 color;
 in mediump vec2 tex_coords;
 in mediump vec3 normal;
 in mediump vec4 pos_light_space;
+in mediump float view_depth;
 
 uniform sampler2D tex_sampler;
+// Material tint
+uniform vec4 tint;
 uniform sampler2D mr_sampler;
 
 uniform sampler2D shadow_sampler;
 
-uniform vec3 light_color;
-uniform vec3 light_direction;
+// Up to 4 directional lights, the first one casting shadows
+layout (std140) uniform Lights {
+    highp mat4 light_space;
+    highp vec4 light_colors[4];
+    highp vec4 light_directions[4];
+    highp int directional_light_count;
+};
 
 // Camera position in world space
-uniform vec3 cam_pos;
+layout (std140) uniform Camera {
+    highp mat4 view;
+    highp mat4 proj;
+    highp vec3 cam_pos;
+};
+
+#include "fog.glsl"
 
 #define PI 3.14159265358979
 
@@ -69,12 +83,13 @@ float calculate_shadow(vec4 pos_light_space, float NoL) {
 }
 
 void main() {
-    vec4 albedo = texture(tex_sampler, tex_coords);
+    vec4 albedo = texture(tex_sampler, tex_coords) * tint;
     // HDR?
     albedo.r = pow(albedo.r, 2.2);
     albedo.g = pow(albedo.g, 2.2);
     albedo.b = pow(albedo.b, 2.2);
-    vec3 c = albedo.rgb;
+    // Vertex colors are linear already
+    vec3 c = albedo.rgb * color;
 
     vec3 ambient = 0.03 * c;
 
@@ -93,43 +108,53 @@ void main() {
     // Light out towards viewer
     vec3 Lo = vec3(0.0);
 
-    // TODO for each light
-    vec3 L = normalize(light_direction);
-    vec3 H = normalize(V + L);
-    float HoV = clamp(dot(H, V), 0.0, 1.0);
-    float NoH = clamp(dot(N, H), 0.0, 1.0);
-    float NoL = clamp(dot(N, L), 0.0, 1.0);
-    float LoH = clamp(dot(L, H), 0.0, 1.0);
+    // Shadows are cast by the first light only
+    float shadow_NoL = 0.0;
+
+    for (int i = 0; i < directional_light_count; ++i) {
+        vec3 L = normalize(light_directions[i].xyz);
+        vec3 H = normalize(V + L);
+        float HoV = clamp(dot(H, V), 0.0, 1.0);
+        float NoH = clamp(dot(N, H), 0.0, 1.0);
+        float NoL = clamp(dot(N, L), 0.0, 1.0);
+        float LoH = clamp(dot(L, H), 0.0, 1.0);
 
-    // No attenuation for directional light
-    vec3 radiance = 8.0 * light_color;
+        // No attenuation for directional light
+        vec3 radiance = 8.0 * light_colors[i].rgb;
 
-    // Frenel-Schlick
-    vec3 f0 = vec3(0.16 * reflectance * reflectance * dielectric) + c * metallic;
-    vec3 F = fresnel_schlick(LoH, f0);
+        // Frenel-Schlick
+        vec3 f0 = vec3(0.16 * reflectance * reflectance * dielectric) + c * metallic;
+        vec3 F = fresnel_schlick(LoH, f0);
 
-    // Distribution of microfacets
-    float D = distribution_ggx(NoH, N, H, roughness);
+        // Distribution of microfacets
+        float D = distribution_ggx(NoH, N, H, roughness);
 
-    // Visibility of microfacets
-    float G = geometry_smith_ggx(NoV, NoL, roughness);
+        // Visibility of microfacets
+        float G = geometry_smith_ggx(NoV, NoL, roughness);
 
-    // Cook-torrance specular microfacet model
-    vec3 Fr = (D * G) * F;
+        // Cook-torrance specular microfacet model
+        vec3 Fr = (D * G) * F;
 
-    // Lambertian diffuse model
-    // Pure metallic materials have no subsurface scattering
-    vec3 Fd = (dielectric * c) / PI;
+        // Lambertian diffuse model
+        // Pure metallic materials have no subsurface scattering
+        vec3 Fd = (dielectric * c) / PI;
 
-    Lo += (Fd + Fr) * NoL * radiance;
-    // TODO end for each light
+        Lo += (Fd + Fr) * NoL * radiance;
+
+        if (i == 0) {
+            shadow_NoL = NoL;
+        }
+    }
 
     vec3 color = occlusion * ambient + Lo;
 
-    // Shadow factor for unique directional light
-    float shadow = calculate_shadow(pos_light_space, NoL);
+    // Shadow factor for the first directional light
+    float shadow = calculate_shadow(pos_light_space, shadow_NoL);
     color = shadow * color;
 
+    // Fog is part of the scene radiance, so it is tonemapped as well
+    color = apply_fog(color);
+
     // HDR? Gamma correction?
     color = color / (color + vec3(1.0));
     color = pow(color, vec3(1.0 / 2.2));
@@ -4,15 +4,28 @@ layout (location = 2) in vec2 in_tex_coords;
 layout (location = 3) in vec3 in_normal;
 
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 proj;
+
+layout (std140) uniform Camera {
+    highp mat4 view;
+    highp mat4 proj;
+    highp vec3 cam_pos;
+};
+
 uniform mat3 model_intr;
-uniform mat4 light_space;
+
+// Up to 4 directional lights, the first one casting shadows
+layout (std140) uniform Lights {
+    highp mat4 light_space;
+    highp vec4 light_colors[4];
+    highp vec4 light_directions[4];
+    highp int directional_light_count;
+};
 
 out vec3 color;
 out vec2 tex_coords;
 out vec3 normal;
 out vec4 pos_light_space;
+out float view_depth;
 
 void main() {
     color = in_color;
@@ -23,5 +36,8 @@ void main() {
 
     pos_light_space = light_space * model_pos;
 
-    gl_Position = proj * view * model_pos;
+    vec4 view_pos = view * model_pos;
+    view_depth = -view_pos.z;
+
+    gl_Position = proj * view_pos;
 }
@@ -8,18 +8,40 @@ in mediump vec3 color;
 in mediump vec2 tex_coords;
 in mediump vec3 normal;
 in mediump vec4 pos_light_space;
+in mediump float view_depth;
 
 uniform sampler2D tex_sampler;
+// Material tint
+uniform vec4 tint;
 uniform float metallic;
 uniform float roughness;
 
 uniform sampler2D shadow_sampler;
 
-uniform vec3 light_color;
-uniform vec3 light_direction;
+// Point light, disabled when its color is black
+uniform vec3 point_light_color;
+uniform vec3 point_light_position;
+// Near and far planes of the shadow cubemap, where far is also the light range
+uniform vec2 point_shadow_planes;
+uniform int point_shadow_enabled;
+uniform highp samplerCube point_shadow_sampler;
+
+// Up to 4 directional lights, the first one casting shadows
+layout (std140) uniform Lights {
+    highp mat4 light_space;
+    highp vec4 light_colors[4];
+    highp vec4 light_directions[4];
+    highp int directional_light_count;
+};
 
 // Camera position in world space
-uniform vec3 cam_pos;
+layout (std140) uniform Camera {
+    highp mat4 view;
+    highp mat4 proj;
+    highp vec3 cam_pos;
+};
+
+#include "fog.glsl"
 
 #define PI 3.14159265358979
 
@@ -61,7 +83,7 @@ float calculate_shadow(vec4 pos_light_space) {
     float current_depth = pos.z;
 
     vec3 normal = normalize(normal);
-    vec3 light_dir = normalize(light_direction);
+    vec3 light_dir = normalize(light_directions[0].xyz);
     float bias = max(0.005 * (1.0 - dot(normal, light_dir)), 0.0005);
     // Greater depth means it is further away
     float shadow = current_depth - bias > closest_depth ? 0.5 : 1.0;
@@ -69,36 +91,32 @@ float calculate_shadow(vec4 pos_light_space) {
     return shadow;
 }
 
-void main() {
-    vec4 albedo = texture(tex_sampler, tex_coords);
-    // HDR?
-    albedo.r = pow(albedo.r, 2.2);
-    albedo.g = pow(albedo.g, 2.2);
-    albedo.b = pow(albedo.b, 2.2);
-    vec3 c = albedo.rgb;
-    
-    vec3 ambient = 0.125 * c;
-
-    // TODO parameter?
-    float reflectance = 0.5;
-
-    vec3 N = normalize(normal);
-    vec3 V = normalize(cam_pos - world_pos);
-    float NoV = abs(dot(N, V)) + 1e-5;
-
-    // Light out towards viewer
-    vec3 Lo = vec3(0.0);
+// Returns 1.0 when the fragment is lit by the point light, 0.5 when in shadow
+float calculate_point_shadow(vec3 light_to_frag) {
+    if (point_shadow_enabled == 0) {
+        return 1.0;
+    }
+
+    // Depth this fragment would have in the face of the cubemap it falls into
+    vec3 dist = abs(light_to_frag);
+    float z = max(dist.x, max(dist.y, dist.z));
+    float near = point_shadow_planes.x;
+    float far = point_shadow_planes.y;
+    float ndc_depth = (far + near) / (far - near) - (2.0 * far * near) / ((far - near) * z);
+    float current_depth = ndc_depth * 0.5 + 0.5;
+
+    float closest_depth = texture(point_shadow_sampler, light_to_frag).r;
+    return current_depth - 0.0005 > closest_depth ? 0.5 : 1.0;
+}
 
-    // TODO for each light
-    vec3 L = normalize(light_direction);
+// Light reflected towards the viewer, given the direction towards the light
+vec3 shade(vec3 N, vec3 V, vec3 L, vec3 radiance, vec3 c, float reflectance) {
     vec3 H = normalize(V + L);
+    float NoV = abs(dot(N, V)) + 1e-5;
     float NoH = clamp(dot(N, H), 0.0, 1.0);
     float NoL = clamp(dot(N, L), 0.0, 1.0);
     float LoH = clamp(dot(L, H), 0.0, 1.0);
 
-    // No attenuation for directional light
-    vec3 radiance = 8.0 * light_color;
-
     // Frenel-shlick
     vec3 f0 = 0.16 * reflectance * reflectance * (1.0 - metallic) + c * metallic;
     vec3 F  = fresnel_schlick(LoH, f0);
@@ -116,8 +134,34 @@ void main() {
     // Pure metallic materials have no subsurface scattering
     vec3 Fd = ((1.0 - metallic) * c) / PI;
 
-    // Lighting!
-    Lo += (Fd + Fr) * radiance * NoL;
+    return (Fd + Fr) * radiance * NoL;
+}
+
+void main() {
+    vec4 albedo = texture(tex_sampler, tex_coords) * tint;
+    // HDR?
+    albedo.r = pow(albedo.r, 2.2);
+    albedo.g = pow(albedo.g, 2.2);
+    albedo.b = pow(albedo.b, 2.2);
+    // Vertex colors are linear already
+    vec3 c = albedo.rgb * color;
+    
+    vec3 ambient = 0.125 * c;
+
+    // TODO parameter?
+    float reflectance = 0.5;
+
+    vec3 N = normalize(normal);
+    vec3 V = normalize(cam_pos - world_pos);
+
+    // Light out towards viewer
+    vec3 Lo = vec3(0.0);
+
+    for (int i = 0; i < directional_light_count; ++i) {
+        // No attenuation for directional light
+        vec3 radiance = 8.0 * light_colors[i].rgb;
+        Lo += shade(N, V, normalize(light_directions[i].xyz), radiance, c, reflectance);
+    }
 
     vec3 color = ambient + Lo;
 
@@ -125,6 +169,20 @@ void main() {
     float shadow = calculate_shadow(pos_light_space);
     color = shadow * color;
 
+    if (point_light_color != vec3(0.0)) {
+        vec3 light_to_frag = world_pos - point_light_position;
+        float distance = length(light_to_frag);
+        // Fades out smoothly when reaching the range of the light
+        float attenuation = clamp(1.0 - distance / point_shadow_planes.y, 0.0, 1.0);
+        vec3 point_radiance = 8.0 * point_light_color * attenuation * attenuation;
+        vec3 L = -light_to_frag / distance;
+        color += shade(N, V, L, point_radiance, c, reflectance)
+            * calculate_point_shadow(light_to_frag);
+    }
+
+    // Fog is part of the scene radiance, so it is tonemapped as well
+    color = apply_fog(color);
+
     // HDR? Gamma correction?
     color = color / (color + vec3(1.0));
     color = pow(color, vec3(1.0/2.2));
@@ -0,0 +1,147 @@
+// Lit without shadows, with spot light variants
+
+precision mediump float;
+
+out mediump vec4 out_color;
+
+// Fragment position in world space
+in mediump vec3 world_pos;
+in mediump vec3 color;
+in mediump vec2 tex_coords;
+in mediump vec3 normal;
+in mediump float view_depth;
+
+uniform sampler2D tex_sampler;
+// Material tint
+uniform vec4 tint;
+uniform float metallic;
+uniform float roughness;
+
+// Up to 4 directional lights
+layout (std140) uniform Lights {
+    highp mat4 light_space;
+    highp vec4 light_colors[4];
+    highp vec4 light_directions[4];
+    highp int directional_light_count;
+};
+
+// Camera position in world space
+layout (std140) uniform Camera {
+    highp mat4 view;
+    highp mat4 proj;
+    highp vec3 cam_pos;
+};
+
+// Linear fog, disabled when end is not greater than start
+uniform vec3 fog_color;
+uniform float fog_start;
+uniform float fog_end;
+
+vec3 apply_fog(vec3 color) {
+    if (fog_end <= fog_start) {
+        return color;
+    }
+    float fog = clamp((fog_end - view_depth) / (fog_end - fog_start), 0.0, 1.0);
+    return mix(fog_color, color, fog);
+}
+
+#define PI 3.14159265358979
+
+#define MEDIUMP_FLT_MAX    65504.0
+#define saturate_mediump(x) min(x, MEDIUMP_FLT_MAX)
+
+// This models the distribution of the microfacet
+// Surfaces are not smooth at the micro level, but made of a
+// large number of randomly aligned planar surface fragments.
+// This implementation is good for half-precision floats.
+float distribution_ggx(float NoH, vec3 normal, vec3 half_vec, float roughness) {
+    vec3 NxH = cross(normal, half_vec);
+    float a = NoH * roughness;
+    float k = roughness / (dot(NxH, NxH) + a * a);
+    float d = k * k * (1.0 / PI);
+    return saturate_mediump(d);
+}
+
+// This models the visibility of the microfacets, or occlusion or shadow-masking
+float geometry_smith_ggx(float NoV, float NoL, float roughness) {
+    float a = roughness;
+    float GGXV = NoL * (NoV * (1.0 - a) + a);
+    float GGXL = NoV * (NoL * (1.0 - a) + a);
+    return 0.5 / (GGXV + GGXL);
+}
+
+vec3 fresnel_schlick(float cos_theta, vec3 f0) {
+    float f = pow(1.0 - cos_theta, 5.0);
+    return f + f0 * (1.0 - f);
+}
+
+// Light reflected towards the viewer, given the direction towards the light
+vec3 shade(vec3 N, vec3 V, vec3 L, vec3 radiance, vec3 c, float reflectance) {
+    vec3 H = normalize(V + L);
+    float NoV = abs(dot(N, V)) + 1e-5;
+    float NoH = clamp(dot(N, H), 0.0, 1.0);
+    float NoL = clamp(dot(N, L), 0.0, 1.0);
+    float LoH = clamp(dot(L, H), 0.0, 1.0);
+
+    // Frenel-shlick
+    vec3 f0 = 0.16 * reflectance * reflectance * (1.0 - metallic) + c * metallic;
+    vec3 F  = fresnel_schlick(LoH, f0);
+
+    // Distribution of microfacets
+    float D = distribution_ggx(NoH, N, H, roughness);
+
+    // Visibility of microfacets
+    float G = geometry_smith_ggx(NoV, NoL, roughness);
+
+    // Cook-torrance specular microfacet model
+    vec3 Fr = (D * G) * F;
+
+    // Lambertian diffuse model
+    // Pure metallic materials have no subsurface scattering
+    vec3 Fd = ((1.0 - metallic) * c) / PI;
+
+    return (Fd + Fr) * radiance * NoL;
+}
+
+#include "spot.glsl"
+
+void main() {
+    vec4 albedo = texture(tex_sampler, tex_coords) * tint;
+    // HDR?
+    albedo.r = pow(albedo.r, 2.2);
+    albedo.g = pow(albedo.g, 2.2);
+    albedo.b = pow(albedo.b, 2.2);
+    // Vertex colors are linear already
+    vec3 c = albedo.rgb * color;
+
+    vec3 ambient = 0.125 * c;
+
+    // TODO parameter?
+    float reflectance = 0.5;
+
+    vec3 N = normalize(normal);
+    vec3 V = normalize(cam_pos - world_pos);
+
+    // Light out towards viewer
+    vec3 Lo = vec3(0.0);
+
+    for (int i = 0; i < directional_light_count; ++i) {
+        // No attenuation for directional light
+        vec3 radiance = 8.0 * light_colors[i].rgb;
+        Lo += shade(N, V, normalize(light_directions[i].xyz), radiance, c, reflectance);
+    }
+
+    Lo += calculate_spot_lights(N, V, c, reflectance);
+
+    vec3 color = ambient + Lo;
+
+    // Fog is part of the scene radiance, so it is tonemapped as well
+    color = apply_fog(color);
+
+    // HDR? Gamma correction?
+    color = color / (color + vec3(1.0));
+    color = pow(color, vec3(1.0/2.2));
+
+    out_color.rgb = color;
+    out_color.a = albedo.a;
+}
@@ -0,0 +1,37 @@
+layout (location = 0) in vec3 in_pos;
+layout (location = 1) in vec3 in_color;
+layout (location = 2) in vec2 in_tex_coords;
+layout (location = 3) in vec3 in_normal;
+
+#include "skin.glsl"
+
+uniform mat4 model;
+
+layout (std140) uniform Camera {
+    highp mat4 view;
+    highp mat4 proj;
+    highp vec3 cam_pos;
+};
+
+uniform mat3 model_intr;
+
+out vec3 world_pos;
+out vec3 color;
+out vec2 tex_coords;
+out vec3 normal;
+out float view_depth;
+
+void main() {
+    color = in_color;
+    tex_coords = in_tex_coords;
+    mat4 skin = get_skin_matrix();
+    normal = model_intr * mat3(skin) * in_normal;
+
+    vec4 model_pos = model * skin * vec4(in_pos, 1.0);
+    world_pos = model_pos.xyz;
+
+    vec4 view_pos = view * model_pos;
+    view_depth = -view_pos.z;
+
+    gl_Position = proj * view_pos;
+}
@@ -0,0 +1,5 @@
+// Default skin, rigid vertices
+
+mat4 get_skin_matrix() {
+    return mat4(1.0);
+}
@@ -0,0 +1,17 @@
+layout (location = 6) in vec4 in_joints;
+layout (location = 7) in vec4 in_weights;
+
+// Up to 32 joint matrices, relative to the node of the mesh
+uniform mat4 joint_matrices[32];
+
+mat4 get_skin_matrix() {
+    // Vertices without weights are not skinned
+    if (in_weights == vec4(0.0)) {
+        return mat4(1.0);
+    }
+
+    return in_weights.x * joint_matrices[int(in_joints.x)]
+        + in_weights.y * joint_matrices[int(in_joints.y)]
+        + in_weights.z * joint_matrices[int(in_joints.z)]
+        + in_weights.w * joint_matrices[int(in_joints.w)];
+}
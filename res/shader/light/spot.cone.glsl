@@ -0,0 +1,30 @@
+// Up to 4 spot lights, each one lighting within a cone
+uniform vec3 spot_light_colors[4];
+uniform vec3 spot_light_positions[4];
+// Directions the spot lights are pointing to
+uniform vec3 spot_light_directions[4];
+// Cosines of the inner and outer angles of the cones, and range of the lights
+uniform vec3 spot_light_cones[4];
+uniform int spot_light_count;
+
+vec3 calculate_spot_lights(vec3 N, vec3 V, vec3 c, float reflectance) {
+    vec3 Lo = vec3(0.0);
+
+    for (int i = 0; i < spot_light_count; ++i) {
+        vec3 light_to_frag = world_pos - spot_light_positions[i];
+        float distance = length(light_to_frag);
+        vec3 L = -light_to_frag / distance;
+
+        // Full light within the inner cone, fading out towards the outer one
+        float cos_theta = dot(-L, normalize(spot_light_directions[i]));
+        float cone = smoothstep(spot_light_cones[i].y, spot_light_cones[i].x, cos_theta);
+
+        // Fades out smoothly when reaching the range of the light
+        float attenuation = clamp(1.0 - distance / spot_light_cones[i].z, 0.0, 1.0);
+        vec3 radiance = 8.0 * spot_light_colors[i] * cone * attenuation * attenuation;
+
+        Lo += shade(N, V, L, radiance, c, reflectance);
+    }
+
+    return Lo;
+}
@@ -0,0 +1,5 @@
+// Default spot, no spot lights
+
+vec3 calculate_spot_lights(vec3 N, vec3 V, vec3 c, float reflectance) {
+    return vec3(0.0);
+}
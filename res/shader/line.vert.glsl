@@ -0,0 +1,17 @@
+layout (location = 0) in vec3 in_pos;
+layout (location = 1) in vec3 in_color;
+
+uniform mat4 model;
+
+layout (std140) uniform Camera {
+    highp mat4 view;
+    highp mat4 proj;
+    highp vec3 cam_pos;
+};
+
+out vec3 color;
+
+void main() {
+    color = in_color;
+    gl_Position = proj * view * model * vec4(in_pos, 1.0);
+}
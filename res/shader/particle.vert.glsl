@@ -0,0 +1,31 @@
+layout (location = 0) in vec3 in_pos;
+layout (location = 1) in vec3 in_color;
+layout (location = 2) in vec2 in_tex_coords;
+layout (location = 3) in vec3 in_normal;
+
+uniform int instance_count;
+uniform mat4 model;
+// There is a limit of 256 uniforms per shader
+uniform mat4 models[128];
+
+layout (std140) uniform Camera {
+    highp mat4 view;
+    highp mat4 proj;
+    highp vec3 cam_pos;
+};
+
+uniform mat3 billboard;
+
+out vec3 color;
+out vec2 tex_coords;
+
+void main() {
+    color = in_color;
+    tex_coords = in_tex_coords;
+
+    // Rotate to face the camera, then move to the particle position
+    mat4 instance_model = models[gl_InstanceID];
+    mat4 model_tmp = instance_model * mat4(billboard) * model;
+
+    gl_Position = proj * view * model_tmp * vec4(in_pos, 1.0);
+}
@@ -2,3 +2,8 @@
 vec3 get_normal(vec3 tangent, vec3 binormal, vec3 normal, vec2 uv) {
     return normalize(normal);
 }
+
+// Texture coordinates are not displaced
+vec2 get_tex_coords(vec3 tangent, vec3 binormal, vec3 normal, vec2 uv, vec3 view_dir) {
+    return uv;
+}
@@ -0,0 +1,47 @@
+uniform sampler2D normal_sampler;
+// White is the highest point of the surface
+uniform sampler2D height_sampler;
+
+// Depth of the lowest point of the surface in texture space
+const float height_scale = 0.05;
+
+vec3 get_normal(vec3 tangent, vec3 binormal, vec3 normal, vec2 uv) {
+    vec3 sampled_normal = texture(normal_sampler, uv).rgb;
+    sampled_normal = sampled_normal * 2.0 - 1.0;
+    mat3 TBN = mat3(normalize(tangent), normalize(binormal), normalize(normal));
+    return normalize(TBN * sampled_normal);
+}
+
+// Parallax occlusion mapping: marches the view ray through layers of the height map
+// until it goes below the surface, then interpolates between the last two layers
+vec2 get_tex_coords(vec3 tangent, vec3 binormal, vec3 normal, vec2 uv, vec3 view_dir) {
+    mat3 TBN = mat3(normalize(tangent), normalize(binormal), normalize(normal));
+    vec3 V = normalize(transpose(TBN) * view_dir);
+
+    // More layers when looking at the surface at grazing angles
+    float layer_count = mix(32.0, 8.0, abs(V.z));
+    float layer_depth = 1.0 / layer_count;
+    vec2 delta = V.xy / max(V.z, 0.05) * height_scale / layer_count;
+
+    // Derivatives are computed once, as the loop is not uniform control flow
+    vec2 dx = dFdx(uv);
+    vec2 dy = dFdy(uv);
+
+    vec2 current_uv = uv;
+    float current_layer = 0.0;
+    float current_depth = 1.0 - textureGrad(height_sampler, current_uv, dx, dy).r;
+    for (int i = 0; i < 32; ++i) {
+        if (current_layer >= current_depth) {
+            break;
+        }
+        current_uv -= delta;
+        current_depth = 1.0 - textureGrad(height_sampler, current_uv, dx, dy).r;
+        current_layer += layer_depth;
+    }
+
+    vec2 prev_uv = current_uv + delta;
+    float after = current_depth - current_layer;
+    float before = 1.0 - textureGrad(height_sampler, prev_uv, dx, dy).r - current_layer + layer_depth;
+    float weight = after / (after - before);
+    return mix(current_uv, prev_uv, weight);
+}
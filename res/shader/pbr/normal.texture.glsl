@@ -6,3 +6,7 @@ vec3 get_normal(vec3 tangent, vec3 binormal, vec3 normal, vec2 uv) {
     mat3 TBN = mat3(normalize(tangent), normalize(binormal), normalize(normal));
     return normalize(TBN * sampled_normal);
 }
+
+vec2 get_tex_coords(vec3 tangent, vec3 binormal, vec3 normal, vec2 uv, vec3 view_dir) {
+    return uv;
+}
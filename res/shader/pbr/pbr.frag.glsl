@@ -12,8 +12,13 @@ in mediump vec3 normal;
 in mediump vec3 tangent;
 in mediump vec3 bitangent;
 in mediump vec4 pos_light_space;
+in mediump float view_depth;
 
 uniform sampler2D tex_sampler;
+// Material tint
+uniform vec4 tint;
+// Emitted light, not affected by shadows
+uniform vec3 emissive;
 
 #include "occlusion.glsl"
 #include "metallic-roughness.glsl"
@@ -21,11 +26,33 @@ uniform sampler2D tex_sampler;
 
 #include "shadow.glsl"
 
-uniform vec3 light_color;
-uniform vec3 light_direction;
+// Up to 4 directional lights, the first one casting shadows
+layout (std140) uniform Lights {
+    highp mat4 light_space;
+    highp vec4 light_colors[4];
+    highp vec4 light_directions[4];
+    highp int directional_light_count;
+};
 
 // Camera position in world space
-uniform vec3 cam_pos;
+layout (std140) uniform Camera {
+    highp mat4 view;
+    highp mat4 proj;
+    highp vec3 cam_pos;
+};
+
+// Linear fog, disabled when end is not greater than start
+uniform vec3 fog_color;
+uniform float fog_start;
+uniform float fog_end;
+
+vec3 apply_fog(vec3 color) {
+    if (fog_end <= fog_start) {
+        return color;
+    }
+    float fog = clamp((fog_end - view_depth) / (fog_end - fog_start), 0.0, 1.0);
+    return mix(fog_color, color, fog);
+}
 
 #define PI 3.14159265358979
 
@@ -58,65 +85,81 @@ vec3 fresnel_schlick(float cos_theta, vec3 f0) {
 }
 
 void main() {
-    vec4 albedo = texture(tex_sampler, tex_coords);
+    vec3 V = normalize(cam_pos - world_pos);
+
+    // Normal variants may displace texture coordinates
+    vec2 uv = get_tex_coords(tangent, bitangent, normal, tex_coords, V);
+
+    vec4 albedo = texture(tex_sampler, uv) * tint;
     // HDR?
     albedo.r = pow(albedo.r, 2.2);
     albedo.g = pow(albedo.g, 2.2);
     albedo.b = pow(albedo.b, 2.2);
-    vec3 c = albedo.rgb;
+    // Vertex colors are linear already
+    vec3 c = albedo.rgb * color;
 
     // TODO parameter?
     float reflectance = 0.5;
-    vec3 metallic_roughness_occlusion = get_metallic_roughness_occlusion(tex_coords);
+    vec3 metallic_roughness_occlusion = get_metallic_roughness_occlusion(uv);
     float occlusion = metallic_roughness_occlusion.r;
     float roughness = metallic_roughness_occlusion.g;
     float metallic = metallic_roughness_occlusion.b;
 
     vec3 ambient = 0.125 * occlusion * c;
 
-    vec3 N = get_normal(tangent, bitangent, normal, tex_coords);
+    vec3 N = get_normal(tangent, bitangent, normal, uv);
 
-    vec3 V = normalize(cam_pos - world_pos);
     float NoV = abs(dot(N, V)) + 1e-5;
 
     // Light out towards viewer
     vec3 Lo = vec3(0.0);
 
-    // TODO for each light
-    vec3 L = normalize(light_direction);
-    vec3 H = normalize(V + L);
-    float NoH = clamp(dot(N, H), 0.0, 1.0);
-    float NoL = clamp(dot(N, L), 0.0, 1.0);
-    float LoH = clamp(dot(L, H), 0.0, 1.0);
+    // Shadows are cast by the first light only
+    float shadow_NoL = 0.0;
+
+    for (int i = 0; i < directional_light_count; ++i) {
+        vec3 L = normalize(light_directions[i].xyz);
+        vec3 H = normalize(V + L);
+        float NoH = clamp(dot(N, H), 0.0, 1.0);
+        float NoL = clamp(dot(N, L), 0.0, 1.0);
+        float LoH = clamp(dot(L, H), 0.0, 1.0);
 
-    // No attenuation for directional light
-    vec3 radiance = 8.0 * light_color;
+        // No attenuation for directional light
+        vec3 radiance = 8.0 * light_colors[i].rgb;
 
-    // Frenel-Schlick
-    vec3 f0 = vec3(0.16 * reflectance * reflectance * (1.0 - metallic)) + c * metallic;
-    vec3 F = fresnel_schlick(NoH, f0);
+        // Frenel-Schlick
+        vec3 f0 = vec3(0.16 * reflectance * reflectance * (1.0 - metallic)) + c * metallic;
+        vec3 F = fresnel_schlick(NoH, f0);
 
-    // Distribution of microfacets
-    float D = distribution_ggx(NoH, N, H, roughness);
+        // Distribution of microfacets
+        float D = distribution_ggx(NoH, N, H, roughness);
 
-    // Visibility of microfacets
-    float G = geometry_smith_ggx(NoV, NoL, roughness);
+        // Visibility of microfacets
+        float G = geometry_smith_ggx(NoV, NoL, roughness);
 
-    // Cook-torrance specular microfacet model
-    vec3 Fr = (D * G) * F;
+        // Cook-torrance specular microfacet model
+        vec3 Fr = (D * G) * F;
 
-    // Lambertian diffuse model
-    // Pure metallic materials have no subsurface scattering
-    vec3 Fd = ((1.0 - metallic) * c) / PI;
+        // Lambertian diffuse model
+        // Pure metallic materials have no subsurface scattering
+        vec3 Fd = ((1.0 - metallic) * c) / PI;
 
-    Lo += (Fd + Fr) * radiance * NoL;
-    // TODO end for each light
+        Lo += (Fd + Fr) * radiance * NoL;
+
+        if (i == 0) {
+            shadow_NoL = NoL;
+        }
+    }
 
     vec3 color = ambient + Lo;
 
-    // Shadow factor for unique directional light
-    float shadow = calculate_shadow(pos_light_space, NoL);
+    // Shadow factor for the first directional light
+    float shadow = calculate_shadow(pos_light_space, shadow_NoL);
     color = shadow * color;
+    color += emissive;
+
+    // Fog is part of the scene radiance, so it is tonemapped as well
+    color = apply_fog(color);
 
     // HDR? Gamma correction?
     color = color / (color + vec3(1.0));
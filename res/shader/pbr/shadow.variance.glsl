@@ -0,0 +1,29 @@
+uniform highp sampler2D shadow_sampler;
+
+// Variance shadow map, where the shadow sampler stores the moments of depth
+float calculate_shadow(vec4 pos_light_space, float NoL) {
+    // Perspective divide so pos is in range [-1, 1]
+    vec3 pos = pos_light_space.xyz / pos_light_space.w;
+    // Now transform range to [0, 1] for shadow map
+    pos = pos * 0.5 + 0.5;
+    if (pos.z > 1.0) {
+        return 1.0;
+    }
+
+    highp vec2 moments = texture(shadow_sampler, pos.xy).rg;
+    float current_depth = pos.z;
+    if (current_depth <= moments.x) {
+        return 1.0;
+    }
+
+    // Chebyshev's inequality gives an upper bound of the probability of being lit
+    highp float variance = max(moments.y - moments.x * moments.x, 0.00002);
+    float d = current_depth - moments.x;
+    float p_max = variance / (variance + d * d);
+
+    // Cut the tail of the distribution to reduce light bleeding
+    p_max = clamp((p_max - 0.2) / 0.8, 0.0, 1.0);
+
+    // Same shade of the depth shadow when fully in shadow
+    return mix(0.5, 1.0, p_max);
+}
@@ -0,0 +1,53 @@
+precision mediump int;
+precision mediump float;
+precision mediump sampler2DMS;
+
+out vec4 out_color;
+
+in vec3 color;
+in vec2 tex_coords;
+
+uniform vec2 extent;
+uniform int tex_samples;
+uniform sampler2DMS tex_sampler;
+
+// 1 is Reinhard, 2 is ACES
+uniform int tonemap;
+uniform float exposure;
+
+vec4 texture_ms(sampler2DMS texture, vec2 coords)
+{
+    vec4 color = vec4(0.0);
+    ivec2 icoords = ivec2(coords * extent);
+
+    for (int i = 0; i < tex_samples; ++i) {
+        color += texelFetch(texture, icoords, i);
+    }
+    color /= float(tex_samples);
+
+    return color;
+}
+
+vec3 reinhard(vec3 color) {
+    return color / (color + vec3(1.0));
+}
+
+// Narkowicz ACES filmic curve fit
+vec3 aces(vec3 color) {
+    float a = 2.51;
+    float b = 0.03;
+    float c = 2.43;
+    float d = 0.59;
+    float e = 0.14;
+    return clamp((color * (a * color + b)) / (color * (c * color + d) + e), 0.0, 1.0);
+}
+
+void main() {
+    vec4 tex_color = texture_ms(tex_sampler, tex_coords);
+    vec3 hdr = exposure * tex_color.rgb;
+
+    vec3 mapped = tonemap == 2 ? aces(hdr) : reinhard(hdr);
+    mapped = pow(mapped, vec3(1.0 / 2.2));
+
+    out_color = vec4(color, 1.0) * vec4(mapped, tex_color.a);
+}
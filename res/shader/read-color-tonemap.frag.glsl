@@ -0,0 +1,38 @@
+precision mediump int;
+precision mediump float;
+precision mediump sampler2D;
+
+out vec4 out_color;
+
+in vec3 color;
+in vec2 tex_coords;
+
+uniform sampler2D tex_sampler;
+
+// 1 is Reinhard, 2 is ACES
+uniform int tonemap;
+uniform float exposure;
+
+vec3 reinhard(vec3 color) {
+    return color / (color + vec3(1.0));
+}
+
+// Narkowicz ACES filmic curve fit
+vec3 aces(vec3 color) {
+    float a = 2.51;
+    float b = 0.03;
+    float c = 2.43;
+    float d = 0.59;
+    float e = 0.14;
+    return clamp((color * (a * color + b)) / (color * (c * color + d) + e), 0.0, 1.0);
+}
+
+void main() {
+    vec4 tex_color = texture(tex_sampler, tex_coords);
+    vec3 hdr = exposure * tex_color.rgb;
+
+    vec3 mapped = tonemap == 2 ? aces(hdr) : reinhard(hdr);
+    mapped = pow(mapped, vec3(1.0 / 2.2));
+
+    out_color = vec4(color, 1.0) * vec4(mapped, tex_color.a);
+}
@@ -7,9 +7,12 @@ out vec4 out_color;
 in vec3 color;
 in vec2 tex_coords;
 
+// Depth values mapped from black to white
+uniform vec2 depth_range;
 uniform sampler2D tex_sampler;
 
 void main() {
     vec4 depth = texture(tex_sampler, tex_coords);
-    out_color = vec4(color, 1.0) * vec4(depth.x, depth.x, depth.x, 1.0);
+    float value = clamp((depth.x - depth_range.x) / (depth_range.y - depth_range.x), 0.0, 1.0);
+    out_color = vec4(color, 1.0) * vec4(value, value, value, 1.0);
 }
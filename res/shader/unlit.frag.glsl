@@ -6,7 +6,9 @@ in mediump vec3 color;
 in mediump vec2 tex_coords;
 
 uniform sampler2D tex_sampler;
+// Material tint
+uniform vec4 tint;
 
 void main() {
-    out_color = vec4(color, 1.0) * texture(tex_sampler, tex_coords);
+    out_color = vec4(color, 1.0) * texture(tex_sampler, tex_coords) * tint;
 }
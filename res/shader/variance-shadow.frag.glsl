@@ -0,0 +1,23 @@
+precision highp float;
+
+out highp vec4 out_color;
+
+in mediump vec2 tex_coords;
+
+uniform sampler2D tex_sampler;
+// Fragments of cutout materials with a lower alpha do not cast shadows
+uniform float alpha_cutoff;
+
+// Writes the first two moments of depth, sampled by variance shadow maps
+void main() {
+    if (texture(tex_sampler, tex_coords).a < alpha_cutoff) {
+        discard;
+    }
+
+    float depth = gl_FragCoord.z;
+    // Accounts for the depth varying across the texel
+    float dx = dFdx(depth);
+    float dy = dFdy(depth);
+    float moment2 = depth * depth + 0.25 * (dx * dx + dy * dy);
+    out_color = vec4(depth, moment2, 0.0, 1.0);
+}
@@ -9,7 +9,7 @@ use super::*;
 
 fn get_uniforms(code: &str) -> Vec<String> {
     let mut uniform_strings = vec![];
-    let unit = glsl::syntax::ShaderStage::parse(&code).unwrap();
+    let unit = glsl::syntax::ShaderStage::parse(code).unwrap();
 
     for dec in &unit {
         match dec {
@@ -29,6 +29,28 @@ fn get_uniforms(code: &str) -> Vec<String> {
     uniform_strings
 }
 
+/// Returns the names of the uniform blocks declared by a shader
+fn get_uniform_blocks(code: &str) -> Vec<String> {
+    let mut block_strings = vec![];
+    let unit = glsl::syntax::ShaderStage::parse(code).unwrap();
+
+    for dec in &unit {
+        if let ExternalDeclaration::Declaration(Declaration::Block(block)) = dec {
+            block_strings.push(String::from(block.name.as_str()));
+        }
+    }
+
+    block_strings
+}
+
+/// Returns a set with the name of the uniform blocks of both vertex and fragment shader
+fn get_all_uniform_blocks(code: &ShaderCode) -> HashSet<String> {
+    let mut blocks: HashSet<String> = HashSet::new();
+    blocks.extend(get_uniform_blocks(&code.vert));
+    blocks.extend(get_uniform_blocks(&code.frag));
+    blocks
+}
+
 /// Returns a set with the name of the uniforms of both vertex and fragment shader
 fn get_all_uniforms(code: &ShaderCode) -> HashSet<String> {
     let mut uniforms: HashSet<String> = HashSet::new();
@@ -156,7 +178,7 @@ fn generate_parent(info: &ShaderInfo) -> Result<String, Box<dyn Error>> {
 
     generated_code.push_str(&format!(
         "\npub const {}_VARIANTS: ",
-        info.prefix.to_uppercase()
+        info.prefix.to_uppercase().replace('-', "_")
     ));
 
     // Define multidimensional array
@@ -164,7 +186,8 @@ fn generate_parent(info: &ShaderInfo) -> Result<String, Box<dyn Error>> {
         generated_code.push_str("[");
     }
     generated_code.push_str("Shaders");
-    for include in &info.includes {
+    // The first include is the outermost dimension, which comes last in the type
+    for include in info.includes.iter().rev() {
         generated_code.push_str(&format!(";{}]", include.variants.len()));
     }
     generated_code.push_str(" = ");
@@ -204,6 +227,7 @@ fn generate_variant(info: &ShaderInfo, variant: &VariantInfo) -> Result<String,
 
     // These are useful to create the location structure code
     let uniform_strings = get_all_uniforms(&variant.code);
+    let block_strings = get_all_uniform_blocks(&variant.code);
 
     generated_code.push_str(&format!("\npub struct {}Loc {{\n", variant.camelcase));
 
@@ -248,22 +272,67 @@ impl {}Shader {{
             .expect("Failed to create shader from {1}");
         let fs = Shader::new(gl::FRAGMENT_SHADER, frag_src)
             .expect("Failed to create shader from {2}");
-        let program = ShaderProgram::new(vs, fs);
+        let program = ShaderProgram::new(vs, fs)
+            .expect("Failed to link program from {1} and {2}");
         let loc = {0}Loc::new(&program);
-        Self {{
-            program, loc
-        }}
-    }}
 "#,
         variant.camelcase,
         vs_path_string,
         fs_path_string
     ));
 
+    // Shared data comes from uniform buffers bound by the renderer
+    if block_strings.contains("Camera") {
+        generated_code
+            .push_str("        program.bind_uniform_block(\"Camera\", CAMERA_BLOCK_BINDING);\n");
+    }
+    if block_strings.contains("Lights") {
+        generated_code
+            .push_str("        program.bind_uniform_block(\"Lights\", LIGHTS_BLOCK_BINDING);\n");
+    }
+
+    // Associate texture units and samplers once, as programs remember them
+    let samplers = [
+        ("tex_sampler", 0),
+        ("normal_sampler", 2),
+        ("occlusion_sampler", 3),
+        ("mr_sampler", 4),
+        ("height_sampler", 6),
+    ];
+    if samplers
+        .iter()
+        .any(|(sampler, _)| uniform_strings.contains(*sampler))
+    {
+        generated_code.push_str("        program.enable();\n");
+    }
+    for (sampler, unit) in samplers.iter() {
+        if uniform_strings.contains(*sampler) {
+            generated_code.push_str(&std::format!(
+                "        unsafe {{ gl::Uniform1i(loc.{}, {}) }};\n",
+                sampler,
+                unit
+            ));
+        }
+    }
+
+    generated_code.push_str(
+        r#"        Self {
+            program, loc
+        }
+    }
+"#,
+    );
+
     generated_code.push_str(&std::format!(
         r#"}}
 
-impl CustomShader for {}Shader {{
+impl Default for {0}Shader {{
+    fn default() -> Self {{
+        Self::new()
+    }}
+}}
+
+impl CustomShader for {0}Shader {{
     fn as_any(&self) -> &dyn Any {{
         self
     }}
@@ -274,19 +343,10 @@ impl CustomShader for {}Shader {{
         variant.camelcase
     ));
 
-    // Associate texture units and samplers
-    if uniform_strings.contains("tex_sampler") {
-        generated_code.push_str("        unsafe { gl::Uniform1i(self.loc.tex_sampler, 0) };\n");
-    }
-    if uniform_strings.contains("normal_sampler") {
-        generated_code.push_str("        unsafe { gl::Uniform1i(self.loc.normal_sampler, 2) };\n");
-    }
-    if uniform_strings.contains("occlusion_sampler") {
+    // Draws without a material, such as sprites, are not tinted
+    if uniform_strings.contains("tint") {
         generated_code
-            .push_str("        unsafe { gl::Uniform1i(self.loc.occlusion_sampler, 3) };\n");
-    }
-    if uniform_strings.contains("mr_sampler") {
-        generated_code.push_str("        unsafe { gl::Uniform1i(self.loc.mr_sampler, 4) };\n");
+            .push_str("        unsafe { gl::Uniform4f(self.loc.tint, 1.0, 1.0, 1.0, 1.0) };\n");
     }
 
     generated_code.push_str("    }\n");
@@ -303,6 +363,20 @@ impl CustomShader for {}Shader {{
         );
     }
 
+    if uniform_strings.contains("wind_strength") {
+        generated_code.push_str(
+            r#"
+    fn bind_wind(&self, wind: &Wind) {
+        unsafe {
+            gl::Uniform1f(self.loc.wind_strength, wind.strength);
+            gl::Uniform1f(self.loc.wind_frequency, wind.frequency);
+            gl::Uniform3fv(self.loc.wind_direction, 1, wind.direction.as_ptr());
+        }
+    }
+"#,
+        );
+    }
+
     if uniform_strings.contains("extent") {
         generated_code.push_str(
             r#"
@@ -319,21 +393,19 @@ impl CustomShader for {}Shader {{
         );
     }
 
-    if uniform_strings.contains("light_color") {
+    if uniform_strings.contains("light_colors") {
         generated_code.push_str(
             r#"
-    fn bind_sun(&self, light_color: &[f32; 3], light_node: &Node, light_space: &na::Matrix4<f32>) {
-        // Light direction should point towards light source thus we negate it
-        let direction = -light_node.trs.get_forward();
-
+    fn bind_sun(&self, lights: &LightsBlock) {
         unsafe {
-            gl::Uniform3fv(self.loc.light_color, 1, light_color as _);
-            gl::Uniform3fv(self.loc.light_direction, 1, direction.as_ptr() as _);
+            gl::Uniform4fv(self.loc.light_colors, MAX_DIRECTIONAL_LIGHTS as _, lights.light_colors.as_ptr() as _);
+            gl::Uniform4fv(self.loc.light_directions, MAX_DIRECTIONAL_LIGHTS as _, lights.light_directions.as_ptr() as _);
+            gl::Uniform1i(self.loc.directional_light_count, lights.directional_light_count);
             gl::UniformMatrix4fv(
                 self.loc.light_space,
                 1,
                 gl::FALSE,
-                light_space.as_ptr(),
+                lights.light_space.as_ptr(),
             );
         }
     }
@@ -356,16 +428,119 @@ impl CustomShader for {}Shader {{
         );
     }
 
-    if uniform_strings.contains("view") {
+    if uniform_strings.contains("point_shadow_sampler") {
+        generated_code.push_str(
+            r#"
+    fn bind_point_light(&self, light: Option<(&PointLight, &Node)>, shadow_map: u32) {
+        // Black color disables the point light in the shader
+        let (color, position, range) = if let Some((light, node)) = light {
+            (light.color, node.trs.get_translation(), light.range)
+        } else {
+            ([0.0; 3], na::Vector3::zeros(), 1.0)
+        };
+
+        unsafe {
+            gl::Uniform3fv(self.loc.point_light_color, 1, color.as_ptr());
+            gl::Uniform3fv(self.loc.point_light_position, 1, position.as_ptr());
+            gl::Uniform2f(self.loc.point_shadow_planes, POINT_SHADOW_NEAR, range);
+            gl::Uniform1i(self.loc.point_shadow_enabled, (shadow_map != 0) as i32);
+            gl::Uniform1i(self.loc.point_shadow_sampler, 5);
+            gl::ActiveTexture(gl::TEXTURE0 + 5);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, shadow_map);
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+    }
+"#,
+        );
+    }
+
+    if uniform_strings.contains("spot_light_count") {
         generated_code.push_str(
             r#"
-    fn bind_camera(&self, camera: &Camera, node: &Node) {
-        let view = node.trs.get_view();
+    fn bind_spot_lights(&self, lights: &[(&SpotLight, &Node)]) {
+        let count = lights.len().min(MAX_SPOT_LIGHTS);
+        let mut colors = [[0.0; 3]; MAX_SPOT_LIGHTS];
+        let mut positions = [[0.0; 3]; MAX_SPOT_LIGHTS];
+        let mut directions = [[0.0; 3]; MAX_SPOT_LIGHTS];
+        let mut cones = [[0.0; 3]; MAX_SPOT_LIGHTS];
+        for (i, (light, node)) in lights.iter().take(count).enumerate() {
+            let [inner, outer] = light.get_cone_cos();
+            colors[i] = light.color;
+            positions[i].copy_from_slice(node.trs.get_translation().as_slice());
+            directions[i].copy_from_slice(node.trs.get_forward().as_slice());
+            cones[i] = [inner, outer, light.range];
+        }
+
         unsafe {
+            gl::Uniform3fv(self.loc.spot_light_colors, MAX_SPOT_LIGHTS as _, colors.as_ptr() as _);
+            gl::Uniform3fv(self.loc.spot_light_positions, MAX_SPOT_LIGHTS as _, positions.as_ptr() as _);
+            gl::Uniform3fv(self.loc.spot_light_directions, MAX_SPOT_LIGHTS as _, directions.as_ptr() as _);
+            gl::Uniform3fv(self.loc.spot_light_cones, MAX_SPOT_LIGHTS as _, cones.as_ptr() as _);
+            gl::Uniform1i(self.loc.spot_light_count, count as i32);
+        }
+    }
+"#,
+        );
+    }
+
+    if uniform_strings.contains("joint_matrices") {
+        generated_code.push_str(
+            r#"
+    fn bind_joints(&self, joint_matrices: &[na::Matrix4<f32>]) {
+        let count = joint_matrices.len().min(MAX_JOINTS);
+        unsafe {
+            gl::UniformMatrix4fv(self.loc.joint_matrices, count as _, gl::FALSE, joint_matrices.as_ptr() as _);
+        }
+    }
+"#,
+        );
+    }
+
+    if uniform_strings.contains("fog_color") {
+        generated_code.push_str(
+            r#"
+    fn bind_fog(&self, fog: Option<&Fog>) {
+        // Start equal to end disables fog in the shader
+        let (color, start, end) = if let Some(fog) = fog {
+            (fog.color, fog.start, fog.end)
+        } else {
+            ([0.0; 3], 0.0, 0.0)
+        };
+
+        unsafe {
+            gl::Uniform3fv(self.loc.fog_color, 1, color.as_ptr());
+            gl::Uniform1f(self.loc.fog_start, start);
+            gl::Uniform1f(self.loc.fog_end, end);
+        }
+    }
+"#,
+        );
+    }
+
+    // Shaders using the camera block may still need to compute something from the camera
+    if uniform_strings.contains("view") || uniform_strings.contains("billboard") {
+        generated_code.push_str(&std::format!(
+            r#"
+    fn bind_camera(&self, {}: &Camera, node: &Node) {{
+        unsafe {{
+"#,
+            // Billboards only need the camera node
+            if uniform_strings.contains("view") {
+                "camera"
+            } else {
+                "_camera"
+            }
+        ));
+
+        if uniform_strings.contains("view") {
+            generated_code.push_str(
+                r#"
+            let view = node.trs.get_view();
             gl::UniformMatrix4fv(self.loc.view, 1, gl::FALSE, view.as_ptr());
             gl::UniformMatrix4fv(self.loc.proj, 1, gl::FALSE, camera.proj.as_ptr());
 "#,
-        );
+            );
+        }
 
         if uniform_strings.contains("cam_pos") {
             generated_code.push_str(
@@ -394,26 +569,28 @@ impl CustomShader for {}Shader {{
     // Bind material
     if uniform_strings.contains("tex_sampler") {
         generated_code.push_str(r#"
-    fn bind_material(&self, textures: &Pack<Texture>, colors: &HashMap<Color, Texture>, material: &Material) {
-        // Bind albedo map
-        if let Some(texture_handle) = material.texture {
-            textures.get(texture_handle).unwrap().bind();
-        } else {
-            colors.get(&material.color).unwrap().bind();
-        }
+    fn bind_material(&self, units: &mut TextureUnits, textures: &Pack<Texture>, colors: &HashMap<Color, Handle<Texture>>, material: &Material) {
+        // Bind albedo map, falling back to a pixel of its color
+        let texture_handle = material.texture.unwrap_or_else(|| colors[&material.color]);
+        units.bind(0, textures.get(texture_handle).unwrap());
 "#);
 
+        if uniform_strings.contains("tint") {
+            generated_code.push_str(
+                r#"
+        let tint = material.get_tint();
+        unsafe { gl::Uniform4f(self.loc.tint, tint[0], tint[1], tint[2], tint[3]); }
+"#,
+            );
+        }
+
         // Normal sampler
         if uniform_strings.contains("normal_sampler") {
             generated_code.push_str(
                 r#"
         // Bind normal map
         if let Some(normals_handle) = material.normals {
-            unsafe {
-                gl::ActiveTexture(gl::TEXTURE0 + 2);
-                textures.get(normals_handle).unwrap().bind();
-                gl::ActiveTexture(gl::TEXTURE0);
-            }
+            units.bind(2, textures.get(normals_handle).unwrap());
         }
 "#,
             );
@@ -424,11 +601,7 @@ impl CustomShader for {}Shader {{
                 r#"
         // Bind metallic roughness texture
         if let Some(occlusion_handle) = material.occlusion {
-            unsafe {
-                gl::ActiveTexture(gl::TEXTURE0 + 3);
-                textures.get(occlusion_handle).unwrap().bind();
-                gl::ActiveTexture(gl::TEXTURE0);
-            }
+            units.bind(3, textures.get(occlusion_handle).unwrap());
         }
 "#,
             );
@@ -439,16 +612,38 @@ impl CustomShader for {}Shader {{
                 r#"
         // Bind metallic roughness texture
         if let Some(mr_handle) = material.metallic_roughness {
-            unsafe {
-                gl::ActiveTexture(gl::TEXTURE0 + 4);
-                textures.get(mr_handle).unwrap().bind();
-                gl::ActiveTexture(gl::TEXTURE0);
-            }
+            units.bind(4, textures.get(mr_handle).unwrap());
+        }
+"#,
+            );
+        }
+
+        if uniform_strings.contains("height_sampler") {
+            generated_code.push_str(
+                r#"
+        // Bind height map
+        if let Some(height_handle) = material.height {
+            units.bind(6, textures.get(height_handle).unwrap());
         }
 "#,
             );
         }
 
+        if uniform_strings.contains("alpha_cutoff") {
+            generated_code.push_str(
+                "\n        unsafe { gl::Uniform1f(self.loc.alpha_cutoff, material.alpha_cutoff); }\n",
+            );
+        }
+
+        if uniform_strings.contains("emissive") {
+            generated_code.push_str(
+                r#"
+        let [r, g, b] = material.emissive;
+        unsafe { gl::Uniform3f(self.loc.emissive, r, g, b); }
+"#,
+            );
+        }
+
         if uniform_strings.contains("metallic") {
             generated_code.push_str(
                 "\n        unsafe { gl::Uniform1f(self.loc.metallic, material.metallic); }\n",
@@ -466,20 +661,26 @@ impl CustomShader for {}Shader {{
 
     // Bind node
     if uniform_strings.contains("model") {
-        generated_code.push_str(
+        generated_code.push_str(&std::format!(
             r#"
-    fn bind_node(&self, node: &Node, transform: &na::Matrix4<f32>) {
-        unsafe {
+    fn bind_node(&self, {}: &Node, transform: &na::Matrix4<f32>) {{
+        unsafe {{
             gl::UniformMatrix4fv(self.loc.model, 1, gl::FALSE, transform.as_ptr());
-        }
+        }}
 "#,
-        );
+            // Only instanced shaders read the transforms of the node
+            if uniform_strings.contains("models") {
+                "node"
+            } else {
+                "_node"
+            }
+        ));
 
         if uniform_strings.contains("models") {
             generated_code.push_str(
                 r#"
         // Instance count
-        let instance_count = std::cmp::max(1, node.transforms.len());
+        let instance_count = node.get_instance_count();
         if self.loc.instance_count >= 0 {
             unsafe {
                 gl::Uniform1i(
@@ -490,7 +691,7 @@ impl CustomShader for {}Shader {{
         }
 
         // Transforms array
-        let transform_ptr = if node.transforms.len() > 0 {
+        let transform_ptr = if !node.transforms.is_empty() {
             node.transforms.as_ptr() as _
         } else {
             transform.as_ptr() as _
@@ -519,17 +720,19 @@ impl CustomShader for {}Shader {{
             );
         }
 
-        if uniform_strings.contains("node_id") {
-            generated_code.push_str(
-                r#"
+        generated_code.push_str("    }\n");
+    }
+
+    if uniform_strings.contains("node_id") {
+        generated_code.push_str(
+            r#"
+    fn bind_node_id(&self, id: usize) {
         unsafe {
-            gl::Uniform1i(self.loc.node_id, node.id as i32);
+            gl::Uniform1i(self.loc.node_id, id as i32);
         }
+    }
 "#,
-            );
-        }
-
-        generated_code.push_str("    }\n");
+        );
     }
 
     generated_code.push_str(
@@ -544,13 +747,13 @@ impl CustomShader for {}Shader {{
         // Draw method
         generated_code.push_str(r#"
     fn draw(&self, node: &Node, primitive: &Primitive) {
-        let instance_count = std::cmp::max(1, node.transforms.len());
+        let instance_count = node.get_instance_count();
 
         if instance_count == 0 {
             unsafe {
                 gl::DrawElements(
-                    gl::TRIANGLES,
-                    primitive.indices.len() as _,
+                    primitive.mode,
+                    primitive.get_index_count() as _,
                     primitive.index_type,
                     0 as _,
                 );
@@ -573,7 +776,7 @@ impl CustomShader for {}Shader {{
                 }
 
                 // Transforms array
-                if node.transforms.len() > 0 {
+                if !node.transforms.is_empty() {
                     unsafe {
                         gl::UniformMatrix4fv(self.loc.models, batch_count as _, gl::FALSE, node.transforms[i * 128].as_ptr() as _);
                     }
@@ -581,8 +784,8 @@ impl CustomShader for {}Shader {{
 
                 unsafe {
                     gl::DrawElementsInstanced(
-                        gl::TRIANGLES,
-                        primitive.indices.len() as _,
+                        primitive.mode,
+                        primitive.get_index_count() as _,
                         primitive.index_type,
                         0 as _,
                         batch_count as _,
@@ -596,11 +799,11 @@ impl CustomShader for {}Shader {{
     } else {
         generated_code.push_str(
             r#"
-    fn draw(&self, node: &Node, primitive: &Primitive) {
-        if primitive.indices.len() == 0 {
+    fn draw(&self, _node: &Node, primitive: &Primitive) {
+        if primitive.indices.is_empty() {
             unsafe {
                 gl::DrawArrays(
-                    gl::TRIANGLES,
+                    primitive.mode,
                     0,
                     primitive.vertices.len() as _,
                 )
@@ -608,8 +811,8 @@ impl CustomShader for {}Shader {{
         } else {
             unsafe {
                 gl::DrawElements(
-                    gl::TRIANGLES,
-                    primitive.indices.len() as _,
+                    primitive.mode,
+                    primitive.get_index_count() as _,
                     primitive.index_type,
                     0 as _,
                 );
@@ -81,10 +81,19 @@ fn find_variants(include_name: &str, dir: &Path) -> Vec<IncludeVariant> {
     variants
 }
 
+/// Shader stage where an include is found
+#[derive(Clone, Copy, PartialEq)]
+pub enum Stage {
+    Vertex,
+    Fragment,
+}
+
 pub struct Include {
     /// Line number where this include was found in the original shader
     pub index: usize,
 
+    pub stage: Stage,
+
     /// Snake case name of the include, such as "occlusion"
     pub name: String,
 
@@ -95,11 +104,12 @@ pub struct Include {
 }
 
 impl Include {
-    pub fn new(index: usize, name: String, dir: &Path) -> Self {
+    pub fn new(index: usize, stage: Stage, name: String, dir: &Path) -> Self {
         let camelcase = util::to_camelcase(&name);
         let variants = find_variants(&name, dir);
         Self {
             index,
+            stage,
             name,
             camelcase,
             variants,
@@ -107,42 +117,43 @@ impl Include {
     }
 }
 
-// Returns the list of names of the includes in the code
-pub fn get_includes(code: &str, dir: &Path) -> Vec<Include> {
+/// Returns the name without extension of the file included by this line, such as "occlusion"
+fn get_include_name(line: &str) -> String {
+    // Get the string with "" or <>
+    let include = line.split('"').nth(1).unwrap_or_else(|| {
+        line.split('<')
+            .nth(1)
+            .expect("Failed to get include name")
+            .split('>')
+            .next()
+            .expect("Failed to get include name")
+    });
+    // Get the name without the extension
+    include
+        .split('.')
+        .next()
+        .expect("Failed to get include name")
+        .to_string()
+}
+
+// Returns the list of names of the includes in the code of a stage
+pub fn get_includes(code: &str, stage: Stage, dir: &Path) -> Vec<Include> {
     let includes = code
         .lines()
         .enumerate()
         // Find lines starting with #include
         .filter(|(_, line)| line.starts_with("#include"))
-        .map(|(index, line)| {
-            // Get the string with "" or <>
-            let include = line.splitn(3, "\"").nth(1).unwrap_or_else(|| {
-                line.split("<")
-                    .nth(1)
-                    .expect("Failed to get include name")
-                    .split(">")
-                    .nth(0)
-                    .expect("Failed to get include name")
-            });
-            // Get the name without the extension
-            (
-                index,
-                include
-                    .split('.')
-                    .nth(0)
-                    .expect("Failed to get include name")
-                    .to_string(),
-            )
-        });
+        .map(|(index, line)| (index, get_include_name(line)));
 
     includes
-        .map(|(index, name)| Include::new(index, name, dir))
+        .map(|(index, name)| Include::new(index, stage, name, dir))
         .collect()
 }
 
-/// Returns the code with this include variant resolved
+/// Returns the code with this include variant resolved in the code of `stage`
 pub fn resolve_includes(
     mut code: ShaderCode,
+    stage: Stage,
     include: &Include,
     include_variant: &IncludeVariant,
 ) -> ShaderCode {
@@ -150,17 +161,24 @@ pub fn resolve_includes(
         "Failed to include {}",
         include_variant.path.to_string_lossy()
     ));
+    // Includes are looked up by name, as they may not be resolved in order
+    let is_include =
+        |line: &str| line.starts_with("#include") && get_include_name(line) == include.name;
+    let stage = match stage {
+        Stage::Vertex => &mut code.vert,
+        Stage::Fragment => &mut code.frag,
+    };
+
     // Insert string within code substituting include line
-    let mut lines = code.frag.lines();
-    let include_index = lines
-        .position(|line| line.starts_with("#include"))
+    let include_index = stage
+        .lines()
+        .position(is_include)
         .expect("Failed to find include line to resolve");
-    // TODO solve vert as well?
-    let mut lines: Vec<String> = code.frag.lines().map(|l| String::from(l)).collect();
+    let mut lines: Vec<String> = stage.lines().map(String::from).collect();
 
     eprintln!("include:{}", include.index);
     lines[include_index] = include_code;
 
-    code.frag = lines.join("\n");
+    *stage = lines.join("\n");
     code
 }
@@ -63,7 +63,7 @@ fn solve_variant(
     include: &Include,
     include_variant: &IncludeVariant,
 ) -> ShaderCode {
-    shader_code::resolve_includes(code, include, include_variant)
+    shader_code::resolve_includes(code, include.stage, include, include_variant)
 }
 
 /// shader_name is something like "pbr"
@@ -143,8 +143,17 @@ impl ShaderInfo {
 
         let code = ShaderCode::new(vert, frag);
 
-        // Collect includes from fragment shader
-        let includes = get_includes(&code.frag, &dir);
+        // Collect includes from fragment shader, then from vertex shader
+        let mut includes = get_includes(&code.frag, Stage::Fragment, &dir);
+        includes.extend(get_includes(&code.vert, Stage::Vertex, &dir));
+
+        // Includes without variants are resolved straight away, as they do not make new shaders
+        let (plain_includes, includes): (Vec<Include>, Vec<Include>) = includes
+            .into_iter()
+            .partition(|include| include.variants.len() == 1);
+        let code = plain_includes.iter().fold(code, |code, include| {
+            shader_code::resolve_includes(code, include.stage, include, &include.variants[0])
+        });
 
         eprintln!("shader:{}:include_count:{}", prefix, includes.len());
         for include in &includes {
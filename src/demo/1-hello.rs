@@ -7,7 +7,9 @@ use nalgebra as na;
 use rustspot::*;
 
 fn main() {
-    let mut spot = Spot::builder().build();
+    // Rendering straight to the default framebuffer, no need for offscreen buffers
+    let frame = Frame::builder().shadow_buffer(false).geometry_buffer(false);
+    let mut spot = Spot::builder().frame(frame).build();
 
     let (mut model, root) = create_model();
 
@@ -21,7 +23,7 @@ fn main() {
 
             match event {
                 sdl2::event::Event::Quit { .. } => break 'gameloop,
-                _ => println!("{:?}", event),
+                _ => log::trace!("{:?}", event),
             }
         }
 
@@ -71,7 +73,7 @@ fn create_model() -> (Model, Handle<Node>) {
 
     let texture = model
         .textures
-        .push(Texture::builder().path("res/img/lena.png").build().unwrap());
+        .push(Texture::open("res/img/lena.png").unwrap_or_else(|_| Texture::missing()));
 
     // Create a material with the previous texture
     let material = model.materials.push(
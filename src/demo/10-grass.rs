@@ -30,6 +30,8 @@ fn main() {
 
         // Handle SDL2 events
         for event in spot.events.poll_iter() {
+            spot.input.handle(&event);
+
             match event {
                 sdl2::event::Event::Quit { .. } => break 'gameloop,
                 sdl2::event::Event::MouseMotion { xrel, yrel, .. } => {
@@ -52,21 +54,6 @@ fn main() {
                     let forward = node.trs.get_forward().scale(y as f32);
                     node.trs.translate(forward.x, forward.y, forward.z);
                 }
-                sdl2::event::Event::JoyAxisMotion {
-                    axis_idx, value, ..
-                } => {
-                    if axis_idx == 0 || axis_idx == 1 {
-                        let node = grass.model.nodes.get_mut(grass.camera).unwrap();
-                        let axis = if axis_idx == 0 {
-                            na::Vector3::y_axis()
-                        } else {
-                            na::Vector3::x_axis()
-                        };
-                        let angle = -(value as f32 / (32768.0 / 2.0)) as f32 * delta.as_secs_f32();
-                        let rotation = na::UnitQuaternion::from_axis_angle(&axis, angle);
-                        node.trs.rotate(&rotation);
-                    }
-                }
                 sdl2::event::Event::JoyDeviceAdded { which, .. } => {
                     let joystick = spot
                         .joystick
@@ -103,27 +90,46 @@ fn main() {
                         .terrain
                         .set_instance_per_unit(&mut grass.model, blades_per_unit as u32);
                 }
-                _ => println!("{:?}", event),
+                _ => log::trace!("{:?}", event),
             }
         }
 
-        let frame = spot.gfx.next_frame();
+        // Rotate the camera with the first two axes of the gamepads
+        let axes = [
+            (0, na::Vector3::<f32>::y_axis()),
+            (1, na::Vector3::x_axis()),
+        ];
+        for joystick in joysticks.iter() {
+            let which = joystick.instance_id();
+            let node = grass.model.nodes.get_mut(grass.camera).unwrap();
+            for &(axis_idx, axis) in axes.iter() {
+                let angle = -2.0 * spot.input.axis(which, axis_idx) * delta.as_secs_f32();
+                let rotation = na::UnitQuaternion::from_axis_angle(&axis, angle);
+                node.trs.rotate(&rotation);
+            }
+        }
+        spot.input.reset();
+
+        // Draw only grass blades the camera can see
+        grass.terrain.cull(&mut grass.model, grass.camera);
+
         spot.gfx
             .renderer
-            .render_shadow(&grass.model, &frame.shadow_buffer);
+            .draw(&grass.model, grass.root, &na::Matrix4::identity());
 
+        let frame = spot.gfx.next_frame();
         spot.gfx
             .renderer
-            .draw(&grass.model, grass.root, &na::Matrix4::identity());
+            .render_shadow(&grass.model, frame.get_shadow_buffer());
 
         spot.gfx
             .renderer
-            .render_geometry(&grass.model, &frame.geometry_buffer);
+            .render_geometry(&grass.model, frame.get_geometry_buffer());
 
         // Draw a simple triangle which cover the whole screen
         spot.gfx
             .renderer
-            .blit_color(&frame.geometry_buffer, &frame.default_framebuffer);
+            .blit_color(frame.get_geometry_buffer(), &frame.default_framebuffer);
 
         // Start a new GUI frame
         let ui = spot.gfx.gui.frame();
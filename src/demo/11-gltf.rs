@@ -113,7 +113,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                             Some(Shaders::last())
                         };
                 }
-                _ => println!("{:?}", event),
+                _ => log::trace!("{:?}", event),
             }
         }
 
@@ -125,19 +125,15 @@ fn main() -> Result<(), Box<dyn Error>> {
 
         spot.gfx
             .renderer
-            .render_shadow(&model, &frame.shadow_buffer);
+            .render_shadow(&model, frame.get_shadow_buffer());
 
         spot.gfx
             .renderer
-            .draw(&model, root, &na::Matrix4::identity());
-
-        spot.gfx
-            .renderer
-            .render_geometry(&model, &frame.geometry_buffer);
+            .render_geometry(&model, frame.get_geometry_buffer());
 
         spot.gfx
             .renderer
-            .blit_color(&frame.geometry_buffer, &frame.default_framebuffer);
+            .blit_color(frame.get_geometry_buffer(), &frame.default_framebuffer);
 
         // Render GUI
         let ui = spot.gfx.gui.frame();
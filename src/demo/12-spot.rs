@@ -0,0 +1,91 @@
+// Copyright © 2021
+// Author: Antonio Caggiano <info@antoniocaggiano.eu>
+// SPDX-License-Identifier: MIT
+
+use nalgebra as na;
+
+use rustspot::*;
+
+mod model;
+
+fn main() {
+    let mut spot = Spot::builder().build();
+
+    let (mut model, root, flashlight) = create_model();
+
+    let mut time = 0.0;
+
+    'gameloop: loop {
+        // Handle SDL2 events
+        for event in spot.events.poll_iter() {
+            match event {
+                sdl2::event::Event::Quit { .. } => break 'gameloop,
+                sdl2::event::Event::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::D),
+                    ..
+                } => {
+                    // Cycle through debug visualizations
+                    let mode = spot.gfx.renderer.get_debug_mode().next();
+                    log::info!("Debug mode {:?}", mode);
+                    spot.gfx.renderer.set_debug_mode(mode);
+                }
+                _ => log::trace!("{:?}", event),
+            }
+        }
+
+        let delta = spot.update();
+        time += delta.as_secs_f32();
+
+        // Sweep the cone of light around the structure
+        let flashlight = model.nodes.get_mut(flashlight).unwrap();
+        flashlight
+            .trs
+            .set_translation(1.5 * time.cos(), 3.0, 1.5 * time.sin());
+
+        spot.gfx
+            .renderer
+            .draw(&model, root, &na::Matrix4::identity());
+
+        let frame = spot.gfx.next_frame();
+        spot.gfx
+            .renderer
+            .render_geometry(&model, &frame.default_framebuffer);
+
+        // Present to the screen
+        spot.gfx.present(frame);
+    }
+}
+
+fn create_model() -> (Model, Handle<Node>, Handle<Node>) {
+    let mut model = Model::new();
+    let root = model::create_structure_scene(&mut model);
+
+    // Dim the sun, so that the spot light stands out
+    for handle in model.directional_lights.handles() {
+        model.directional_lights.get_mut(handle).unwrap().intensity = 0.125;
+    }
+
+    // Lit by spot lights
+    for handle in model.materials.handles() {
+        model.materials.get_mut(handle).unwrap().shader =
+            LIGHT_VARIANTS[LightSpotVariant::Cone as usize][LightSkinVariant::Default as usize];
+    }
+
+    let light = model.spot_lights.push(
+        SpotLight::color(1.0, 0.9, 0.7)
+            .with_range(8.0)
+            .with_cone(0.25, 0.4),
+    );
+    let mut flashlight = Node::new();
+    flashlight.name = String::from("flashlight");
+    flashlight.spot_light = light;
+    // Looking down
+    flashlight.trs.rotate(&na::UnitQuaternion::from_axis_angle(
+        &na::Vector3::x_axis(),
+        -std::f32::consts::FRAC_PI_2,
+    ));
+    let flashlight = model.nodes.push(flashlight);
+    model.nodes.get_mut(root).unwrap().children.push(flashlight);
+
+    (model, root, flashlight)
+}
@@ -11,87 +11,47 @@ fn main() {
 
     let (mut model, root) = create_model();
 
-    'gameloop: loop {
-        // Handle SDL2 events
-        for event in spot.events.poll_iter() {
-            match event {
-                sdl2::event::Event::Quit { .. } => break 'gameloop,
-                _ => println!("{:?}", event),
-            }
-        }
-
-        let delta = spot.update();
-
+    spot.run(|spot, delta| {
         let rot =
             na::UnitQuaternion::from_axis_angle(&na::Vector3::y_axis(), delta.as_secs_f32() / 2.0);
         model.nodes.get_mut(root).unwrap().trs.rotate(&rot);
 
-        spot.gfx
-            .renderer
-            .draw(&model, root, &na::Matrix4::identity());
+        spot.gfx.render_with(|renderer, frame| {
+            renderer.draw(&model, root, &na::Matrix4::identity());
+            renderer.render_geometry(&model, &frame.default_framebuffer);
+        });
 
-        let frame = spot.gfx.next_frame();
-        spot.gfx
-            .renderer
-            .render_geometry(&model, &frame.default_framebuffer);
-
-        // Present to the screen
-        spot.gfx.present(frame);
-    }
+        ControlFlow::Continue
+    });
 }
 
 fn create_model() -> (Model, Handle<Node>) {
     let mut model = Model::new();
 
-    let color_textures = vec![
-        model
-            .textures
-            .push(Texture::pixel(Color::rgba(233, 225, 78, 255))), // yellow
-        model
-            .textures
-            .push(Texture::pixel(Color::rgba(170, 221, 84, 255))), // green
-        model
-            .textures
-            .push(Texture::pixel(Color::rgba(145, 209, 125, 255))),
-        model
-            .textures
-            .push(Texture::pixel(Color::rgba(106, 174, 185, 255))), // cyan
-        model
-            .textures
-            .push(Texture::pixel(Color::rgba(87, 137, 210, 255))), // blue
-        model
-            .textures
-            .push(Texture::pixel(Color::rgba(103, 114, 194, 255))),
-        model
-            .textures
-            .push(Texture::pixel(Color::rgba(110, 95, 162, 255))), // purple
-        model
-            .textures
-            .push(Texture::pixel(Color::rgba(128, 102, 149, 255))),
-        model
-            .textures
-            .push(Texture::pixel(Color::rgba(183, 105, 119, 255))), // red
-        model
-            .textures
-            .push(Texture::pixel(Color::rgba(212, 103, 98, 255))),
-        model
-            .textures
-            .push(Texture::pixel(Color::rgba(224, 138, 3, 255))), // orange
-        model
-            .textures
-            .push(Texture::pixel(Color::rgba(236, 195, 79, 255))),
-        model
-            .textures
-            .push(Texture::pixel(Color::rgba(233, 225, 78, 255))), // yellow
+    let colors = vec![
+        Color::rgba(233, 225, 78, 255), // yellow
+        Color::rgba(170, 221, 84, 255), // green
+        Color::rgba(145, 209, 125, 255),
+        Color::rgba(106, 174, 185, 255), // cyan
+        Color::rgba(87, 137, 210, 255),  // blue
+        Color::rgba(103, 114, 194, 255),
+        Color::rgba(110, 95, 162, 255), // purple
+        Color::rgba(128, 102, 149, 255),
+        Color::rgba(183, 105, 119, 255), // red
+        Color::rgba(212, 103, 98, 255),
+        Color::rgba(224, 138, 3, 255), // orange
+        Color::rgba(236, 195, 79, 255),
+        Color::rgba(233, 225, 78, 255), // yellow
     ];
 
-    // Create a material with the previous texture
+    // Create a material with the previous color
     let mut materials = vec![];
-    for texture in color_textures {
+    for color in colors {
+        model.color_texture(color);
         materials.push(
             model.materials.push(
                 Material::builder()
-                    .texture(texture)
+                    .color(color)
                     .shader(Shaders::Unlit)
                     .build(),
             ),
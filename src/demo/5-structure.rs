@@ -17,7 +17,7 @@ fn main() {
         for event in spot.events.poll_iter() {
             match event {
                 sdl2::event::Event::Quit { .. } => break 'gameloop,
-                _ => println!("{:?}", event),
+                _ => log::trace!("{:?}", event),
             }
         }
 
@@ -27,14 +27,14 @@ fn main() {
             na::UnitQuaternion::from_axis_angle(&na::Vector3::y_axis(), delta.as_secs_f32() / 2.0);
         model.nodes.get_mut(root).unwrap().trs.rotate(&rot);
 
-        let frame = spot.gfx.next_frame();
         spot.gfx
             .renderer
-            .render_shadow(&model, &frame.shadow_buffer);
+            .draw(&model, root, &na::Matrix4::identity());
 
+        let frame = spot.gfx.next_frame();
         spot.gfx
             .renderer
-            .draw(&model, root, &na::Matrix4::identity());
+            .render_shadow(&model, frame.get_shadow_buffer());
 
         spot.gfx
             .renderer
@@ -16,7 +16,7 @@ fn main() {
         for event in spot.events.poll_iter() {
             match event {
                 sdl2::event::Event::Quit { .. } => break 'gameloop,
-                _ => println!("{:?}", event),
+                _ => log::trace!("{:?}", event),
             }
         }
 
@@ -34,7 +34,7 @@ fn main() {
         let frame = spot.gfx.next_frame();
         spot.gfx
             .renderer
-            .render_geometry(&model, &frame.geometry_buffer);
+            .render_geometry(&model, frame.get_geometry_buffer());
 
         // Render GUI
         let ui = spot.gfx.gui.frame();
@@ -50,7 +50,7 @@ fn main() {
         // Draw a simple triangle which cover the whole screen
         spot.gfx
             .renderer
-            .blit_color(&frame.geometry_buffer, &frame.default_framebuffer);
+            .blit_color(frame.get_geometry_buffer(), &frame.default_framebuffer);
 
         spot.gfx.renderer.render_gui(ui, &frame.default_framebuffer);
 
@@ -64,7 +64,7 @@ fn create_model() -> (Model, Handle<Node>) {
 
     let texture = model
         .textures
-        .push(Texture::builder().path("res/img/lena.png").build().unwrap());
+        .push(Texture::open("res/img/lena.png").unwrap_or_else(|_| Texture::missing()));
 
     // Create a material with the previous texture
     let material = model.materials.push(
@@ -18,7 +18,7 @@ fn main() {
         for event in spot.events.poll_iter() {
             match event {
                 sdl2::event::Event::Quit { .. } => break 'gameloop,
-                _ => println!("{:?}", event),
+                _ => log::trace!("{:?}", event),
             }
         }
 
@@ -36,7 +36,7 @@ fn main() {
         let frame = spot.gfx.next_frame();
         spot.gfx
             .renderer
-            .render_geometry(&model, &frame.geometry_buffer);
+            .render_geometry(&model, frame.get_geometry_buffer());
 
         // Render GUI
         let ui = spot.gfx.gui.frame();
@@ -51,7 +51,7 @@ fn main() {
 
         spot.gfx
             .renderer
-            .blit_depth(&frame.geometry_buffer, &frame.default_framebuffer);
+            .blit_depth(frame.get_geometry_buffer(), &frame.default_framebuffer);
 
         spot.gfx.renderer.render_gui(ui, &frame.default_framebuffer);
 
@@ -65,7 +65,7 @@ fn create_model() -> (Model, Handle<Node>) {
 
     let texture = model
         .textures
-        .push(Texture::builder().path("res/img/lena.png").build().unwrap());
+        .push(Texture::open("res/img/lena.png").unwrap_or_else(|_| Texture::missing()));
 
     // Create a material with the previous texture
     let material = model
@@ -48,27 +48,25 @@ fn main() {
         let frame = spot.gfx.next_frame();
         spot.gfx
             .renderer
-            .render_shadow(&model, &frame.shadow_buffer);
+            .render_shadow(&model, frame.get_shadow_buffer());
+
+        // Geometry is rendered even when showing the shadowmap, as it is the pass
+        // clearing what was drawn
+        spot.gfx
+            .renderer
+            .render_geometry(&model, frame.get_geometry_buffer());
 
         match render_source {
             RenderSource::Default => {
                 spot.gfx
                     .renderer
-                    .draw(&model, root, &na::Matrix4::identity());
-
-                spot.gfx
-                    .renderer
-                    .render_geometry(&model, &frame.geometry_buffer);
-
-                spot.gfx
-                    .renderer
-                    .blit_color(&frame.geometry_buffer, &frame.default_framebuffer);
+                    .blit_color(frame.get_geometry_buffer(), &frame.default_framebuffer);
             }
 
             RenderSource::Shadowmap => {
                 spot.gfx
                     .renderer
-                    .blit_depth(&frame.shadow_buffer, &frame.default_framebuffer);
+                    .blit_depth(frame.get_shadow_buffer(), &frame.default_framebuffer);
             }
         }
 
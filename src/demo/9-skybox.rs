@@ -24,6 +24,8 @@ fn main() {
 
         // Handle SDL2 events
         for event in spot.events.poll_iter() {
+            spot.input.handle(&event);
+
             match event {
                 sdl2::event::Event::Quit { .. } => break 'gameloop,
                 sdl2::event::Event::MouseMotion { xrel, yrel, .. } => {
@@ -52,26 +54,6 @@ fn main() {
                         }
                     }
                 }
-                sdl2::event::Event::JoyAxisMotion {
-                    axis_idx, value, ..
-                } => {
-                    if axis_idx == 0 || axis_idx == 1 {
-                        for node in model.nodes.iter_mut() {
-                            if node.camera.valid() {
-                                let axis = if axis_idx == 0 {
-                                    na::Vector3::y_axis()
-                                } else {
-                                    na::Vector3::x_axis()
-                                };
-                                let angle =
-                                    -(value as f32 / (32768.0 / 2.0)) as f32 * delta.as_secs_f32();
-                                let rotation = na::UnitQuaternion::from_axis_angle(&axis, angle);
-                                node.trs.rotate(&rotation);
-                                break;
-                            }
-                        }
-                    }
-                }
                 sdl2::event::Event::JoyDeviceAdded { which, .. } => {
                     let joystick = spot
                         .joystick
@@ -79,35 +61,33 @@ fn main() {
                         .expect("Failed to open controller");
                     joysticks.push(joystick);
                 }
-                _ => println!("{:?}", event),
+                _ => log::trace!("{:?}", event),
+            }
+        }
+
+        // Rotate the camera with the first two axes of the gamepads
+        let axes = [
+            (0, na::Vector3::<f32>::y_axis()),
+            (1, na::Vector3::x_axis()),
+        ];
+        for joystick in joysticks.iter() {
+            let which = joystick.instance_id();
+            if let Some(node) = model.nodes.iter_mut().find(|node| node.camera.valid()) {
+                for &(axis_idx, axis) in axes.iter() {
+                    let angle = -2.0 * spot.input.axis(which, axis_idx) * delta.as_secs_f32();
+                    let rotation = na::UnitQuaternion::from_axis_angle(&axis, angle);
+                    node.trs.rotate(&rotation);
+                }
             }
         }
+        spot.input.reset();
 
         let rot =
             na::UnitQuaternion::from_axis_angle(&na::Vector3::y_axis(), delta.as_secs_f32() / 16.0);
         model.nodes.get_mut(root).unwrap().trs.rotate(&rot);
 
-        spot.gfx
-            .renderer
-            .draw(&model, root, &na::Matrix4::identity());
-
         let frame = spot.gfx.next_frame();
-        spot.gfx
-            .renderer
-            .render_shadow(&model, &frame.shadow_buffer);
-
-        spot.gfx
-            .renderer
-            .draw(&model, root, &na::Matrix4::identity());
-
-        spot.gfx
-            .renderer
-            .render_geometry(&model, &frame.geometry_buffer);
-
-        // Draw a simple triangle which cover the whole screen
-        spot.gfx
-            .renderer
-            .blit_color(&frame.geometry_buffer, &frame.default_framebuffer);
+        spot.gfx.renderer.render(&model, root, &frame);
 
         // Present to the screen
         spot.gfx.present(frame);
@@ -106,11 +106,10 @@ pub fn create_structure_scene(model: &mut Model) -> Handle<Node> {
     root.children.push(camera_node);
 
     // Cyan material
-    let texture = model
-        .textures
-        .push(Texture::pixel(Color::rgba(160, 170, 180, 255)));
+    let color = Color::rgba(160, 170, 180, 255);
+    model.color_texture(color);
     let material = Material::builder()
-        .texture(texture)
+        .color(color)
         .shader(Shaders::PbrOcclusionDefaultMetallicRoughnessDefaultNormalDefaultShadowTexture)
         .build();
     let material = model.materials.push(material);
@@ -125,11 +124,10 @@ pub fn create_structure_scene(model: &mut Model) -> Handle<Node> {
     root.children.push(model.nodes.push(floor));
 
     // White material
-    let texture = model
-        .textures
-        .push(Texture::pixel(Color::rgba(255, 255, 255, 255)));
+    let color = Color::rgba(255, 255, 255, 255);
+    model.color_texture(color);
     let material = Material::builder()
-        .texture(texture)
+        .color(color)
         .shader(Shaders::PbrOcclusionDefaultMetallicRoughnessDefaultNormalDefaultShadowTexture)
         .build();
     let material = model.materials.push(material);
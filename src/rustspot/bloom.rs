@@ -0,0 +1,148 @@
+// Copyright © 2021
+// Author: Antonio Caggiano <info@antoniocaggiano.eu>
+// SPDX-License-Identifier: MIT
+
+use super::*;
+
+/// Parameters of the bloom post-process applied by `Renderer::blit_color`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BloomParams {
+    /// Pixels with a luminance above this contribute to bloom
+    pub threshold: f32,
+    /// Scale applied to the blurred pixels before adding them to the image
+    pub intensity: f32,
+    /// Number of horizontal and vertical blur passes
+    pub passes: u32,
+    /// Bright pixels are blurred at this mip of the source, 1 being half the size
+    pub mip: u32,
+}
+
+impl BloomParams {
+    pub fn new(threshold: f32, intensity: f32) -> Self {
+        Self {
+            threshold,
+            intensity,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the extent of the blur textures for a source of a certain extent
+    pub fn get_extent(&self, source: Extent2D) -> Extent2D {
+        Extent2D::new(
+            (source.width >> self.mip).max(1),
+            (source.height >> self.mip).max(1),
+        )
+    }
+}
+
+impl Default for BloomParams {
+    fn default() -> Self {
+        Self {
+            threshold: 0.8,
+            intensity: 1.0,
+            passes: 4,
+            mip: 1,
+        }
+    }
+}
+
+/// Ping-pong framebuffers used to blur bright pixels, one pass reading from one buffer
+/// and writing to the other. At the end the result is found in the first buffer.
+pub struct BloomChain {
+    /// Extent of the source this chain was created for
+    source_extent: Extent2D,
+    mip: u32,
+    pub buffers: [CustomFramebuffer; 2],
+}
+
+impl BloomChain {
+    pub fn new(source_extent: Extent2D, params: &BloomParams) -> Self {
+        let extent = params.get_extent(source_extent);
+        let buffers = [
+            CustomFramebuffer::color(extent),
+            CustomFramebuffer::color(extent),
+        ];
+
+        // The blur should not sample the border color
+        for buffer in buffers.iter() {
+            let texture = &buffer.color_textures[0];
+            texture.bind();
+            unsafe {
+                gl::TexParameteri(texture.target, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+                gl::TexParameteri(texture.target, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            }
+            texture.unbind();
+        }
+
+        Self {
+            source_extent,
+            mip: params.mip,
+            buffers,
+        }
+    }
+
+    /// Whether this chain can be reused for a source with these parameters
+    pub fn is_compatible(&self, source_extent: Extent2D, params: &BloomParams) -> bool {
+        self.source_extent == source_extent && self.mip == params.mip
+    }
+
+    pub fn get_extent(&self) -> Extent2D {
+        self.buffers[0].framebuffer.extent
+    }
+
+    /// Returns the texture with the blurred bright pixels
+    pub fn get_result(&self) -> &Texture {
+        &self.buffers[0].color_textures[0]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mip_extent() {
+        let params = BloomParams::default();
+        assert_eq!(
+            params.get_extent(Extent2D::new(480, 320)),
+            Extent2D::new(240, 160)
+        );
+
+        let params = BloomParams {
+            mip: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            params.get_extent(Extent2D::new(480, 4)),
+            Extent2D::new(60, 1)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn ping_pong_textures() {
+        let _context = TestContext::new();
+
+        let params = BloomParams {
+            mip: 2,
+            ..Default::default()
+        };
+        let source_extent = Extent2D::new(64, 32);
+        let chain = BloomChain::new(source_extent, &params);
+        assert_eq!(chain.get_extent(), Extent2D::new(16, 8));
+
+        for buffer in chain.buffers.iter() {
+            assert_eq!(buffer.color_textures.len(), 1);
+            assert_eq!(buffer.color_textures[0].extent, Extent2D::new(16, 8));
+            assert!(buffer.depth_texture.is_none());
+        }
+        assert_ne!(
+            chain.buffers[0].color_textures[0].handle,
+            chain.buffers[1].color_textures[0].handle
+        );
+
+        assert!(chain.is_compatible(source_extent, &params));
+        assert!(!chain.is_compatible(Extent2D::new(64, 64), &params));
+        assert!(!chain.is_compatible(source_extent, &BloomParams::default()));
+    }
+}
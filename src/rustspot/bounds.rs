@@ -0,0 +1,125 @@
+// Copyright © 2021
+// Author: Antonio Caggiano <info@antoniocaggiano.eu>
+// SPDX-License-Identifier: MIT
+
+use nalgebra as na;
+
+use crate::*;
+
+/// Axis aligned bounding box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: na::Vector3<f32>,
+    pub max: na::Vector3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: na::Vector3<f32>, max: na::Vector3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    /// Returns the smallest box containing all the vertices
+    pub fn from_vertices(vertices: &[Vertex]) -> Self {
+        if vertices.is_empty() {
+            return Self::new(na::Vector3::zeros(), na::Vector3::zeros());
+        }
+
+        let mut min = na::Vector3::repeat(f32::MAX);
+        let mut max = na::Vector3::repeat(f32::MIN);
+
+        for vertex in vertices {
+            let position = na::Vector3::from(vertex.position);
+            min = min.inf(&position);
+            max = max.sup(&position);
+        }
+
+        Self::new(min, max)
+    }
+
+    pub fn get_center(&self) -> na::Vector3<f32> {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn get_size(&self) -> na::Vector3<f32> {
+        self.max - self.min
+    }
+
+    /// Returns the eight corners of the box, bottom face first
+    pub fn get_corners(&self) -> [na::Vector3<f32>; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            na::Vector3::new(min.x, min.y, min.z),
+            na::Vector3::new(max.x, min.y, min.z),
+            na::Vector3::new(max.x, min.y, max.z),
+            na::Vector3::new(min.x, min.y, max.z),
+            na::Vector3::new(min.x, max.y, min.z),
+            na::Vector3::new(max.x, max.y, min.z),
+            na::Vector3::new(max.x, max.y, max.z),
+            na::Vector3::new(min.x, max.y, max.z),
+        ]
+    }
+
+    /// Returns the smallest box containing both this box and the other one
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self::new(self.min.inf(&other.min), self.max.sup(&other.max))
+    }
+
+    /// Returns a new box containing this one once transformed
+    pub fn transform(&self, transform: &na::Matrix4<f32>) -> Self {
+        let mut min = na::Vector3::repeat(f32::MAX);
+        let mut max = na::Vector3::repeat(f32::MIN);
+
+        for corner in self.get_corners().iter() {
+            let corner = transform.transform_point(&na::Point3::from(*corner)).coords;
+            min = min.inf(&corner);
+            max = max.sup(&corner);
+        }
+
+        Self::new(min, max)
+    }
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::new(na::Vector3::zeros(), na::Vector3::zeros())
+    }
+}
+
+/// Bounds computed by the renderer while drawing, useful to tune shadows and culling
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SceneBounds {
+    /// World space box containing all the primitives drawn, none when nothing was drawn
+    pub aabb: Option<Aabb>,
+    /// Corners of the frustum of the first camera drawn, near plane first
+    pub frustum_corners: Option<[na::Vector3<f32>; 8]>,
+}
+
+impl SceneBounds {
+    /// Grows the scene box to contain another one
+    pub fn add(&mut self, aabb: &Aabb) {
+        self.aabb = Some(match &self.aabb {
+            Some(scene) => scene.union(aabb),
+            None => *aabb,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn union() {
+        let mut bounds = SceneBounds::default();
+        assert!(bounds.aabb.is_none());
+
+        bounds.add(&Aabb::new(na::Vector3::zeros(), na::Vector3::repeat(1.0)));
+        bounds.add(&Aabb::new(
+            na::Vector3::new(-1.0, 0.5, 0.5),
+            na::Vector3::new(0.0, 2.0, 0.5),
+        ));
+        let aabb = bounds.aabb.unwrap();
+        assert_eq!(aabb.min, na::Vector3::new(-1.0, 0.0, 0.0));
+        assert_eq!(aabb.max, na::Vector3::new(1.0, 2.0, 1.0));
+    }
+}
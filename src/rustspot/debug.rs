@@ -0,0 +1,132 @@
+// Copyright © 2021
+// Author: Antonio Caggiano <info@antoniocaggiano.eu>
+// SPDX-License-Identifier: MIT
+
+use nalgebra as na;
+
+use crate::*;
+
+/// Edges of a box as pairs of indices into `Aabb::get_corners()`
+const AABB_EDGES: [(usize, usize); 12] = [
+    // Bottom
+    (0, 1),
+    (1, 2),
+    (2, 3),
+    (3, 0),
+    // Top
+    (4, 5),
+    (5, 6),
+    (6, 7),
+    (7, 4),
+    // Sides
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
+/// Collects line segments to visualize things which are not part of the scene,
+/// such as bounding boxes and lights. Lines are stored in world space, two vertices
+/// for each segment, and they are drawn by the renderer as a line list.
+#[derive(Default)]
+pub struct DebugDraw {
+    pub lines: Vec<Vertex>,
+}
+
+impl DebugDraw {
+    pub fn new() -> Self {
+        Self { lines: vec![] }
+    }
+
+    pub fn line(&mut self, a: &na::Vector3<f32>, b: &na::Vector3<f32>, color: [f32; 3]) {
+        let mut vertex = Vertex::new();
+        vertex.color = color;
+
+        vertex.position = [a.x, a.y, a.z];
+        self.lines.push(vertex);
+
+        vertex.position = [b.x, b.y, b.z];
+        self.lines.push(vertex);
+    }
+
+    /// Adds the twelve edges of the box transformed in world space
+    pub fn aabb(&mut self, aabb: &Aabb, transform: &na::Matrix4<f32>, color: [f32; 3]) {
+        let corners = aabb.get_corners();
+        let corners: Vec<na::Vector3<f32>> = corners
+            .iter()
+            .map(|c| transform.transform_point(&na::Point3::from(*c)).coords)
+            .collect();
+
+        for &(a, b) in AABB_EDGES.iter() {
+            self.line(&corners[a], &corners[b], color);
+        }
+    }
+
+    /// Adds a small cross at the light position and an arrow along its direction
+    pub fn directional_light(&mut self, transform: &na::Matrix4<f32>, color: [f32; 3]) {
+        let position = transform.transform_point(&na::Point3::origin()).coords;
+        let forward = transform
+            .transform_vector(&-na::Vector3::z())
+            .normalize();
+
+        let size = 0.125;
+        self.line(
+            &(position - na::Vector3::x() * size),
+            &(position + na::Vector3::x() * size),
+            color,
+        );
+        self.line(
+            &(position - na::Vector3::y() * size),
+            &(position + na::Vector3::y() * size),
+            color,
+        );
+        self.line(
+            &(position - na::Vector3::z() * size),
+            &(position + na::Vector3::z() * size),
+            color,
+        );
+
+        // Arrow pointing where the light goes
+        let tip = position + forward;
+        self.line(&position, &tip, color);
+
+        let side = if forward.cross(&na::Vector3::y()).norm() > 0.0 {
+            forward.cross(&na::Vector3::y()).normalize()
+        } else {
+            na::Vector3::x()
+        };
+        let back = tip - forward * size * 2.0;
+        self.line(&tip, &(back + side * size), color);
+        self.line(&tip, &(back - side * size), color);
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn aabb_lines() {
+        let mut vertices = vec![Vertex::new(); 2];
+        vertices[0].position = [-1.0, -1.0, -1.0];
+        vertices[1].position = [1.0, 1.0, 1.0];
+        let aabb = Aabb::from_vertices(&vertices);
+
+        let mut debug = DebugDraw::new();
+        debug.aabb(&aabb, &na::Matrix4::identity(), [1.0, 0.0, 0.0]);
+        assert_eq!(debug.lines.len(), AABB_EDGES.len() * 2);
+
+        // One more drawn primitive means one more box
+        let transform = na::Matrix4::new_translation(&na::Vector3::new(2.0, 0.0, 0.0));
+        debug.aabb(&aabb, &transform, [1.0, 0.0, 0.0]);
+        assert_eq!(debug.lines.len(), AABB_EDGES.len() * 4);
+        assert_eq!(debug.lines[AABB_EDGES.len() * 2].position, [1.0, -1.0, -1.0]);
+
+        debug.clear();
+        assert!(debug.lines.is_empty());
+    }
+}
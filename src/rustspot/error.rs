@@ -0,0 +1,300 @@
+// Copyright © 2021
+// Author: Antonio Caggiano <info@antoniocaggiano.eu>
+// SPDX-License-Identifier: MIT
+
+use std::fmt;
+
+/// Problems found in the geometry of a primitive, each one with the index of the offending vertex
+#[derive(Debug, PartialEq)]
+pub enum MeshError {
+    NanPosition(usize),
+    NanNormal(usize),
+    /// Normal length is too far from 1.0
+    NonUnitNormal(usize),
+    /// Tangent is NaN or zero while the primitive uses normal mapping
+    DegenerateTangent(usize),
+    /// An index refers to a vertex which does not exist
+    IndexOutOfRange {
+        index: usize,
+        vertex_count: usize,
+    },
+    /// Index data length is not a multiple of the index type size
+    InvalidIndexData,
+    /// Primitive restart index does not fit the index type
+    InvalidRestartIndex(u32),
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MeshError::NanPosition(i) => write!(f, "Vertex {} has a NaN position", i),
+            MeshError::NanNormal(i) => write!(f, "Vertex {} has a NaN normal", i),
+            MeshError::NonUnitNormal(i) => write!(f, "Vertex {} has a non-unit normal", i),
+            MeshError::DegenerateTangent(i) => write!(f, "Vertex {} has a degenerate tangent", i),
+            MeshError::IndexOutOfRange {
+                index,
+                vertex_count,
+            } => write!(
+                f,
+                "Index {} is out of range for {} vertices",
+                index, vertex_count
+            ),
+            MeshError::InvalidIndexData => write!(f, "Index data does not match its type"),
+            MeshError::InvalidRestartIndex(i) => {
+                write!(f, "Restart index {} does not fit the index type", i)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+/// Handles of a model which do not resolve, each one with the index of the element holding it
+#[derive(Debug, PartialEq)]
+pub enum ModelError {
+    MissingChild {
+        node: usize,
+        child: usize,
+    },
+    MissingMesh {
+        node: usize,
+        mesh: usize,
+    },
+    MissingCamera {
+        node: usize,
+        camera: usize,
+    },
+    MissingSkin {
+        node: usize,
+        skin: usize,
+    },
+    MissingLight {
+        node: usize,
+        light: usize,
+    },
+    MissingPrimitive {
+        mesh: usize,
+        primitive: usize,
+    },
+    MissingMaterial {
+        primitive: usize,
+        material: usize,
+    },
+    MissingTexture {
+        material: usize,
+        texture: usize,
+    },
+    /// Material without a texture whose color has no pixel texture in the model
+    MissingColor {
+        material: usize,
+    },
+    MissingJoint {
+        skin: usize,
+        joint: usize,
+    },
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModelError::MissingChild { node, child } => {
+                write!(f, "Node {} refers to missing child {}", node, child)
+            }
+            ModelError::MissingMesh { node, mesh } => {
+                write!(f, "Node {} refers to missing mesh {}", node, mesh)
+            }
+            ModelError::MissingCamera { node, camera } => {
+                write!(f, "Node {} refers to missing camera {}", node, camera)
+            }
+            ModelError::MissingSkin { node, skin } => {
+                write!(f, "Node {} refers to missing skin {}", node, skin)
+            }
+            ModelError::MissingLight { node, light } => {
+                write!(f, "Node {} refers to missing light {}", node, light)
+            }
+            ModelError::MissingPrimitive { mesh, primitive } => {
+                write!(f, "Mesh {} refers to missing primitive {}", mesh, primitive)
+            }
+            ModelError::MissingMaterial {
+                primitive,
+                material,
+            } => write!(
+                f,
+                "Primitive {} refers to missing material {}",
+                primitive, material
+            ),
+            ModelError::MissingTexture { material, texture } => write!(
+                f,
+                "Material {} refers to missing texture {}",
+                material, texture
+            ),
+            ModelError::MissingColor { material } => {
+                write!(f, "Material {} has no texture for its color", material)
+            }
+            ModelError::MissingJoint { skin, joint } => {
+                write!(f, "Skin {} refers to missing joint {}", skin, joint)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+/// Texel data or regions which do not fit a texture
+#[derive(Debug, PartialEq)]
+pub enum TextureError {
+    /// Data length in bytes does not match the pixels it should contain
+    DataLength { expected: usize, len: usize },
+    /// Region does not fit within the texture
+    RegionOutOfBounds {
+        x: u32,
+        y: u32,
+        region: crate::Extent2D,
+        extent: crate::Extent2D,
+    },
+    /// Multisample textures can not be updated from main memory
+    Multisample,
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::DataLength { expected, len } => write!(
+                f,
+                "Texture data of {} bytes does not match the {} bytes expected",
+                len, expected
+            ),
+            TextureError::RegionOutOfBounds {
+                x,
+                y,
+                region,
+                extent,
+            } => write!(
+                f,
+                "Region {}x{} at ({}, {}) is out of texture bounds {}x{}",
+                region.width, region.height, x, y, extent.width, extent.height
+            ),
+            TextureError::Multisample => write!(f, "Can not update a multisample texture"),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+/// Errors which can happen while loading resources or creating GL objects
+#[derive(Debug)]
+pub enum RustspotError {
+    Io(std::io::Error),
+    Png(png::DecodingError),
+    Gltf(gltf::Error),
+    /// Compilation log of the shader
+    ShaderCompile(String),
+    /// Linking log of the shader program
+    ShaderLink(String),
+    /// Reason returned by `glCheckFramebufferStatus`
+    FramebufferIncomplete(crate::FramebufferStatus),
+    Mesh(MeshError),
+    Texture(TextureError),
+    /// Data written past the end of a buffer, with its end offset and the buffer size in bytes
+    BufferOverflow {
+        end: usize,
+        size: usize,
+    },
+    /// Malformed Wavefront OBJ, with the offending line
+    Obj(String),
+    /// All the handles of a model which do not resolve
+    Model(Vec<ModelError>),
+}
+
+impl fmt::Display for RustspotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RustspotError::Io(err) => write!(f, "IO error: {}", err),
+            RustspotError::Png(err) => write!(f, "PNG error: {}", err),
+            RustspotError::Gltf(err) => write!(f, "glTF error: {}", err),
+            RustspotError::ShaderCompile(log) => write!(f, "Shader compilation failed: {}", log),
+            RustspotError::ShaderLink(log) => write!(f, "Shader linking failed: {}", log),
+            RustspotError::FramebufferIncomplete(status) => {
+                write!(f, "Framebuffer is not complete: {}", status)
+            }
+            RustspotError::Mesh(err) => write!(f, "Invalid mesh: {}", err),
+            RustspotError::Texture(err) => write!(f, "Invalid texture: {}", err),
+            RustspotError::BufferOverflow { end, size } => write!(
+                f,
+                "Data ending at {} overflows a buffer of {} bytes",
+                end, size
+            ),
+            RustspotError::Obj(err) => write!(f, "OBJ error: {}", err),
+            RustspotError::Model(errs) => {
+                write!(f, "Invalid model:")?;
+                for err in errs {
+                    write!(f, " {}.", err)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for RustspotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RustspotError::Io(err) => Some(err),
+            RustspotError::Png(err) => Some(err),
+            RustspotError::Gltf(err) => Some(err),
+            RustspotError::Mesh(err) => Some(err),
+            RustspotError::Texture(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for RustspotError {
+    fn from(err: std::io::Error) -> Self {
+        RustspotError::Io(err)
+    }
+}
+
+impl From<png::DecodingError> for RustspotError {
+    fn from(err: png::DecodingError) -> Self {
+        match err {
+            png::DecodingError::IoError(err) => RustspotError::Io(err),
+            err => RustspotError::Png(err),
+        }
+    }
+}
+
+impl From<MeshError> for RustspotError {
+    fn from(err: MeshError) -> Self {
+        RustspotError::Mesh(err)
+    }
+}
+
+impl From<TextureError> for RustspotError {
+    fn from(err: TextureError) -> Self {
+        RustspotError::Texture(err)
+    }
+}
+
+impl From<gltf::Error> for RustspotError {
+    fn from(err: gltf::Error) -> Self {
+        match err {
+            gltf::Error::Io(err) => RustspotError::Io(err),
+            err => RustspotError::Gltf(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn missing_file() {
+        match Model::builder("res/missing/missing.gltf") {
+            Err(RustspotError::Io(err)) => assert_eq!(err.kind(), std::io::ErrorKind::NotFound),
+            _ => panic!("Expected an IO error"),
+        }
+    }
+}
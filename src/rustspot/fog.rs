@@ -0,0 +1,20 @@
+// Copyright © 2021
+// Author: Antonio Caggiano <info@antoniocaggiano.eu>
+// SPDX-License-Identifier: MIT
+
+/// Linear distance fog. Fragments closer than `start` are not affected,
+/// while fragments further than `end` get the fog color.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fog {
+    pub color: [f32; 3],
+    /// View-space depth where fog starts
+    pub start: f32,
+    /// View-space depth where fog is complete
+    pub end: f32,
+}
+
+impl Fog {
+    pub fn new(color: [f32; 3], start: f32, end: f32) -> Self {
+        Self { color, start, end }
+    }
+}
@@ -2,12 +2,82 @@
 // Author: Antonio Caggiano <info@antoniocaggiano.eu>
 // SPDX-License-Identifier: MIT
 
+use std::fmt;
+
 use super::*;
 
+/// Reason why a framebuffer is not complete, as returned by `glCheckFramebufferStatus`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FramebufferStatus {
+    /// The default framebuffer is bound but it does not exist
+    Undefined,
+    /// An attachment is not renderable, or it has a zero width or height
+    IncompleteAttachment,
+    /// There are no attachments at all
+    MissingAttachment,
+    /// A draw buffer refers to a color attachment which is not there
+    IncompleteDrawBuffer,
+    /// The read buffer refers to a color attachment which is not there
+    IncompleteReadBuffer,
+    /// The combination of internal formats is not supported by the implementation
+    Unsupported,
+    /// Attachments have different numbers of samples
+    IncompleteMultisample,
+    /// Some attachments are layered while others are not
+    IncompleteLayerTargets,
+    Unknown(gl::types::GLenum),
+}
+
+impl From<gl::types::GLenum> for FramebufferStatus {
+    fn from(status: gl::types::GLenum) -> Self {
+        match status {
+            gl::FRAMEBUFFER_UNDEFINED => FramebufferStatus::Undefined,
+            gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => FramebufferStatus::IncompleteAttachment,
+            gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => FramebufferStatus::MissingAttachment,
+            gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => FramebufferStatus::IncompleteDrawBuffer,
+            gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => FramebufferStatus::IncompleteReadBuffer,
+            gl::FRAMEBUFFER_UNSUPPORTED => FramebufferStatus::Unsupported,
+            gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => FramebufferStatus::IncompleteMultisample,
+            gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => FramebufferStatus::IncompleteLayerTargets,
+            status => FramebufferStatus::Unknown(status),
+        }
+    }
+}
+
+impl fmt::Display for FramebufferStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FramebufferStatus::Undefined => write!(f, "default framebuffer does not exist"),
+            FramebufferStatus::IncompleteAttachment => {
+                write!(f, "an attachment is not renderable or has no size")
+            }
+            FramebufferStatus::MissingAttachment => write!(f, "no image is attached"),
+            FramebufferStatus::IncompleteDrawBuffer => {
+                write!(f, "a draw buffer has no attachment")
+            }
+            FramebufferStatus::IncompleteReadBuffer => {
+                write!(f, "the read buffer has no attachment")
+            }
+            FramebufferStatus::Unsupported => {
+                write!(f, "combination of attachment formats is not supported")
+            }
+            FramebufferStatus::IncompleteMultisample => {
+                write!(f, "attachments have different sample counts")
+            }
+            FramebufferStatus::IncompleteLayerTargets => {
+                write!(f, "attachments are not all layered")
+            }
+            FramebufferStatus::Unknown(status) => write!(f, "unknown status {:#x}", status),
+        }
+    }
+}
+
 pub struct FramebufferBuilder<'a> {
     extent: Extent2D,
     color_texture: Option<&'a Texture>,
     depth_texture: Option<&'a Texture>,
+    /// Whether the depth texture holds stencil as well
+    stencil: bool,
 }
 
 impl<'a> FramebufferBuilder<'a> {
@@ -16,6 +86,7 @@ impl<'a> FramebufferBuilder<'a> {
             extent: Extent2D::default(),
             color_texture: None,
             depth_texture: None,
+            stencil: false,
         }
     }
 
@@ -31,10 +102,19 @@ impl<'a> FramebufferBuilder<'a> {
 
     pub fn depth_attachment(mut self, depth_texture: &'a Texture) -> Self {
         self.depth_texture = Some(depth_texture);
+        self.stencil = false;
+        self
+    }
+
+    /// Attaches a texture created with `Texture::depth_stencil` as both depth and stencil
+    pub fn depth_stencil_attachment(mut self, depth_stencil_texture: &'a Texture) -> Self {
+        self.depth_texture = Some(depth_stencil_texture);
+        self.stencil = true;
         self
     }
 
-    pub fn build(self) -> Framebuffer {
+    /// Fails when the attachments do not make a complete framebuffer
+    pub fn build(self) -> Result<Framebuffer, RustspotError> {
         let mut handle = 0;
         unsafe { gl::GenFramebuffers(1, &mut handle as _) };
 
@@ -42,14 +122,18 @@ impl<'a> FramebufferBuilder<'a> {
         framebuffer.bind();
 
         framebuffer.set_color_attachment(&self.color_texture);
-        framebuffer.set_depth_attachment(&self.depth_texture);
+        if self.stencil {
+            framebuffer.set_depth_stencil_attachment(&self.depth_texture);
+        } else {
+            framebuffer.set_depth_attachment(&self.depth_texture);
+        }
 
-        if !framebuffer.is_complete() {
-            println!("Framebuffer is not complete");
-            super::gl_check();
+        if let Err(status) = framebuffer.status() {
+            log::error!("Framebuffer is not complete: {}", status);
+            return Err(RustspotError::FramebufferIncomplete(status));
         }
 
-        framebuffer
+        Ok(framebuffer)
     }
 }
 
@@ -122,9 +206,32 @@ impl Framebuffer {
         self.set_attachment(gl::DEPTH_ATTACHMENT, depth_texture);
     }
 
-    fn is_complete(&self) -> bool {
+    fn set_depth_stencil_attachment(&mut self, depth_stencil_texture: &Option<&Texture>) {
+        self.set_attachment(gl::DEPTH_STENCIL_ATTACHMENT, depth_stencil_texture);
+    }
+
+    /// Attaches one face of a depth cubemap, from 0 for positive X to 5 for negative Z.
+    /// The framebuffer should be bound.
+    pub fn set_depth_cube_face(&self, cube_texture: &Texture, face: u32) {
+        assert!(face < 6);
+        unsafe {
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                cube_texture.handle,
+                0,
+            );
+        }
+    }
+
+    /// Checks whether the framebuffer is complete. It should be bound.
+    pub fn status(&self) -> Result<(), FramebufferStatus> {
         let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
-        status == gl::FRAMEBUFFER_COMPLETE
+        match status {
+            gl::FRAMEBUFFER_COMPLETE => Ok(()),
+            status => Err(FramebufferStatus::from(status)),
+        }
     }
 
     pub fn bind_read(&self) {
@@ -196,14 +303,17 @@ pub struct CustomFramebuffer {
 }
 
 impl CustomFramebuffer {
-    fn geometry(extent: Extent2D) -> Self {
-        let color_texture = Texture::color(extent, 1);
-        let depth_texture = Texture::depth(extent, 1);
+    /// Multisampled when `samples` is greater than 1, in which case it is resolved when read
+    fn geometry(extent: Extent2D, samples: u32) -> Self {
+        let color_texture = Texture::color(extent, samples);
+        // Stencil is there for effects such as outlines
+        let depth_texture = Texture::depth_stencil(extent, samples);
         let framebuffer = Framebuffer::builder()
             .extent(extent)
             .color_attachment(&color_texture)
-            .depth_attachment(&depth_texture)
-            .build();
+            .depth_stencil_attachment(&depth_texture)
+            .build()
+            .unwrap();
 
         Self {
             framebuffer,
@@ -212,13 +322,30 @@ impl CustomFramebuffer {
         }
     }
 
+    /// Returns a framebuffer with only one color attachment, useful for post-processing
+    pub fn color(extent: Extent2D) -> Self {
+        let color_texture = Texture::color(extent, 1);
+        let framebuffer = Framebuffer::builder()
+            .extent(extent)
+            .color_attachment(&color_texture)
+            .build()
+            .unwrap();
+
+        Self {
+            framebuffer,
+            color_textures: vec![color_texture],
+            depth_texture: None,
+        }
+    }
+
     pub fn shadow() -> Self {
         let extent = Extent2D::new(512, 512);
         let depth_texture = Texture::depth(extent, 1);
         let framebuffer = Framebuffer::builder()
             .extent(extent)
             .depth_attachment(&depth_texture)
-            .build();
+            .build()
+            .unwrap();
 
         Self {
             framebuffer,
@@ -226,6 +353,119 @@ impl CustomFramebuffer {
             depth_texture: Some(depth_texture),
         }
     }
+
+    /// Returns a framebuffer storing moments of depth in a float color attachment.
+    /// A depth attachment is needed for depth testing while rendering them, not for blurring.
+    pub fn moments(extent: Extent2D, depth: bool) -> Self {
+        let color_texture = Texture::moments(extent);
+        let depth_texture = if depth {
+            Some(Texture::depth(extent, 1))
+        } else {
+            None
+        };
+
+        let mut builder = Framebuffer::builder()
+            .extent(extent)
+            .color_attachment(&color_texture);
+        if let Some(depth_texture) = &depth_texture {
+            builder = builder.depth_attachment(depth_texture);
+        }
+        let framebuffer = builder.build().unwrap();
+
+        Self {
+            framebuffer,
+            color_textures: vec![color_texture],
+            depth_texture,
+        }
+    }
+
+    /// Returns a framebuffer with a depth cubemap for point light shadows.
+    /// Faces are attached one at a time with `Framebuffer::set_depth_cube_face`.
+    /// Fails when the first face does not make a complete framebuffer.
+    pub fn shadow_cube(extent: Extent2D) -> Result<Self, RustspotError> {
+        let depth_texture = Texture::depth_cube(extent);
+
+        let mut handle = 0;
+        unsafe { gl::GenFramebuffers(1, &mut handle as _) };
+        let framebuffer = Framebuffer::new(handle, extent);
+        framebuffer.bind();
+        framebuffer.set_depth_cube_face(&depth_texture, 0);
+
+        if let Err(status) = framebuffer.status() {
+            log::error!("Shadow cube framebuffer is not complete: {}", status);
+            return Err(RustspotError::FramebufferIncomplete(status));
+        }
+
+        Ok(Self {
+            framebuffer,
+            color_textures: vec![],
+            depth_texture: Some(depth_texture),
+        })
+    }
+
+    /// Reads back the RGBA pixels of a color attachment, rows from bottom to top.
+    /// Multisampled attachments are resolved first.
+    pub fn read_color(&self, index: usize) -> (Extent2D, Vec<u8>) {
+        let color_texture = &self.color_textures[index];
+        let extent = color_texture.extent;
+
+        // Pixels of a multisampled texture can not be read directly
+        let resolved = if color_texture.samples > 1 {
+            let resolved_texture = Texture::color(extent, 1);
+            let resolved_framebuffer = Framebuffer::builder()
+                .extent(extent)
+                .color_attachment(&resolved_texture)
+                .build()
+                .unwrap();
+            self.framebuffer.bind_read();
+            resolved_framebuffer.bind_draw();
+            unsafe {
+                gl::ReadBuffer(gl::COLOR_ATTACHMENT0 + index as u32);
+                gl::BlitFramebuffer(
+                    0,
+                    0,
+                    extent.width as _,
+                    extent.height as _,
+                    0,
+                    0,
+                    extent.width as _,
+                    extent.height as _,
+                    gl::COLOR_BUFFER_BIT,
+                    gl::NEAREST,
+                );
+            }
+            Some((resolved_framebuffer, resolved_texture))
+        } else {
+            None
+        };
+
+        let mut pixels = vec![0u8; extent.width as usize * extent.height as usize * 4];
+        unsafe {
+            match &resolved {
+                Some((framebuffer, _)) => {
+                    framebuffer.bind_read();
+                    gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+                }
+                None => {
+                    self.framebuffer.bind_read();
+                    gl::ReadBuffer(gl::COLOR_ATTACHMENT0 + index as u32);
+                }
+            }
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                extent.width as _,
+                extent.height as _,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as _,
+            );
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        }
+
+        (extent, pixels)
+    }
 }
 
 impl DrawableOnto for CustomFramebuffer {
@@ -238,30 +478,277 @@ impl DrawableOnto for CustomFramebuffer {
     }
 }
 
+/// Chooses which offscreen buffers a frame allocates
+pub struct FrameBuilder {
+    extent: Extent2D,
+    offscreen_extent: Extent2D,
+    shadow_buffer: bool,
+    geometry_buffer: bool,
+    samples: u32,
+}
+
+impl FrameBuilder {
+    pub fn new() -> Self {
+        Self {
+            extent: Extent2D::default(),
+            offscreen_extent: Extent2D::default(),
+            shadow_buffer: true,
+            geometry_buffer: true,
+            samples: 1,
+        }
+    }
+
+    pub fn extent(mut self, extent: Extent2D) -> Self {
+        self.extent = extent;
+        self
+    }
+
+    pub fn offscreen_extent(mut self, offscreen_extent: Extent2D) -> Self {
+        self.offscreen_extent = offscreen_extent;
+        self
+    }
+
+    /// Disable it when not rendering shadows
+    pub fn shadow_buffer(mut self, enabled: bool) -> Self {
+        self.shadow_buffer = enabled;
+        self
+    }
+
+    /// Disable it when rendering straight to the default framebuffer
+    pub fn geometry_buffer(mut self, enabled: bool) -> Self {
+        self.geometry_buffer = enabled;
+        self
+    }
+
+    /// Samples per pixel of the geometry buffer, 1 meaning no MSAA
+    pub fn samples(mut self, samples: u32) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    pub fn build(self) -> Frame {
+        let shadow_buffer = if self.shadow_buffer {
+            Some(CustomFramebuffer::shadow())
+        } else {
+            None
+        };
+        let geometry_buffer = if self.geometry_buffer {
+            Some(CustomFramebuffer::geometry(
+                self.offscreen_extent,
+                self.samples,
+            ))
+        } else {
+            None
+        };
+        let mut default_framebuffer = DefaultFramebuffer::new(self.extent);
+        // We render offscreen and then present the result to the default framebuffer
+        default_framebuffer.framebuffer.virtual_extent = self.offscreen_extent;
+
+        Frame {
+            shadow_buffer,
+            geometry_buffer,
+            default_framebuffer,
+        }
+    }
+}
+
+impl Default for FrameBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// A frame maintains the state of both offscreen and default framebuffers.
 pub struct Frame {
-    pub shadow_buffer: CustomFramebuffer,
-    pub geometry_buffer: CustomFramebuffer,
+    /// None when the frame was built without it
+    pub shadow_buffer: Option<CustomFramebuffer>,
+    /// None when the frame was built without it
+    pub geometry_buffer: Option<CustomFramebuffer>,
     // This is an option as the user can get the ownership of this when drawing
     pub default_framebuffer: DefaultFramebuffer,
 }
 
 impl Frame {
+    pub fn builder() -> FrameBuilder {
+        FrameBuilder::new()
+    }
+
     pub fn new(extent: Extent2D, offscreen_extent: Extent2D) -> Self {
-        let shadow_buffer = CustomFramebuffer::shadow();
-        let geometry_buffer = CustomFramebuffer::geometry(offscreen_extent);
-        let mut default_framebuffer = DefaultFramebuffer::new(extent);
-        // We render offscreen and then present the result to the default framebuffer
-        default_framebuffer.framebuffer.virtual_extent = offscreen_extent;
+        Self::builder()
+            .extent(extent)
+            .offscreen_extent(offscreen_extent)
+            .build()
+    }
 
-        Self {
-            shadow_buffer,
-            geometry_buffer,
-            default_framebuffer,
-        }
+    /// Panics if the frame was built without a shadow buffer
+    pub fn get_shadow_buffer(&self) -> &CustomFramebuffer {
+        self.shadow_buffer
+            .as_ref()
+            .expect("Frame has no shadow buffer")
+    }
+
+    /// Panics if the frame was built without a geometry buffer
+    pub fn get_geometry_buffer(&self) -> &CustomFramebuffer {
+        self.geometry_buffer
+            .as_ref()
+            .expect("Frame has no geometry buffer")
     }
 
     pub fn get_default_framebuffer(&mut self) -> &DefaultFramebuffer {
         &self.default_framebuffer
     }
 }
+
+/// These tests need a GL context, hence they run only with the headless feature
+#[cfg(all(test, feature = "headless"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn headless_framebuffer() {
+        let _context = TestContext::new();
+
+        let extent = Extent2D::new(32, 32);
+        let color_texture = Texture::color(extent, 1);
+        let depth_texture = Texture::depth(extent, 1);
+        let framebuffer = Framebuffer::builder()
+            .extent(extent)
+            .color_attachment(&color_texture)
+            .depth_attachment(&depth_texture)
+            .build()
+            .unwrap();
+
+        framebuffer.bind();
+        assert_eq!(framebuffer.status(), Ok(()));
+        assert!(color_texture.extent == extent);
+    }
+
+    #[test]
+    fn missing_attachment() {
+        let _context = TestContext::new();
+
+        match Framebuffer::builder().extent(Extent2D::new(32, 32)).build() {
+            Err(RustspotError::FramebufferIncomplete(status)) => {
+                assert_eq!(status, FramebufferStatus::MissingAttachment)
+            }
+            _ => panic!("Expected an incomplete framebuffer"),
+        }
+    }
+
+    #[test]
+    fn shadow_cube_faces() {
+        let _context = TestContext::new();
+
+        let extent = Extent2D::new(16, 16);
+        let shadow_cube = CustomFramebuffer::shadow_cube(extent).unwrap();
+        let cube_texture = shadow_cube.depth_texture.as_ref().unwrap();
+        assert_eq!(cube_texture.target, gl::TEXTURE_CUBE_MAP);
+
+        shadow_cube.framebuffer.bind();
+        cube_texture.bind();
+        for face in 0..6 {
+            let mut width = 0;
+            let mut format = 0;
+            unsafe {
+                let target = gl::TEXTURE_CUBE_MAP_POSITIVE_X + face;
+                gl::GetTexLevelParameteriv(target, 0, gl::TEXTURE_WIDTH, &mut width);
+                gl::GetTexLevelParameteriv(target, 0, gl::TEXTURE_INTERNAL_FORMAT, &mut format);
+            }
+            assert_eq!(width as u32, extent.width);
+            assert_eq!(format as u32, gl::DEPTH_COMPONENT32F);
+
+            shadow_cube
+                .framebuffer
+                .set_depth_cube_face(cube_texture, face);
+            assert_eq!(shadow_cube.framebuffer.status(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn depth_stencil_framebuffer() {
+        let _context = TestContext::new();
+
+        let extent = Extent2D::new(32, 32);
+        let color_texture = Texture::color(extent, 1);
+        let depth_stencil_texture = Texture::depth_stencil(extent, 1);
+        let framebuffer = Framebuffer::builder()
+            .extent(extent)
+            .color_attachment(&color_texture)
+            .depth_stencil_attachment(&depth_stencil_texture)
+            .build()
+            .unwrap();
+
+        framebuffer.bind();
+        assert_eq!(framebuffer.status(), Ok(()));
+
+        let mut stencil_bits = 0;
+        unsafe {
+            gl::GetFramebufferAttachmentParameteriv(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_STENCIL_ATTACHMENT,
+                gl::FRAMEBUFFER_ATTACHMENT_STENCIL_SIZE,
+                &mut stencil_bits,
+            )
+        };
+        assert_eq!(stencil_bits, 8);
+    }
+
+    #[test]
+    fn frame_without_shadow_buffer() {
+        let _context = TestContext::new();
+
+        let extent = Extent2D::new(32, 32);
+        let frame = Frame::builder()
+            .extent(extent)
+            .offscreen_extent(extent)
+            .shadow_buffer(false)
+            .build();
+        assert!(frame.shadow_buffer.is_none());
+        assert!(frame.get_geometry_buffer().get_framebuffer().extent == extent);
+
+        let frame = Frame::builder().geometry_buffer(false).build();
+        assert!(frame.geometry_buffer.is_none());
+        assert!(frame.shadow_buffer.is_some());
+    }
+
+    #[test]
+    fn multisampled_geometry_buffer() {
+        let _context = TestContext::new();
+
+        let extent = Extent2D::new(32, 32);
+        let frame = Frame::builder()
+            .extent(extent)
+            .offscreen_extent(extent)
+            .samples(4)
+            .build();
+        let geometry_buffer = frame.get_geometry_buffer();
+        let color_texture = &geometry_buffer.color_textures[0];
+        assert_eq!(color_texture.samples, 4);
+        assert_eq!(color_texture.target, gl::TEXTURE_2D_MULTISAMPLE);
+
+        geometry_buffer.framebuffer.bind();
+        assert_eq!(geometry_buffer.framebuffer.status(), Ok(()));
+
+        // Resolved when read back
+        let (_, pixels) = geometry_buffer.read_color(0);
+        assert_eq!(pixels.len(), 32 * 32 * 4);
+    }
+
+    #[test]
+    fn read_color_attachment() {
+        let _context = TestContext::new();
+
+        let extent = Extent2D::new(8, 4);
+        let geometry_buffer = CustomFramebuffer::geometry(extent, 1);
+        geometry_buffer.framebuffer.bind();
+        unsafe {
+            gl::ClearColor(1.0, 0.0, 1.0, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+        }
+
+        let (read_extent, pixels) = geometry_buffer.read_color(0);
+        assert!(read_extent == extent);
+        assert_eq!(pixels.len(), 8 * 4 * 4);
+        assert!(pixels.chunks(4).all(|pixel| pixel == [255, 0, 255, 255]));
+    }
+}
@@ -1,4 +1,4 @@
-use std::ffi::CStr;
+use std::{cell::Cell, ffi::CStr};
 
 use nalgebra as na;
 
@@ -13,6 +13,10 @@ pub struct Vertex {
     pub normal: na::Vector3<f32>,
     pub tangent: na::Vector3<f32>,
     pub bitangent: na::Vector3<f32>,
+    /// Indices of the joints of the skin influencing this vertex
+    pub joints: [f32; 4],
+    /// How much each joint influences this vertex, all zeros when not skinned
+    pub weights: [f32; 4],
 }
 
 impl Vertex {
@@ -24,6 +28,8 @@ impl Vertex {
             normal: na::Vector3::z(),
             tangent: na::Vector3::zeros(),
             bitangent: na::Vector3::zeros(),
+            joints: [0.0; 4],
+            weights: [0.0; 4],
         }
     }
 }
@@ -61,13 +67,29 @@ impl Color {
 
 pub struct Vbo {
     handle: u32,
+    usage: gl::types::GLenum,
+    /// Bytes of storage allocated by the last `upload`
+    size: usize,
 }
 
 impl Vbo {
     pub fn new() -> Vbo {
+        Self::with_usage(gl::STATIC_DRAW)
+    }
+
+    /// Creates a buffer meant to be updated frequently, such as per-frame data
+    pub fn new_dynamic() -> Vbo {
+        Self::with_usage(gl::DYNAMIC_DRAW)
+    }
+
+    fn with_usage(usage: gl::types::GLenum) -> Vbo {
         let mut handle = 0;
         unsafe { gl::GenBuffers(1, &mut handle) };
-        Vbo { handle }
+        Vbo {
+            handle,
+            usage,
+            size: 0,
+        }
     }
 
     pub fn bind(&self) {
@@ -76,15 +98,44 @@ impl Vbo {
 
     pub fn upload<T>(&mut self, vertices: &[T]) {
         self.bind();
+        self.size = std::mem::size_of_val(vertices);
         unsafe {
             gl::BufferData(
                 gl::ARRAY_BUFFER,
-                (vertices.len() * std::mem::size_of::<T>()) as isize,
+                self.size as isize,
                 vertices.as_ptr() as *const libc::c_void,
-                gl::STATIC_DRAW,
+                self.usage,
             )
         };
     }
+
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
+    /// Overwrites part of the buffer starting at `offset`, expressed in number of `T` elements.
+    /// The buffer storage should already be big enough, see `upload`.
+    pub fn update<T>(&self, offset: usize, data: &[T]) -> Result<(), RustspotError> {
+        let start = offset * std::mem::size_of::<T>();
+        let len = std::mem::size_of_val(data);
+        if start + len > self.size {
+            return Err(RustspotError::BufferOverflow {
+                end: start + len,
+                size: self.size,
+            });
+        }
+
+        self.bind();
+        unsafe {
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                start as isize,
+                len as isize,
+                data.as_ptr() as *const libc::c_void,
+            )
+        };
+        Ok(())
+    }
 }
 
 impl Drop for Vbo {
@@ -155,31 +206,215 @@ impl Drop for Vao {
     }
 }
 
+/// Where a bounding volume lies with respect to the frustum of a camera
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Containment {
+    Outside,
+    /// Partially within the frustum, or close to its corners
+    Intersecting,
+    Inside,
+}
+
+/// Kind of projection of a camera, with the parameter which is specific to it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CameraMode {
+    /// Vertical field of view in radians
+    Perspective { fov: f32 },
+    /// Height of the view volume in world units
+    Orthographic { height: f32 },
+}
+
 /// A node can refer to a camera to apply a transform to place it in the scene
 pub struct Camera {
     pub proj: na::Matrix4<f32>,
+
+    // Parameters the projection is built from
+    mode: CameraMode,
+    aspect: f32,
+    near: f32,
+    far: f32,
+
+    /// Minimum and maximum field of view reachable by zooming
+    fov_limits: (f32, f32),
 }
 
 impl Camera {
-    pub fn orthographic(width: u32, height: u32, near: f32, far: f32) -> Camera {
-        let proj = na::Orthographic3::new(
-            -(width as f32) / 2.0,
-            width as f32 / 2.0,
-            -(height as f32) / 2.0,
-            (height as f32) / 2.0,
+    fn new(mode: CameraMode, aspect: f32, near: f32, far: f32) -> Camera {
+        let mut camera = Camera {
+            proj: na::Matrix4::identity(),
+            mode,
+            aspect,
             near,
             far,
-        );
-        Camera {
-            proj: proj.to_homogeneous(),
-        }
+            fov_limits: (
+                std::f32::consts::PI / 32.0,
+                std::f32::consts::FRAC_PI_2 + std::f32::consts::FRAC_PI_4,
+            ),
+        };
+        camera.update_proj();
+        camera
+    }
+
+    pub fn orthographic(width: u32, height: u32, near: f32, far: f32) -> Camera {
+        let mode = CameraMode::Orthographic {
+            height: height as f32,
+        };
+        Self::new(mode, width as f32 / height as f32, near, far)
+    }
+
+    /// Pixel space camera for 2D overlays, where (0, 0) is the top-left corner and
+    /// (width, height) the bottom-right one. Same projection used for the GUI.
+    /// Changing its mode centers the view volume again.
+    pub fn orthographic_2d(width: u32, height: u32) -> Camera {
+        let mut camera = Self::orthographic(width, height, -1.0, 1.0);
+        let proj = na::Orthographic3::new(0.0, width as f32, height as f32, 0.0, -1.0, 1.0);
+        camera.proj = proj.to_homogeneous();
+        camera
     }
 
     pub fn perspective(width: f32, height: f32) -> Camera {
-        let proj = na::Perspective3::new(width / height, 3.14 / 4.0, 0.1, 100.0);
-        Camera {
-            proj: proj.to_homogeneous(),
+        let mode = CameraMode::Perspective { fov: 3.14 / 4.0 };
+        Self::new(mode, width / height, 0.1, 100.0)
+    }
+
+    pub fn get_mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    /// Switches between perspective and orthographic projection, keeping aspect ratio
+    /// and near and far planes
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        self.mode = mode;
+        self.update_proj();
+    }
+
+    pub fn get_aspect(&self) -> f32 {
+        self.aspect
+    }
+
+    /// Sets how far `zoom` can narrow or widen the field of view of a perspective camera
+    pub fn set_fov_limits(&mut self, min: f32, max: f32) {
+        assert!(min > 0.0 && min <= max);
+        self.fov_limits = (min, max);
+    }
+
+    pub fn get_fov_limits(&self) -> (f32, f32) {
+        self.fov_limits
+    }
+
+    /// Zooms in for positive `delta` and out for negative `delta`, scaling the field of view
+    /// by `e^-delta` within its limits, such as `0.1` for each step of the mouse wheel.
+    /// Unlike moving the camera node forward, the point of view does not change.
+    /// Orthographic cameras scale the height of their view volume instead.
+    pub fn zoom(&mut self, delta: f32) {
+        let scale = (-delta).exp();
+        self.mode = match self.mode {
+            CameraMode::Perspective { fov } => {
+                let (min, max) = self.fov_limits;
+                let fov = (fov * scale).max(min).min(max);
+                CameraMode::Perspective { fov }
+            }
+            CameraMode::Orthographic { height } => CameraMode::Orthographic {
+                height: height * scale,
+            },
+        };
+        self.update_proj();
+    }
+
+    fn update_proj(&mut self) {
+        self.proj = match self.mode {
+            CameraMode::Perspective { fov } => {
+                na::Perspective3::new(self.aspect, fov, self.near, self.far).to_homogeneous()
+            }
+            CameraMode::Orthographic { height } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * self.aspect;
+                na::Orthographic3::new(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.near,
+                    self.far,
+                )
+                .to_homogeneous()
+            }
+        };
+    }
+
+    /// Returns left, right, bottom, top, near, and far planes of the frustum in world space.
+    /// Each plane is `(a, b, c, d)` where `(a, b, c)` is the normal pointing inside,
+    /// so that a point `p` is inside when `a * p.x + b * p.y + c * p.z + d >= 0`.
+    pub fn frustum_planes(&self, camera_node: &Node) -> [na::Vector4<f32>; 6] {
+        let m = self.proj * camera_node.trs.get_view();
+        let (r0, r1, r2, r3) = (m.row(0), m.row(1), m.row(2), m.row(3));
+
+        let mut planes = [
+            (r3 + r0).transpose(),
+            (r3 - r0).transpose(),
+            (r3 + r1).transpose(),
+            (r3 - r1).transpose(),
+            (r3 + r2).transpose(),
+            (r3 - r2).transpose(),
+        ];
+
+        // Normalize so that distances are in world units
+        for plane in planes.iter_mut() {
+            *plane /= plane.xyz().norm();
         }
+
+        planes
+    }
+
+    /// Returns the corners of the frustum in world space, the four of the near plane first
+    pub fn frustum_corners(&self, camera_node: &Node) -> [na::Vector3<f32>; 8] {
+        let inverse = (self.proj * camera_node.trs.get_view())
+            .try_inverse()
+            .unwrap_or_else(na::Matrix4::identity);
+
+        let mut corners = [na::Vector3::zeros(); 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            // Counter-clockwise from the bottom left of the NDC cube
+            let x = if i % 4 == 1 || i % 4 == 2 { 1.0 } else { -1.0 };
+            let y = if i % 4 >= 2 { 1.0 } else { -1.0 };
+            let z = if i < 4 { -1.0 } else { 1.0 };
+            *corner = inverse.transform_point(&na::Point3::new(x, y, z)).coords;
+        }
+        corners
+    }
+
+    /// Classifies a sphere against the frustum planes. It is a cheap test, but spheres
+    /// near the corners of the frustum may be found intersecting while being outside.
+    pub fn sphere_in_frustum(
+        planes: &[na::Vector4<f32>; 6],
+        center: &na::Vector3<f32>,
+        radius: f32,
+    ) -> Containment {
+        let mut ret = Containment::Inside;
+        for plane in planes.iter() {
+            let distance = plane.xyz().dot(center) + plane.w;
+            if distance < -radius {
+                return Containment::Outside;
+            }
+            if distance < radius {
+                ret = Containment::Intersecting;
+            }
+        }
+        ret
+    }
+
+    /// Returns whether a box is at least partially within the frustum planes,
+    /// checking the corner of the box which is farthest along the normal of each plane
+    pub fn is_aabb_visible(planes: &[na::Vector4<f32>; 6], aabb: &Aabb) -> bool {
+        planes.iter().all(|plane| {
+            let pick = |normal: f32, min: f32, max: f32| if normal >= 0.0 { max } else { min };
+            let farthest = na::Vector3::new(
+                pick(plane.x, aabb.min.x, aabb.max.x),
+                pick(plane.y, aabb.min.y, aabb.max.y),
+                pick(plane.z, aabb.min.z, aabb.max.z),
+            );
+            plane.xyz().dot(&farthest) + plane.w >= 0.0
+        })
     }
 
     pub fn bind(&self, program: &ShaderProgram, view: &Node) {
@@ -197,6 +432,14 @@ impl Camera {
 pub struct Trs {
     isometry: na::Isometry3<f32>,
     scale: na::Vector3<f32>,
+
+    // Matrices are computed on demand and cached until the next change
+    matrix: Cell<Option<na::Matrix4<f32>>>,
+    view: Cell<Option<na::Matrix4<f32>>>,
+
+    /// How many times the model matrix has been computed
+    #[cfg(test)]
+    matrix_computations: Cell<u32>,
 }
 
 impl Trs {
@@ -204,9 +447,19 @@ impl Trs {
         Self {
             isometry: na::Isometry3::identity(),
             scale: na::Vector3::new(1.0, 1.0, 1.0),
+            matrix: Cell::new(None),
+            view: Cell::new(None),
+            #[cfg(test)]
+            matrix_computations: Cell::new(0),
         }
     }
 
+    /// Invalidates cached matrices, to be called by every mutating method
+    fn set_dirty(&mut self) {
+        self.matrix.set(None);
+        self.view.set(None);
+    }
+
     pub fn get_translation(&self) -> na::Vector3<f32> {
         na::Vector3::new(
             self.isometry.translation.x,
@@ -216,31 +469,77 @@ impl Trs {
     }
 
     pub fn get_matrix(&self) -> na::Matrix4<f32> {
-        self.isometry
+        if let Some(matrix) = self.matrix.get() {
+            return matrix;
+        }
+
+        #[cfg(test)]
+        self.matrix_computations
+            .set(self.matrix_computations.get() + 1);
+
+        let matrix = self
+            .isometry
             .to_homogeneous()
-            .prepend_nonuniform_scaling(&self.scale)
+            .prepend_nonuniform_scaling(&self.scale);
+        self.matrix.set(Some(matrix));
+        matrix
     }
 
     pub fn get_view(&self) -> na::Matrix4<f32> {
-        self.isometry.inverse().to_homogeneous()
+        if let Some(view) = self.view.get() {
+            return view;
+        }
+
+        let view = self.isometry.inverse().to_homogeneous();
+        self.view.set(Some(view));
+        view
     }
 
     pub fn rotate(&mut self, rotation: &na::Unit<na::Quaternion<f32>>) {
+        self.set_dirty();
         self.isometry.append_rotation_mut(&rotation);
     }
 
+    /// Replaces the rotation, keeping translation and scale
+    pub fn set_rotation(&mut self, rotation: &na::UnitQuaternion<f32>) {
+        self.set_dirty();
+        self.isometry.rotation = *rotation;
+    }
+
+    /// Returns the rotation as angles in radians around the X, Y, and Z axes,
+    /// the same ones accepted by `set_euler`
+    pub fn get_euler(&self) -> (f32, f32, f32) {
+        self.isometry.rotation.euler_angles()
+    }
+
+    /// Replaces the rotation with one made of angles in radians around the X, Y, and Z axes,
+    /// applied in this order. Useful for driving orientation with sliders.
+    pub fn set_euler(&mut self, x: f32, y: f32, z: f32) {
+        self.set_dirty();
+        self.isometry.rotation = na::UnitQuaternion::from_euler_angles(x, y, z);
+    }
+
     pub fn translate(&mut self, x: f32, y: f32, z: f32) {
+        self.set_dirty();
         self.isometry
             .append_translation_mut(&na::Translation3::new(x, y, z));
     }
 
+    /// Moves to an absolute position, unlike `translate` which moves relatively to the current one
+    pub fn set_translation(&mut self, x: f32, y: f32, z: f32) {
+        self.set_dirty();
+        self.isometry.translation = na::Translation3::new(x, y, z);
+    }
+
     pub fn set_scale(&mut self, x: f32, y: f32, z: f32) {
+        self.set_dirty();
         self.scale.x = x;
         self.scale.y = y;
         self.scale.z = z;
     }
 
     pub fn scale(&mut self, x: f32, y: f32, z: f32) {
+        self.set_dirty();
         self.scale.x *= x;
         self.scale.y *= y;
         self.scale.z *= z;
@@ -284,7 +583,7 @@ pub fn gl_check() {
     }
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Extent2D {
     pub width: u32,
     pub height: u32,
@@ -302,10 +601,57 @@ impl Default for Extent2D {
     }
 }
 
+/// Rectangle of a framebuffer in pixels, starting from its bottom-left corner
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Returns a viewport covering the whole extent
+    pub fn from_extent(extent: Extent2D) -> Self {
+        Self::new(0, 0, extent.width, extent.height)
+    }
+
+    /// Splits the viewport in two halves side by side, useful for split-screen
+    pub fn split_horizontal(&self) -> (Viewport, Viewport) {
+        let left_width = self.width / 2;
+        let left = Viewport::new(self.x, self.y, left_width, self.height);
+        let right = Viewport::new(
+            self.x + left_width as i32,
+            self.y,
+            self.width - left_width,
+            self.height,
+        );
+        (left, right)
+    }
+
+    /// Sets this as the current GL viewport
+    pub fn apply(&self) {
+        unsafe { gl::Viewport(self.x, self.y, self.width as _, self.height as _) };
+    }
+}
+
 pub struct Video {
     system: sdl2::VideoSubsystem,
     window: sdl2::video::Window,
     pub gl: sdl2::video::GLContext,
+    /// Profile of the context actually created, which may differ from the requested one
+    profile: sdl2::video::GLProfile,
+    /// Version of the context actually created, at least the requested one
+    version: (u8, u8),
 }
 
 impl Video {
@@ -325,6 +671,42 @@ impl Video {
         }
     }
 
+    /// Reads profile and version of the current context from GL, as SDL
+    /// only reports the attributes which were requested
+    fn query_context() -> (sdl2::video::GLProfile, (u8, u8)) {
+        let (major, minor) = Gfx::get_gl_version();
+
+        let version = unsafe { gl::GetString(gl::VERSION) };
+        let is_gles = !version.is_null()
+            && unsafe { CStr::from_ptr(version as _) }
+                .to_bytes()
+                .starts_with(b"OpenGL ES");
+
+        let profile = if is_gles {
+            sdl2::video::GLProfile::GLES
+        } else {
+            let mut mask = 0;
+            unsafe { gl::GetIntegerv(gl::CONTEXT_PROFILE_MASK, &mut mask) };
+            if mask as u32 & gl::CONTEXT_COMPATIBILITY_PROFILE_BIT != 0 {
+                sdl2::video::GLProfile::Compatibility
+            } else {
+                sdl2::video::GLProfile::Core
+            }
+        };
+
+        (profile, (major as u8, minor as u8))
+    }
+
+    /// Returns the profile of the GL context
+    pub fn profile(&self) -> sdl2::video::GLProfile {
+        self.profile
+    }
+
+    /// Returns the `(major, minor)` version of the GL context
+    pub fn version(&self) -> (u8, u8) {
+        self.version
+    }
+
     pub fn get_drawable_extent(&self) -> Extent2D {
         // Default framebuffer drawable size could be different than window size depending on DPI
         let (width, height) = self.window.drawable_size();
@@ -337,25 +719,39 @@ impl Video {
     }
 
     fn new(sdl: &sdl2::Sdl, extent: Extent2D) -> Self {
+        Self::create(sdl, extent, false)
+    }
+
+    /// Creates a GL context with a window which is never shown
+    #[cfg(feature = "headless")]
+    fn new_hidden(sdl: &sdl2::Sdl, extent: Extent2D) -> Self {
+        Self::create(sdl, extent, true)
+    }
+
+    fn create(sdl: &sdl2::Sdl, extent: Extent2D, hidden: bool) -> Self {
         let system = sdl.video().expect("Failed initializing video");
 
         let attr = system.gl_attr();
         attr.set_context_profile(Self::get_context_profile());
         let (major, minor) = Self::get_context_version();
         attr.set_context_version(major, minor);
+        attr.set_stencil_size(8);
 
         // We need these only if rendering directly onto default framebuffer
         // attr.set_multisample_buffers(1);
         // attr.set_multisample_samples(2);
 
-        let window = match system
-            .window("Test", extent.width, extent.height)
+        let mut builder = system.window("Test", extent.width, extent.height);
+        builder
             .opengl()
             .allow_highdpi()
             .position_centered()
-            .resizable()
-            .build()
-        {
+            .resizable();
+        if hidden {
+            builder.hidden();
+        }
+
+        let window = match builder.build() {
             Ok(w) => w,
             Err(_) => {
                 panic!("Failed initializing SDL window");
@@ -368,7 +764,15 @@ impl Video {
 
         gl::load_with(|symbol| system.gl_get_proc_address(symbol) as *const _);
 
-        Self { system, window, gl }
+        let (profile, version) = Self::query_context();
+
+        Self {
+            system,
+            window,
+            gl,
+            profile,
+            version,
+        }
     }
 }
 
@@ -376,13 +780,19 @@ extern "system" fn debug_callback(
     _source: gl::types::GLenum,
     _type: gl::types::GLenum,
     _id: gl::types::GLenum,
-    _severity: gl::types::GLenum,
+    severity: gl::types::GLenum,
     _length: gl::types::GLsizei,
     message: *const gl::types::GLchar,
     _user_param: *mut libc::c_void,
 ) {
     let msg = unsafe { CStr::from_ptr(message as _) };
-    println!("{}", msg.to_str().unwrap());
+    let msg = msg.to_string_lossy();
+    match severity {
+        gl::DEBUG_SEVERITY_HIGH => log::error!("{}", msg),
+        gl::DEBUG_SEVERITY_MEDIUM => log::warn!("{}", msg),
+        gl::DEBUG_SEVERITY_LOW => log::info!("{}", msg),
+        _ => log::debug!("{}", msg),
+    }
 }
 
 pub struct Gfx {
@@ -397,23 +807,38 @@ pub struct Gfx {
 
 impl Gfx {
     pub fn new(sdl: &sdl2::Sdl, extent: Extent2D, offscreen_extent: Extent2D) -> Self {
+        Self::with_frame(
+            sdl,
+            extent,
+            Frame::builder().offscreen_extent(offscreen_extent),
+        )
+    }
+
+    /// The extent of the default framebuffer is taken from the window, whatever the builder says
+    pub fn with_frame(sdl: &sdl2::Sdl, extent: Extent2D, frame: FrameBuilder) -> Self {
         let video = Video::new(sdl, extent);
+        Self::from_video(video, frame)
+    }
 
-        if !cfg!(target_os = "macos") {
-            unsafe {
-                gl::Enable(gl::DEBUG_OUTPUT);
-                gl::DebugMessageCallback(Some(debug_callback), std::ptr::null());
-            }
-        }
+    /// Creates a GL context without showing any window, so that GL objects
+    /// can be created and rendered offscreen, in tests for instance
+    #[cfg(feature = "headless")]
+    pub fn new_headless(sdl: &sdl2::Sdl, extent: Extent2D) -> Self {
+        let video = Video::new_hidden(sdl, extent);
+        Self::from_video(video, Frame::builder().offscreen_extent(extent))
+    }
 
-        let gl_version = Self::get_gl_version();
-        println!("OpenGL v{}.{}", gl_version.0, gl_version.1);
+    fn from_video(video: Video, frame: FrameBuilder) -> Self {
+        Self::set_gl_debug_output(cfg!(debug_assertions));
+
+        let (major, minor) = video.version();
+        log::info!("OpenGL {:?} v{}.{}", video.profile(), major, minor);
 
         let mut gui = imgui::Context::create();
         let renderer = Renderer::new(&mut gui.fonts());
 
         let extent = video.get_drawable_extent();
-        let frame = Some(Frame::new(extent, offscreen_extent));
+        let frame = Some(frame.extent(extent).build());
 
         Self {
             frame,
@@ -423,6 +848,37 @@ impl Gfx {
         }
     }
 
+    /// Sets the background color of the geometry pass and of blits, see `Renderer::set_clear_color`
+    pub fn set_clear_color(&mut self, color: [f32; 4]) {
+        self.renderer.set_clear_color(color);
+    }
+
+    /// Enables or disables logging of GL debug messages through the `log` crate
+    pub fn set_gl_debug(&self, enabled: bool) {
+        Self::set_gl_debug_output(enabled);
+    }
+
+    fn set_gl_debug_output(enabled: bool) {
+        // Debug output is not available on macOS
+        if cfg!(target_os = "macos") {
+            return;
+        }
+
+        unsafe {
+            if enabled {
+                gl::Enable(gl::DEBUG_OUTPUT);
+                gl::DebugMessageCallback(Some(debug_callback), std::ptr::null());
+            } else {
+                gl::Disable(gl::DEBUG_OUTPUT);
+            }
+        }
+    }
+
+    /// Profile of the GL context, useful to pick shaders and features at runtime
+    pub fn get_gl_profile(&self) -> sdl2::video::GLProfile {
+        self.video.profile()
+    }
+
     pub fn get_gl_version() -> (i32, i32) {
         let (mut major, mut minor) = (0, 0);
         unsafe {
@@ -432,6 +888,18 @@ impl Gfx {
         (major, minor)
     }
 
+    /// Returns whether the current GL context supports an extension, e.g. `GL_EXT_texture_filter_anisotropic`
+    pub fn has_extension(name: &str) -> bool {
+        let mut count = 0;
+        unsafe { gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count) };
+
+        (0..count as u32).any(|i| {
+            let extension = unsafe { gl::GetStringi(gl::EXTENSIONS, i) };
+            !extension.is_null()
+                && unsafe { CStr::from_ptr(extension as _) }.to_bytes() == name.as_bytes()
+        })
+    }
+
     /// Borrow the frame
     pub fn get_frame(&self) -> &Frame {
         self.frame.as_ref().unwrap()
@@ -441,6 +909,11 @@ impl Gfx {
         self.frame.as_mut().unwrap()
     }
 
+    /// Lends the renderer and the current frame to `f`, such as within `Spot::run`
+    pub fn render_with<F: FnOnce(&mut Renderer, &Frame)>(&mut self, f: F) {
+        f(&mut self.renderer, self.frame.as_ref().unwrap())
+    }
+
     pub fn next_frame(&mut self) -> Frame {
         self.frame.take().unwrap()
     }
@@ -450,6 +923,8 @@ impl Gfx {
         self.video.window.gl_swap_window();
     }
 
+    /// Advances the GUI by `delta` and syncs the extent of the default framebuffer.
+    /// Time of shaders is `Renderer::delta`, which follows the game instead.
     pub fn update(&mut self, delta: Duration, input: &Input) {
         let extent = self.video.get_drawable_extent();
         let window_extent = self.video.get_window_extent();
@@ -474,7 +949,245 @@ impl Gfx {
         // Sync framebuffer extent value we store as well
         let frame = self.get_frame_mut();
         frame.default_framebuffer.framebuffer.extent = extent;
+    }
+}
+
+/// SDL and a headless GL context for tests. Only one `Sdl` can exist at a time, while tests
+/// run in parallel, so a lock is held until the context is dropped, one test after the other.
+#[cfg(all(test, feature = "headless"))]
+pub(crate) struct TestContext {
+    pub gfx: Gfx,
+    pub sdl: sdl2::Sdl,
+    _lock: std::sync::MutexGuard<'static, ()>,
+}
+
+#[cfg(all(test, feature = "headless"))]
+impl TestContext {
+    pub fn new() -> Self {
+        static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+        // A failing test poisons the lock, which is no reason to fail the following ones
+        let lock = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let sdl = sdl2::init().expect("Failed to initialize SDL");
+        let gfx = Gfx::new_headless(&sdl, Extent2D::new(64, 64));
+        Self {
+            gfx,
+            sdl,
+            _lock: lock,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn camera_mode() {
+        let mut camera = Camera::perspective(64.0, 32.0);
+        assert_eq!(camera.get_aspect(), 2.0);
+
+        camera.set_mode(CameraMode::Orthographic { height: 10.0 });
+        let ortho = na::Orthographic3::new(-10.0, 10.0, -5.0, 5.0, 0.1, 100.0);
+        assert_eq!(camera.proj, ortho.to_homogeneous());
+
+        let fov = std::f32::consts::FRAC_PI_2;
+        camera.set_mode(CameraMode::Perspective { fov });
+        let perspective = na::Perspective3::new(2.0, fov, 0.1, 100.0);
+        assert_eq!(camera.proj, perspective.to_homogeneous());
+        assert_eq!(camera.get_mode(), CameraMode::Perspective { fov });
+
+        // Same projection as before
+        let camera = Camera::orthographic(64, 32, 0.1, 1.0);
+        let ortho = na::Orthographic3::new(-32.0, 32.0, -16.0, 16.0, 0.1, 1.0);
+        assert_eq!(camera.proj, ortho.to_homogeneous());
+    }
+
+    #[test]
+    fn zoom() {
+        let mut camera = Camera::perspective(64.0, 32.0);
+        camera.set_fov_limits(0.25, 1.0);
+        let fov = |camera: &Camera| match camera.get_mode() {
+            CameraMode::Perspective { fov } => fov,
+            _ => panic!("Expected a perspective camera"),
+        };
+        let before = fov(&camera);
+
+        camera.zoom(0.1);
+        assert!(fov(&camera) < before);
+        let perspective = na::Perspective3::new(2.0, fov(&camera), 0.1, 100.0);
+        assert_eq!(camera.proj, perspective.to_homogeneous());
+
+        camera.zoom(100.0);
+        assert_eq!(fov(&camera), 0.25);
+        camera.zoom(-100.0);
+        assert_eq!(fov(&camera), 1.0);
+
+        let mut camera = Camera::orthographic(4, 2, 0.1, 1.0);
+        camera.zoom(std::f32::consts::LN_2);
+        assert_eq!(camera.get_mode(), CameraMode::Orthographic { height: 1.0 });
+    }
+
+    #[test]
+    fn sphere_in_frustum() {
+        // Looking towards -Z, from -2 to 2 along X, from -1 to 1 along Y, from -1 to -3 along Z
+        let camera = Camera::orthographic(4, 2, 1.0, 3.0);
+        let planes = camera.frustum_planes(&Node::new());
+
+        let center = na::Vector3::new(0.0, 0.0, -2.0);
+        assert_eq!(
+            Camera::sphere_in_frustum(&planes, &center, 0.5),
+            Containment::Inside
+        );
+        assert_eq!(
+            Camera::sphere_in_frustum(&planes, &center, 1.5),
+            Containment::Intersecting
+        );
+        let behind = na::Vector3::new(0.0, 0.0, 1.0);
+        assert_eq!(
+            Camera::sphere_in_frustum(&planes, &behind, 0.5),
+            Containment::Outside
+        );
+        let right = na::Vector3::new(3.0, 0.0, -2.0);
+        assert_eq!(
+            Camera::sphere_in_frustum(&planes, &right, 0.5),
+            Containment::Outside
+        );
+
+        // Close to a corner, the sphere intersects two planes while being outside
+        let corner = na::Vector3::new(2.6, 1.6, -2.0);
+        assert_eq!(
+            Camera::sphere_in_frustum(&planes, &corner, 0.7),
+            Containment::Intersecting
+        );
+        let aabb = Aabb::new(corner.add_scalar(-0.5), corner.add_scalar(0.5));
+        assert!(!Camera::is_aabb_visible(&planes, &aabb));
+        let aabb = Aabb::new(center.add_scalar(-0.5), center.add_scalar(0.5));
+        assert!(Camera::is_aabb_visible(&planes, &aabb));
+    }
+
+    #[test]
+    fn frustum_corners() {
+        let camera = Camera::orthographic(4, 2, 1.0, 3.0);
+        let corners = camera.frustum_corners(&Node::new());
+
+        // Looking towards -Z
+        let near = na::Vector3::new(-2.0, -1.0, -1.0);
+        let far = na::Vector3::new(2.0, 1.0, -3.0);
+        assert!((corners[0] - near).norm() < 1e-5);
+        assert!((corners[6] - far).norm() < 1e-5);
+    }
 
-        self.renderer.delta += delta.as_secs_f32();
+    #[test]
+    fn pixel_to_ndc() {
+        let camera = Camera::orthographic_2d(640, 480);
+        let ndc = |x, y| camera.proj.transform_point(&na::Point3::new(x, y, 0.0));
+
+        assert_eq!(ndc(0.0, 0.0), na::Point3::new(-1.0, 1.0, 0.0));
+        assert_eq!(ndc(640.0, 480.0), na::Point3::new(1.0, -1.0, 0.0));
+        assert_eq!(ndc(320.0, 240.0), na::Point3::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn absolute_translation() {
+        let mut trs = Trs::new();
+        let rotation = na::UnitQuaternion::from_axis_angle(&na::Vector3::y_axis(), 1.0);
+        trs.set_rotation(&rotation);
+
+        trs.set_translation(1.0, 2.0, 3.0);
+        trs.set_translation(1.0, 2.0, 3.0);
+        assert_eq!(trs.get_translation(), na::Vector3::new(1.0, 2.0, 3.0));
+
+        trs.translate(1.0, 0.0, 0.0);
+        assert_eq!(trs.get_translation(), na::Vector3::new(2.0, 2.0, 3.0));
+        assert_eq!(trs.isometry.rotation, rotation);
+    }
+
+    #[test]
+    fn euler_round_trip() {
+        let mut trs = Trs::new();
+        trs.translate(1.0, 2.0, 3.0);
+
+        for &(x, y, z) in [(0.3, -0.5, 1.2), (-2.0, 1.0, 0.1), (0.0, 0.0, -3.0)].iter() {
+            trs.set_euler(x, y, z);
+            let (ex, ey, ez) = trs.get_euler();
+            assert!((ex - x).abs() < 1e-5);
+            assert!((ey - y).abs() < 1e-5);
+            assert!((ez - z).abs() < 1e-5);
+        }
+
+        // Only the rotation is replaced
+        assert_eq!(trs.get_translation(), na::Vector3::new(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn cached_matrix() {
+        let mut trs = Trs::new();
+        trs.translate(1.0, 2.0, 3.0);
+
+        let matrix = trs.get_matrix();
+        assert_eq!(trs.get_matrix(), matrix);
+        assert_eq!(trs.matrix_computations.get(), 1);
+
+        trs.scale(2.0, 2.0, 2.0);
+        assert_eq!(trs.get_matrix()[(0, 0)], 2.0);
+        assert_eq!(trs.matrix_computations.get(), 2);
+
+        // View follows changes as well
+        trs.set_translation(0.0, 0.0, 1.0);
+        assert_eq!(trs.get_view()[(2, 3)], -1.0);
+        trs.translate(0.0, 0.0, 1.0);
+        assert_eq!(trs.get_view()[(2, 3)], -2.0);
+    }
+
+    #[test]
+    fn split_viewport() {
+        let viewport = Viewport::new(8, 0, 65, 32);
+        let (left, right) = viewport.split_horizontal();
+        assert_eq!(left, Viewport::new(8, 0, 32, 32));
+        assert_eq!(right, Viewport::new(40, 0, 33, 32));
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn context_profile_and_version() {
+        let context = TestContext::new();
+        let gfx = &context.gfx;
+
+        let (major, minor) = Gfx::get_gl_version();
+        assert_eq!(gfx.video.version(), (major as u8, minor as u8));
+
+        // Drivers may grant a newer version than the one requested to SDL
+        let attr = gfx.video.system.gl_attr();
+        assert!(gfx.video.version() >= attr.context_version());
+        assert_eq!(gfx.get_gl_profile(), attr.context_profile());
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn vbo_update() {
+        let _context = TestContext::new();
+
+        let mut vbo = Vbo::new_dynamic();
+        vbo.upload(&[0u32, 1, 2, 3]);
+        assert_eq!(vbo.get_size(), 16);
+        vbo.update(1, &[5u32, 6]).unwrap();
+        assert_eq!(vbo.get_size(), 16);
+
+        // Past the end of the buffer
+        match vbo.update(3, &[7u32, 8]) {
+            Err(RustspotError::BufferOverflow { end, size }) => assert_eq!((end, size), (20, 16)),
+            _ => panic!("Expected a buffer overflow error"),
+        }
+
+        let mut data = [0u32; 4];
+        vbo.bind();
+        unsafe {
+            let mapped = gl::MapBufferRange(gl::ARRAY_BUFFER, 0, 16, gl::MAP_READ_BIT);
+            assert!(!mapped.is_null());
+            std::ptr::copy_nonoverlapping(mapped as *const u32, data.as_mut_ptr(), data.len());
+            gl::UnmapBuffer(gl::ARRAY_BUFFER);
+        }
+        assert_eq!(data, [0, 5, 6, 3]);
     }
 }
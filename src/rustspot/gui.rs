@@ -1,7 +1,9 @@
 use crate::*;
 
 pub struct GuiRes {
-    _font_texture: Texture,
+    pub font_texture: Texture,
+    /// Glyphs of the default font, useful to draw text outside imgui
+    pub glyphs: GlyphAtlas,
     pub program: ShaderProgram,
     pub mesh_res: MeshRes,
 }
@@ -17,6 +19,9 @@ impl GuiRes {
             .unwrap();
         fonts.tex_id = (font_texture.handle as usize).into();
 
+        let font_id = fonts.fonts()[0];
+        let glyphs = GlyphAtlas::from_imgui(fonts.get_font(font_id).unwrap());
+
         // Shaders
         let vert_source = r#"
         layout (location = 0) in vec2 in_position;
@@ -58,10 +63,13 @@ impl GuiRes {
         let frag = Shader::new(gl::FRAGMENT_SHADER, frag_source.as_bytes())
             .expect("Failed to create imgui fragment shader");
 
-        let program = ShaderProgram::new(vert, frag);
+        let program =
+            ShaderProgram::new(vert, frag).expect("Failed to link imgui shader program");
 
         // Mesh resources
-        let mesh_res = MeshRes::new();
+        let mut mesh_res = MeshRes::new();
+        // Vertices are streamed every frame
+        mesh_res.vbo = Vbo::new_dynamic();
 
         mesh_res.vao.bind();
         mesh_res.vbo.bind();
@@ -98,7 +106,8 @@ impl GuiRes {
         }
 
         Self {
-            _font_texture: font_texture,
+            font_texture,
+            glyphs,
             program,
             mesh_res,
         }
@@ -1,10 +1,62 @@
+use std::collections::{HashMap, HashSet};
+
 use sdl2::{event::Event, keyboard::Keycode, mouse::MouseButton};
 
 // Copyright © 2021
 // Author: Antonio Caggiano <info@antoniocaggiano.eu>
 // SPDX-License-Identifier: MIT
 
-#[derive(Clone, Copy)]
+/// Number of axes tracked for each gamepad
+pub const GAMEPAD_AXES: usize = 8;
+/// Number of buttons tracked for each gamepad
+pub const GAMEPAD_BUTTONS: usize = 32;
+
+/// Applies a dead zone to a value in [-1, 1], rescaling what is left so that it starts from 0
+fn apply_deadzone(value: f32, deadzone: f32) -> f32 {
+    let magnitude = value.abs();
+    if magnitude <= deadzone {
+        0.0
+    } else {
+        value.signum() * ((magnitude - deadzone) / (1.0 - deadzone)).min(1.0)
+    }
+}
+
+/// State of a joystick or game controller. Game controller events use the
+/// indices of SDL standard mappings, such as `sdl2::controller::Axis::LeftX as u8`.
+#[derive(Clone, Copy, Default)]
+pub struct Gamepad {
+    /// Normalized to [-1, 1], before applying any dead zone
+    pub axes: [f32; GAMEPAD_AXES],
+    pub button_down: [bool; GAMEPAD_BUTTONS],
+    /// Whether a button was pressed since last reset
+    pub button_down_updated: [bool; GAMEPAD_BUTTONS],
+}
+
+impl Gamepad {
+    pub fn new() -> Self {
+        Self {
+            axes: [0.0; GAMEPAD_AXES],
+            button_down: [false; GAMEPAD_BUTTONS],
+            button_down_updated: [false; GAMEPAD_BUTTONS],
+        }
+    }
+
+    fn set_axis(&mut self, index: u8, value: i16) {
+        if let Some(axis) = self.axes.get_mut(index as usize) {
+            *axis = (value as f32 / i16::MAX as f32).max(-1.0);
+        }
+    }
+
+    fn set_button(&mut self, index: u8, down: bool) {
+        let index = index as usize;
+        if index < GAMEPAD_BUTTONS {
+            self.button_down[index] = down;
+            self.button_down_updated[index] |= down;
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Input {
     // Left, right, middle, x1, x2
     pub mouse_down: [bool; 5],
@@ -12,8 +64,21 @@ pub struct Input {
     pub mouse_up_updated: [bool; 5],
 
     pub mouse_pos: [f32; 2],
+    /// Mouse motion since last reset, which keeps going when the cursor
+    /// reaches the border of the window in relative mouse mode
+    pub mouse_delta: [f32; 2],
+    /// Whether the cursor is hidden and locked within the window
+    relative_mouse: bool,
 
     pub ctrl_down: bool,
+
+    /// Gamepads by joystick instance id, added on their first event
+    pub gamepads: HashMap<u32, Gamepad>,
+    /// Instance ids of gamepads opened as game controllers, whose joystick events are ignored
+    /// as they come with different indices for the same axes and buttons
+    controllers: HashSet<u32>,
+    /// Axis values below this one are reported as 0
+    deadzone: f32,
 }
 
 impl Input {
@@ -23,8 +88,102 @@ impl Input {
             mouse_down_updated: [false; 5],
             mouse_up_updated: [false; 5],
             mouse_pos: [0.0; 2],
+            mouse_delta: [0.0; 2],
+            relative_mouse: false,
             ctrl_down: false,
+            gamepads: HashMap::new(),
+            controllers: HashSet::new(),
+            deadzone: 0.1,
+        }
+    }
+
+    /// Sets the dead zone of gamepad axes, a value in [0, 1) which is 0.1 by default
+    pub fn set_deadzone(&mut self, deadzone: f32) {
+        self.deadzone = deadzone.clamp(0.0, 0.99);
+    }
+
+    pub fn get_deadzone(&self) -> f32 {
+        self.deadzone
+    }
+
+    /// Returns the value in [-1, 1] of an axis of a gamepad, 0 when unknown or within the dead zone
+    pub fn axis(&self, which: u32, axis_idx: u8) -> f32 {
+        let value = self
+            .gamepads
+            .get(&which)
+            .and_then(|gamepad| gamepad.axes.get(axis_idx as usize))
+            .copied()
+            .unwrap_or(0.0);
+        apply_deadzone(value, self.deadzone)
+    }
+
+    /// Returns the position of a stick made of two axes. The dead zone is radial,
+    /// so that the direction is preserved when moving diagonally.
+    pub fn stick(&self, which: u32, x_axis_idx: u8, y_axis_idx: u8) -> [f32; 2] {
+        let get = |axis_idx: u8| {
+            self.gamepads
+                .get(&which)
+                .and_then(|gamepad| gamepad.axes.get(axis_idx as usize))
+                .copied()
+                .unwrap_or(0.0)
+        };
+        let (x, y) = (get(x_axis_idx), get(y_axis_idx));
+
+        let length = (x * x + y * y).sqrt();
+        let scaled_length = apply_deadzone(length, self.deadzone);
+        if scaled_length == 0.0 {
+            [0.0, 0.0]
+        } else {
+            [x / length * scaled_length, y / length * scaled_length]
+        }
+    }
+
+    /// Returns whether a button of a gamepad is held down
+    pub fn button(&self, which: u32, button_idx: u8) -> bool {
+        self.gamepads
+            .get(&which)
+            .and_then(|gamepad| gamepad.button_down.get(button_idx as usize))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Returns whether a button of a gamepad was pressed since last reset
+    pub fn button_pressed(&self, which: u32, button_idx: u8) -> bool {
+        self.gamepads
+            .get(&which)
+            .and_then(|gamepad| gamepad.button_down_updated.get(button_idx as usize))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    fn get_gamepad(&mut self, which: u32) -> &mut Gamepad {
+        self.gamepads.entry(which).or_default()
+    }
+
+    /// Returns the state of a gamepad fed by joystick events, none for game controllers
+    fn get_joystick(&mut self, which: u32) -> Option<&mut Gamepad> {
+        if self.controllers.contains(&which) {
+            None
+        } else {
+            Some(self.get_gamepad(which))
+        }
+    }
+
+    /// Returns the state of a game controller, dropping what its joystick events stored before
+    fn get_controller(&mut self, which: u32) -> &mut Gamepad {
+        if self.controllers.insert(which) {
+            self.gamepads.insert(which, Gamepad::new());
         }
+        self.get_gamepad(which)
+    }
+
+    /// Only stores the flag, use `Spot::set_relative_mouse` to lock the cursor as well
+    pub fn set_relative_mouse(&mut self, relative: bool) {
+        self.relative_mouse = relative;
+    }
+
+    pub fn is_relative_mouse(&self) -> bool {
+        self.relative_mouse
     }
 
     fn mouse_button_as_index(mouse_btn: &MouseButton) -> usize {
@@ -96,6 +255,8 @@ impl Input {
                 yrel,
             } => {
                 self.mouse_pos = [*x as f32, *y as f32];
+                self.mouse_delta[0] += *xrel as f32;
+                self.mouse_delta[1] += *yrel as f32;
             }
             Event::MouseButtonDown {
                 timestamp,
@@ -146,7 +307,11 @@ impl Input {
                 which,
                 axis_idx,
                 value,
-            } => (),
+            } => {
+                if let Some(gamepad) = self.get_joystick(*which) {
+                    gamepad.set_axis(*axis_idx, *value);
+                }
+            }
             Event::JoyBallMotion {
                 timestamp,
                 which,
@@ -164,32 +329,46 @@ impl Input {
                 timestamp,
                 which,
                 button_idx,
-            } => (),
+            } => {
+                if let Some(gamepad) = self.get_joystick(*which) {
+                    gamepad.set_button(*button_idx, true);
+                }
+            }
             Event::JoyButtonUp {
                 timestamp,
                 which,
                 button_idx,
-            } => (),
+            } => {
+                if let Some(gamepad) = self.get_joystick(*which) {
+                    gamepad.set_button(*button_idx, false);
+                }
+            }
             Event::JoyDeviceAdded { timestamp, which } => (),
-            Event::JoyDeviceRemoved { timestamp, which } => (),
+            Event::JoyDeviceRemoved { timestamp, which } => {
+                self.gamepads.remove(which);
+                self.controllers.remove(which);
+            }
             Event::ControllerAxisMotion {
                 timestamp,
                 which,
                 axis,
                 value,
-            } => (),
+            } => self.get_controller(*which).set_axis(*axis as u8, *value),
             Event::ControllerButtonDown {
                 timestamp,
                 which,
                 button,
-            } => (),
+            } => self.get_controller(*which).set_button(*button as u8, true),
             Event::ControllerButtonUp {
                 timestamp,
                 which,
                 button,
-            } => (),
+            } => self.get_controller(*which).set_button(*button as u8, false),
             Event::ControllerDeviceAdded { timestamp, which } => (),
-            Event::ControllerDeviceRemoved { timestamp, which } => (),
+            Event::ControllerDeviceRemoved { timestamp, which } => {
+                self.gamepads.remove(which);
+                self.controllers.remove(which);
+            }
             Event::ControllerDeviceRemapped { timestamp, which } => (),
             Event::FingerDown {
                 timestamp,
@@ -295,5 +474,130 @@ impl Input {
     pub fn reset(&mut self) {
         self.mouse_down_updated = [false; 5];
         self.mouse_up_updated = [false; 5];
+        self.mouse_delta = [0.0; 2];
+        for gamepad in self.gamepads.values_mut() {
+            gamepad.button_down_updated = [false; GAMEPAD_BUTTONS];
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn relative_mouse() {
+        let mut input = Input::new();
+        assert!(!input.is_relative_mouse());
+        input.set_relative_mouse(true);
+        assert!(input.is_relative_mouse());
+        input.set_relative_mouse(false);
+        assert!(!input.is_relative_mouse());
+
+        let motion = Event::MouseMotion {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            mousestate: sdl2::mouse::MouseState::from_sdl_state(0),
+            x: 0,
+            y: 0,
+            xrel: 4,
+            yrel: -2,
+        };
+        input.handle(&motion);
+        input.handle(&motion);
+        assert_eq!(input.mouse_delta, [8.0, -4.0]);
+
+        input.reset();
+        assert_eq!(input.mouse_delta, [0.0, 0.0]);
+    }
+
+    #[test]
+    fn gamepad_state() {
+        let mut input = Input::new();
+        assert_eq!(input.axis(0, 1), 0.0);
+        assert!(!input.button(0, 3));
+
+        input.handle(&Event::JoyAxisMotion {
+            timestamp: 0,
+            which: 0,
+            axis_idx: 1,
+            value: i16::MIN,
+        });
+        input.handle(&Event::JoyButtonDown {
+            timestamp: 0,
+            which: 0,
+            button_idx: 3,
+        });
+        assert_eq!(input.axis(0, 1), -1.0);
+        assert!(input.button(0, 3));
+        assert!(input.button_pressed(0, 3));
+        // Other gamepads are not affected
+        assert!(!input.button(1, 3));
+
+        // Still held, but not pressed anymore
+        input.reset();
+        assert!(input.button(0, 3));
+        assert!(!input.button_pressed(0, 3));
+
+        input.handle(&Event::ControllerAxisMotion {
+            timestamp: 0,
+            which: 1,
+            axis: sdl2::controller::Axis::RightX,
+            value: i16::MAX,
+        });
+        assert_eq!(input.axis(1, sdl2::controller::Axis::RightX as u8), 1.0);
+
+        // Joystick events of a game controller use other indices, so they are ignored
+        input.handle(&Event::JoyAxisMotion {
+            timestamp: 0,
+            which: 1,
+            axis_idx: sdl2::controller::Axis::RightX as u8,
+            value: 0,
+        });
+        input.handle(&Event::JoyButtonDown {
+            timestamp: 0,
+            which: 1,
+            button_idx: 3,
+        });
+        assert_eq!(input.axis(1, sdl2::controller::Axis::RightX as u8), 1.0);
+        assert!(!input.button(1, 3));
+
+        input.handle(&Event::JoyButtonUp {
+            timestamp: 0,
+            which: 0,
+            button_idx: 3,
+        });
+        assert!(!input.button(0, 3));
+    }
+
+    #[test]
+    fn deadzone() {
+        let mut input = Input::new();
+        input.set_deadzone(0.2);
+
+        let motion = |input: &mut Input, axis_idx, value| {
+            input.handle(&Event::JoyAxisMotion {
+                timestamp: 0,
+                which: 0,
+                axis_idx,
+                value,
+            })
+        };
+
+        motion(&mut input, 0, i16::MAX / 10);
+        assert_eq!(input.axis(0, 0), 0.0);
+        motion(&mut input, 0, -i16::MAX / 10 * 6);
+        assert!((input.axis(0, 0) + 0.5).abs() < 0.001);
+        motion(&mut input, 0, i16::MAX);
+        assert_eq!(input.axis(0, 0), 1.0);
+
+        // Diagonal values within the radial dead zone are dropped together
+        motion(&mut input, 0, i16::MAX / 10);
+        motion(&mut input, 1, i16::MAX / 10);
+        assert_eq!(input.stick(0, 0, 1), [0.0, 0.0]);
+        motion(&mut input, 1, i16::MAX);
+        let stick = input.stick(0, 0, 1);
+        assert!(stick[1] > 0.9 && stick[0] > 0.0 && stick[0] < 0.15);
     }
 }
@@ -2,12 +2,18 @@ use std::time::Duration;
 
 use clap::{App, Arg, ArgMatches};
 
+pub mod error;
+pub use error::*;
+
 pub mod shader;
 pub use shader::*;
 
 pub mod shaders;
 pub use shaders::*;
 
+pub mod uniform;
+pub use uniform::*;
+
 pub mod light;
 pub use light::*;
 
@@ -20,9 +26,29 @@ pub use frame::*;
 pub mod sky;
 pub use sky::*;
 
+pub mod fog;
+pub use fog::*;
+
+pub mod bloom;
+pub use bloom::*;
+
+pub mod shadow;
+pub use shadow::*;
+
+pub mod scatter;
+pub use scatter::*;
 pub mod terrain;
 pub use terrain::*;
 
+pub mod particle;
+pub use particle::*;
+
+pub mod sprite;
+pub use sprite::*;
+
+pub mod text;
+pub use text::*;
+
 pub mod renderer;
 pub use renderer::*;
 
@@ -35,9 +61,23 @@ pub use material::*;
 pub mod mesh;
 pub use mesh::*;
 
+#[cfg(feature = "obj")]
+pub mod obj;
+#[cfg(feature = "obj")]
+pub use obj::*;
+
+pub mod bounds;
+pub use bounds::*;
+
+pub mod debug;
+pub use debug::*;
+
 pub mod node;
 pub use node::*;
 
+pub mod skin;
+pub use skin::*;
+
 pub mod input;
 pub use input::*;
 
@@ -53,6 +93,9 @@ pub use util::*;
 pub struct SpotBuilder<'a, 'b> {
     extent: Extent2D,
     offscreen_extent: Extent2D,
+    frame: FrameBuilder,
+    samples: u32,
+    gl_debug: bool,
 
     app: App<'a, 'b>,
 }
@@ -93,6 +136,9 @@ impl<'a, 'b> SpotBuilder<'a, 'b> {
         Self {
             extent: Extent2D::new(480, 320),
             offscreen_extent: Extent2D::new(480, 320),
+            frame: Frame::builder(),
+            samples: 1,
+            gl_debug: cfg!(debug_assertions),
             app,
         }
     }
@@ -133,6 +179,25 @@ impl<'a, 'b> SpotBuilder<'a, 'b> {
         self
     }
 
+    /// Chooses which buffers the frame allocates. Extents are the ones of this builder.
+    pub fn frame(mut self, frame: FrameBuilder) -> Self {
+        self.frame = frame;
+        self
+    }
+
+    /// Samples per pixel of the offscreen geometry buffer, which is resolved when blitting
+    /// to the screen. By default it is 1, meaning no MSAA.
+    pub fn samples(mut self, samples: u32) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    /// Whether GL debug messages should be logged. By default they are only in debug builds.
+    pub fn gl_debug(mut self, enabled: bool) -> Self {
+        self.gl_debug = enabled;
+        self
+    }
+
     pub fn build(self) -> Spot {
         let (spot, _) = self.build_with_matches();
         spot
@@ -148,10 +213,23 @@ impl<'a, 'b> SpotBuilder<'a, 'b> {
             self.offscreen_extent = offscreen_extent;
         }
 
-        (Spot::new(self.extent, self.offscreen_extent), matches)
+        let frame = self
+            .frame
+            .offscreen_extent(self.offscreen_extent)
+            .samples(self.samples);
+        let spot = Spot::with_frame(self.extent, frame);
+        spot.gfx.set_gl_debug(self.gl_debug);
+        (spot, matches)
     }
 }
 
+/// Whether `Spot::run` should keep going after a frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ControlFlow {
+    Continue,
+    Break,
+}
+
 pub struct Spot {
     pub input: Input,
     pub timer: Timer,
@@ -167,13 +245,17 @@ impl Spot {
     }
 
     pub fn new(extent: Extent2D, offscreen_extent: Extent2D) -> Self {
+        Self::with_frame(extent, Frame::builder().offscreen_extent(offscreen_extent))
+    }
+
+    pub fn with_frame(extent: Extent2D, frame: FrameBuilder) -> Self {
         let sdl = sdl2::init().expect("Failed to initialize SDL2");
         let joystick = sdl
             .joystick()
             .expect("Failed to initialize SDL2 joystick subsystem");
         let events = sdl.event_pump().expect("Failed to initialize SDL2 events");
 
-        let gfx = Gfx::new(&sdl, extent, offscreen_extent);
+        let gfx = Gfx::with_frame(&sdl, extent, frame);
 
         let timer = Timer::new();
 
@@ -189,9 +271,105 @@ impl Spot {
         }
     }
 
+    /// Hides the cursor and locks it within the window, so that mouse motion is reported
+    /// through `Input::mouse_delta` even when the cursor would leave the window
+    pub fn set_relative_mouse(&mut self, relative: bool) {
+        self.sdl.mouse().set_relative_mouse_mode(relative);
+        self.input.set_relative_mouse(relative);
+    }
+
+    /// Sets the background color of the scene, as RGBA
+    pub fn set_clear_color(&mut self, color: [f32; 4]) {
+        self.gfx.set_clear_color(color);
+    }
+
+    /// Returns the scaled delta time for the logic of the application, see `Timer`
     pub fn update(&mut self) -> Duration {
         let delta = self.timer.get_delta();
-        self.gfx.update(delta, &self.input);
+        // The GUI keeps going even when the game is paused, while shader animations stop
+        self.gfx.update(self.timer.get_real_delta(), &self.input);
+        self.gfx.renderer.delta += delta.as_secs_f32();
         delta
     }
+
+    /// Runs the main loop until a quit event arrives or `frame_fn` returns `ControlFlow::Break`.
+    /// Each frame, events are handled by `Input`, then `frame_fn` is called with the delta time
+    /// of `update`, and the frame is presented. Passes can be recorded through `Gfx::render_with`.
+    /// Applications which need the events themselves can still write their own loop.
+    pub fn run(&mut self, mut frame_fn: impl FnMut(&mut Spot, Duration) -> ControlFlow) {
+        loop {
+            let mut quit = false;
+            for event in self.events.poll_iter() {
+                if let sdl2::event::Event::Quit { .. } = event {
+                    quit = true;
+                }
+                self.input.handle(&event);
+            }
+            if quit {
+                break;
+            }
+
+            let delta = self.update();
+            let flow = frame_fn(self, delta);
+            self.input.reset();
+
+            let frame = self.gfx.next_frame();
+            self.gfx.present(frame);
+
+            if flow == ControlFlow::Break {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn gl_debug_flag() {
+        assert_eq!(SpotBuilder::new().gl_debug, cfg!(debug_assertions));
+        assert!(!SpotBuilder::new().gl_debug(false).gl_debug);
+        assert!(SpotBuilder::new().gl_debug(true).gl_debug);
+    }
+
+    #[test]
+    fn samples() {
+        assert_eq!(SpotBuilder::new().samples, 1);
+        assert_eq!(SpotBuilder::new().samples(4).samples, 4);
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn run_until_quit() {
+        let context = TestContext::new();
+        let sdl = context.sdl;
+        let mut spot = Spot {
+            input: Input::new(),
+            timer: Timer::new(),
+            gfx: context.gfx,
+            events: sdl.event_pump().unwrap(),
+            joystick: sdl.joystick().unwrap(),
+            sdl,
+        };
+
+        let mut frames = 0;
+        spot.run(|spot, _| {
+            frames += 1;
+            if frames == 3 {
+                let quit = sdl2::event::Event::Quit { timestamp: 0 };
+                spot.sdl.event().unwrap().push_event(quit).unwrap();
+            }
+            ControlFlow::Continue
+        });
+        assert_eq!(frames, 3);
+
+        let mut frames = 0;
+        spot.run(|_, _| {
+            frames += 1;
+            ControlFlow::Break
+        });
+        assert_eq!(frames, 1);
+    }
 }
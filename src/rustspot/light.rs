@@ -1,18 +1,45 @@
 use crate::*;
+use nalgebra as na;
 
 pub struct DirectionalLight {
     pub color: [f32; 3],
+    /// Scale applied to the color, values above 1.0 give HDR lighting
+    pub intensity: f32,
+    /// Only the first light drawn which casts shadows gets a shadowmap
+    pub casts_shadow: bool,
 }
 
 impl DirectionalLight {
     pub fn new() -> Self {
         Self {
             color: [1.0, 1.0, 1.0],
+            intensity: 1.0,
+            casts_shadow: true,
         }
     }
 
     pub fn color(r: f32, g: f32, b: f32) -> Self {
-        Self { color: [r, g, b] }
+        Self {
+            color: [r, g, b],
+            intensity: 1.0,
+            casts_shadow: true,
+        }
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn with_shadow(mut self, casts_shadow: bool) -> Self {
+        self.casts_shadow = casts_shadow;
+        self
+    }
+
+    /// Returns the color scaled by the intensity, which is what shaders receive
+    pub fn get_radiance(&self) -> [f32; 3] {
+        let [r, g, b] = self.color;
+        [r * self.intensity, g * self.intensity, b * self.intensity]
     }
 
     pub fn bind(&self, program: &ShaderProgram, node: &Node) {
@@ -20,7 +47,8 @@ impl DirectionalLight {
         let direction = -node.trs.get_forward();
 
         unsafe {
-            gl::Uniform3fv(program.loc.light_color, 1, &self.color as *const f32);
+            let radiance = self.get_radiance();
+            gl::Uniform3fv(program.loc.light_color, 1, radiance.as_ptr());
             gl::Uniform3fv(
                 program.loc.light_direction,
                 1,
@@ -30,14 +58,187 @@ impl DirectionalLight {
     }
 }
 
+/// Near plane of the cameras rendering point light shadows
+pub const POINT_SHADOW_NEAR: f32 = 0.1;
+
 pub struct PointLight {
     pub color: [f32; 3],
+    /// Distance reached by the light, which is also the far plane of its shadow cameras
+    pub range: f32,
 }
 
 impl PointLight {
     pub fn new() -> Self {
         Self {
             color: [1.0, 1.0, 1.0],
+            range: 16.0,
+        }
+    }
+
+    /// Returns the projection used to render one face of the shadow cubemap
+    pub fn get_shadow_proj(&self) -> na::Matrix4<f32> {
+        na::Perspective3::new(
+            1.0,
+            std::f32::consts::FRAC_PI_2,
+            POINT_SHADOW_NEAR,
+            self.range,
+        )
+        .to_homogeneous()
+    }
+
+    /// Returns the views looking at the six faces of a cubemap from `position`,
+    /// following the order and orientation of `GL_TEXTURE_CUBE_MAP_POSITIVE_X` and others
+    pub fn get_shadow_views(position: &na::Point3<f32>) -> [na::Matrix4<f32>; 6] {
+        let faces = [
+            (na::Vector3::x(), -na::Vector3::y()),
+            (-na::Vector3::x(), -na::Vector3::y()),
+            (na::Vector3::y(), na::Vector3::z()),
+            (-na::Vector3::y(), -na::Vector3::z()),
+            (na::Vector3::z(), -na::Vector3::y()),
+            (-na::Vector3::z(), -na::Vector3::y()),
+        ];
+
+        let mut views = [na::Matrix4::identity(); 6];
+        for (view, (direction, up)) in views.iter_mut().zip(faces.iter()) {
+            *view = na::Matrix4::look_at_rh(position, &(position + direction), up);
         }
+        views
+    }
+
+    /// Returns the light space matrices of the six faces of the shadow cubemap
+    pub fn get_shadow_matrices(&self, position: &na::Point3<f32>) -> [na::Matrix4<f32>; 6] {
+        let proj = self.get_shadow_proj();
+        let mut matrices = Self::get_shadow_views(position);
+        for matrix in matrices.iter_mut() {
+            *matrix = proj * *matrix;
+        }
+        matrices
+    }
+}
+
+impl Default for PointLight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Maximum number of spot lights lighting the scene
+pub const MAX_SPOT_LIGHTS: usize = 4;
+
+/// Light emitted from the position of its node towards its forward direction, within a cone
+pub struct SpotLight {
+    pub color: [f32; 3],
+    /// Distance reached by the light
+    pub range: f32,
+    /// Angle in radians from the axis of the cone where light starts fading out
+    pub inner_angle: f32,
+    /// Angle in radians from the axis of the cone where light is gone
+    pub outer_angle: f32,
+}
+
+impl SpotLight {
+    pub fn new() -> Self {
+        Self {
+            color: [1.0, 1.0, 1.0],
+            range: 16.0,
+            inner_angle: std::f32::consts::PI / 8.0,
+            outer_angle: std::f32::consts::PI / 6.0,
+        }
+    }
+
+    pub fn color(r: f32, g: f32, b: f32) -> Self {
+        let mut ret = Self::new();
+        ret.color = [r, g, b];
+        ret
+    }
+
+    pub fn with_range(mut self, range: f32) -> Self {
+        self.range = range;
+        self
+    }
+
+    /// Angles in radians, the inner one is clamped to the outer one
+    pub fn with_cone(mut self, inner_angle: f32, outer_angle: f32) -> Self {
+        self.inner_angle = inner_angle.min(outer_angle);
+        self.outer_angle = outer_angle;
+        self
+    }
+
+    /// Returns the cosines of the inner and outer angles, which shaders compare against
+    pub fn get_cone_cos(&self) -> [f32; 2] {
+        [self.inner_angle.cos(), self.outer_angle.cos()]
+    }
+}
+
+impl Default for SpotLight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sun_intensity() {
+        let light = DirectionalLight::color(1.0, 0.5, 0.25).with_intensity(4.0);
+        assert_eq!(light.get_radiance(), [4.0, 2.0, 1.0]);
+        assert_eq!(DirectionalLight::new().get_radiance(), [1.0; 3]);
+
+        // Values uploaded to the lights uniform block
+        let mut block = LightsBlock::new(&na::Matrix4::identity());
+        block.push_directional_light(&light.get_radiance(), &Node::new());
+        assert_eq!(block.light_colors[0], [4.0, 2.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn six_shadow_matrices() {
+        let light = PointLight::new();
+        let position = na::Point3::new(1.0, 2.0, 3.0);
+        let matrices = light.get_shadow_matrices(&position);
+        assert_eq!(matrices.len(), 6);
+
+        let directions = [
+            na::Vector3::x(),
+            -na::Vector3::x(),
+            na::Vector3::y(),
+            -na::Vector3::y(),
+            na::Vector3::z(),
+            -na::Vector3::z(),
+        ];
+        for (matrix, direction) in matrices.iter().zip(directions.iter()) {
+            // A point in front of the light along the face direction ends up at the center
+            let point = matrix.transform_point(&(position + direction * 4.0));
+            assert!(point.x.abs() < 1e-5);
+            assert!(point.y.abs() < 1e-5);
+            assert!(point.z > -1.0 && point.z < 1.0);
+
+            // While a point behind it is outside the clip volume
+            let behind = matrix * (position - direction * 4.0).to_homogeneous();
+            assert!(behind.w < 0.0);
+        }
+    }
+
+    #[test]
+    fn spot_cone() {
+        let light = SpotLight::new().with_cone(0.5, 0.25);
+        assert_eq!(light.inner_angle, 0.25);
+
+        let [inner, outer] = SpotLight::new().with_cone(0.0, 1.0).get_cone_cos();
+        assert_eq!(inner, 1.0);
+        assert!(outer < inner);
+    }
+
+    #[test]
+    fn light_spot_variant() {
+        // Generated by the build script from the spot and skin includes of the light shader
+        let variants = LightSpotVariant::all();
+        assert_eq!(variants.len(), 2);
+        let cone = LightSpotVariant::Cone as usize;
+        let default = LightSkinVariant::Default as usize;
+        assert!(LIGHT_VARIANTS[cone][default] == Shaders::LightSpotConeSkinDefault);
+        let joints = LightSkinVariant::Joints as usize;
+        assert!(LIGHT_VARIANTS[cone][joints] == Shaders::LightSpotConeSkinJoints);
     }
 }
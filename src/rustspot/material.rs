@@ -6,10 +6,15 @@ use crate::*;
 
 pub struct MaterialBuilder {
     shader: Shaders,
+    color: Color,
     texture: Option<Handle<Texture>>,
     normals: Option<Handle<Texture>>,
+    height: Option<Handle<Texture>>,
     occlusion: Option<Handle<Texture>>,
     metallic_roughness: Option<Handle<Texture>>,
+    tint: Color,
+    emissive: [f32; 3],
+    alpha_cutoff: f32,
 
     metallic: f32,
     roughness: f32,
@@ -19,10 +24,15 @@ impl MaterialBuilder {
     pub fn new() -> Self {
         Self {
             shader: Shaders::Default,
+            color: Color::new(),
             texture: None,
             normals: None,
+            height: None,
             occlusion: None,
             metallic_roughness: None,
+            tint: Color::new(),
+            emissive: [0.0; 3],
+            alpha_cutoff: 0.0,
             metallic: 1.0,
             roughness: 1.0,
         }
@@ -33,6 +43,19 @@ impl MaterialBuilder {
         self
     }
 
+    /// Uses the unlit shader, which shows the color of the material without any lighting.
+    /// Useful for UI elements and flat surfaces.
+    pub fn unlit(mut self) -> Self {
+        self.shader = Shaders::Unlit;
+        self
+    }
+
+    /// Albedo used when there is no texture, see `Model::color_texture`
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
     pub fn texture(mut self, texture: Handle<Texture>) -> Self {
         self.texture = Some(texture);
         self
@@ -43,6 +66,12 @@ impl MaterialBuilder {
         self
     }
 
+    /// Height map used by the parallax normal variant of the PBR shader
+    pub fn height(mut self, height: Handle<Texture>) -> Self {
+        self.height = Some(height);
+        self
+    }
+
     pub fn occlusion(mut self, occlusion: Handle<Texture>) -> Self {
         self.occlusion = Some(occlusion);
         self
@@ -53,6 +82,23 @@ impl MaterialBuilder {
         self
     }
 
+    pub fn tint(mut self, tint: Color) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    /// Linear color emitted by the surface, which can go beyond 1.0 for HDR
+    pub fn emissive(mut self, emissive: [f32; 3]) -> Self {
+        self.emissive = emissive;
+        self
+    }
+
+    /// Makes this a cutout material, where texels with a lower alpha do not cast shadows
+    pub fn alpha_cutoff(mut self, alpha_cutoff: f32) -> Self {
+        self.alpha_cutoff = alpha_cutoff;
+        self
+    }
+
     pub fn metallic(mut self, metallic: f32) -> Self {
         self.metallic = metallic;
         self
@@ -66,9 +112,14 @@ impl MaterialBuilder {
     pub fn build(self) -> Material {
         let mut material = Material::new();
         material.shader = self.shader;
+        material.color = self.color;
         material.texture = self.texture;
         material.normals = self.normals;
+        material.height = self.height;
         material.occlusion = self.occlusion;
+        material.tint = self.tint;
+        material.emissive = self.emissive;
+        material.alpha_cutoff = self.alpha_cutoff;
         material.metallic = self.metallic;
         material.roughness = self.roughness;
         material
@@ -80,7 +131,15 @@ pub struct Material {
     pub color: Color,
     pub texture: Option<Handle<Texture>>,
     pub normals: Option<Handle<Texture>>,
+    /// Height map for parallax occlusion mapping, white being the highest point
+    pub height: Option<Handle<Texture>>,
     pub occlusion: Option<Handle<Texture>>,
+    /// Multiplied with the albedo, whether it comes from the texture or the color
+    pub tint: Color,
+    /// Linear color added to the lit color, not affected by lights nor shadows
+    pub emissive: [f32; 3],
+    /// Texels with a lower alpha are discarded by shadow passes, 0.0 to keep all of them
+    pub alpha_cutoff: f32,
 
     // PBR factors
     pub metallic_roughness: Option<Handle<Texture>>,
@@ -99,10 +158,120 @@ impl Material {
             color: Color::new(),
             texture: None,
             normals: None,
+            height: None,
             occlusion: None,
             metallic_roughness: None,
+            tint: Color::new(),
+            emissive: [0.0; 3],
+            alpha_cutoff: 0.0,
             metallic: 1.0,
             roughness: 1.0,
         }
     }
+
+    /// Returns an unlit material showing `color` whatever the lights, which looks like it
+    /// is emitting light, such as the bulb of a lamp. The model should have a pixel
+    /// texture of this color in its `colors`, as `Model::add_point_light` makes sure.
+    pub fn emissive_unlit(color: Color) -> Self {
+        let mut material = Self::builder().unlit().build();
+        material.color = color;
+        material
+    }
+
+    /// Returns the tint as normalized RGBA values
+    pub fn get_tint(&self) -> [f32; 4] {
+        let tint = self.tint.as_slice();
+        [
+            tint[0] as f32 / 255.0,
+            tint[1] as f32 / 255.0,
+            tint[2] as f32 / 255.0,
+            tint[3] as f32 / 255.0,
+        ]
+    }
+
+    /// Returns whether this material ignores lights
+    pub fn is_unlit(&self) -> bool {
+        self.shader == Shaders::Unlit
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unlit() {
+        let material = Material::builder().unlit().build();
+        assert!(material.shader == Shaders::Unlit);
+        assert!(material.is_unlit());
+        assert!(!Material::new().is_unlit());
+    }
+
+    #[test]
+    fn emissive_unlit() {
+        let color = Color::rgba(255, 200, 0, 255);
+        let material = Material::emissive_unlit(color);
+        assert!(material.is_unlit());
+        assert!(material.color == color);
+        assert!(material.texture.is_none());
+    }
+
+    #[test]
+    fn cutout_shadow() {
+        let material = Material::builder().alpha_cutoff(0.5).build();
+        assert_eq!(material.alpha_cutoff, 0.5);
+        assert_eq!(Material::new().alpha_cutoff, 0.0);
+
+        // Does not compile if shadow shaders lack the alpha test uniforms
+        let _ = |loc: &DepthSkinDefaultLoc| [loc.tex_sampler, loc.alpha_cutoff];
+        let _ = |loc: &VarianceShadowSkinDefaultLoc| [loc.tex_sampler, loc.alpha_cutoff];
+    }
+
+    #[test]
+    fn parallax_variant() {
+        assert!(PbrNormalVariant::all().contains(&PbrNormalVariant::Parallax));
+        assert_eq!(PbrNormalVariant::Parallax.as_str(), "Parallax");
+
+        let material = Material::builder().height(Handle::new(1)).build();
+        assert_eq!(material.height.unwrap().id, 1);
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn tinted_texture() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let white = model.textures.push(Texture::pixel(Color::new()));
+        let material = model.materials.push(
+            Material::builder()
+                .unlit()
+                .texture(white)
+                .tint(Color::rgba(255, 0, 0, 255))
+                .build(),
+        );
+        let quad = model.primitives.push(Primitive::quad(material));
+        let mesh = model.meshes.push(Mesh::new(vec![quad]));
+        let camera = model.cameras.push(Camera::orthographic(1, 1, 0.1, 100.0));
+        let mut camera_node = Node::builder().camera(camera).build();
+        camera_node.trs.translate(0.0, 0.0, 1.0);
+        let children = vec![
+            model.nodes.push(Node::builder().mesh(mesh).build()),
+            model.nodes.push(camera_node),
+        ];
+        let root = model.nodes.push(Node::builder().children(children).build());
+
+        let frame = gfx.next_frame();
+        gfx.renderer
+            .draw(&model, root, &nalgebra::Matrix4::identity());
+        gfx.renderer
+            .render_geometry(&model, frame.get_geometry_buffer());
+
+        // The quad covers the whole buffer
+        let (extent, pixels) = frame.get_geometry_buffer().read_color(0);
+        let center = ((extent.height / 2 * extent.width + extent.width / 2) * 4) as usize;
+        assert_eq!(&pixels[center..center + 4], &[255, 0, 0, 255]);
+        gfx.present(frame);
+    }
 }
@@ -22,6 +22,12 @@ impl MeshRes {
     }
 
     pub fn from(vertices: &[Vertex], indices: &Vec<u8>) -> Self {
+        MeshRes::from_with_layout(vertices, indices, true)
+    }
+
+    /// Tangent and bitangent attributes are enabled only when `has_tangents` is true,
+    /// otherwise shaders read the default attribute value instead of meaningless zeros.
+    pub fn from_with_layout(vertices: &[Vertex], indices: &Vec<u8>, has_tangents: bool) -> Self {
         let mut res = MeshRes::new();
 
         res.vao.bind();
@@ -49,14 +55,23 @@ impl MeshRes {
             gl::VertexAttribPointer(3, 3, gl::FLOAT, gl::TRUE, stride, (8 * f32size) as _);
             gl::EnableVertexAttribArray(3);
 
-            // TODO enable only when normal map is available?
-            // Tangent
-            gl::VertexAttribPointer(4, 3, gl::FLOAT, gl::TRUE, stride, (11 * f32size) as _);
-            gl::EnableVertexAttribArray(4);
+            if has_tangents {
+                // Tangent
+                gl::VertexAttribPointer(4, 3, gl::FLOAT, gl::TRUE, stride, (11 * f32size) as _);
+                gl::EnableVertexAttribArray(4);
+
+                // Bitangent
+                gl::VertexAttribPointer(5, 3, gl::FLOAT, gl::TRUE, stride, (14 * f32size) as _);
+                gl::EnableVertexAttribArray(5);
+            }
+
+            // Joints
+            gl::VertexAttribPointer(6, 4, gl::FLOAT, gl::FALSE, stride, (17 * f32size) as _);
+            gl::EnableVertexAttribArray(6);
 
-            // Bitangent
-            gl::VertexAttribPointer(5, 3, gl::FLOAT, gl::TRUE, stride, (14 * f32size) as _);
-            gl::EnableVertexAttribArray(5);
+            // Weights
+            gl::VertexAttribPointer(7, 4, gl::FLOAT, gl::FALSE, stride, (21 * f32size) as _);
+            gl::EnableVertexAttribArray(7);
         }
 
         res
@@ -67,10 +82,26 @@ impl MeshRes {
     }
 }
 
+/// How normals of loaded primitives are treated
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum NormalsMode {
+    /// Normals are left as they come
+    #[default]
+    Keep,
+    /// Every face gets its own vertices, all of them with the normal of the face
+    Flat,
+    /// Every vertex gets the average normal of the faces sharing it
+    Smooth,
+}
+
 pub struct PrimitiveBuilder {
     vertices: Vec<Vertex>,
     indices: Vec<u8>,
     index_type: gl::types::GLenum,
+    mode: gl::types::GLenum,
+    restart_index: Option<u32>,
+    /// None means tangents are detected from vertices
+    tangents: Option<bool>,
 
     material: Option<Handle<Material>>,
 }
@@ -81,6 +112,9 @@ impl PrimitiveBuilder {
             vertices: vec![],
             indices: vec![],
             index_type: gl::UNSIGNED_BYTE,
+            mode: gl::TRIANGLES,
+            restart_index: None,
+            tangents: None,
             material: None,
         }
     }
@@ -100,16 +134,299 @@ impl PrimitiveBuilder {
         self
     }
 
+    /// Topology of the primitive, `gl::TRIANGLES` by default
+    pub fn mode(mut self, mode: gl::types::GLenum) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Index which starts a new strip or loop rather than referring to a vertex
+    pub fn restart_index(mut self, restart_index: u32) -> Self {
+        self.restart_index = Some(restart_index);
+        self
+    }
+
+    /// Whether vertices come with tangents for normal mapping.
+    /// When not specified, it is true if any vertex has a non-zero tangent.
+    pub fn tangents(mut self, tangents: bool) -> Self {
+        self.tangents = Some(tangents);
+        self
+    }
+
     pub fn material(mut self, material: Option<Handle<Material>>) -> Self {
         self.material = material;
         self
     }
 
+    /// Recomputes normals of the vertices set so far. Only triangle lists are supported,
+    /// other topologies are left untouched. `Flat` mode replaces vertices and indices.
+    pub fn normals_mode(mut self, normals_mode: NormalsMode) -> Self {
+        if normals_mode == NormalsMode::Keep {
+            return self;
+        }
+        if self.mode != gl::TRIANGLES {
+            log::warn!("Can not recompute normals of mode {:#x}", self.mode);
+            return self;
+        }
+
+        // Primitives without indices draw their vertices in order
+        let indices = if self.indices.is_empty() {
+            (0..self.vertices.len()).collect()
+        } else {
+            read_indices(&self.indices, self.index_type)
+        };
+
+        let face_normal = |face: &[usize]| {
+            let a = na::Vector3::from(self.vertices[face[0]].position);
+            let b = na::Vector3::from(self.vertices[face[1]].position);
+            let c = na::Vector3::from(self.vertices[face[2]].position);
+            // Not normalized, so that bigger faces weigh more when averaging
+            (b - a).cross(&(c - a))
+        };
+
+        match normals_mode {
+            NormalsMode::Flat => {
+                let mut vertices = Vec::with_capacity(indices.len());
+                for face in indices.chunks_exact(3) {
+                    let normal = face_normal(face).normalize();
+                    for &index in face {
+                        let mut vertex = self.vertices[index];
+                        vertex.normal = normal;
+                        vertices.push(vertex);
+                    }
+                }
+                let indices: Vec<usize> = (0..vertices.len()).collect();
+                let (indices, index_type) = write_indices(&indices);
+                self.vertices = vertices;
+                self.indices = indices;
+                self.index_type = index_type;
+            }
+            NormalsMode::Smooth => {
+                let mut normals = vec![na::Vector3::zeros(); self.vertices.len()];
+                for face in indices.chunks_exact(3) {
+                    let normal = face_normal(face);
+                    for &index in face {
+                        normals[index] += normal;
+                    }
+                }
+                for (vertex, normal) in self.vertices.iter_mut().zip(normals) {
+                    // Vertices not belonging to any face keep their normal
+                    if normal != na::Vector3::zeros() {
+                        vertex.normal = normal.normalize();
+                    }
+                }
+            }
+            NormalsMode::Keep => unreachable!(),
+        }
+
+        self
+    }
+
+    /// Computes tangents and bitangents of the vertices set so far from their normals and
+    /// texture coordinates, enabling normal mapping. Only triangle lists are supported.
+    pub fn compute_tangents(mut self) -> Self {
+        if self.mode != gl::TRIANGLES {
+            log::warn!("Can not compute tangents of mode {:#x}", self.mode);
+            return self;
+        }
+
+        let indices = if self.indices.is_empty() {
+            (0..self.vertices.len()).collect()
+        } else {
+            read_indices(&self.indices, self.index_type)
+        };
+
+        let mut tangents = vec![na::Vector3::zeros(); self.vertices.len()];
+        let mut bitangents = vec![na::Vector3::zeros(); self.vertices.len()];
+        for face in indices.chunks_exact(3) {
+            let [a, b, c] = [
+                &self.vertices[face[0]],
+                &self.vertices[face[1]],
+                &self.vertices[face[2]],
+            ];
+            let edge1 = na::Vector3::from(b.position) - na::Vector3::from(a.position);
+            let edge2 = na::Vector3::from(c.position) - na::Vector3::from(a.position);
+            let du1 = b.tex_coords[0] - a.tex_coords[0];
+            let dv1 = b.tex_coords[1] - a.tex_coords[1];
+            let du2 = c.tex_coords[0] - a.tex_coords[0];
+            let dv2 = c.tex_coords[1] - a.tex_coords[1];
+
+            let det = du1 * dv2 - du2 * dv1;
+            // Texture coordinates do not span the face
+            if det.abs() < f32::EPSILON {
+                continue;
+            }
+            let tangent = (edge1 * dv2 - edge2 * dv1) / det;
+            let bitangent = (edge2 * du1 - edge1 * du2) / det;
+            for &index in face {
+                tangents[index] += tangent;
+                bitangents[index] += bitangent;
+            }
+        }
+
+        for (i, vertex) in self.vertices.iter_mut().enumerate() {
+            // Gram-Schmidt, so that the tangent is orthogonal to the normal
+            let normal = vertex.normal;
+            let tangent = tangents[i] - normal * normal.dot(&tangents[i]);
+            if tangent.norm() < f32::EPSILON {
+                continue;
+            }
+            vertex.tangent = tangent.normalize();
+
+            // Texture coordinates may be mirrored
+            let handedness = if normal.cross(&vertex.tangent).dot(&bitangents[i]) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            vertex.bitangent = normal.cross(&vertex.tangent) * handedness;
+        }
+
+        self
+    }
+
+    /// Checks the geometry before creating GL resources for it
+    pub fn validate(&self) -> Result<(), MeshError> {
+        let has_tangents = self
+            .tangents
+            .unwrap_or_else(|| Primitive::detect_tangents(&self.vertices));
+        validate_geometry(
+            &self.vertices,
+            &self.indices,
+            self.index_type,
+            self.restart_index,
+            has_tangents,
+        )
+    }
+
     pub fn build(self) -> Primitive {
-        Primitive::new(self.vertices, self.indices, self.index_type, self.material)
+        let has_tangents = self
+            .tangents
+            .unwrap_or_else(|| Primitive::detect_tangents(&self.vertices));
+        let mut primitive = Primitive::new_with_layout(
+            self.vertices,
+            self.indices,
+            self.index_type,
+            self.material,
+            has_tangents,
+        );
+        primitive.mode = self.mode;
+        primitive.restart_index = self.restart_index;
+        primitive
+    }
+}
+
+/// Size in bytes of an index of this type
+fn index_size(index_type: gl::types::GLenum) -> usize {
+    match index_type {
+        gl::UNSIGNED_SHORT => 2,
+        gl::UNSIGNED_INT => 4,
+        _ => 1,
     }
 }
 
+/// Returns whether a restart index can be stored with this index type. On GLES the
+/// restart index is fixed to the maximum value of the type, hence it should be that one.
+fn fits_restart_index(restart_index: u32, index_type: gl::types::GLenum) -> bool {
+    let max = match index_type {
+        gl::UNSIGNED_BYTE => u8::MAX as u32,
+        gl::UNSIGNED_SHORT => u16::MAX as u32,
+        _ => u32::MAX,
+    };
+    if cfg!(feature = "gles") {
+        restart_index == max
+    } else {
+        restart_index <= max
+    }
+}
+
+/// Reads little endian indices stored as bytes. Trailing bytes are ignored.
+fn read_indices(indices: &[u8], index_type: gl::types::GLenum) -> Vec<usize> {
+    indices
+        .chunks_exact(index_size(index_type))
+        .map(|bytes| {
+            bytes
+                .iter()
+                .rev()
+                .fold(0, |index, &byte| (index << 8) | byte as usize)
+        })
+        .collect()
+}
+
+/// Stores indices as bytes using the smallest type which can hold all of them
+pub(crate) fn write_indices(indices: &[usize]) -> (Vec<u8>, gl::types::GLenum) {
+    let max = indices.iter().copied().max().unwrap_or(0);
+    if max <= u8::MAX as usize {
+        (
+            indices.iter().map(|&i| i as u8).collect(),
+            gl::UNSIGNED_BYTE,
+        )
+    } else if max <= u16::MAX as usize {
+        let bytes = indices
+            .iter()
+            .flat_map(|&i| (i as u16).to_le_bytes().to_vec())
+            .collect();
+        (bytes, gl::UNSIGNED_SHORT)
+    } else {
+        let bytes = indices
+            .iter()
+            .flat_map(|&i| (i as u32).to_le_bytes().to_vec())
+            .collect();
+        (bytes, gl::UNSIGNED_INT)
+    }
+}
+
+/// Maximum deviation from 1.0 allowed for the length of a normal
+const NORMAL_TOLERANCE: f32 = 0.01;
+
+fn validate_geometry(
+    vertices: &[Vertex],
+    indices: &[u8],
+    index_type: gl::types::GLenum,
+    restart_index: Option<u32>,
+    has_tangents: bool,
+) -> Result<(), MeshError> {
+    for (i, vertex) in vertices.iter().enumerate() {
+        if vertex.position.iter().any(|p| p.is_nan()) {
+            return Err(MeshError::NanPosition(i));
+        }
+        if vertex.normal.iter().any(|n| n.is_nan()) {
+            return Err(MeshError::NanNormal(i));
+        }
+        if (vertex.normal.norm() - 1.0).abs() > NORMAL_TOLERANCE {
+            return Err(MeshError::NonUnitNormal(i));
+        }
+        if has_tangents
+            && (vertex.tangent.iter().any(|t| t.is_nan()) || vertex.tangent == na::Vector3::zeros())
+        {
+            return Err(MeshError::DegenerateTangent(i));
+        }
+    }
+
+    // Indices are stored as bytes regardless of their type
+    if !indices.len().is_multiple_of(index_size(index_type)) {
+        return Err(MeshError::InvalidIndexData);
+    }
+    if let Some(restart_index) = restart_index {
+        if !fits_restart_index(restart_index, index_type) {
+            return Err(MeshError::InvalidRestartIndex(restart_index));
+        }
+    }
+    for index in read_indices(indices, index_type) {
+        if restart_index == Some(index as u32) {
+            continue;
+        }
+        if index >= vertices.len() {
+            return Err(MeshError::IndexOutOfRange {
+                index,
+                vertex_count: vertices.len(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
 /// Geometry to be rendered with a given material
 pub struct Primitive {
     pub vertices: Vec<Vertex>,
@@ -117,9 +434,23 @@ pub struct Primitive {
     pub indices: Vec<u8>,
     pub index_type: gl::types::GLenum,
 
+    /// Topology used to draw, such as `gl::TRIANGLES`, `gl::LINE_STRIP`, or `gl::POINTS`
+    pub mode: gl::types::GLenum,
+
+    /// Index starting a new strip, enabling primitive restart while drawing
+    pub restart_index: Option<u32>,
+
     /// None means default material
     pub material: Option<Handle<Material>>,
 
+    /// Bounding box in model space
+    pub aabb: Aabb,
+
+    /// Center and radius in model space
+    sphere: (na::Vector3<f32>, f32),
+
+    has_tangents: bool,
+
     // Res could be computed on the fly, but we would need to hash both vertices and indices,
     // therefore we store it here and it is responsibility of the scene builder to avoid an
     // explosion of primitive resources at run-time.
@@ -138,17 +469,81 @@ impl Primitive {
         index_type: gl::types::GLenum,
         material: Option<Handle<Material>>,
     ) -> Self {
-        let res = MeshRes::from(&vertices, &indices);
+        let has_tangents = Primitive::detect_tangents(&vertices);
+        Primitive::new_with_layout(vertices, indices, index_type, material, has_tangents)
+    }
+
+    fn new_with_layout(
+        vertices: Vec<Vertex>,
+        indices: Vec<u8>,
+        index_type: gl::types::GLenum,
+        material: Option<Handle<Material>>,
+        has_tangents: bool,
+    ) -> Self {
+        let res = MeshRes::from_with_layout(&vertices, &indices, has_tangents);
+        let aabb = Aabb::from_vertices(&vertices);
+        let sphere = Primitive::compute_sphere(&vertices, &aabb);
 
         Self {
             vertices,
             indices,
             index_type,
+            mode: gl::TRIANGLES,
+            restart_index: None,
             material,
+            aabb,
+            sphere,
+            has_tangents,
             res,
         }
     }
 
+    /// Returns true if any of the vertices has a non-zero tangent
+    fn detect_tangents(vertices: &[Vertex]) -> bool {
+        vertices.iter().any(|v| v.tangent != na::Vector3::zeros())
+    }
+
+    /// Centered in the box, but tighter than the sphere enclosing the box
+    fn compute_sphere(vertices: &[Vertex], aabb: &Aabb) -> (na::Vector3<f32>, f32) {
+        let center = aabb.get_center();
+        let radius = vertices
+            .iter()
+            .map(|v| (na::Vector3::from(v.position) - center).norm())
+            .fold(0.0, f32::max);
+        (center, radius)
+    }
+
+    /// Returns center and radius of a sphere containing the vertices in model space,
+    /// cheaper than the box to test against a frustum
+    pub fn bounding_sphere(&self) -> (na::Vector3<f32>, f32) {
+        self.sphere
+    }
+
+    /// Replaces the vertices, uploading them again and updating the bounds
+    pub fn set_vertices(&mut self, vertices: Vec<Vertex>) {
+        self.aabb = Aabb::from_vertices(&vertices);
+        self.sphere = Primitive::compute_sphere(&vertices, &self.aabb);
+        self.res.vao.bind();
+        self.res.vbo.upload(&vertices);
+        self.vertices = vertices;
+    }
+
+    /// Whether tangent and bitangent attributes are fed to shaders
+    pub fn has_tangents(&self) -> bool {
+        self.has_tangents
+    }
+
+    /// Checks for NaN or non-unit vectors and for indices referring to missing vertices
+    pub fn validate(&self) -> Result<(), MeshError> {
+        validate_geometry(
+            &self.vertices,
+            &self.indices,
+            self.index_type,
+            self.restart_index,
+            self.has_tangents,
+        )
+    }
+
     /// Returns a new unit triangle primitive
     pub fn triangle(material: Handle<Material>) -> Self {
         let mut vertices = vec![Vertex::new(); 3];
@@ -304,17 +699,102 @@ impl Primitive {
             .build()
     }
 
+    /// Returns a cylinder along the Y axis centered at the origin, with `segments` side faces
+    pub fn cylinder(material: Handle<Material>, segments: u16, height: f32, radius: f32) -> Self {
+        let (vertices, indices) = frustum_geometry(segments, height, radius, radius);
+        Self::from_u16_indices(material, vertices, &indices)
+    }
+
+    /// Returns a cone along the Y axis centered at the origin, with its apex at the top
+    pub fn cone(material: Handle<Material>, segments: u16, height: f32, radius: f32) -> Self {
+        let (vertices, indices) = frustum_geometry(segments, height, radius, 0.0);
+        Self::from_u16_indices(material, vertices, &indices)
+    }
+
+    /// Returns a UV sphere centered at the origin, with `segments` columns and half as many rows
+    pub fn sphere(material: Handle<Material>, segments: u16, radius: f32) -> Self {
+        let (vertices, indices) = sphere_geometry(segments, radius);
+        Self::from_u16_indices(material, vertices, &indices)
+    }
+
+    /// Returns a square of side 1 on the XY plane like `quad`, split into `segments` by
+    /// `segments` cells, so that its vertices can be displaced
+    pub fn grid(material: Handle<Material>, segments: u16) -> Self {
+        let segments = segments.max(1);
+        let side = segments + 1;
+
+        let mut vertices = Vec::with_capacity(side as usize * side as usize);
+        for row in 0..side {
+            for column in 0..side {
+                let u = column as f32 / segments as f32;
+                let v = row as f32 / segments as f32;
+                let mut vertex = Vertex::new();
+                vertex.position = [u - 0.5, v - 0.5, 0.0];
+                vertex.tex_coords = [u, v];
+                vertices.push(vertex);
+            }
+        }
+
+        let mut indices = vec![];
+        for row in 0..segments {
+            for column in 0..segments {
+                let i = row * side + column;
+                indices.extend_from_slice(&[i, i + 1, i + side + 1, i + side + 1, i + side, i]);
+            }
+        }
+
+        Self::from_u16_indices(material, vertices, &indices)
+    }
+
+    fn from_u16_indices(
+        material: Handle<Material>,
+        vertices: Vec<Vertex>,
+        indices: &[u16],
+    ) -> Self {
+        let indices = indices
+            .iter()
+            .flat_map(|i| i.to_le_bytes().to_vec())
+            .collect();
+        Self::builder()
+            .vertices(vertices)
+            .indices(indices)
+            .index_type(gl::UNSIGNED_SHORT)
+            .material(Some(material))
+            .build()
+    }
+
     /// This function is going to bind only this primitive's VAO. We do not bind the
     /// primitives' material here because we expect the renderer has already bound it.
     pub fn bind(&self) {
         self.res.bind();
+        self.bind_restart();
+    }
+
+    /// Enables primitive restart for this primitive, or disables it when it has no restart index
+    fn bind_restart(&self) {
+        unsafe {
+            match self.restart_index {
+                Some(_) if cfg!(feature = "gles") => gl::Enable(gl::PRIMITIVE_RESTART_FIXED_INDEX),
+                Some(restart_index) => {
+                    gl::Enable(gl::PRIMITIVE_RESTART);
+                    gl::PrimitiveRestartIndex(restart_index);
+                }
+                None if cfg!(feature = "gles") => gl::Disable(gl::PRIMITIVE_RESTART_FIXED_INDEX),
+                None => gl::Disable(gl::PRIMITIVE_RESTART),
+            }
+        }
+    }
+
+    /// Returns the number of indices, which are stored as bytes
+    pub fn get_index_count(&self) -> usize {
+        self.indices.len() / index_size(self.index_type)
     }
 
     pub fn draw(&self) {
         unsafe {
             gl::DrawElements(
-                gl::TRIANGLES,
-                self.indices.len() as _,
+                self.mode,
+                self.get_index_count() as _,
                 self.index_type,
                 0 as _,
             );
@@ -322,6 +802,122 @@ impl Primitive {
     }
 }
 
+/// Generates side walls and caps of a cylinder whose top can be narrower than its bottom,
+/// down to a cone when `top_radius` is zero, in which case there is no top cap.
+/// Side vertices come first, two for each column, with the seam duplicated for texture
+/// coordinates, then the center and the ring of each cap.
+fn frustum_geometry(
+    segments: u16,
+    height: f32,
+    bottom_radius: f32,
+    top_radius: f32,
+) -> (Vec<Vertex>, Vec<u16>) {
+    let segments = segments.max(3);
+    let (bottom, top) = (-height / 2.0, height / 2.0);
+    let angle = |i: u16| i as f32 / segments as f32 * 2.0 * std::f32::consts::PI;
+
+    let mut vertices = vec![];
+    let mut indices = vec![];
+
+    // Side walls, normals leaning up as the top gets narrower
+    for i in 0..=segments {
+        let (sin, cos) = angle(i).sin_cos();
+        let normal = na::Vector3::new(sin * height, bottom_radius - top_radius, cos * height);
+        let u = i as f32 / segments as f32;
+
+        for &(y, radius, v) in [(bottom, bottom_radius, 0.0), (top, top_radius, 1.0)].iter() {
+            let mut vertex = Vertex::new();
+            vertex.position = [sin * radius, y, cos * radius];
+            vertex.normal = normal.normalize();
+            vertex.tex_coords = [u, v];
+            vertices.push(vertex);
+        }
+    }
+    for i in 0..segments {
+        let (b0, t0, b1, t1) = (2 * i, 2 * i + 1, 2 * i + 2, 2 * i + 3);
+        indices.extend_from_slice(&[b0, b1, t1]);
+        // The second triangle would be degenerate at the apex
+        if top_radius > 0.0 {
+            indices.extend_from_slice(&[b0, t1, t0]);
+        }
+    }
+
+    // Caps facing down and up, wound so that they are front facing from outside
+    for &(y, radius, up) in [(bottom, bottom_radius, -1.0), (top, top_radius, 1.0)].iter() {
+        if radius <= 0.0 {
+            continue;
+        }
+
+        let center = vertices.len() as u16;
+        let mut vertex = Vertex::new();
+        vertex.position = [0.0, y, 0.0];
+        vertex.normal = na::Vector3::new(0.0, up, 0.0);
+        vertex.tex_coords = [0.5, 0.5];
+        vertices.push(vertex);
+
+        for i in 0..segments {
+            let (sin, cos) = angle(i).sin_cos();
+            vertex.position = [sin * radius, y, cos * radius];
+            vertex.tex_coords = [0.5 + sin / 2.0, 0.5 + cos / 2.0];
+            vertices.push(vertex);
+
+            let current = center + 1 + i;
+            let next = center + 1 + (i + 1) % segments;
+            if up > 0.0 {
+                indices.extend_from_slice(&[center, current, next]);
+            } else {
+                indices.extend_from_slice(&[center, next, current]);
+            }
+        }
+    }
+
+    (vertices, indices)
+}
+
+/// Generates a sphere row by row from the bottom pole to the top one, duplicating
+/// the seam and the poles for texture coordinates.
+fn sphere_geometry(segments: u16, radius: f32) -> (Vec<Vertex>, Vec<u16>) {
+    let columns = segments.max(3);
+    let rows = (columns / 2).max(2);
+
+    let mut vertices = vec![];
+    for row in 0..=rows {
+        let v = row as f32 / rows as f32;
+        // From the bottom pole at -PI/2 to the top pole at PI/2
+        let (y, ring) = ((v - 0.5) * std::f32::consts::PI).sin_cos();
+
+        for column in 0..=columns {
+            let u = column as f32 / columns as f32;
+            let (sin, cos) = (u * 2.0 * std::f32::consts::PI).sin_cos();
+            let normal = na::Vector3::new(sin * ring, y, cos * ring);
+
+            let mut vertex = Vertex::new();
+            vertex.position = (normal * radius).into();
+            vertex.normal = normal;
+            vertex.tex_coords = [u, v];
+            vertices.push(vertex);
+        }
+    }
+
+    let mut indices = vec![];
+    let stride = columns + 1;
+    for row in 0..rows {
+        for column in 0..columns {
+            let (b0, b1) = (row * stride + column, row * stride + column + 1);
+            let (t0, t1) = (b0 + stride, b1 + stride);
+            // Triangles touching the poles would be degenerate
+            if row > 0 {
+                indices.extend_from_slice(&[b0, b1, t1]);
+            }
+            if row < rows - 1 {
+                indices.extend_from_slice(&[b0, t1, t0]);
+            }
+        }
+    }
+
+    (vertices, indices)
+}
+
 /// A mesh is an array of primitives to be rendered. A node can contain
 /// one mesh, and a node's transform places the mesh in the scene
 pub struct Mesh {
@@ -337,3 +933,280 @@ impl Mesh {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cylinder_and_cone() {
+        let (vertices, indices) = frustum_geometry(8, 2.0, 1.0, 1.0);
+        // Two columns of side vertices with the seam, then center and ring of both caps
+        assert_eq!(vertices.len(), 2 * 9 + 2 * 9);
+        assert_eq!(indices.len(), 8 * 6 + 2 * 8 * 3);
+        assert!(indices.iter().all(|&i| (i as usize) < vertices.len()));
+
+        for vertex in vertices[..18].iter() {
+            let axis = na::Vector3::y();
+            assert!(vertex.normal.dot(&axis).abs() < 1e-6);
+            assert!((vertex.normal.norm() - 1.0).abs() < 1e-6);
+        }
+        // Top cap
+        assert_eq!(vertices[27].normal, na::Vector3::y());
+
+        let (vertices, indices) = frustum_geometry(8, 2.0, 1.0, 0.0);
+        assert_eq!(vertices.len(), 2 * 9 + 9);
+        assert_eq!(indices.len(), 8 * 3 + 8 * 3);
+        // Apex
+        assert_eq!(vertices[1].position, [0.0, 1.0, 0.0]);
+        assert!(vertices[0].normal.y > 0.0);
+    }
+
+    #[test]
+    fn sphere() {
+        let (vertices, indices) = sphere_geometry(8, 2.0);
+        // Five rows of nine vertices, with the seam
+        assert_eq!(vertices.len(), 5 * 9);
+        // Two triangles for each quad, but only one for quads touching the poles
+        assert_eq!(indices.len(), (2 * 8 * 2 + 2 * 8) * 3);
+        assert!(indices.iter().all(|&i| (i as usize) < vertices.len()));
+
+        for vertex in vertices.iter() {
+            let position = na::Vector3::from(vertex.position);
+            assert!((position.norm() - 2.0).abs() < 1e-5);
+            assert!((vertex.normal - position / 2.0).norm() < 1e-5);
+        }
+        assert!((vertices[0].position[1] + 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn tangent_detection() {
+        let mut vertices = vec![Vertex::new(); 3];
+        assert!(!Primitive::detect_tangents(&vertices));
+        vertices[1].tangent = na::Vector3::x();
+        assert!(Primitive::detect_tangents(&vertices));
+    }
+
+    #[test]
+    fn invalid_geometry() {
+        let builder = Primitive::builder()
+            .vertices(vec![Vertex::new(); 3])
+            .indices(vec![0, 1, 2]);
+        assert_eq!(builder.validate(), Ok(()));
+
+        let builder = builder.indices(vec![0, 1, 3]);
+        assert_eq!(
+            builder.validate(),
+            Err(MeshError::IndexOutOfRange {
+                index: 3,
+                vertex_count: 3
+            })
+        );
+
+        // Little endian 16-bit indices
+        let builder = builder
+            .index_type(gl::UNSIGNED_SHORT)
+            .indices(vec![0, 0, 1, 0, 0, 1]);
+        assert_eq!(
+            builder.validate(),
+            Err(MeshError::IndexOutOfRange {
+                index: 256,
+                vertex_count: 3
+            })
+        );
+
+        let mut vertices = vec![Vertex::new(); 3];
+        vertices[1].normal = na::Vector3::new(0.0, 2.0, 0.0);
+        let builder = Primitive::builder().vertices(vertices);
+        assert_eq!(builder.validate(), Err(MeshError::NonUnitNormal(1)));
+
+        let mut vertices = vec![Vertex::new(); 3];
+        vertices[2].position[0] = f32::NAN;
+        let builder = Primitive::builder().vertices(vertices);
+        assert_eq!(builder.validate(), Err(MeshError::NanPosition(2)));
+
+        // Normal mapping needs tangents
+        let builder = Primitive::builder()
+            .vertices(vec![Vertex::new(); 3])
+            .tangents(true);
+        assert_eq!(builder.validate(), Err(MeshError::DegenerateTangent(0)));
+    }
+
+    #[test]
+    fn restart_index() {
+        let builder = Primitive::builder()
+            .vertices(vec![Vertex::new(); 4])
+            .mode(gl::LINE_STRIP)
+            .indices(vec![0, 1, 255, 2, 3])
+            .restart_index(255);
+        assert_eq!(builder.validate(), Ok(()));
+
+        // Does not fit a byte
+        let builder = builder.restart_index(256);
+        assert_eq!(builder.validate(), Err(MeshError::InvalidRestartIndex(256)));
+    }
+
+    #[test]
+    fn flat_and_smooth_normals() {
+        // Two triangles sharing an edge, folded along it
+        let mut vertices = vec![Vertex::new(); 4];
+        vertices[0].position = [0.0, 0.0, 0.0];
+        vertices[1].position = [1.0, 0.0, 0.0];
+        vertices[2].position = [0.0, 1.0, 0.0];
+        vertices[3].position = [-1.0, 0.0, 1.0];
+        let indices = vec![0, 1, 2, 0, 2, 3];
+
+        let flat = Primitive::builder()
+            .vertices(vertices.clone())
+            .indices(indices.clone())
+            .normals_mode(NormalsMode::Flat);
+        assert_eq!(flat.vertices.len(), 6);
+        assert_eq!(flat.indices, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(flat.validate(), Ok(()));
+
+        let first = flat.vertices[0].normal;
+        let second = flat.vertices[3].normal;
+        assert!(flat.vertices[..3].iter().all(|v| v.normal == first));
+        assert!(flat.vertices[3..].iter().all(|v| v.normal == second));
+        assert_eq!(first, na::Vector3::z());
+        assert!((first - second).norm() > 0.1);
+
+        let smooth = Primitive::builder()
+            .vertices(vertices)
+            .indices(indices)
+            .normals_mode(NormalsMode::Smooth);
+        assert_eq!(smooth.vertices.len(), 4);
+        assert_eq!(smooth.validate(), Ok(()));
+        // Shared vertices lie between the two faces
+        let shared = smooth.vertices[0].normal;
+        assert!(shared.dot(&first) < 1.0 && shared.dot(&second) < 1.0);
+        assert_eq!(smooth.vertices[1].normal, first);
+    }
+
+    #[test]
+    fn computed_tangents() {
+        // Triangle facing +Z with U along X and V along Y
+        let mut vertices = vec![Vertex::new(); 3];
+        vertices[0].position = [0.0, 0.0, 0.0];
+        vertices[1].position = [1.0, 0.0, 0.0];
+        vertices[1].tex_coords = [1.0, 0.0];
+        vertices[2].position = [0.0, 1.0, 0.0];
+        vertices[2].tex_coords = [0.0, 1.0];
+        for vertex in vertices.iter_mut() {
+            vertex.normal = na::Vector3::z();
+        }
+
+        let builder = Primitive::builder()
+            .vertices(vertices)
+            .indices(vec![0, 1, 2])
+            .compute_tangents();
+        for vertex in &builder.vertices {
+            assert!((vertex.tangent - na::Vector3::x()).norm() < 1e-6);
+            assert!((vertex.bitangent - na::Vector3::y()).norm() < 1e-6);
+        }
+        assert_eq!(builder.validate(), Ok(()));
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn bounding_sphere() {
+        let _context = TestContext::new();
+
+        // A diamond, whose vertices are not at the corners of its box
+        let mut vertices = vec![Vertex::new(); 4];
+        vertices[0].position = [0.0, 0.0, 0.0];
+        vertices[1].position = [2.0, 0.0, 0.0];
+        vertices[2].position = [1.0, 1.0, 0.0];
+        vertices[3].position = [1.0, -1.0, 0.0];
+        let primitive = Primitive::builder().vertices(vertices).build();
+
+        let (center, radius) = primitive.bounding_sphere();
+        assert_eq!(center, na::Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(radius, 1.0);
+        // Tighter than the sphere containing the box
+        assert!(radius < primitive.aabb.get_size().norm() / 2.0);
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn tangent_layouts() {
+        let _context = TestContext::new();
+
+        let is_enabled = |primitive: &Primitive, location| {
+            primitive.bind();
+            let mut enabled = 0;
+            unsafe {
+                gl::GetVertexAttribiv(location, gl::VERTEX_ATTRIB_ARRAY_ENABLED, &mut enabled)
+            };
+            enabled != 0
+        };
+
+        let quad = Primitive::quad(Handle::none());
+        assert!(!quad.has_tangents());
+        assert!(is_enabled(&quad, 3));
+        assert!(!is_enabled(&quad, 4));
+        assert!(!is_enabled(&quad, 5));
+
+        let mut vertices = vec![Vertex::new(); 3];
+        for vertex in vertices.iter_mut() {
+            vertex.tangent = na::Vector3::x();
+            vertex.bitangent = na::Vector3::y();
+        }
+        let triangle = Primitive::builder()
+            .vertices(vertices)
+            .indices(vec![0, 1, 2])
+            .build();
+        assert!(triangle.has_tangents());
+        assert!(is_enabled(&triangle, 4));
+        assert!(is_enabled(&triangle, 5));
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn restart_strip() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        // Two horizontal segments through the centers of the middle row of pixels
+        let y = 0.5 / 64.0;
+        let mut vertices = vec![Vertex::new(); 4];
+        for (vertex, &x) in vertices.iter_mut().zip([-0.4, -0.1, 0.1, 0.4].iter()) {
+            vertex.position = [x, y, 0.0];
+        }
+
+        let mut model = Model::new();
+        model.color_texture(Color::new());
+        let material = model.materials.push(Material::builder().unlit().build());
+        let strip = Primitive::builder()
+            .vertices(vertices)
+            .mode(gl::LINE_STRIP)
+            .indices(vec![0, 1, 255, 2, 3])
+            .restart_index(255)
+            .material(Some(material))
+            .build();
+        assert_eq!(strip.get_index_count(), 5);
+        let strip = model.primitives.push(strip);
+        let mesh = model.meshes.push(Mesh::new(vec![strip]));
+        let camera = model.cameras.push(Camera::orthographic(1, 1, 0.1, 100.0));
+        let mut camera_node = Node::builder().camera(camera).build();
+        camera_node.trs.translate(0.0, 0.0, 1.0);
+        let children = vec![
+            model.nodes.push(Node::builder().mesh(mesh).build()),
+            model.nodes.push(camera_node),
+        ];
+        let root = model.nodes.push(Node::builder().children(children).build());
+
+        let frame = gfx.next_frame();
+        gfx.renderer.draw(&model, root, &na::Matrix4::identity());
+        gfx.renderer
+            .render_geometry(&model, frame.get_geometry_buffer());
+
+        let (extent, pixels) = frame.get_geometry_buffer().read_color(0);
+        let red = |x: u32| pixels[((extent.height / 2 * extent.width + x) * 4) as usize];
+        // Both segments are drawn, but not what would connect them
+        assert_eq!(red(16), 255);
+        assert_eq!(red(48), 255);
+        assert!(red(32) < 255);
+        gfx.present(frame);
+    }
+}
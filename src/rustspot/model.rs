@@ -3,15 +3,16 @@
 // SPDX-License-Identifier: MIT
 
 use std::{
-    collections::HashMap,
-    convert::TryInto,
-    error::Error,
+    collections::{HashMap, HashSet},
     path::{Path, PathBuf},
+    sync::Mutex,
 };
 
 use gltf::Gltf;
 use nalgebra as na;
-use rayon::iter::{ParallelBridge, ParallelIterator};
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, ParallelBridge, ParallelIterator,
+};
 
 use super::*;
 
@@ -26,6 +27,28 @@ fn data_type_as_size(data_type: gltf::accessor::DataType) -> usize {
     }
 }
 
+/// Returns the `KHR_materials_emissive_strength` of each material in the glTF JSON
+fn get_emissive_strengths(json: &[u8]) -> Vec<f32> {
+    let root: serde_json::Value = match serde_json::from_slice(json) {
+        Ok(root) => root,
+        Err(_) => return vec![],
+    };
+
+    let materials = match root["materials"].as_array() {
+        Some(materials) => materials,
+        None => return vec![],
+    };
+
+    materials
+        .iter()
+        .map(|material| {
+            material["extensions"]["KHR_materials_emissive_strength"]["emissiveStrength"]
+                .as_f64()
+                .unwrap_or(1.0) as f32
+        })
+        .collect()
+}
+
 fn data_type_as_gl(data_type: gltf::accessor::DataType) -> gl::types::GLenum {
     match data_type {
         gltf::accessor::DataType::I8 => todo!(),
@@ -37,6 +60,18 @@ fn data_type_as_gl(data_type: gltf::accessor::DataType) -> gl::types::GLenum {
     }
 }
 
+fn mode_as_gl(mode: gltf::mesh::Mode) -> gl::types::GLenum {
+    match mode {
+        gltf::mesh::Mode::Points => gl::POINTS,
+        gltf::mesh::Mode::Lines => gl::LINES,
+        gltf::mesh::Mode::LineLoop => gl::LINE_LOOP,
+        gltf::mesh::Mode::LineStrip => gl::LINE_STRIP,
+        gltf::mesh::Mode::Triangles => gl::TRIANGLES,
+        gltf::mesh::Mode::TriangleStrip => gl::TRIANGLE_STRIP,
+        gltf::mesh::Mode::TriangleFan => gl::TRIANGLE_FAN,
+    }
+}
+
 fn dimensions_as_size(dimensions: gltf::accessor::Dimensions) -> usize {
     match dimensions {
         gltf::accessor::Dimensions::Scalar => 1,
@@ -59,28 +94,258 @@ fn get_stride(accessor: &gltf::Accessor) -> usize {
     data_type_as_size(accessor.data_type()) * dimensions_as_size(accessor.dimensions())
 }
 
+/// Reads `count` elements of type `T` which are `stride` bytes apart.
+/// Tightly packed elements are copied all at once.
+fn read_elements<T: Copy + Default>(data: &[u8], count: usize, stride: usize) -> Vec<T> {
+    let size = std::mem::size_of::<T>();
+    assert!(stride >= size);
+    assert!(count == 0 || (count - 1) * stride + size <= data.len());
+
+    let mut elements = vec![T::default(); count];
+    let dst = elements.as_mut_ptr() as *mut u8;
+
+    // Copying bytes, as elements in the buffer may not be aligned
+    unsafe {
+        if stride == size {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), dst, count * size);
+        } else {
+            for i in 0..count {
+                std::ptr::copy_nonoverlapping(
+                    data.as_ptr().add(i * stride),
+                    dst.add(i * size),
+                    size,
+                );
+            }
+        }
+    }
+
+    elements
+}
+
+/// Reads `count` positions which are `stride` bytes apart, exposed for the benchmarks
+#[cfg(feature = "bench")]
+pub fn read_positions(data: &[u8], count: usize, stride: usize) -> Vec<[f32; 3]> {
+    read_elements(data, count, stride)
+}
+
+/// Makes sure there is a vertex for each element of an attribute
+fn resize_vertices(vertices: &mut Vec<Vertex>, count: usize) {
+    if vertices.len() < count {
+        vertices.resize(count, Vertex::new());
+    }
+}
+
+/// Reads `count` RGB or RGBA colors, as floats or normalized unsigned integers.
+/// Alpha is dropped, as vertices have no place for it.
+fn read_colors(
+    data: &[u8],
+    count: usize,
+    stride: usize,
+    data_type: gltf::accessor::DataType,
+    dimensions: gltf::accessor::Dimensions,
+) -> Result<Vec<[f32; 3]>, RustspotError> {
+    use gltf::accessor::{DataType, Dimensions};
+
+    fn normalize<T: Copy + Into<f32>, const N: usize>(
+        colors: Vec<[T; N]>,
+        max: f32,
+    ) -> Vec<[f32; 3]> {
+        colors
+            .into_iter()
+            .map(|c| [c[0].into() / max, c[1].into() / max, c[2].into() / max])
+            .collect()
+    }
+
+    let colors = match (data_type, dimensions) {
+        (DataType::F32, Dimensions::Vec3) => read_elements::<[f32; 3]>(data, count, stride),
+        (DataType::F32, Dimensions::Vec4) => {
+            normalize(read_elements::<[f32; 4]>(data, count, stride), 1.0)
+        }
+        (DataType::U8, Dimensions::Vec3) => {
+            normalize(read_elements::<[u8; 3]>(data, count, stride), 255.0)
+        }
+        (DataType::U8, Dimensions::Vec4) => {
+            normalize(read_elements::<[u8; 4]>(data, count, stride), 255.0)
+        }
+        (DataType::U16, Dimensions::Vec3) => {
+            normalize(read_elements::<[u16; 3]>(data, count, stride), 65535.0)
+        }
+        (DataType::U16, Dimensions::Vec4) => {
+            normalize(read_elements::<[u16; 4]>(data, count, stride), 65535.0)
+        }
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid color format {:?} {:?}", data_type, dimensions),
+            )
+            .into())
+        }
+    };
+
+    Ok(colors)
+}
+
+/// Reads `count` vectors of four floats or unsigned integers, such as joints and weights.
+/// When `normalized`, integers are mapped to [0, 1], otherwise they are converted as they are.
+fn read_vec4(
+    data: &[u8],
+    count: usize,
+    stride: usize,
+    data_type: gltf::accessor::DataType,
+    normalized: bool,
+) -> Result<Vec<[f32; 4]>, RustspotError> {
+    use gltf::accessor::DataType;
+
+    fn convert<T: Copy + Into<f32>>(elements: Vec<[T; 4]>, max: f32) -> Vec<[f32; 4]> {
+        elements
+            .into_iter()
+            .map(|e| {
+                [
+                    e[0].into() / max,
+                    e[1].into() / max,
+                    e[2].into() / max,
+                    e[3].into() / max,
+                ]
+            })
+            .collect()
+    }
+
+    let vec4s = match data_type {
+        DataType::F32 => read_elements::<[f32; 4]>(data, count, stride),
+        DataType::U8 => {
+            let max = if normalized { 255.0 } else { 1.0 };
+            convert(read_elements::<[u8; 4]>(data, count, stride), max)
+        }
+        DataType::U16 => {
+            let max = if normalized { 65535.0 } else { 1.0 };
+            convert(read_elements::<[u16; 4]>(data, count, stride), max)
+        }
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid vec4 component type {:?}", data_type),
+            )
+            .into())
+        }
+    };
+
+    Ok(vec4s)
+}
+
+/// Called with the number of loading steps done so far and the total number of steps
+pub type ProgressCallback = Box<dyn FnMut(usize, usize) + Send>;
+
+/// Loading steps are buffers, images, materials, and meshes
+struct Progress {
+    /// The mutex only makes the builder shareable with loading threads,
+    /// as the callback is called by the thread owning the builder
+    callback: Option<Mutex<ProgressCallback>>,
+    done: usize,
+    total: usize,
+}
+
+impl Progress {
+    fn advance(&mut self) {
+        self.done += 1;
+        if let Some(callback) = self.callback.as_mut() {
+            (callback.get_mut().unwrap())(self.done, self.total);
+        }
+    }
+}
+
 pub struct ModelBuilder {
     uri_buffers: Vec<Vec<u8>>,
     parent_dir: PathBuf,
     gltf: Gltf,
+    /// Index of the scene to load, the default one when not set
+    scene: Option<usize>,
+    /// Whether to check the geometry of primitives while loading them
+    validate: bool,
+    normals_mode: NormalsMode,
+    /// Whether to flip texture coordinates vertically
+    flip_v: bool,
+    progress: Progress,
+    /// KHR_materials_emissive_strength of each material, 1.0 when missing
+    emissive_strengths: Vec<f32>,
 }
 
 impl ModelBuilder {
     /// Creates a model loading a GLTF file
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, RustspotError> {
+        let data = std::fs::read(path.as_ref())?;
+        let gltf = Gltf::from_slice(&data)?;
+        let emissive_strengths = if data.starts_with(b"glTF") {
+            get_emissive_strengths(&gltf::Glb::from_slice(&data)?.json)
+        } else {
+            get_emissive_strengths(&data)
+        };
+
         let ret = Self {
             uri_buffers: vec![],
             parent_dir: path
                 .as_ref()
                 .parent()
-                .ok_or("Failed to get parent directory")?
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "Failed to get parent directory",
+                    )
+                })?
                 .into(),
-            gltf: Gltf::open(path)?,
+            gltf,
+            scene: None,
+            validate: false,
+            normals_mode: NormalsMode::Keep,
+            flip_v: false,
+            progress: Progress {
+                callback: None,
+                done: 0,
+                total: 0,
+            },
+            emissive_strengths,
         };
         Ok(ret)
     }
 
-    fn load_uri_buffers(&mut self) -> Result<(), Box<dyn Error>> {
+    /// Selects which scene of the glTF file to load
+    pub fn scene(mut self, index: usize) -> Self {
+        self.scene = Some(index);
+        self
+    }
+
+    /// Checks primitives for NaN or non-unit vectors and out of range indices,
+    /// and all the handles of the model once loaded with `Model::validate`,
+    /// failing to build the model when any of them is invalid
+    pub fn validate(mut self, validate: bool) -> Self {
+        self.validate = validate;
+        self
+    }
+
+    /// Whether to recompute normals of triangles for flat or smooth shading
+    pub fn normals_mode(mut self, normals_mode: NormalsMode) -> Self {
+        self.normals_mode = normals_mode;
+        self
+    }
+
+    /// Flips the V texture coordinate of vertices, for models expecting the texture
+    /// origin at the top-left corner, while OpenGL puts it at the bottom-left one
+    pub fn flip_v(mut self, flip_v: bool) -> Self {
+        self.flip_v = flip_v;
+        self
+    }
+
+    /// Sets a function called every time a buffer, an image, a material, or a mesh is loaded,
+    /// useful to show a loading bar
+    pub fn on_progress(mut self, callback: ProgressCallback) -> Self {
+        self.progress.total = self.gltf.buffers().len()
+            + self.gltf.images().len()
+            + self.gltf.materials().len()
+            + self.gltf.meshes().len();
+        self.progress.callback = Some(Mutex::new(callback));
+        self
+    }
+
+    fn load_uri_buffers(&mut self) -> Result<(), RustspotError> {
         let mut timer = Timer::new();
 
         for buffer in self.gltf.buffers() {
@@ -90,12 +355,13 @@ impl ModelBuilder {
                     let data = std::fs::read(uri)?;
                     assert!(buffer.index() == self.uri_buffers.len());
                     self.uri_buffers.push(data);
+                    self.progress.advance();
                 }
                 _ => unimplemented!(),
             }
         }
 
-        println!("Buffers loaded ({}s)", timer.get_delta().as_secs_f32());
+        log::debug!("Buffers loaded ({}s)", timer.get_delta().as_secs_f32());
         Ok(())
     }
 
@@ -119,45 +385,97 @@ impl ModelBuilder {
         &data[offset..end_offset]
     }
 
-    pub fn load_textures(&mut self, model: &mut Model) {
-        let mut timer = Timer::new();
+    /// Returns the bytes of an image embedded in a buffer view
+    fn get_view_data(&self, view: &gltf::buffer::View) -> Result<&[u8], RustspotError> {
+        let buffer = view.buffer();
+        if let gltf::buffer::Source::Bin = buffer.source() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Images embedded in a GLB binary chunk are not supported",
+            )
+            .into());
+        }
 
-        // Let us load textures first
-        let texture_builders: Vec<TextureBuilder> = self
+        let data = &self.uri_buffers[buffer.index()];
+        let end_offset = view.offset() + view.length();
+        if end_offset > data.len() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!(
+                    "Image view {} ends at {} past buffer {} of {} bytes",
+                    view.index(),
+                    end_offset,
+                    buffer.index(),
+                    data.len()
+                ),
+            )
+            .into());
+        }
+        Ok(&data[view.offset()..end_offset])
+    }
+
+    /// Decodes images into main memory, one for each thread, as it does not involve GL
+    fn decode_images(&self) -> Result<Vec<TextureBuilder<'static>>, RustspotError> {
+        enum ImageSource<'a> {
+            Path(PathBuf),
+            Bytes(&'a [u8]),
+        }
+
+        let sources = self
             .gltf
             .images()
+            .map(|image| match image.source() {
+                gltf::image::Source::View { view, .. } => {
+                    self.get_view_data(&view).map(ImageSource::Bytes)
+                }
+                // Join gltf parent dir to URI
+                gltf::image::Source::Uri { uri, .. } => {
+                    Ok(ImageSource::Path(self.parent_dir.join(uri)))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // A parallel iterator over a vector splits work better than a bridged one
+        sources
+            .into_par_iter()
             .enumerate()
-            .par_bridge()
-            .map(|(i, image)| {
-                match image.source() {
-                    gltf::image::Source::View { .. } => todo!(),
-                    gltf::image::Source::Uri { uri, .. } => {
-                        // Join gltf parent dir to URI
-                        let path = self.parent_dir.join(uri);
-                        Texture::builder().id(i as u32).path(path)
-                    }
+            .map(|(i, source)| {
+                let builder = Texture::builder().id(i as u32);
+                match source {
+                    ImageSource::Path(path) => builder.path(path),
+                    ImageSource::Bytes(bytes) => builder.png(bytes),
                 }
             })
-            .collect();
+            .collect()
+    }
+
+    pub fn load_textures(&mut self, model: &mut Model) -> Result<(), RustspotError> {
+        let mut timer = Timer::new();
+
+        // Let us load textures first
+        let texture_builders = self.decode_images()?;
 
-        println!(
+        log::debug!(
             "Loaded images from file ({}s)",
             timer.get_delta().as_secs_f32()
         );
 
         // This can not be done in parallel as OpenGL is not multithread-friendly
-        let mut textures: Vec<Texture> = texture_builders
-            .into_iter()
-            .map(|builder| builder.build().unwrap())
-            .collect();
+        let mut textures = Vec::with_capacity(texture_builders.len());
+        for builder in texture_builders {
+            textures.push(builder.build()?);
+            self.progress.advance();
+        }
 
         textures.sort_by_key(|tex| tex.id);
         model.textures = Pack::from(textures);
 
-        println!(
+        log::debug!(
             "Loaded images to GPU ({}s)",
             timer.get_delta().as_secs_f32()
         );
+
+        Ok(())
     }
 
     pub fn load_texture(
@@ -182,19 +500,17 @@ impl ModelBuilder {
 
                 return Some(texture_handle);
             }
-            _ => unimplemented!(),
+            // Textures are sorted by the index of the image they were decoded from
+            gltf::image::Source::View { .. } => Some(Handle::new(texture.source().index())),
         }
     }
 
-    pub fn load_materials(
-        &mut self,
-        textures: &Pack<Texture>,
-        colors: &mut HashMap<Color, Texture>,
-        materials: &mut Pack<Material>,
-    ) -> Result<(), Box<dyn Error>> {
+    pub fn load_materials(&mut self, model: &mut Model) -> Result<(), RustspotError> {
         let _ = ScopedTimer::new("Materials loaded");
 
-        for gmaterial in self.gltf.materials() {
+        model.materials.reserve(self.gltf.materials().len());
+
+        for (i, gmaterial) in self.gltf.materials().enumerate() {
             let mut material = Material::builder().shader(Shaders::LightShadow).build();
 
             let pbr = gmaterial.pbr_metallic_roughness();
@@ -205,7 +521,7 @@ impl ModelBuilder {
 
             // Load albedo
             if let Some(gtexture) = pbr.base_color_texture() {
-                material.texture = self.load_texture(textures, &gtexture.texture());
+                material.texture = self.load_texture(&model.textures, &gtexture.texture());
             } else {
                 let gcolor = gmaterial.pbr_metallic_roughness().base_color_factor();
                 let color = Color::rgba(
@@ -215,27 +531,25 @@ impl ModelBuilder {
                     (gcolor[3] * 255.0) as u8,
                 );
                 material.color = color;
-                if !colors.contains_key(&color) {
-                    let texture = Texture::builder().data(color.as_slice()).build()?;
-                    colors.insert(color, texture);
-                }
+                model.color_texture(color);
             }
 
             // Load normal map
             if let Some(gtexture) = gmaterial.normal_texture() {
-                material.normals = self.load_texture(&textures, &gtexture.texture());
+                material.normals = self.load_texture(&model.textures, &gtexture.texture());
                 normal_variant = PbrNormalVariant::Texture;
             }
 
             // Load ambient occlusion texture
             if let Some(gtexture) = gmaterial.occlusion_texture() {
-                material.occlusion = self.load_texture(&textures, &gtexture.texture());
+                material.occlusion = self.load_texture(&model.textures, &gtexture.texture());
                 occlusion_variant = PbrOcclusionVariant::Texture;
             }
 
             // Load metallic rougness texture
             if let Some(gtexture) = pbr.metallic_roughness_texture() {
-                material.metallic_roughness = self.load_texture(&textures, &gtexture.texture());
+                material.metallic_roughness =
+                    self.load_texture(&model.textures, &gtexture.texture());
                 metallic_roughness_variant = PbrMetallicRoughnessVariant::Texture;
             }
 
@@ -243,36 +557,65 @@ impl ModelBuilder {
             material.shader =
                 PBR_VARIANTS[occlusion_variant as usize][metallic_roughness_variant as usize][normal_variant as usize][PbrShadowVariant::Texture as usize];
 
+            // KHR_materials_unlit
+            if gmaterial.unlit() {
+                material.shader = Shaders::Unlit;
+            }
+
             material.metallic = pbr.metallic_factor();
             material.roughness = pbr.roughness_factor();
 
-            materials.push(material);
+            if gmaterial.alpha_mode() == gltf::material::AlphaMode::Mask {
+                material.alpha_cutoff = gmaterial.alpha_cutoff().unwrap_or(0.5);
+            }
+
+            // KHR_materials_emissive_strength
+            let strength = self.emissive_strengths.get(i).copied().unwrap_or(1.0);
+            let emissive = gmaterial.emissive_factor();
+            material.emissive = [
+                emissive[0] * strength,
+                emissive[1] * strength,
+                emissive[2] * strength,
+            ];
+
+            model.materials.push(material);
+            self.progress.advance();
         }
 
         Ok(())
     }
 
-    pub fn build(&mut self) -> Result<Model, Box<dyn Error>> {
+    pub fn build(&mut self) -> Result<Model, RustspotError> {
         let mut model = Model::new();
 
         self.load_uri_buffers()?;
-        self.load_textures(&mut model);
-        self.load_materials(&model.textures, &mut model.colors, &mut model.materials)?;
+        self.load_textures(&mut model)?;
+        self.load_materials(&mut model)?;
         self.load_meshes(&mut model)?;
-
-        // Load scene
-        let scene = self.gltf.scenes().next().unwrap();
+        self.load_skins(&mut model);
+
+        // Load the selected scene, falling back to the default one and then to the first one
+        let scene_index = self
+            .scene
+            .or_else(|| self.gltf.default_scene().map(|scene| scene.index()))
+            .unwrap_or(0);
+        let scene = self.gltf.scenes().nth(scene_index).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Failed to find scene {}", scene_index),
+            )
+        })?;
         let root = Node::builder()
             .name("Root".into())
             .children(
                 scene
                     .nodes()
-                    .par_bridge()
                     .map(|gchild| Handle::new(gchild.index() + 1))
                     .collect(),
             )
             .build();
         // Root is always at index 0
+        model.nodes.reserve(self.gltf.nodes().len() + 1);
         model.nodes.push(root);
 
         // Load nodes
@@ -283,7 +626,6 @@ impl ModelBuilder {
                 .children(
                     gnode
                         .children()
-                        .par_bridge()
                         .map(|gchild| Handle::new(gchild.index() + 1))
                         .collect(),
                 );
@@ -312,109 +654,175 @@ impl ModelBuilder {
                 node_builder = node_builder.mesh(Handle::new(mesh.index()));
             }
 
+            if let Some(skin) = gnode.skin() {
+                node_builder = node_builder.skin(Handle::new(skin.index()));
+            }
+
+            // KHR_lights_punctual
+            if let Some(glight) = gnode.light() {
+                if let gltf::khr_lights_punctual::Kind::Directional = glight.kind() {
+                    let [r, g, b] = glight.color();
+                    let light = DirectionalLight::color(r, g, b).with_intensity(glight.intensity());
+                    let light = model.directional_lights.push(light);
+                    node_builder = node_builder.directional_light(light);
+                }
+            }
+
             let node = node_builder.build();
             model.nodes.push(node);
         }
 
+        if self.validate {
+            model.validate().map_err(RustspotError::Model)?;
+        }
+
         Ok(model)
     }
 
-    fn load_meshes(&self, model: &mut Model) -> Result<(), Box<dyn Error>> {
-        for gmesh in self.gltf.meshes() {
-            let mut primitive_handles = vec![];
+    /// Reads vertices and indices of a primitive. It does not need GL, hence it can run on any thread.
+    fn load_primitive(
+        &self,
+        gprimitive: &gltf::Primitive,
+    ) -> Result<PrimitiveBuilder, RustspotError> {
+        let mut vertices = vec![];
+
+        // Load normals first, so we can process tangents later
+        for (semantic, accessor) in gprimitive.attributes() {
+            if semantic == gltf::mesh::Semantic::Normals {
+                self.load_normals(&mut vertices, &accessor)?;
+            }
+        }
 
-            for gprimitive in gmesh.primitives() {
-                let mut vertices = vec![];
+        for (semantic, accessor) in gprimitive.attributes() {
+            match semantic {
+                gltf::mesh::Semantic::Positions => self.load_positions(&mut vertices, &accessor)?,
+                gltf::mesh::Semantic::TexCoords(_) => {
+                    self.load_tex_coords(&mut vertices, &accessor)?
+                }
+                gltf::mesh::Semantic::Tangents => self.load_tangents(&mut vertices, &accessor)?,
+                gltf::mesh::Semantic::Colors(0) => self.load_colors(&mut vertices, &accessor)?,
+                gltf::mesh::Semantic::Joints(0) => self.load_joints(&mut vertices, &accessor)?,
+                gltf::mesh::Semantic::Weights(0) => self.load_weights(&mut vertices, &accessor)?,
+                gltf::mesh::Semantic::Normals => (),
+                _ => log::warn!("Semantic not implemented {:?}", semantic),
+            }
+        }
 
-                let mode = gprimitive.mode();
-                assert!(mode == gltf::mesh::Mode::Triangles);
+        let mut indices = vec![];
+        let mut index_type = gl::UNSIGNED_BYTE;
+        if let Some(accessor) = gprimitive.indices() {
+            let data_type = accessor.data_type();
+            index_type = data_type_as_gl(data_type);
 
-                // Load normals first, so we can process tangents later
-                for (semantic, accessor) in gprimitive.attributes() {
-                    if semantic == gltf::mesh::Semantic::Normals {
-                        self.load_normals(&mut vertices, &accessor)?;
-                    }
-                }
+            // Use bytes regardless of the index data type
+            let data = self.get_data_start(&accessor);
+            let length = accessor.count() * data_type_as_size(data_type);
+            indices = Vec::from(&data[..length]);
+        }
 
-                for (semantic, accessor) in gprimitive.attributes() {
-                    match semantic {
-                        gltf::mesh::Semantic::Positions => {
-                            self.load_positions(&mut vertices, &accessor)?
-                        }
-                        gltf::mesh::Semantic::TexCoords(_) => {
-                            self.load_tex_coords(&mut vertices, &accessor)?
-                        }
-                        gltf::mesh::Semantic::Tangents => {
-                            self.load_tangents(&mut vertices, &accessor)?
-                        }
-                        _ => println!("Semantic not implemented {:?}", semantic),
-                    }
-                }
+        let material = gprimitive.material().index().map(|id| Handle::new(id));
+        let has_tangents = gprimitive.get(&gltf::mesh::Semantic::Tangents).is_some();
 
-                let mut indices = vec![];
-                let mut index_type = gl::UNSIGNED_BYTE;
-                if let Some(accessor) = gprimitive.indices() {
-                    let data_type = accessor.data_type();
-                    index_type = data_type_as_gl(data_type);
-
-                    // Data type can vary
-                    let data = self.get_data_start(&accessor);
-                    let d = &data[0];
-                    let length = accessor.count() * data_type_as_size(data_type);
-                    // Use bytes regardless of the index data type
-                    let slice: &[u8] =
-                        unsafe { std::slice::from_raw_parts(d as *const u8 as _, length) };
-                    indices = Vec::from(slice);
-                }
+        let builder = Primitive::builder()
+            .vertices(vertices)
+            .indices(indices)
+            .index_type(index_type)
+            .mode(mode_as_gl(gprimitive.mode()))
+            .tangents(has_tangents)
+            .material(material)
+            .normals_mode(self.normals_mode);
 
-                let material = gprimitive.material().index().map(|id| Handle::new(id));
+        if self.validate {
+            builder.validate()?;
+        }
 
-                let primitive = Primitive::builder()
-                    .vertices(vertices)
-                    .indices(indices)
-                    .index_type(index_type)
-                    .material(material)
-                    .build();
-                let primitive_handle = model.primitives.push(primitive);
-                primitive_handles.push(primitive_handle);
-            }
+        Ok(builder)
+    }
+
+    fn load_meshes(&mut self, model: &mut Model) -> Result<(), RustspotError> {
+        let mut timer = Timer::new();
+
+        // Reading attributes is done in parallel, as it does not involve GL
+        let mut gmeshes: Vec<(usize, Vec<PrimitiveBuilder>)> = self
+            .gltf
+            .meshes()
+            .par_bridge()
+            .map(|gmesh| {
+                let builders = gmesh
+                    .primitives()
+                    .map(|gprimitive| self.load_primitive(&gprimitive))
+                    .collect::<Result<_, _>>()?;
+                Ok((gmesh.index(), builders))
+            })
+            .collect::<Result<_, RustspotError>>()?;
+
+        // Bridged iterators do not preserve order
+        gmeshes.sort_by_key(|(index, _)| *index);
+
+        log::debug!(
+            "Loaded vertices from buffers ({}s)",
+            timer.get_delta().as_secs_f32()
+        );
+
+        model.meshes.reserve(gmeshes.len());
+        model
+            .primitives
+            .reserve(gmeshes.iter().map(|(_, builders)| builders.len()).sum());
+
+        for (_, builders) in gmeshes {
+            let primitive_handles = builders
+                .into_iter()
+                .map(|builder| model.primitives.push(builder.build()))
+                .collect();
 
             let mesh = Mesh::new(primitive_handles);
             model.meshes.push(mesh);
+            self.progress.advance();
         }
 
+        log::debug!(
+            "Loaded vertices to GPU ({}s)",
+            timer.get_delta().as_secs_f32()
+        );
+
         Ok(())
     }
 
-    fn load_positions(
+    /// Checks the accessor of a float vertex attribute and reads all of its elements
+    fn read_attribute<T: Copy + Default>(
         &self,
-        vertices: &mut Vec<Vertex>,
         accessor: &gltf::Accessor,
-    ) -> Result<(), Box<dyn Error>> {
+        dimensions: gltf::accessor::Dimensions,
+    ) -> Vec<T> {
         let data_type = accessor.data_type();
         assert!(data_type == gltf::accessor::DataType::F32);
-        let count = accessor.count();
-        let dimensions = accessor.dimensions();
-        assert!(dimensions == gltf::accessor::Dimensions::Vec3);
+        assert!(accessor.dimensions() == dimensions);
+        assert_eq!(
+            std::mem::size_of::<T>(),
+            dimensions_as_size(dimensions) * data_type_as_size(data_type)
+        );
 
         let view = accessor.view().unwrap();
-
         let target = view.target().unwrap_or(gltf::buffer::Target::ArrayBuffer);
         assert!(target == gltf::buffer::Target::ArrayBuffer);
 
         let data = self.get_data_start(accessor);
         let stride = get_stride(accessor);
 
-        for i in 0..count {
-            let offset = i * stride;
-            assert!(offset < data.len());
-            let d = &data[offset];
-            let position = unsafe { std::slice::from_raw_parts::<f32>(d as *const u8 as _, 3) };
+        read_elements(data, accessor.count(), stride)
+    }
 
-            if vertices.len() <= i {
-                vertices.push(Vertex::new())
-            }
-            vertices[i].position = position.try_into()?;
+    fn load_positions(
+        &self,
+        vertices: &mut Vec<Vertex>,
+        accessor: &gltf::Accessor,
+    ) -> Result<(), RustspotError> {
+        let positions: Vec<[f32; 3]> =
+            self.read_attribute(accessor, gltf::accessor::Dimensions::Vec3);
+
+        resize_vertices(vertices, positions.len());
+        for (vertex, position) in vertices.iter_mut().zip(positions) {
+            vertex.position = position;
         }
 
         Ok(())
@@ -424,103 +832,130 @@ impl ModelBuilder {
         &self,
         vertices: &mut Vec<Vertex>,
         accessor: &gltf::Accessor,
-    ) -> Result<(), Box<dyn Error>> {
-        let data_type = accessor.data_type();
-        assert!(data_type == gltf::accessor::DataType::F32);
-        let count = accessor.count();
-        let dimensions = accessor.dimensions();
-        assert!(dimensions == gltf::accessor::Dimensions::Vec3);
-
-        let view = accessor.view().unwrap();
-        let target = view.target().unwrap_or(gltf::buffer::Target::ArrayBuffer);
-        assert!(target == gltf::buffer::Target::ArrayBuffer);
+    ) -> Result<(), RustspotError> {
+        let normals: Vec<[f32; 3]> =
+            self.read_attribute(accessor, gltf::accessor::Dimensions::Vec3);
 
-        let data = self.get_data_start(accessor);
-        let stride = get_stride(accessor);
+        resize_vertices(vertices, normals.len());
+        for (vertex, normal) in vertices.iter_mut().zip(normals) {
+            vertex.normal = na::Vector3::from(normal);
+        }
 
-        for i in 0..count {
-            let offset = i * stride;
-            assert!(offset < data.len());
-            let d = &data[offset];
-            let normal = unsafe { std::slice::from_raw_parts::<f32>(d as *const u8 as _, 3) };
+        Ok(())
+    }
 
-            if vertices.len() <= i {
-                vertices.push(Vertex::new())
+    fn load_tex_coords(
+        &self,
+        vertices: &mut Vec<Vertex>,
+        accessor: &gltf::Accessor,
+    ) -> Result<(), RustspotError> {
+        let tex_coords: Vec<[f32; 2]> =
+            self.read_attribute(accessor, gltf::accessor::Dimensions::Vec2);
+
+        resize_vertices(vertices, tex_coords.len());
+        for (vertex, tex_coords) in vertices.iter_mut().zip(tex_coords) {
+            vertex.tex_coords = tex_coords;
+            if self.flip_v {
+                vertex.tex_coords[1] = 1.0 - tex_coords[1];
             }
-            vertices[i].normal[0] = normal[0];
-            vertices[i].normal[1] = normal[1];
-            vertices[i].normal[2] = normal[2];
         }
 
         Ok(())
     }
 
-    fn load_tex_coords(
+    fn load_tangents(
         &self,
         vertices: &mut Vec<Vertex>,
         accessor: &gltf::Accessor,
-    ) -> Result<(), Box<dyn Error>> {
-        let data_type = accessor.data_type();
-        assert!(data_type == gltf::accessor::DataType::F32);
-        let count = accessor.count();
-        let dimensions = accessor.dimensions();
-        assert!(dimensions == gltf::accessor::Dimensions::Vec2);
+    ) -> Result<(), RustspotError> {
+        let tangents: Vec<[f32; 4]> =
+            self.read_attribute(accessor, gltf::accessor::Dimensions::Vec4);
 
-        let view = accessor.view().unwrap();
-        let target = view.target().unwrap_or(gltf::buffer::Target::ArrayBuffer);
-        assert!(target == gltf::buffer::Target::ArrayBuffer);
+        resize_vertices(vertices, tangents.len());
+        for (vertex, tangent) in vertices.iter_mut().zip(tangents) {
+            vertex.tangent = na::Vector3::new(tangent[0], tangent[1], tangent[2]);
+
+            // Compute bitangent as for glTF 2.0 spec
+            vertex.bitangent = vertex.normal.cross(&vertex.tangent) * tangent[3];
+        }
 
+        Ok(())
+    }
+
+    fn load_joints(
+        &self,
+        vertices: &mut Vec<Vertex>,
+        accessor: &gltf::Accessor,
+    ) -> Result<(), RustspotError> {
         let data = self.get_data_start(accessor);
         let stride = get_stride(accessor);
+        let joints = read_vec4(data, accessor.count(), stride, accessor.data_type(), false)?;
 
-        for i in 0..count {
-            let offset = i * stride;
-            assert!(offset < data.len());
-            let d = &data[offset];
-            let tex_coords = unsafe { std::slice::from_raw_parts::<f32>(d as *const u8 as _, 2) };
-
-            if vertices.len() <= i {
-                vertices.push(Vertex::new())
-            }
-            vertices[i].tex_coords = tex_coords.try_into()?;
+        resize_vertices(vertices, joints.len());
+        for (vertex, joints) in vertices.iter_mut().zip(joints) {
+            vertex.joints = joints;
         }
 
         Ok(())
     }
 
-    fn load_tangents(
+    fn load_weights(
         &self,
         vertices: &mut Vec<Vertex>,
         accessor: &gltf::Accessor,
-    ) -> Result<(), Box<dyn Error>> {
-        let data_type = accessor.data_type();
-        assert!(data_type == gltf::accessor::DataType::F32);
-        let count = accessor.count();
-        let dimensions = accessor.dimensions();
-        assert!(dimensions == gltf::accessor::Dimensions::Vec4);
-
-        let view = accessor.view().unwrap();
-        let target = view.target().unwrap_or(gltf::buffer::Target::ArrayBuffer);
-        assert!(target == gltf::buffer::Target::ArrayBuffer);
-
+    ) -> Result<(), RustspotError> {
         let data = self.get_data_start(accessor);
         let stride = get_stride(accessor);
+        let weights = read_vec4(data, accessor.count(), stride, accessor.data_type(), true)?;
 
-        for i in 0..count {
-            let offset = i * stride;
-            assert!(offset < data.len());
-            let d = &data[offset];
-            let tangent = unsafe { std::slice::from_raw_parts::<f32>(d as *const u8 as _, 4) };
+        resize_vertices(vertices, weights.len());
+        for (vertex, weights) in vertices.iter_mut().zip(weights) {
+            vertex.weights = weights;
+        }
 
-            if vertices.len() <= i {
-                vertices.push(Vertex::new())
+        Ok(())
+    }
+
+    /// Joints refer to nodes, whose handles are offset by the root node
+    fn load_skins(&self, model: &mut Model) {
+        for gskin in self.gltf.skins() {
+            let joints = gskin
+                .joints()
+                .map(|joint| Handle::new(joint.index() + 1))
+                .collect();
+            let mut skin = Skin::new(joints);
+
+            // Identity matrices when not specified
+            if let Some(accessor) = gskin.inverse_bind_matrices() {
+                let matrices: Vec<[f32; 16]> =
+                    self.read_attribute(&accessor, gltf::accessor::Dimensions::Mat4);
+                for (inverse_bind, matrix) in skin.inverse_bind_matrices.iter_mut().zip(matrices) {
+                    *inverse_bind = na::Matrix4::from_column_slice(&matrix);
+                }
             }
-            vertices[i].tangent[0] = tangent[0];
-            vertices[i].tangent[1] = tangent[1];
-            vertices[i].tangent[2] = tangent[2];
 
-            // Compute bitangent as for glTF 2.0 spec
-            vertices[i].bitangent = vertices[i].normal.cross(&vertices[i].tangent) * tangent[3];
+            model.skins.push(skin);
+        }
+    }
+
+    fn load_colors(
+        &self,
+        vertices: &mut Vec<Vertex>,
+        accessor: &gltf::Accessor,
+    ) -> Result<(), RustspotError> {
+        let data = self.get_data_start(accessor);
+        let stride = get_stride(accessor);
+        let colors = read_colors(
+            data,
+            accessor.count(),
+            stride,
+            accessor.data_type(),
+            accessor.dimensions(),
+        )?;
+
+        resize_vertices(vertices, colors.len());
+        for (vertex, color) in vertices.iter_mut().zip(colors) {
+            vertex.color = color;
         }
 
         Ok(())
@@ -528,7 +963,8 @@ impl ModelBuilder {
 }
 
 pub struct Model {
-    pub colors: HashMap<Color, Texture>,
+    /// Pixel textures in `textures` of materials without a texture, see `color_texture`
+    pub colors: HashMap<Color, Handle<Texture>>,
     pub textures: Pack<Texture>,
     pub materials: Pack<Material>,
     pub primitives: Pack<Primitive>,
@@ -536,11 +972,13 @@ pub struct Model {
     pub nodes: Pack<Node>,
     pub directional_lights: Pack<DirectionalLight>,
     pub point_lights: Pack<PointLight>,
+    pub spot_lights: Pack<SpotLight>,
     pub cameras: Pack<Camera>,
+    pub skins: Pack<Skin>,
 }
 
 impl Model {
-    pub fn builder<P: AsRef<Path>>(path: P) -> Result<ModelBuilder, Box<dyn Error>> {
+    pub fn builder<P: AsRef<Path>>(path: P) -> Result<ModelBuilder, RustspotError> {
         ModelBuilder::new(path)
     }
 
@@ -554,7 +992,785 @@ impl Model {
             nodes: Pack::new(),
             directional_lights: Pack::new(),
             point_lights: Pack::new(),
+            spot_lights: Pack::new(),
             cameras: Pack::new(),
+            skins: Pack::new(),
+        }
+    }
+
+    /// Returns a one pixel texture of this color, creating it only the first time.
+    /// It is registered in `colors`, so materials of this color without a texture can be drawn.
+    pub fn color_texture(&mut self, color: Color) -> Handle<Texture> {
+        if let Some(&handle) = self.colors.get(&color) {
+            return handle;
+        }
+
+        let handle = self.textures.push(Texture::pixel(color));
+        self.colors.insert(color, handle);
+        handle
+    }
+
+    /// Checks that all the handles of the model resolve. A `none` handle is not an error,
+    /// as it stands for something missing on purpose, such as a node without a mesh.
+    pub fn validate(&self) -> Result<(), Vec<ModelError>> {
+        fn resolves<T>(pack: &Pack<T>, handle: Handle<T>) -> bool {
+            handle.is_none() || pack.contains(handle)
+        }
+
+        let mut errors = vec![];
+
+        for handle in self.nodes.handles() {
+            let node = self.nodes.get(handle).unwrap();
+            let id = handle.id;
+            for &child in node.children.iter() {
+                if !resolves(&self.nodes, child) {
+                    errors.push(ModelError::MissingChild {
+                        node: id,
+                        child: child.id,
+                    });
+                }
+            }
+            let meshes = std::iter::once(node.mesh).chain(node.lods.iter().map(|lod| lod.1));
+            for mesh in meshes {
+                if !resolves(&self.meshes, mesh) {
+                    errors.push(ModelError::MissingMesh {
+                        node: id,
+                        mesh: mesh.id,
+                    });
+                }
+            }
+            if !resolves(&self.cameras, node.camera) {
+                errors.push(ModelError::MissingCamera {
+                    node: id,
+                    camera: node.camera.id,
+                });
+            }
+            if !resolves(&self.skins, node.skin) {
+                errors.push(ModelError::MissingSkin {
+                    node: id,
+                    skin: node.skin.id,
+                });
+            }
+            let resolved_lights = [
+                resolves(&self.directional_lights, node.directional_light),
+                resolves(&self.point_lights, node.point_light),
+                resolves(&self.spot_lights, node.spot_light),
+            ];
+            let lights = [
+                node.directional_light.id,
+                node.point_light.id,
+                node.spot_light.id,
+            ];
+            for (&resolved, &light) in resolved_lights.iter().zip(lights.iter()) {
+                if !resolved {
+                    errors.push(ModelError::MissingLight { node: id, light });
+                }
+            }
+        }
+
+        for handle in self.meshes.handles() {
+            let mesh = self.meshes.get(handle).unwrap();
+            for &primitive in mesh.primitives.iter() {
+                if !resolves(&self.primitives, primitive) {
+                    errors.push(ModelError::MissingPrimitive {
+                        mesh: handle.id,
+                        primitive: primitive.id,
+                    });
+                }
+            }
+        }
+
+        for handle in self.primitives.handles() {
+            let primitive = self.primitives.get(handle).unwrap();
+            // No material stands for the default one
+            if let Some(material) = primitive.material {
+                if !resolves(&self.materials, material) {
+                    errors.push(ModelError::MissingMaterial {
+                        primitive: handle.id,
+                        material: material.id,
+                    });
+                }
+            }
+        }
+
+        for handle in self.materials.handles() {
+            let material = self.materials.get(handle).unwrap();
+            let textures = [
+                material.texture,
+                material.normals,
+                material.height,
+                material.occlusion,
+                material.metallic_roughness,
+            ];
+            for texture in textures.iter().flatten() {
+                if !resolves(&self.textures, *texture) {
+                    errors.push(ModelError::MissingTexture {
+                        material: handle.id,
+                        texture: texture.id,
+                    });
+                }
+            }
+            // The albedo falls back to a pixel texture of the material color
+            if material.texture.is_none() && !self.colors.contains_key(&material.color) {
+                errors.push(ModelError::MissingColor {
+                    material: handle.id,
+                });
+            }
+        }
+
+        for handle in self.skins.handles() {
+            let skin = self.skins.get(handle).unwrap();
+            for &joint in skin.joints.iter() {
+                if !resolves(&self.nodes, joint) {
+                    errors.push(ModelError::MissingJoint {
+                        skin: handle.id,
+                        joint: joint.id,
+                    });
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Returns the world space box containing the primitives of the subtree starting at `root`,
+    /// or none when there are no primitives. Levels of detail and instances are not considered.
+    pub fn scene_aabb(&self, root: Handle<Node>) -> Option<Aabb> {
+        let mut bounds = SceneBounds::default();
+        self.add_node_bounds(root, &na::Matrix4::identity(), &mut bounds);
+        bounds.aabb
+    }
+
+    fn add_node_bounds(
+        &self,
+        node_handle: Handle<Node>,
+        transform: &na::Matrix4<f32>,
+        bounds: &mut SceneBounds,
+    ) {
+        let node = match self.nodes.get(node_handle) {
+            Some(node) => node,
+            None => return,
+        };
+
+        let transform = transform * node.get_matrix();
+        if let Some(mesh) = self.meshes.get(node.mesh) {
+            for &primitive in mesh.primitives.iter() {
+                if let Some(primitive) = self.primitives.get(primitive) {
+                    bounds.add(&primitive.aabb.transform(&transform));
+                }
+            }
+        }
+
+        for &child in node.children.iter() {
+            self.add_node_bounds(child, &transform, bounds);
+        }
+    }
+
+    /// Adds a point light with a small glowing sphere as its child, so that it can be seen.
+    /// The sphere is within the near plane of point shadows, thus it does not cast any.
+    /// Returns the light node, which should be added to the scene graph.
+    pub fn add_point_light(&mut self, position: na::Vector3<f32>, color: Color) -> Handle<Node> {
+        self.color_texture(color);
+
+        let material = self.materials.push(Material::emissive_unlit(color));
+        let radius = POINT_SHADOW_NEAR / 2.0;
+        let primitive = self
+            .primitives
+            .push(Primitive::sphere(material, 12, radius));
+        let mesh = self.meshes.push(Mesh::new(vec![primitive]));
+        let bulb = self.nodes.push(Node::builder().mesh(mesh).build());
+
+        let mut light = PointLight::new();
+        let rgba = color.as_slice();
+        light.color = [
+            rgba[0] as f32 / 255.0,
+            rgba[1] as f32 / 255.0,
+            rgba[2] as f32 / 255.0,
+        ];
+
+        let mut light_node = Node::builder().children(vec![bulb]).build();
+        light_node.point_light = self.point_lights.push(light);
+        light_node
+            .trs
+            .set_translation(position.x, position.y, position.z);
+        self.nodes.push(light_node)
+    }
+
+    /// Removes a node and all its descendants, detaching it from its parent. When `free_meshes`
+    /// is true, meshes and primitives which are not used by any remaining node are removed too.
+    pub fn remove_subtree(&mut self, root: Handle<Node>, free_meshes: bool) {
+        // Collect the whole subtree before removing anything
+        let mut subtree = vec![];
+        let mut stack = vec![root];
+        while let Some(handle) = stack.pop() {
+            if let Some(node) = self.nodes.get(handle) {
+                stack.extend(node.children.iter().cloned());
+                subtree.push(handle);
+            }
+        }
+
+        for node in self.nodes.iter_mut() {
+            node.children.retain(|child| child.id != root.id);
+        }
+
+        let mut meshes = HashSet::new();
+        for handle in subtree {
+            let node = self.nodes.get(handle).unwrap();
+            meshes.insert(node.mesh.id);
+            meshes.extend(node.lods.iter().map(|(_, mesh)| mesh.id));
+            self.nodes.remove(handle);
+        }
+
+        if free_meshes {
+            self.remove_orphan_meshes(meshes);
+        }
+    }
+
+    /// Removes those meshes not used by any node, and their primitives not used by other meshes
+    fn remove_orphan_meshes(&mut self, mut meshes: HashSet<usize>) {
+        meshes.remove(&Handle::<Mesh>::none().id);
+        for node in self.nodes.iter() {
+            meshes.remove(&node.mesh.id);
+            for (_, mesh) in node.lods.iter() {
+                meshes.remove(&mesh.id);
+            }
         }
+
+        let mut primitives = HashSet::new();
+        for mesh_id in meshes {
+            let mesh = Handle::new(mesh_id);
+            for primitive in self.meshes.get(mesh).unwrap().primitives.iter() {
+                primitives.insert(primitive.id);
+            }
+            self.meshes.remove(mesh);
+        }
+
+        for mesh in self.meshes.iter() {
+            for primitive in mesh.primitives.iter() {
+                primitives.remove(&primitive.id);
+            }
+        }
+
+        for primitive_id in primitives {
+            self.primitives.remove(Handle::new(primitive_id));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Writes a glTF file and its binary buffer to a temporary directory
+    fn write_gltf(name: &str, json: &str, bin: &[u8]) -> PathBuf {
+        let dir = std::env::temp_dir().join("rustspot-test");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(format!("{}.bin", name)), bin).unwrap();
+        let path = dir.join(format!("{}.gltf", name));
+        std::fs::write(&path, json.replace("BIN", &format!("{}.bin", name))).unwrap();
+        path
+    }
+
+    /// Three points connected as a line strip
+    fn write_line_strip() -> PathBuf {
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "uri": "BIN", "byteLength": 36 }],
+            "bufferViews": [{ "buffer": 0, "byteLength": 36, "target": 34962 }],
+            "accessors": [{
+                "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                "min": [0.0, 0.0, 0.0], "max": [2.0, 1.0, 0.0]
+            }],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "mode": 3 }] }],
+            "nodes": [{ "mesh": 0 }],
+            "scenes": [{ "nodes": [0] }]
+        }"#;
+        let bin = as_bytes(&[0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 2.0, 0.0, 0.0]);
+        write_gltf("line-strip", json, &bin)
+    }
+
+    #[test]
+    fn line_strip_mode() {
+        let builder = Model::builder(write_line_strip()).unwrap();
+        let gmesh = builder.gltf.meshes().next().unwrap();
+        let gprimitive = gmesh.primitives().next().unwrap();
+        assert_eq!(mode_as_gl(gprimitive.mode()), gl::LINE_STRIP);
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn load_line_strip() {
+        let _context = TestContext::new();
+
+        let model = Model::builder(write_line_strip()).unwrap().build().unwrap();
+        assert_eq!(model.primitives[0].mode, gl::LINE_STRIP);
+        assert_eq!(model.primitives[0].vertices.len(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn load_progress() {
+        let _context = TestContext::new();
+
+        // One buffer, two materials, and one mesh
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "uri": "BIN", "byteLength": 36 }],
+            "bufferViews": [{ "buffer": 0, "byteLength": 36, "target": 34962 }],
+            "accessors": [{
+                "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]
+            }],
+            "materials": [{ "name": "a" }, { "name": "b" }],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "material": 0 }] }],
+            "nodes": [{ "mesh": 0 }],
+            "scenes": [{ "nodes": [0] }]
+        }"#;
+        let bin = as_bytes(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        let path = write_gltf("progress", json, &bin);
+
+        let steps = std::sync::Arc::new(Mutex::new(vec![]));
+        let callback_steps = steps.clone();
+        Model::builder(&path)
+            .unwrap()
+            .on_progress(Box::new(move |done, total| {
+                callback_steps.lock().unwrap().push((done, total))
+            }))
+            .build()
+            .unwrap();
+
+        assert_eq!(*steps.lock().unwrap(), vec![(1, 4), (2, 4), (3, 4), (4, 4)]);
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn unlit_extension() {
+        let _context = TestContext::new();
+
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "extensionsUsed": ["KHR_materials_unlit"],
+            "materials": [
+                { "extensions": { "KHR_materials_unlit": {} } },
+                { "name": "lit" }
+            ],
+            "scenes": [{ "nodes": [] }]
+        }"#;
+        let path = write_gltf("unlit", json, &[]);
+
+        let model = Model::builder(&path).unwrap().build().unwrap();
+        assert!(model.materials[0].is_unlit());
+        assert!(!model.materials[1].is_unlit());
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn emissive_strength_extension() {
+        let _context = TestContext::new();
+
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "extensionsUsed": ["KHR_materials_emissive_strength"],
+            "materials": [
+                {
+                    "emissiveFactor": [1.0, 0.5, 0.0],
+                    "extensions": { "KHR_materials_emissive_strength": { "emissiveStrength": 3.0 } }
+                },
+                { "emissiveFactor": [1.0, 0.5, 0.0] }
+            ],
+            "scenes": [{ "nodes": [] }]
+        }"#;
+        let path = write_gltf("emissive-strength", json, &[]);
+
+        let model = Model::builder(&path).unwrap().build().unwrap();
+        assert_eq!(model.materials[0].emissive, [3.0, 1.5, 0.0]);
+        assert_eq!(model.materials[1].emissive, [1.0, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn dangling_node_handles() {
+        let mut model = Model::new();
+        assert_eq!(model.validate(), Ok(()));
+
+        let mut node = Node::new();
+        node.camera = Handle::new(2);
+        node.children.push(Handle::new(5));
+        let node = model.nodes.push(node);
+
+        let errors = model.validate().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.contains(&ModelError::MissingCamera {
+            node: node.id,
+            camera: 2
+        }));
+        assert!(errors.contains(&ModelError::MissingChild {
+            node: node.id,
+            child: 5
+        }));
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn dangling_material() {
+        let _context = TestContext::new();
+
+        let mut model = Model::new();
+        model.color_texture(Color::new());
+        let material = model.materials.push(Material::builder().build());
+        model.primitives.push(Primitive::quad(material));
+        assert_eq!(model.validate(), Ok(()));
+
+        let primitive = model.primitives.push(Primitive::quad(Handle::new(3)));
+        assert_eq!(
+            model.validate(),
+            Err(vec![ModelError::MissingMaterial {
+                primitive: primitive.id,
+                material: 3
+            }])
+        );
+    }
+
+    #[test]
+    fn remove_branch() {
+        let mut model = Model::new();
+        let shared = model.meshes.push(Mesh::new(vec![]));
+        let only_c = model.meshes.push(Mesh::new(vec![]));
+        let only_a = model.meshes.push(Mesh::new(vec![]));
+
+        let mut node = |name: &str, mesh: Handle<Mesh>, children: Vec<Handle<Node>>| {
+            let mut node = Node::new();
+            node.name = name.into();
+            node.mesh = mesh;
+            node.children = children;
+            model.nodes.push(node)
+        };
+        // root -> a -> b
+        //      -> c
+        let b = node("b", shared, vec![]);
+        let a = node("a", only_a, vec![b]);
+        let c = node("c", only_c, vec![]);
+        let root = node("root", Handle::none(), vec![a, c]);
+
+        model.remove_subtree(a, true);
+
+        assert_eq!(model.nodes.len(), 2);
+        let root = model.nodes.get(root).unwrap();
+        assert_eq!(root.children.len(), 1);
+        assert_eq!(root.children[0].id, c.id);
+
+        let c = model.nodes.get(c).unwrap();
+        assert_eq!(c.name, "c");
+
+        // Only the mesh used by a is gone
+        assert_eq!(model.meshes.len(), 1);
+        assert!(model.meshes.get(c.mesh).is_some());
+    }
+
+    #[test]
+    fn select_scene() {
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "nodes": [{ "name": "a" }, { "name": "b" }, { "name": "c" }],
+            "scenes": [{ "nodes": [0] }, { "nodes": [2, 1] }],
+            "scene": 0
+        }"#;
+        let path = write_gltf("two-scenes", json, &[]);
+
+        let model = Model::builder(&path).unwrap().build().unwrap();
+        let root = &model.nodes[0];
+        assert_eq!(root.children.len(), 1);
+        assert_eq!(model.nodes[root.children[0].id].name, "a");
+
+        let model = Model::builder(&path).unwrap().scene(1).build().unwrap();
+        let names: Vec<&str> = model.nodes[0]
+            .children
+            .iter()
+            .map(|child| model.nodes[child.id].name.as_str())
+            .collect();
+        // Children keep the order of the scene
+        assert_eq!(names, vec!["c", "b"]);
+
+        assert!(Model::builder(&path).unwrap().scene(2).build().is_err());
+    }
+
+    fn as_bytes(floats: &[f32]) -> Vec<u8> {
+        floats
+            .iter()
+            .flat_map(|f| f.to_le_bytes().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn read_packed_and_strided() {
+        let data = as_bytes(&[0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+        let packed: Vec<[f32; 3]> = read_elements(&data, 2, 12);
+        assert_eq!(packed, vec![[0.0, 1.0, 2.0], [3.0, 4.0, 5.0]]);
+
+        // Interleaved with another attribute
+        let strided: Vec<[f32; 2]> = read_elements(&data, 2, 12);
+        assert_eq!(strided, vec![[0.0, 1.0], [3.0, 4.0]]);
+
+        // Not aligned to floats
+        let mut unaligned = vec![0];
+        unaligned.extend(&data);
+        let unaligned: Vec<[f32; 3]> = read_elements(&unaligned[1..], 2, 12);
+        assert_eq!(unaligned, packed);
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn reuse_color() {
+        let _context = TestContext::new();
+
+        let mut model = Model::new();
+        let red = model.color_texture(Color::rgba(255, 0, 0, 255));
+        let green = model.color_texture(Color::rgba(0, 255, 0, 255));
+        assert_ne!(red.id, green.id);
+        assert_eq!(model.color_texture(Color::rgba(255, 0, 0, 255)).id, red.id);
+        assert_eq!(model.textures.iter().count(), 2);
+        assert_eq!(model.colors.len(), 2);
+    }
+
+    #[test]
+    fn read_color_formats() {
+        use gltf::accessor::{DataType, Dimensions};
+
+        let data = as_bytes(&[0.0, 0.5, 1.0, 1.0, 0.25, 0.75]);
+        let colors = read_colors(&data, 2, 12, DataType::F32, Dimensions::Vec3).unwrap();
+        assert_eq!(colors, vec![[0.0, 0.5, 1.0], [1.0, 0.25, 0.75]]);
+
+        // Alpha is dropped
+        let data = [255, 0, 51, 128, 0, 255, 0, 255];
+        let colors = read_colors(&data, 2, 4, DataType::U8, Dimensions::Vec4).unwrap();
+        assert_eq!(colors, vec![[1.0, 0.0, 0.2], [0.0, 1.0, 0.0]]);
+
+        let data: Vec<u8> = [65535u16, 0, 0]
+            .iter()
+            .flat_map(|c| c.to_le_bytes().to_vec())
+            .collect();
+        let colors = read_colors(&data, 1, 6, DataType::U16, Dimensions::Vec3).unwrap();
+        assert_eq!(colors, vec![[1.0, 0.0, 0.0]]);
+
+        assert!(read_colors(&data, 1, 6, DataType::I16, Dimensions::Vec3).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn load_vertex_colors() {
+        let _context = TestContext::new();
+
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "uri": "BIN", "byteLength": 48 }],
+            "bufferViews": [
+                { "buffer": 0, "byteLength": 36, "target": 34962 },
+                { "buffer": 0, "byteOffset": 36, "byteLength": 12, "target": 34962 }
+            ],
+            "accessors": [{
+                "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]
+            }, {
+                "bufferView": 1, "componentType": 5121, "normalized": true,
+                "count": 3, "type": "VEC4"
+            }],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0, "COLOR_0": 1 } }] }],
+            "nodes": [{ "mesh": 0 }],
+            "scenes": [{ "nodes": [0] }]
+        }"#;
+        let mut bin = as_bytes(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        bin.extend(&[255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255]);
+        let path = write_gltf("vertex-colors", json, &bin);
+
+        let model = Model::builder(path).unwrap().build().unwrap();
+        let vertices = &model.primitives[0].vertices;
+        assert_eq!(vertices[0].color, [1.0, 0.0, 0.0]);
+        assert_eq!(vertices[1].color, [0.0, 1.0, 0.0]);
+        assert_eq!(vertices[2].color, [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn decode_images_in_parallel() {
+        let dir = std::env::temp_dir().join("rustspot-test");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let count = 8;
+        let extent = Extent2D::new(16, 16);
+        for i in 0..count {
+            let file = std::fs::File::create(dir.join(format!("decode-{}.png", i))).unwrap();
+            let mut encoder = png::Encoder::new(file, extent.width, extent.height);
+            encoder.set_color(png::ColorType::RGB);
+            encoder.set_depth(png::BitDepth::Eight);
+            let data: Vec<u8> = (0..extent.width * extent.height * 3)
+                .map(|j| (j * (i + 1) % 251) as u8)
+                .collect();
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&data).unwrap();
+        }
+
+        let images: Vec<String> = (0..count)
+            .map(|i| format!(r#"{{ "uri": "decode-{}.png" }}"#, i))
+            .collect();
+        let json = format!(
+            r#"{{ "asset": {{ "version": "2.0" }}, "images": [{}] }}"#,
+            images.join(", ")
+        );
+        let path = write_gltf("decode", &json, &[]);
+        let builder = Model::builder(&path).unwrap();
+
+        // Same images in the same order as decoding them one at a time
+        let decoded = builder.decode_images().unwrap();
+        assert_eq!(decoded.len(), count as usize);
+        for (i, texture) in decoded.iter().enumerate() {
+            let expected = Texture::builder()
+                .path(dir.join(format!("decode-{}.png", i)))
+                .unwrap();
+            assert_eq!(texture.id, i as u32);
+            assert!(texture.extent == expected.extent);
+            assert_eq!(texture.owned_data, expected.owned_data);
+        }
+    }
+
+    /// Reads one element at a time, as the bulk read in `read_elements` replaced
+    fn read_positions_per_element(data: &[u8], count: usize, stride: usize) -> Vec<[f32; 3]> {
+        let mut positions = vec![];
+        for i in 0..count {
+            let mut position = [0.0; 3];
+            for (j, value) in position.iter_mut().enumerate() {
+                let offset = i * stride + j * 4;
+                let mut bytes = [0; 4];
+                bytes.copy_from_slice(&data[offset..offset + 4]);
+                *value = f32::from_le_bytes(bytes);
+            }
+            positions.push(position);
+        }
+        positions
+    }
+
+    /// Timing lives in `benches/read_elements.rs`
+    #[test]
+    fn read_large_attribute() {
+        let count = 1 << 12;
+        let floats: Vec<f32> = (0..count * 4).map(|i| i as f32).collect();
+        let data = as_bytes(&floats);
+
+        for &stride in &[12, 16] {
+            let count = floats.len() * 4 / stride;
+            let bulk: Vec<[f32; 3]> = read_elements(&data, count, stride);
+            assert_eq!(bulk, read_positions_per_element(&data, count, stride));
+        }
+    }
+
+    #[test]
+    fn read_joints_and_weights() {
+        let joints = [0u8, 1, 2, 255];
+        let read = read_vec4(&joints, 1, 4, gltf::accessor::DataType::U8, false).unwrap();
+        assert_eq!(read, vec![[0.0, 1.0, 2.0, 255.0]]);
+
+        // Normalized weights
+        let read = read_vec4(&joints, 1, 4, gltf::accessor::DataType::U8, true).unwrap();
+        assert_eq!(read, vec![[0.0, 1.0 / 255.0, 2.0 / 255.0, 1.0]]);
+
+        let weights = as_bytes(&[0.5, 0.5, 0.0, 0.0]);
+        let read = read_vec4(&weights, 1, 16, gltf::accessor::DataType::F32, true).unwrap();
+        assert_eq!(read, vec![[0.5, 0.5, 0.0, 0.0]]);
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn load_skin() {
+        let _context = TestContext::new();
+
+        // A triangle deformed by two joints, the second one bound one unit up
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "uri": "BIN", "byteLength": 236 }],
+            "bufferViews": [
+                { "buffer": 0, "byteLength": 36, "target": 34962 },
+                { "buffer": 0, "byteOffset": 36, "byteLength": 24, "target": 34962 },
+                { "buffer": 0, "byteOffset": 60, "byteLength": 48, "target": 34962 },
+                { "buffer": 0, "byteOffset": 108, "byteLength": 128 }
+            ],
+            "accessors": [
+                {
+                    "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                    "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]
+                },
+                { "bufferView": 1, "componentType": 5123, "count": 3, "type": "VEC4" },
+                { "bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC4" },
+                { "bufferView": 3, "componentType": 5126, "count": 2, "type": "MAT4" }
+            ],
+            "meshes": [{ "primitives": [{
+                "attributes": { "POSITION": 0, "JOINTS_0": 1, "WEIGHTS_0": 2 }
+            }] }],
+            "skins": [{ "joints": [1, 2], "inverseBindMatrices": 3 }],
+            "nodes": [
+                { "mesh": 0, "skin": 0 },
+                { "children": [2] },
+                { "translation": [0.0, 1.0, 0.0] }
+            ],
+            "scenes": [{ "nodes": [0, 1] }]
+        }"#;
+
+        let mut bin = as_bytes(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        for joints in [[0u16, 0, 0, 0], [0, 0, 0, 0], [1, 0, 0, 0]].iter() {
+            bin.extend(joints.iter().flat_map(|j| j.to_le_bytes().to_vec()));
+        }
+        bin.extend(as_bytes(&[
+            1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.5, 0.5, 0.0, 0.0,
+        ]));
+        let mut inverse_bind = na::Matrix4::<f32>::identity().as_slice().to_vec();
+        inverse_bind.extend(
+            na::Matrix4::new_translation(&-na::Vector3::y())
+                .as_slice()
+                .iter(),
+        );
+        bin.extend(as_bytes(&inverse_bind));
+        assert_eq!(bin.len(), 236);
+        let path = write_gltf("skin", json, &bin);
+
+        let model = Model::builder(&path).unwrap().build().unwrap();
+        let vertices = &model.primitives[0].vertices;
+        assert_eq!(vertices[2].joints, [1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(vertices[2].weights, [0.5, 0.5, 0.0, 0.0]);
+
+        let skin = &model.skins[0];
+        assert_eq!(skin.joints.len(), 2);
+        // Node handles are offset by the root
+        assert_eq!(skin.joints[1].id, 3);
+        assert_eq!(
+            skin.inverse_bind_matrices[1],
+            na::Matrix4::new_translation(&-na::Vector3::y())
+        );
+        assert!(model.skins.get(model.nodes[1].skin).is_some());
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn add_point_light() {
+        let _context = TestContext::new();
+
+        let mut model = Model::new();
+        let color = Color::rgba(255, 0, 0, 255);
+        let light = model.add_point_light(na::Vector3::new(1.0, 2.0, 3.0), color);
+
+        let light_node = model.nodes.get(light).unwrap();
+        let point_light = model.point_lights.get(light_node.point_light).unwrap();
+        assert_eq!(point_light.color, [1.0, 0.0, 0.0]);
+        assert_eq!(
+            light_node.trs.get_translation(),
+            na::Vector3::new(1.0, 2.0, 3.0)
+        );
+
+        assert_eq!(light_node.children.len(), 1);
+        let bulb = model.nodes.get(light_node.children[0]).unwrap();
+        let mesh = model.meshes.get(bulb.mesh).unwrap();
+        let primitive = model.primitives.get(mesh.primitives[0]).unwrap();
+        let material = model.materials.get(primitive.material.unwrap()).unwrap();
+        assert!(material.is_unlit());
+        assert!(model.colors.contains_key(&color));
     }
 }
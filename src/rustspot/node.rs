@@ -12,11 +12,12 @@ pub struct NodeBuilder {
     pub translation: na::Translation3<f32>,
     pub rotation: na::UnitQuaternion<f32>,
     pub scale: na::Vector3<f32>,
-    pub matrix: na::Matrix4<f32>,
+    pub matrix: Option<na::Matrix4<f32>>,
     pub children: Vec<Handle<Node>>,
     pub mesh: Option<Handle<Mesh>>,
     pub camera: Option<Handle<Camera>>,
     pub directional_light: Option<Handle<DirectionalLight>>,
+    pub skin: Option<Handle<Skin>>,
 }
 
 impl NodeBuilder {
@@ -27,11 +28,12 @@ impl NodeBuilder {
             translation: na::Translation3::new(0.0, 0.0, 0.0),
             rotation: na::UnitQuaternion::default(),
             scale: na::Vector3::new(1.0, 1.0, 1.0),
-            matrix: na::Matrix4::identity(),
+            matrix: None,
             children: vec![],
             mesh: None,
             camera: None,
             directional_light: None,
+            skin: None,
         }
     }
 
@@ -60,8 +62,9 @@ impl NodeBuilder {
         self
     }
 
+    /// Transform used in place of translation, rotation, and scale
     pub fn matrix(mut self, matrix: na::Matrix4<f32>) -> Self {
-        self.matrix = matrix;
+        self.matrix = Some(matrix);
         self
     }
 
@@ -85,6 +88,11 @@ impl NodeBuilder {
         self
     }
 
+    pub fn skin(mut self, skin: Handle<Skin>) -> Self {
+        self.skin = Some(skin);
+        self
+    }
+
     pub fn build(self) -> Node {
         let mut node = Node::new();
         node.id = self.id;
@@ -94,6 +102,8 @@ impl NodeBuilder {
         node.trs
         .translate(self.translation.x, self.translation.y, self.translation.z);
 
+        node.matrix_override = self.matrix;
+
         node.children = self.children;
         if let Some(mesh) = self.mesh {
             node.mesh = mesh;
@@ -104,6 +114,9 @@ impl NodeBuilder {
         if let Some(light) = self.directional_light {
             node.directional_light = light;
         }
+        if let Some(skin) = self.skin {
+            node.skin = skin;
+        }
         node
     }
 }
@@ -113,13 +126,22 @@ pub struct Node {
     pub id: u32,
     pub name: String,
     pub trs: Trs,
+    /// Used in place of the `trs` transform when set, for matrices which can not be
+    /// represented as translation, rotation, and scale, such as skinning results or shears
+    pub matrix_override: Option<na::Matrix4<f32>>,
     pub mesh: Handle<Mesh>,
     /// Transform matrices when it needs to draw instanced meshes.
     pub transforms: Vec<na::Matrix4<f32>>,
     pub directional_light: Handle<DirectionalLight>,
     pub point_light: Handle<PointLight>,
+    pub spot_light: Handle<SpotLight>,
     pub camera: Handle<Camera>,
+    /// Skin deforming the mesh of this node
+    pub skin: Handle<Skin>,
     pub children: Vec<Handle<Node>>,
+    /// Lower detail meshes with the camera distance from which they are used,
+    /// sorted by distance. Closer than the first one, `mesh` is used.
+    pub lods: Vec<(f32, Handle<Mesh>)>,
 }
 
 impl Node {
@@ -132,15 +154,54 @@ impl Node {
             id: 0,
             name: String::new(),
             trs: Trs::new(),
+            matrix_override: None,
             mesh: Handle::none(),
             transforms: vec![],
             directional_light: Handle::none(),
             point_light: Handle::none(),
+            spot_light: Handle::none(),
             camera: Handle::none(),
+            skin: Handle::none(),
             children: vec![],
+            lods: vec![],
         }
     }
 
+    /// Returns the local transform of this node
+    pub fn get_matrix(&self) -> na::Matrix4<f32> {
+        self.matrix_override
+            .unwrap_or_else(|| self.trs.get_matrix())
+    }
+
+    /// Returns the mesh to draw when the camera is at `distance` from this node
+    pub fn get_lod_mesh(&self, distance: f32) -> Handle<Mesh> {
+        self.lods
+            .iter()
+            .rev()
+            .find(|(threshold, _)| distance >= *threshold)
+            .map_or(self.mesh, |(_, mesh)| *mesh)
+    }
+
+    /// Replaces the transforms of the instances to draw
+    pub fn set_instances(&mut self, transforms: Vec<na::Matrix4<f32>>) {
+        self.transforms = transforms;
+    }
+
+    /// Adds an instance to draw with the given transform
+    pub fn push_instance(&mut self, transform: na::Matrix4<f32>) {
+        self.transforms.push(transform);
+    }
+
+    /// Without instances, the node is drawn once with its own transform
+    pub fn clear_instances(&mut self) {
+        self.transforms.clear();
+    }
+
+    /// Returns how many times the mesh of this node is drawn
+    pub fn get_instance_count(&self) -> usize {
+        std::cmp::max(1, self.transforms.len())
+    }
+
     pub fn bind(&self, program: &ShaderProgram, transform: &na::Matrix4<f32>) {
         let intr = transform
             .remove_column(3)
@@ -161,3 +222,58 @@ impl std::fmt::Debug for Node {
         write!(f, "Node {}", self.name)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn far_node_low_detail() {
+        let mut node = Node::new();
+        node.mesh = Handle::new(0);
+        node.lods.push((10.0, Handle::new(1)));
+        node.lods.push((50.0, Handle::new(2)));
+
+        assert_eq!(node.get_lod_mesh(1.0).id, 0);
+        assert_eq!(node.get_lod_mesh(10.0).id, 1);
+        assert_eq!(node.get_lod_mesh(100.0).id, 2);
+
+        node.lods.clear();
+        assert_eq!(node.get_lod_mesh(100.0).id, 0);
+    }
+
+    #[test]
+    fn instances() {
+        let mut node = Node::new();
+        assert_eq!(node.get_instance_count(), 1);
+
+        node.push_instance(na::Matrix4::new_translation(&na::Vector3::x()));
+        node.push_instance(na::Matrix4::new_translation(&na::Vector3::y()));
+        assert_eq!(node.transforms.len(), 2);
+        assert_eq!(node.get_instance_count(), 2);
+
+        node.set_instances(vec![na::Matrix4::identity(); 3]);
+        assert_eq!(node.get_instance_count(), 3);
+
+        // Back to a single draw with the node transform
+        node.clear_instances();
+        assert!(node.transforms.is_empty());
+        assert_eq!(node.get_instance_count(), 1);
+    }
+
+    #[test]
+    fn matrix_override() {
+        let mut node = Node::new();
+        node.trs.translate(1.0, 0.0, 0.0);
+        assert_eq!(node.get_matrix(), node.trs.get_matrix());
+
+        // A shear can not be represented by translation, rotation, and scale
+        let mut shear = na::Matrix4::identity();
+        shear[(0, 1)] = 0.5;
+        node.matrix_override = Some(shear);
+        assert_eq!(node.get_matrix(), shear);
+
+        let node = Node::builder().matrix(shear).build();
+        assert_eq!(node.matrix_override, Some(shear));
+    }
+}
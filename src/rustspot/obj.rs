@@ -0,0 +1,271 @@
+// Copyright © 2021
+// Author: Antonio Caggiano <info@antoniocaggiano.eu>
+// SPDX-License-Identifier: MIT
+
+use std::{collections::HashMap, path::Path};
+
+use nalgebra as na;
+
+use super::*;
+
+/// Geometry parsed from a Wavefront OBJ, with one vertex for each distinct
+/// combination of position, texture coordinates, and normal found in faces
+pub struct ObjGeometry {
+    pub vertices: Vec<Vertex>,
+    /// Triangle list, polygons are split into fans
+    pub indices: Vec<usize>,
+    /// Whether every vertex comes with a normal
+    pub has_normals: bool,
+    /// Whether every vertex comes with texture coordinates
+    pub has_tex_coords: bool,
+}
+
+/// Parses an index of a face element, which starts from 1 or counts back from the end when negative
+fn parse_index(token: &str, count: usize, line: usize) -> Result<usize, RustspotError> {
+    let index: i64 = token
+        .parse()
+        .map_err(|_| RustspotError::Obj(format!("line {}: invalid index {}", line, token)))?;
+    let index = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    if index < 0 || index as usize >= count {
+        return Err(RustspotError::Obj(format!(
+            "line {}: index {} out of range",
+            line, token
+        )));
+    }
+    Ok(index as usize)
+}
+
+/// Fills `values` with the numbers of a statement
+fn parse_floats(
+    tokens: std::str::SplitWhitespace,
+    values: &mut [f32],
+    line: usize,
+) -> Result<(), RustspotError> {
+    let mut count = 0;
+    for (value, token) in values.iter_mut().zip(tokens) {
+        *value = token
+            .parse()
+            .map_err(|_| RustspotError::Obj(format!("line {}: invalid number {}", line, token)))?;
+        count += 1;
+    }
+    if count < values.len() {
+        return Err(RustspotError::Obj(format!(
+            "line {}: expected {} numbers",
+            line,
+            values.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Parses positions, texture coordinates, normals, and faces of an OBJ source.
+/// Other statements, such as groups and materials, are ignored.
+pub fn parse_obj(source: &str) -> Result<ObjGeometry, RustspotError> {
+    let mut positions: Vec<[f32; 3]> = vec![];
+    let mut tex_coords: Vec<[f32; 2]> = vec![];
+    let mut normals: Vec<[f32; 3]> = vec![];
+
+    let mut geometry = ObjGeometry {
+        vertices: vec![],
+        indices: vec![],
+        has_normals: true,
+        has_tex_coords: true,
+    };
+    // Index of the vertex made of position, texture coordinates, and normal indices
+    let mut cache: HashMap<(usize, Option<usize>, Option<usize>), usize> = HashMap::new();
+
+    for (i, text) in source.lines().enumerate() {
+        let line = i + 1;
+        let mut tokens = text.split_whitespace();
+        match tokens.next() {
+            Some("v") => {
+                let mut position = [0.0; 3];
+                parse_floats(tokens, &mut position, line)?;
+                positions.push(position);
+            }
+            Some("vt") => {
+                let mut tex_coord = [0.0; 2];
+                parse_floats(tokens, &mut tex_coord, line)?;
+                // OBJ puts the origin at the bottom-left, while images start from the top
+                tex_coords.push([tex_coord[0], 1.0 - tex_coord[1]]);
+            }
+            Some("vn") => {
+                let mut normal = [0.0; 3];
+                parse_floats(tokens, &mut normal, line)?;
+                normals.push(normal);
+            }
+            Some("f") => {
+                let mut face = vec![];
+                for element in tokens {
+                    let mut parts = element.split('/');
+                    let position = parse_index(parts.next().unwrap(), positions.len(), line)?;
+                    let tex_coord = match parts.next() {
+                        Some(token) if !token.is_empty() => {
+                            Some(parse_index(token, tex_coords.len(), line)?)
+                        }
+                        _ => None,
+                    };
+                    let normal = match parts.next() {
+                        Some(token) if !token.is_empty() => {
+                            Some(parse_index(token, normals.len(), line)?)
+                        }
+                        _ => None,
+                    };
+
+                    let key = (position, tex_coord, normal);
+                    let index = match cache.get(&key) {
+                        Some(&index) => index,
+                        None => {
+                            let mut vertex = Vertex::new();
+                            vertex.position = positions[position];
+                            match tex_coord {
+                                Some(tex_coord) => vertex.tex_coords = tex_coords[tex_coord],
+                                None => geometry.has_tex_coords = false,
+                            }
+                            match normal {
+                                Some(normal) => {
+                                    vertex.normal = na::Vector3::from(normals[normal]).normalize()
+                                }
+                                None => geometry.has_normals = false,
+                            }
+                            geometry.vertices.push(vertex);
+                            cache.insert(key, geometry.vertices.len() - 1);
+                            geometry.vertices.len() - 1
+                        }
+                    };
+                    face.push(index);
+                }
+
+                if face.len() < 3 {
+                    return Err(RustspotError::Obj(format!(
+                        "line {}: face with less than 3 vertices",
+                        line
+                    )));
+                }
+                for j in 1..face.len() - 1 {
+                    geometry
+                        .indices
+                        .extend_from_slice(&[face[0], face[j], face[j + 1]]);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    if geometry.vertices.is_empty() {
+        geometry.has_normals = false;
+        geometry.has_tex_coords = false;
+    }
+
+    Ok(geometry)
+}
+
+impl Primitive {
+    /// Loads a triangle list from a Wavefront OBJ file. Normals are computed when the file
+    /// does not provide them, and tangents are computed when it has texture coordinates.
+    pub fn load_obj<P: AsRef<Path>>(
+        path: P,
+        material: Handle<Material>,
+    ) -> Result<Primitive, RustspotError> {
+        let source = std::fs::read_to_string(path)?;
+        let geometry = parse_obj(&source)?;
+        let (indices, index_type) = write_indices(&geometry.indices);
+
+        let mut builder = Primitive::builder()
+            .vertices(geometry.vertices)
+            .indices(indices)
+            .index_type(index_type)
+            .material(Some(material));
+        if !geometry.has_normals {
+            builder = builder.normals_mode(NormalsMode::Smooth);
+        }
+        if geometry.has_tex_coords {
+            builder = builder.compute_tangents();
+        }
+
+        builder.validate()?;
+        Ok(builder.build())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Unit cube with a normal and texture coordinates for each face
+    const CUBE: &str = "
+# Cube
+v -0.5 -0.5  0.5
+v  0.5 -0.5  0.5
+v  0.5  0.5  0.5
+v -0.5  0.5  0.5
+v -0.5 -0.5 -0.5
+v  0.5 -0.5 -0.5
+v  0.5  0.5 -0.5
+v -0.5  0.5 -0.5
+vt 0.0 0.0
+vt 1.0 0.0
+vt 1.0 1.0
+vt 0.0 1.0
+vn 0.0 0.0 1.0
+vn 0.0 0.0 -1.0
+vn 1.0 0.0 0.0
+vn -1.0 0.0 0.0
+vn 0.0 1.0 0.0
+vn 0.0 -1.0 0.0
+f 1/1/1 2/2/1 3/3/1 4/4/1
+f 6/1/2 5/2/2 8/3/2 7/4/2
+f 2/1/3 6/2/3 7/3/3 3/4/3
+f 5/1/4 1/2/4 4/3/4 8/4/4
+f 4/1/5 3/2/5 7/3/5 8/4/5
+f 5/1/6 6/2/6 2/3/6 1/4/6
+";
+
+    #[test]
+    fn cube() {
+        let geometry = parse_obj(CUBE).unwrap();
+        // Faces do not share normals, hence vertices
+        assert_eq!(geometry.vertices.len(), 24);
+        assert_eq!(geometry.indices.len(), 36);
+        assert!(geometry.has_normals);
+        assert!(geometry.has_tex_coords);
+        assert_eq!(geometry.vertices[0].normal, na::Vector3::z());
+        assert_eq!(geometry.vertices[2].tex_coords, [1.0, 0.0]);
+    }
+
+    #[test]
+    fn positions_only() {
+        let source = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3\nf -4 -2 -1\n";
+        let geometry = parse_obj(source).unwrap();
+        assert_eq!(geometry.vertices.len(), 4);
+        assert_eq!(geometry.indices, vec![0, 1, 2, 0, 2, 3]);
+        assert!(!geometry.has_normals);
+        assert!(!geometry.has_tex_coords);
+
+        match parse_obj("v 0 0 0\nf 1 2 3\n") {
+            Err(RustspotError::Obj(message)) => assert!(message.starts_with("line 2")),
+            _ => panic!("Expected an OBJ error"),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn load_cube() {
+        let _context = TestContext::new();
+
+        let dir = std::env::temp_dir().join("rustspot-test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cube.obj");
+        std::fs::write(&path, CUBE).unwrap();
+
+        let primitive = Primitive::load_obj(&path, Handle::new(0)).unwrap();
+        assert_eq!(primitive.vertices.len(), 24);
+        assert_eq!(primitive.get_index_count(), 36);
+        assert_eq!(primitive.index_type, gl::UNSIGNED_BYTE);
+        assert!(primitive.has_tangents());
+    }
+}
@@ -0,0 +1,220 @@
+// Copyright © 2021
+// Author: Antonio Caggiano <info@antoniocaggiano.eu>
+// SPDX-License-Identifier: MIT
+
+use super::*;
+use nalgebra as na;
+use noise::NoiseFn;
+
+/// Upper bound for the number of particles of a system
+const PARTICLE_MAX: usize = 4096;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Particle {
+    pub position: na::Vector3<f32>,
+    pub velocity: na::Vector3<f32>,
+    /// Seconds since this particle has been emitted
+    pub age: f32,
+}
+
+impl Particle {
+    pub fn is_alive(&self, lifetime: f32) -> bool {
+        self.age < lifetime
+    }
+}
+
+/// Simple particle system simulated on the CPU. Particles are drawn as billboarded
+/// quads through the instanced draw path, one instance transform for each living particle.
+pub struct ParticleSystem {
+    pub node: Handle<Node>,
+    /// Particles emitted per second
+    pub rate: f32,
+    /// Seconds a particle lives before being recycled
+    pub lifetime: f32,
+    /// Initial velocity of new particles
+    pub velocity: na::Vector3<f32>,
+    /// How much the initial velocity is randomized
+    pub spread: f32,
+    /// Acceleration applied to all particles, gravity for instance
+    pub acceleration: na::Vector3<f32>,
+    /// Size of the quad of a particle
+    pub size: f32,
+
+    particles: Vec<Particle>,
+    max_count: usize,
+    /// Fraction of particle left to emit from previous steps
+    emission: f32,
+    emitted_count: u32,
+    perlin: noise::Perlin,
+    mesh: Handle<Mesh>,
+}
+
+impl ParticleSystem {
+    fn create_node(model: &mut Model) -> (Handle<Node>, Handle<Mesh>) {
+        let color = Color::rgba(255, 255, 255, 255);
+        let texture = model.color_texture(color);
+        let material = Material::builder()
+            .shader(Shaders::Particle)
+            .texture(texture)
+            .build();
+
+        let material = model.materials.push(material);
+        let primitives = vec![model.primitives.push(Primitive::quad(material))];
+        let mesh = model.meshes.push(Mesh::new(primitives));
+
+        let mut node = Node::new();
+        node.name = String::from("particles");
+
+        (model.nodes.push(node), mesh)
+    }
+
+    pub fn new(model: &mut Model) -> Self {
+        let (node, mesh) = Self::create_node(model);
+        let mut ret = Self::with_node(node, mesh);
+        ret.upload(model);
+        ret
+    }
+
+    /// Creates a particle system using an existing node and mesh
+    fn with_node(node: Handle<Node>, mesh: Handle<Mesh>) -> Self {
+        Self {
+            node,
+            rate: 16.0,
+            lifetime: 2.0,
+            velocity: na::Vector3::y(),
+            spread: 0.25,
+            acceleration: na::Vector3::zeros(),
+            size: 0.125,
+            particles: vec![],
+            max_count: 256,
+            emission: 0.0,
+            emitted_count: 0,
+            perlin: noise::Perlin::new(),
+            mesh,
+        }
+    }
+
+    pub fn set_max_count(&mut self, max_count: usize) {
+        let max_count = std::cmp::min(max_count, PARTICLE_MAX);
+        self.particles.truncate(max_count);
+        self.max_count = max_count;
+    }
+
+    pub fn get_max_count(&self) -> usize {
+        self.max_count
+    }
+
+    pub fn get_particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    pub fn get_alive_count(&self) -> usize {
+        self.particles
+            .iter()
+            .filter(|p| p.is_alive(self.lifetime))
+            .count()
+    }
+
+    fn emit(&mut self) {
+        let i = self.emitted_count as f64;
+        self.emitted_count = self.emitted_count.wrapping_add(1);
+
+        // [-1.0, 1.0]
+        let random_x = self.perlin.get([228.24 * i, 654.56 * i]) as f32;
+        let random_z = self.perlin.get([310.85 * i, 142.98 * i]) as f32;
+
+        let particle = Particle {
+            position: na::Vector3::zeros(),
+            velocity: self.velocity + self.spread * na::Vector3::new(random_x, 0.0, random_z),
+            age: 0.0,
+        };
+
+        // Recycle a dead particle if possible
+        let lifetime = self.lifetime;
+        if let Some(dead) = self.particles.iter_mut().find(|p| !p.is_alive(lifetime)) {
+            *dead = particle;
+        } else if self.particles.len() < self.max_count {
+            self.particles.push(particle);
+        }
+    }
+
+    /// Advances the simulation by `delta` seconds
+    pub fn step(&mut self, delta: f32) {
+        let lifetime = self.lifetime;
+        for particle in self.particles.iter_mut() {
+            if particle.is_alive(lifetime) {
+                particle.age += delta;
+                particle.velocity += self.acceleration * delta;
+                particle.position += particle.velocity * delta;
+            }
+        }
+
+        self.emission += self.rate * delta;
+        while self.emission >= 1.0 {
+            self.emission -= 1.0;
+            self.emit();
+        }
+    }
+
+    /// Creates transform matrices for the living particles, relative to the node
+    fn create_transforms(&self) -> Vec<na::Matrix4<f32>> {
+        self.particles
+            .iter()
+            .filter(|p| p.is_alive(self.lifetime))
+            .map(|p| na::Matrix4::new_scaling(self.size).append_translation(&p.position))
+            .collect()
+    }
+
+    fn upload(&mut self, model: &mut Model) {
+        let transforms = self.create_transforms();
+        let node = model.nodes.get_mut(self.node).unwrap();
+        // An instanced node without transforms would still draw one quad
+        node.mesh = if transforms.is_empty() {
+            Handle::none()
+        } else {
+            self.mesh
+        };
+        node.transforms = transforms;
+    }
+
+    /// Steps the simulation and updates the instance transforms of the node
+    pub fn update(&mut self, model: &mut Model, delta: f32) {
+        self.step(delta);
+        self.upload(model);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn step_ages_and_recycles() {
+        let mut system = ParticleSystem::with_node(Handle::none(), Handle::none());
+        system.rate = 2.0;
+        system.lifetime = 1.0;
+        system.set_max_count(2);
+
+        // Two particles per second
+        system.step(0.5);
+        assert_eq!(system.get_particles().len(), 1);
+        system.step(0.5);
+        assert_eq!(system.get_particles().len(), 2);
+        assert_eq!(system.get_particles()[0].age, 0.5);
+        assert_eq!(system.get_particles()[1].age, 0.0);
+        assert!(system.get_particles()[0].position.y > 0.0);
+
+        // The first one dies and its slot is taken by a new particle
+        system.step(0.5);
+        assert_eq!(system.get_particles().len(), 2);
+        assert_eq!(system.get_alive_count(), 2);
+        assert_eq!(system.get_particles()[0].age, 0.0);
+        assert_eq!(system.get_particles()[1].age, 0.5);
+
+        // No more particles are emitted while rate is zero
+        system.rate = 0.0;
+        system.step(1.0);
+        assert_eq!(system.get_alive_count(), 0);
+        assert!(system.create_transforms().is_empty());
+    }
+}
@@ -7,6 +7,120 @@ use super::*;
 use nalgebra as na;
 use std::collections::HashMap;
 
+/// Tone mapping operator applied when presenting the offscreen color to the screen.
+/// Values match the `tonemap` uniform of the read-color tonemap shaders.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToneMap {
+    /// Color is copied as it is
+    None = 0,
+    Reinhard = 1,
+    Aces = 2,
+}
+
+/// Debug visualizations replacing the shaders of materials during the geometry pass
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugMode {
+    Off,
+    /// World space normals as colors
+    Normals,
+    /// Texture coordinates as red and green
+    Uvs,
+    /// Brighter where more fragments are drawn on top of each other
+    Overdraw,
+    /// A constant color for each object, telling apart nodes by id
+    FlatColor,
+}
+
+impl DebugMode {
+    /// Returns the debug shader of this mode, none when off
+    pub fn get_shader(self) -> Option<Shaders> {
+        let channel = match self {
+            DebugMode::Off => return None,
+            DebugMode::Normals => DebugChannelVariant::Normals,
+            DebugMode::Uvs => DebugChannelVariant::Uvs,
+            DebugMode::Overdraw => DebugChannelVariant::Overdraw,
+            DebugMode::FlatColor => DebugChannelVariant::Default,
+        };
+        Some(DEBUG_VARIANTS[channel as usize])
+    }
+
+    /// Returns the following mode, going back to `Off` after the last one
+    pub fn next(self) -> Self {
+        match self {
+            DebugMode::Off => DebugMode::Normals,
+            DebugMode::Normals => DebugMode::Uvs,
+            DebugMode::Uvs => DebugMode::Overdraw,
+            DebugMode::Overdraw => DebugMode::FlatColor,
+            DebugMode::FlatColor => DebugMode::Off,
+        }
+    }
+}
+
+/// Stencil test state applied during the geometry pass
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stencil {
+    /// Comparison between reference and stored values, such as `gl::ALWAYS`
+    pub func: gl::types::GLenum,
+    pub reference: i32,
+    /// Mask applied to both reference and stored values before comparing
+    pub mask: u32,
+    /// Action when the stencil test fails
+    pub fail: gl::types::GLenum,
+    /// Action when the stencil test passes but the depth test fails
+    pub depth_fail: gl::types::GLenum,
+    /// Action when both stencil and depth tests pass
+    pub pass: gl::types::GLenum,
+    /// Bits which can be written into the stencil buffer
+    pub write_mask: u32,
+}
+
+impl Stencil {
+    /// Writes `reference` wherever something is drawn, useful to mark selected objects
+    pub fn write(reference: i32) -> Self {
+        Self {
+            func: gl::ALWAYS,
+            reference,
+            mask: 0xFF,
+            fail: gl::KEEP,
+            depth_fail: gl::KEEP,
+            pass: gl::REPLACE,
+            write_mask: 0xFF,
+        }
+    }
+
+    /// Draws only where the stencil buffer is not `reference`, leaving it untouched.
+    /// Used to draw outlines around objects previously marked with `write`.
+    pub fn not_equal(reference: i32) -> Self {
+        Self {
+            func: gl::NOTEQUAL,
+            reference,
+            mask: 0xFF,
+            fail: gl::KEEP,
+            depth_fail: gl::KEEP,
+            pass: gl::KEEP,
+            write_mask: 0x00,
+        }
+    }
+
+    fn apply(&self) {
+        unsafe {
+            gl::Enable(gl::STENCIL_TEST);
+            gl::StencilFunc(self.func, self.reference, self.mask);
+            gl::StencilOp(self.fail, self.depth_fail, self.pass);
+            gl::StencilMask(self.write_mask);
+        }
+    }
+}
+
+/// Outline drawn around a node at the end of the geometry pass, see `Renderer::outline`
+#[derive(Clone, Copy)]
+pub struct Outline {
+    pub node: Handle<Node>,
+    pub color: Color,
+    /// How much bigger than the node the outline is, such as 0.05 for 5%
+    pub thickness: f32,
+}
+
 pub struct Renderer {
     /// Delta time used as a uniform in shaders
     pub delta: f32,
@@ -19,29 +133,87 @@ pub struct Renderer {
     /// Shader to use for rendering instead of the one referred by the materials
     pub override_shader: Option<Shaders>,
 
+    /// Set through `set_debug_mode`, which overrides the shader
+    debug_mode: DebugMode,
+
+    /// Material used by primitives without one
+    pub default_material: Material,
+    /// White pixel used as albedo by the default material
+    default_textures: Pack<Texture>,
+    default_colors: HashMap<Color, Handle<Texture>>,
+
     /// List of shader enums to bind with materials referring to them.
     shaders: HashMap<Shaders, Vec<usize>>,
 
-    /// Node with the directional light to use for rendering
-    directional_light: Handle<Node>,
+    /// List of directional light nodes to use while drawing the scene.
+    /// Only the first one casts shadows.
+    directional_lights: Vec<Handle<Node>>,
 
     /// List of point light handles to use while drawing the scene paired with the node to use
     point_lights: Vec<Handle<Node>>,
 
+    /// List of spot light nodes to use while drawing the scene
+    spot_lights: Vec<Handle<Node>>,
+
     /// List of camera handles to use while drawing the scene paired with the node to use
     pub cameras: Vec<(Handle<Camera>, Handle<Node>)>,
 
+    /// When set, the scene is rendered only from this camera rather than from all the cameras
+    /// drawn. Nothing is rendered if it is not part of the drawn scene graph.
+    pub active_camera: Option<Handle<Camera>>,
+
+    /// Region of the target where geometry is rendered, the whole target when none
+    viewport: Option<Viewport>,
+
+    /// Background of the geometry pass and of the targets of blits, as RGBA
+    clear_color: [f32; 4],
+
     /// List of material handles to bind with primitives referring to them.
     materials: HashMap<usize, Vec<usize>>,
 
+    /// Textures bound by materials, so that shared ones are not bound again
+    texture_units: TextureUnits,
+
     /// List of primitive handles to draw with nodes referring to them.
     /// Together with nodes, we store their transform matrix computed during the scene graph traversal.
     primitives: HashMap<usize, HashMap<usize, na::Matrix4<f32>>>,
 
+    /// World transforms of all the nodes drawn, used to compute joint matrices of skins
+    node_transforms: HashMap<usize, na::Matrix4<f32>>,
+
+    /// Bounds of what has been drawn so far
+    scene_bounds: SceneBounds,
+    /// Bounds of what was drawn before the draw state was last cleared
+    last_scene_bounds: SceneBounds,
+
     pub read_depth_program: ShaderProgram,
+    /// Depth values shown by `blit_depth`, from black to white.
+    /// Narrow it to tell apart depths which are close to each other.
+    /// A range narrower than `1e-5` is widened to it, avoiding a division by zero.
+    pub depth_view_range: (f32, f32),
     pub read_color_program: ShaderProgram,
     pub read_depth_ms_program: ShaderProgram,
     pub read_color_ms_program: ShaderProgram,
+    pub read_color_tonemap_program: ShaderProgram,
+    pub read_color_tonemap_ms_program: ShaderProgram,
+
+    /// Whether GL encodes colors written by the geometry passes to sRGB targets, such as an
+    /// sRGB default framebuffer. Textures marked as sRGB are decoded to linear when sampled
+    /// either way, but lit shaders and tone mapping already apply gamma correction themselves,
+    /// hence enable this only with shaders writing linear colors, to avoid correcting twice.
+    pub framebuffer_srgb: bool,
+
+    /// Tone mapping and gamma correction used by `blit_color`
+    pub tonemap: ToneMap,
+    /// Scale applied to colors before tone mapping
+    pub exposure: f32,
+
+    /// Bloom added by `blit_color`, disabled when `None`
+    pub bloom: Option<BloomParams>,
+    /// Created on first use and when the source extent changes
+    bloom_chain: Option<BloomChain>,
+    pub bloom_threshold_program: ShaderProgram,
+    pub bloom_blur_program: ShaderProgram,
 
     /// Orthographic camera and node for camera
     pub screen_camera: Camera,
@@ -51,12 +223,58 @@ pub struct Renderer {
     pub quad_primitive: Primitive,
     pub quad_node: Node,
 
+    /// Camera data shared by all shaders using the `Camera` block
+    camera_block: UniformBuffer,
+    /// Light data shared by all shaders using the `Lights` block
+    lights_block: UniformBuffer,
+
     /// Used for shadows
     pub light_space: na::Matrix4<f32>,
+    /// Near and far planes of the shadow camera, fitted to what was drawn when `None`
+    shadow_depth_range: Option<(f32, f32)>,
+    /// Technique used by `render_shadow`, which selects the shadow variant of PBR shaders
+    pub shadow_technique: ShadowTechnique,
+    /// Created on first use of variance shadows and when the shadow target changes size
+    variance_shadow: Option<VarianceShadow>,
     /// Handle to the shadowmap
     pub shadow_map: u32,
+    /// Handle to the depth cubemap of the first point light, 0 when it casts no shadows
+    pub point_shadow_map: u32,
 
     pub sky: Sky,
+
+    /// Distance fog applied by lit shaders, disabled when `None`
+    pub fog: Option<Fog>,
+
+    /// Wind swaying the grass, see `Terrain::set_wind`
+    pub wind: Wind,
+
+    /// Camera node used to select the level of detail of nodes. When none, the first
+    /// camera drawn so far is used, and nodes drawn before any camera use their full detail.
+    pub lod_camera: Handle<Node>,
+
+    /// Renders depth with a cheap shader before the color pass, so that
+    /// expensive fragment shaders do not run on occluded pixels
+    pub depth_prepass: bool,
+
+    /// Stencil test of the geometry pass, disabled when `None`.
+    /// The target needs a stencil buffer, as the geometry buffer of the frame has.
+    pub stencil: Option<Stencil>,
+
+    /// Whether to skip primitives outside the frustum of the camera in the geometry pass.
+    /// Instanced and skinned nodes are always drawn, as their bounds are not known.
+    pub frustum_culling: bool,
+    /// Primitive draws skipped by frustum culling during the last geometry pass
+    culled_count: u32,
+
+    /// Outlines requested for the nodes drawn so far
+    outlines: Vec<Outline>,
+
+    /// Whether to draw bounding boxes of primitives and light gizmos
+    pub debug_draw_aabbs: bool,
+    /// Lines collected while drawing, rendered at the end of the geometry pass
+    pub debug_draw: DebugDraw,
+    debug_res: MeshRes,
 }
 
 impl Renderer {
@@ -81,6 +299,26 @@ impl Renderer {
             "res/shader/read-color-ms.frag.glsl",
         );
 
+        let read_color_tonemap_program = ShaderProgram::open(
+            "res/shader/unlit.vert.glsl",
+            "res/shader/read-color-tonemap.frag.glsl",
+        );
+
+        let read_color_tonemap_ms_program = ShaderProgram::open(
+            "res/shader/unlit.vert.glsl",
+            "res/shader/read-color-tonemap-ms.frag.glsl",
+        );
+
+        let bloom_threshold_program = ShaderProgram::open(
+            "res/shader/unlit.vert.glsl",
+            "res/shader/bloom-threshold.frag.glsl",
+        );
+
+        let bloom_blur_program = ShaderProgram::open(
+            "res/shader/unlit.vert.glsl",
+            "res/shader/bloom-blur.frag.glsl",
+        );
+
         let screen_camera = Camera::orthographic(1, 1, 0.1, 100.0);
         let mut screen_node = Node::new();
         screen_node.trs.translate(0.0, 0.0, 1.0);
@@ -91,22 +329,58 @@ impl Renderer {
 
         let sky = Sky::new();
 
+        let default_material = Material::new();
+        let mut default_textures = Pack::new();
+        let mut default_colors = HashMap::new();
+        default_colors.insert(
+            default_material.color,
+            default_textures.push(Texture::pixel(default_material.color)),
+        );
+
+        // Vertex layout is set once, vertex data is uploaded every frame
+        let debug_res = MeshRes::from(&[], &vec![]);
+
         Renderer {
             delta: 0.0,
             gui_res: GuiRes::new(fonts),
             custom_shaders: create_shaders(),
             override_shader: None,
+            debug_mode: DebugMode::Off,
+            default_material,
+            default_textures,
+            default_colors,
             shaders: HashMap::new(),
-            directional_light: Handle::none(),
+            directional_lights: Vec::new(),
             point_lights: Vec::new(),
+            spot_lights: Vec::new(),
             cameras: Vec::new(),
+            active_camera: None,
+            viewport: None,
+            clear_color: [0.2, 0.3, 0.5, 0.0],
             materials: HashMap::new(),
+            texture_units: TextureUnits::new(),
             primitives: HashMap::new(),
+            node_transforms: HashMap::new(),
+            scene_bounds: SceneBounds::default(),
+            last_scene_bounds: SceneBounds::default(),
 
             read_depth_program,
+            depth_view_range: (0.0, 1.0),
             read_color_program,
             read_depth_ms_program,
             read_color_ms_program,
+            read_color_tonemap_program,
+            read_color_tonemap_ms_program,
+
+            framebuffer_srgb: false,
+
+            tonemap: ToneMap::None,
+            exposure: 1.0,
+
+            bloom: None,
+            bloom_chain: None,
+            bloom_threshold_program,
+            bloom_blur_program,
 
             screen_camera,
             screen_node,
@@ -114,29 +388,63 @@ impl Renderer {
             quad_primitive,
             quad_node,
 
+            camera_block: UniformBuffer::new(CAMERA_BLOCK_BINDING),
+            lights_block: UniformBuffer::new(LIGHTS_BLOCK_BINDING),
+
             light_space: na::Matrix4::identity(),
+            shadow_depth_range: None,
+            shadow_technique: ShadowTechnique::default(),
+            variance_shadow: None,
             shadow_map: 0,
+            point_shadow_map: 0,
             sky,
+            fog: None,
+            wind: Wind::default(),
+
+            lod_camera: Handle::none(),
+
+            depth_prepass: false,
+
+            stencil: None,
+
+            frustum_culling: true,
+            culled_count: 0,
+            outlines: vec![],
+            debug_draw_aabbs: false,
+            debug_draw: DebugDraw::new(),
+            debug_res,
         }
     }
 
     /// Draw does not render immediately, instead it creates a list of mesh resources.
     /// At the same time it computes transform matrices for each node to be bound later on.
     pub fn draw(&mut self, model: &Model, node_handle: Handle<Node>, transform: &na::Matrix4<f32>) {
-        // Precompute transform matrix
-        let node = &model.nodes[node_handle.id];
-        let temp_transform = transform * node.trs.get_matrix();
-
         // The current node
         let node = model.nodes.get(node_handle).unwrap();
 
+        // Precompute transform matrix
+        let temp_transform = transform * node.get_matrix();
+        self.node_transforms.insert(node_handle.id, temp_transform);
+
         // Here we add this to a list of nodes that should be rendered
-        let mesh = node.mesh;
+        let mesh = self.select_lod(model, node, &temp_transform);
         if let Some(mesh) = model.meshes.get(mesh) {
             for &primitive_handle in mesh.primitives.iter() {
                 let primitive = model.primitives.get(primitive_handle).unwrap();
-                let material_handle = primitive.material.unwrap();
-                let material = model.materials.get(material_handle).unwrap();
+                // An invalid handle stands for the default material
+                let material_handle = Handle::from_option(primitive.material);
+                let material = model
+                    .materials
+                    .get(material_handle)
+                    .unwrap_or(&self.default_material);
+
+                self.scene_bounds
+                    .add(&primitive.aabb.transform(&temp_transform));
+
+                if self.debug_draw_aabbs {
+                    self.debug_draw
+                        .aabb(&primitive.aabb, &temp_transform, [0.0, 1.0, 0.0]);
+                }
 
                 // Store this association shader program, material
                 let key = material.shader;
@@ -178,13 +486,23 @@ impl Renderer {
             }
         }
 
-        // Check if current node has a directional light and set it for rendering
+        // Check if current node has a directional light and add it to the current list,
+        // unless it was already drawn, as lights would otherwise add up
         if model
             .directional_lights
             .get(node.directional_light)
             .is_some()
+            && !self
+                .directional_lights
+                .iter()
+                .any(|h| h.id == node_handle.id)
         {
-            self.directional_light = node_handle;
+            self.directional_lights.push(node_handle);
+
+            if self.debug_draw_aabbs {
+                self.debug_draw
+                    .directional_light(&temp_transform, [1.0, 1.0, 0.0]);
+            }
         }
 
         // Check if current node has a point light and add it to the current list
@@ -192,9 +510,23 @@ impl Renderer {
             self.point_lights.push(node_handle);
         }
 
-        // Here we check if the current node has a camera, just add it
-        if model.cameras.get(node.camera).is_some() {
-            self.cameras.push((node.camera, node_handle));
+        // Same for spot lights
+        if model.spot_lights.get(node.spot_light).is_some() {
+            self.spot_lights.push(node_handle);
+        }
+
+        // Here we check if the current node has a camera, just add it unless it is not the active one
+        let is_active = self
+            .active_camera
+            .is_none_or(|active| active.id == node.camera.id);
+        if let Some(camera) = model.cameras.get(node.camera) {
+            if is_active {
+                self.cameras.push((node.camera, node_handle));
+
+                if self.scene_bounds.frustum_corners.is_none() {
+                    self.scene_bounds.frustum_corners = Some(camera.frustum_corners(node));
+                }
+            }
         }
 
         // And all its children recursively
@@ -203,19 +535,129 @@ impl Renderer {
         }
     }
 
-    /// Renders a shadowmap. It should be called after drawing.
+    /// Renders a scene with the usual sequence of passes, presenting the result to the default
+    /// framebuffer of the frame. Passes whose buffer was not allocated by the frame are skipped,
+    /// and without a geometry buffer the scene is rendered straight to the default framebuffer.
+    pub fn render(&mut self, model: &Model, root: Handle<Node>, frame: &Frame) {
+        let transform = na::Matrix4::identity();
+
+        self.draw(model, root, &transform);
+        if let Some(shadow_buffer) = &frame.shadow_buffer {
+            self.render_shadow(model, shadow_buffer);
+        }
+        match &frame.geometry_buffer {
+            Some(geometry_buffer) => {
+                self.render_geometry(model, geometry_buffer);
+                self.blit_color(geometry_buffer, &frame.default_framebuffer);
+            }
+            None => self.render_geometry(model, &frame.default_framebuffer),
+        }
+    }
+
+    /// Returns the mesh of the node according to its distance from the LOD camera
+    fn select_lod(&self, model: &Model, node: &Node, transform: &na::Matrix4<f32>) -> Handle<Mesh> {
+        if node.lods.is_empty() {
+            return node.mesh;
+        }
+
+        let camera_node = if self.lod_camera.valid() {
+            model.nodes.get(self.lod_camera)
+        } else {
+            self.cameras
+                .first()
+                .and_then(|(_, camera_node)| model.nodes.get(*camera_node))
+        };
+
+        match camera_node {
+            Some(camera_node) => {
+                let distance =
+                    (transform.column(3).xyz() - camera_node.trs.get_translation()).norm();
+                node.get_lod_mesh(distance)
+            }
+            None => node.mesh,
+        }
+    }
+
+    /// Returns the nodes of the directional lights drawn so far, in drawing order.
+    /// The first one is the primary light, which casts shadows.
+    pub fn get_directional_lights(&self) -> &[Handle<Node>] {
+        &self.directional_lights
+    }
+
+    /// Returns the first directional light drawn which casts shadows
+    fn get_shadow_light(&self, model: &Model) -> Option<Handle<Node>> {
+        self.directional_lights
+            .iter()
+            .copied()
+            .find(|&node_handle| {
+                let node = model.nodes.get(node_handle).unwrap();
+                model
+                    .directional_lights
+                    .get(node.directional_light)
+                    .is_some_and(|light| light.casts_shadow)
+            })
+    }
+
+    /// Collects the directional lights drawn, exceeding ones are ignored
+    fn get_lights_block(&self, model: &Model) -> LightsBlock {
+        // Shaders expect the light casting shadows to come first
+        let shadow_light = self.get_shadow_light(model);
+        let other_lights = self
+            .directional_lights
+            .iter()
+            .filter(|light_node| shadow_light.is_none_or(|shadow| shadow.id != light_node.id));
+
+        let mut block = LightsBlock::new(&self.light_space);
+        for &light_node in shadow_light.iter().chain(other_lights) {
+            let light_node = model.nodes.get(light_node).unwrap();
+            let light = model
+                .directional_lights
+                .get(light_node.directional_light)
+                .unwrap();
+            if !block.push_directional_light(&light.get_radiance(), light_node) {
+                log::warn!(
+                    "Too many directional lights, max is {}",
+                    MAX_DIRECTIONAL_LIGHTS
+                );
+                break;
+            }
+        }
+        block
+    }
+
+    /// Renders a shadowmap. It should be called after drawing and before the geometry pass,
+    /// which uses what was drawn as well.
+    /// Without a directional light casting shadows, the shadowmap is just cleared so that
+    /// nothing is in shadow.
+    /// With variance shadows, moments are rendered to a buffer of the renderer with the same extent
+    /// of the target, rather than to the target itself.
     pub fn render_shadow<D: DrawableOnto>(&mut self, model: &Model, target: &D) {
-        self.shadow_map = target.get_depth_texture().unwrap().handle;
+        // Other passes bind textures on their own
+        self.texture_units.invalidate();
+
+        let variance = self.shadow_technique == ShadowTechnique::Variance;
+        if variance {
+            let extent = target.get_framebuffer().extent;
+            match &self.variance_shadow {
+                Some(shadow) if shadow.get_extent() == extent => (),
+                _ => self.variance_shadow = Some(VarianceShadow::new(extent)),
+            }
+        }
 
-        let framebuffer = target.get_framebuffer();
+        let (framebuffer, variants) = match &self.variance_shadow {
+            Some(shadow) if variance => {
+                self.shadow_map = shadow.get_result().handle;
+                (&shadow.buffers[0].framebuffer, VARIANCE_SHADOW_VARIANTS)
+            }
+            _ => {
+                self.shadow_map = target.get_depth_texture().unwrap().handle;
+                (target.get_framebuffer(), DEPTH_VARIANTS)
+            }
+        };
+        let extent = framebuffer.extent;
         framebuffer.bind();
         unsafe {
-            gl::Viewport(
-                0,
-                0,
-                framebuffer.extent.width as _,
-                framebuffer.extent.height as _,
-            );
+            gl::Viewport(0, 0, extent.width as _, extent.height as _);
 
             gl::Enable(gl::BLEND);
             gl::BlendEquation(gl::FUNC_ADD);
@@ -224,43 +666,296 @@ impl Renderer {
             gl::Enable(gl::DEPTH_TEST);
             gl::Disable(gl::SCISSOR_TEST);
 
+            if variance {
+                // Farthest depth with no variance, so that nothing is in shadow
+                gl::ClearColor(1.0, 1.0, 0.0, 0.0);
+                gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            } else {
+                gl::Clear(gl::DEPTH_BUFFER_BIT);
+            }
+        }
+
+        let light_node = self
+            .get_shadow_light(model)
+            .and_then(|light_node| model.nodes.get(light_node));
+        if let Some(light_node) = light_node {
+            // Keep track for next pass
+            self.light_space = self.draw_shadow_casters(model, variants, light_node, extent);
+        }
+
+        if variance {
+            self.blur_variance_shadow();
+        }
+    }
+
+    /// Renders the depth of what was drawn from the point of view of `light` into a region of
+    /// the depth texture of `target`, leaving the rest of it untouched. This way the shadow maps
+    /// of many lights can be packed into one texture, an atlas.
+    /// Returns the light-space matrix of the region, which maps to its own normalized coordinates,
+    /// or `None` when `light` is not a node with a directional light, leaving the region untouched.
+    pub fn render_shadow_region<D: DrawableOnto>(
+        &mut self,
+        model: &Model,
+        target: &D,
+        viewport: Viewport,
+        light: Handle<Node>,
+    ) -> Option<na::Matrix4<f32>> {
+        let light_node = model.nodes.get(light).filter(|node| {
+            model
+                .directional_lights
+                .get(node.directional_light)
+                .is_some()
+        });
+        let light_node = match light_node {
+            Some(node) => node,
+            None => {
+                log::warn!("Node {} has no directional light to cast shadows", light.id);
+                return None;
+            }
+        };
+
+        self.texture_units.invalidate();
+
+        target.get_framebuffer().bind();
+        unsafe {
+            gl::Viewport(
+                viewport.x,
+                viewport.y,
+                viewport.width as _,
+                viewport.height as _,
+            );
+            // Clear only the region
+            gl::Enable(gl::SCISSOR_TEST);
+            gl::Scissor(
+                viewport.x,
+                viewport.y,
+                viewport.width as _,
+                viewport.height as _,
+            );
+
+            gl::Disable(gl::BLEND);
+            gl::Disable(gl::CULL_FACE);
+            gl::Enable(gl::DEPTH_TEST);
             gl::Clear(gl::DEPTH_BUFFER_BIT);
         }
 
-        // Draw only depth
-        let draw_shadow_program = &self.custom_shaders[Shaders::Depth as usize];
-        draw_shadow_program.bind();
+        let extent = Extent2D::new(viewport.width, viewport.height);
+        let light_space = self.draw_shadow_casters(model, DEPTH_VARIANTS, light_node, extent);
 
-        if let Some(light_node) = model.nodes.get(self.directional_light) {
-            // Bind directional light as camera view
-            // Create orthographic camera but how big?
-            let camera = Camera::orthographic(8, 8, 1.0, 8.0);
-            draw_shadow_program.bind_camera(&camera, &light_node);
-            // Keep track for next pass
-            self.light_space = camera.proj * light_node.trs.get_view();
-        } else {
-            // No light, skip rendering
-            return;
+        unsafe { gl::Disable(gl::SCISSOR_TEST) };
+        Some(light_space)
+    }
+
+    /// Draws what was drawn so far from the point of view of a light with a shadow `shader`,
+    /// returning the light-space matrix of a shadow map with `extent`
+    fn draw_shadow_casters(
+        &mut self,
+        model: &Model,
+        variants: [Shaders; 2],
+        light_node: &Node,
+        extent: Extent2D,
+    ) -> na::Matrix4<f32> {
+        // Bind directional light as camera view
+        // Create orthographic camera but how big?
+        let (near, far) = self.get_shadow_depth_range(model, light_node);
+        let mut camera = Camera::orthographic(8, 8, near, far);
+        // Aligned to texels to avoid shimmering
+        let light_space = snap_to_texel(&mut camera, &light_node.trs.get_view(), extent);
+        self.camera_block
+            .upload(&CameraBlock::new(&camera, light_node));
+
+        // Draw the scene from the light point of view, only depth or its moments
+        self.draw_depth(model, &variants, Some((&camera, light_node)), |_| true);
+
+        light_space
+    }
+
+    /// Draws the nodes accepted by `filter` with the variant of a depth shader matching
+    /// whether they are skinned. The camera block should already hold the point of view.
+    fn draw_depth<F: Fn(&Node) -> bool>(
+        &mut self,
+        model: &Model,
+        variants: &[Shaders; 2],
+        camera: Option<(&Camera, &Node)>,
+        filter: F,
+    ) {
+        // Rigid nodes first, then skinned ones which need their joints
+        for &skinned in [false, true].iter() {
+            let program = &self.custom_shaders[get_skin_variant(variants, skinned) as usize];
+            let mut bound = false;
+
+            for (primitive_id, node_res) in self.primitives.iter() {
+                let primitive = model.primitives.get(Handle::new(*primitive_id)).unwrap();
+                let nodes: Vec<_> = node_res
+                    .iter()
+                    .map(|(node_id, transform)| {
+                        (model.nodes.get(Handle::new(*node_id)).unwrap(), transform)
+                    })
+                    .filter(|(node, _)| {
+                        model.skins.get(node.skin).is_some() == skinned && filter(node)
+                    })
+                    .collect();
+                if nodes.is_empty() {
+                    continue;
+                }
+
+                if !bound {
+                    program.bind();
+                    if let Some((camera, camera_node)) = camera {
+                        program.bind_camera(camera, camera_node);
+                    }
+                    bound = true;
+                }
+
+                // Cutout materials need their albedo to discard transparent texels
+                let (textures, colors, material) =
+                    match primitive.material.and_then(|m| model.materials.get(m)) {
+                        Some(material) => (&model.textures, &model.colors, material),
+                        None => (
+                            &self.default_textures,
+                            &self.default_colors,
+                            &self.default_material,
+                        ),
+                    };
+                program.bind_material(&mut self.texture_units, textures, colors, material);
+
+                // Bind the primitive, bind the nodes using that primitive, draw the primitive.
+                program.bind_primitive(primitive);
+                for (node, transform) in nodes {
+                    program.bind_node(node, transform);
+                    if let Some(skin) = model.skins.get(node.skin) {
+                        let joint_matrices =
+                            skin.get_joint_matrices(transform, &self.node_transforms);
+                        program.bind_joints(&joint_matrices);
+                    }
+                    program.draw(node, primitive);
+                }
+            }
+        }
+    }
+
+    /// Blurs the moments of the variance shadow map, which softens the edges of shadows
+    fn blur_variance_shadow(&self) {
+        let shadow = match &self.variance_shadow {
+            Some(shadow) => shadow,
+            None => return,
+        };
+        let extent = shadow.get_extent();
+
+        unsafe {
+            gl::Disable(gl::BLEND);
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Disable(gl::CULL_FACE);
+        }
+
+        // Horizontally into the second buffer and vertically back
+        let blur_program = &shadow.blur_program;
+        blur_program.enable();
+        unsafe {
+            gl::Uniform2f(
+                blur_program.loc.extent,
+                extent.width as f32,
+                extent.height as f32,
+            );
+        }
+        let direction_loc = blur_program.get_uniform_location("direction");
+        for (from, to, direction) in [(0, 1, [1.0, 0.0]), (1, 0, [0.0, 1.0])].iter() {
+            shadow.buffers[*to].framebuffer.bind();
+            unsafe { gl::Uniform2f(direction_loc, direction[0], direction[1]) };
+            shadow.buffers[*from].color_textures[0].bind();
+            self.draw_screen_quad(blur_program);
+        }
+    }
+
+    /// Sets near and far planes of the shadow camera, as distances from the directional light
+    pub fn set_shadow_depth_range(&mut self, near: f32, far: f32) {
+        self.shadow_depth_range = Some((near, far));
+    }
+
+    /// Fits near and far planes of the shadow camera to what was drawn, which is the default
+    pub fn fit_shadow_depth_range(&mut self) {
+        self.shadow_depth_range = None;
+    }
+
+    /// Returns the depth range set by the user, or the one of the bounding boxes drawn so far
+    /// as seen by the light. Instances of a node are not taken into account.
+    fn get_shadow_depth_range(&self, model: &Model, light_node: &Node) -> (f32, f32) {
+        if let Some(range) = self.shadow_depth_range {
+            return range;
         }
 
-        // Draw the scene from the light point of view
+        let view = light_node.trs.get_view();
+        let mut range: Option<(f32, f32)> = None;
         for (primitive_id, node_res) in self.primitives.iter() {
-            let primitive = &model.primitives[*primitive_id];
+            let primitive = model.primitives.get(Handle::new(*primitive_id)).unwrap();
+            for transform in node_res.values() {
+                let aabb = primitive.aabb.transform(&(view * transform));
+                // The light looks towards -Z
+                let (near, far) = (-aabb.max.z, -aabb.min.z);
+                range = Some(match range {
+                    Some((min, max)) => (min.min(near), max.max(far)),
+                    None => (near, far),
+                });
+            }
+        }
 
-            // Bind the primitive, bind the nodes using that primitive, draw the primitive.
-            draw_shadow_program.bind_primitive(&primitive);
-            for (node_id, transform) in node_res.iter() {
-                let node = &model.nodes[*node_id];
-                draw_shadow_program.bind_node(node, &transform);
-                draw_shadow_program.draw(node, primitive);
+        match range {
+            // A little margin avoids clipping surfaces right on the planes
+            Some((near, far)) => (near - 0.1, far + 0.1),
+            None => (1.0, 8.0),
+        }
+    }
+
+    /// Renders the shadow cubemap of the first point light drawn, one face at a time.
+    /// It should be called after drawing, and before the geometry pass which clears what was drawn.
+    pub fn render_point_shadow(&mut self, model: &Model, target: &CustomFramebuffer) {
+        let light_node = match self.point_lights.first() {
+            Some(&light_node) => model.nodes.get(light_node).unwrap(),
+            None => {
+                self.point_shadow_map = 0;
+                return;
             }
+        };
+        let light = model.point_lights.get(light_node.point_light).unwrap();
+
+        let cube_texture = target.depth_texture.as_ref().unwrap();
+        self.point_shadow_map = cube_texture.handle;
+        self.texture_units.invalidate();
+
+        let framebuffer = target.get_framebuffer();
+        framebuffer.bind();
+        // Faces are as big as the cube, while the viewport of the caller is restored afterwards
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            gl::Viewport(
+                0,
+                0,
+                cube_texture.extent.width as _,
+                cube_texture.extent.height as _,
+            );
+            gl::Disable(gl::BLEND);
+            gl::Disable(gl::CULL_FACE);
+            gl::Enable(gl::DEPTH_TEST);
+            gl::DepthFunc(gl::LESS);
+            gl::Disable(gl::SCISSOR_TEST);
         }
 
-        self.shaders.clear();
-        self.point_lights.clear();
-        self.cameras.clear();
-        self.materials.clear();
-        self.primitives.clear()
+        let position = light_node.trs.get_translation();
+        let proj = light.get_shadow_proj();
+        let views = PointLight::get_shadow_views(&na::Point3::from(position));
+
+        for (face, view) in views.iter().enumerate() {
+            framebuffer.set_depth_cube_face(cube_texture, face as u32);
+            unsafe { gl::Clear(gl::DEPTH_BUFFER_BIT) };
+
+            self.camera_block
+                .upload(&CameraBlock::from_matrices(view, &proj, &position));
+            self.draw_depth(model, &DEPTH_VARIANTS, None, |_| true);
+        }
+
+        let [x, y, width, height] = viewport;
+        unsafe { gl::Viewport(x, y, width, height) };
     }
 
     /// Renders depth from offscreen framebuffer to the screen
@@ -276,7 +971,7 @@ impl Renderer {
                 framebuffer.extent.width as _,
                 framebuffer.extent.height as _,
             );
-            gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+            self.apply_clear_color();
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
         }
 
@@ -300,6 +995,12 @@ impl Renderer {
                 read_depth_program.loc.tex_samples,
                 depth_texture.samples as i32,
             );
+            let (near, far) = self.depth_view_range;
+            gl::Uniform2f(
+                read_depth_program.get_uniform_location("depth_range"),
+                near,
+                far.max(near + 1e-5),
+            );
         }
 
         // Bind camera
@@ -320,12 +1021,99 @@ impl Renderer {
         self.quad_primitive.draw();
     }
 
+    /// Returns the program used to read a color texture with the current tone mapping
+    pub fn get_read_color_program(&self, samples: u32) -> &ShaderProgram {
+        match (self.tonemap, samples > 1) {
+            (ToneMap::None, false) => &self.read_color_program,
+            (ToneMap::None, true) => &self.read_color_ms_program,
+            (_, false) => &self.read_color_tonemap_program,
+            (_, true) => &self.read_color_tonemap_ms_program,
+        }
+    }
+
+    /// Draws the screen quad covering the currently bound framebuffer with a program
+    fn draw_screen_quad(&self, program: &ShaderProgram) {
+        self.screen_camera.bind(program, &self.screen_node);
+        self.quad_primitive.bind();
+        self.quad_node.bind(program, &na::Matrix4::identity());
+        self.quad_primitive.draw();
+    }
+
+    /// Extracts bright pixels of the source and blurs them across the bloom chain.
+    /// Returns false when bloom can not be applied, as with multisampled sources.
+    fn render_bloom(&mut self, source: &CustomFramebuffer, params: &BloomParams) -> bool {
+        let source_texture = &source.color_textures[0];
+        if source_texture.samples > 1 {
+            return false;
+        }
+
+        let source_extent = source.get_framebuffer().extent;
+        match &self.bloom_chain {
+            Some(chain) if chain.is_compatible(source_extent, params) => (),
+            _ => self.bloom_chain = Some(BloomChain::new(source_extent, params)),
+        }
+        let chain = self.bloom_chain.as_ref().unwrap();
+        let extent = chain.get_extent();
+
+        unsafe {
+            gl::Disable(gl::BLEND);
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Disable(gl::CULL_FACE);
+            gl::Disable(gl::SCISSOR_TEST);
+        }
+
+        // Bright pixels go to the first buffer
+        chain.buffers[0].framebuffer.bind();
+        let threshold_program = &self.bloom_threshold_program;
+        threshold_program.enable();
+        unsafe {
+            gl::Uniform1f(
+                threshold_program.get_uniform_location("threshold"),
+                params.threshold,
+            );
+        }
+        source_texture.bind();
+        self.draw_screen_quad(threshold_program);
+
+        // Blur horizontally into the second buffer, then vertically back into the first one
+        let blur_program = &self.bloom_blur_program;
+        blur_program.enable();
+        unsafe {
+            gl::Uniform2f(
+                blur_program.loc.extent,
+                extent.width as f32,
+                extent.height as f32,
+            );
+        }
+        let direction_loc = blur_program.get_uniform_location("direction");
+        for _ in 0..params.passes {
+            for (from, to, direction) in [(0, 1, [1.0, 0.0]), (1, 0, [0.0, 1.0])].iter() {
+                chain.buffers[*to].framebuffer.bind();
+                unsafe { gl::Uniform2f(direction_loc, direction[0], direction[1]) };
+                chain.buffers[*from].color_textures[0].bind();
+                self.draw_screen_quad(blur_program);
+            }
+        }
+
+        true
+    }
+
     /// Renders colors from offscreen framebuffer to the screen
     pub fn blit_color<D: DrawableOnto>(&mut self, source: &CustomFramebuffer, target: &D) {
+        let bloom = self.bloom;
+        let bloom = match bloom {
+            Some(params) if self.render_bloom(source, &params) => Some(params),
+            _ => None,
+        };
+
         let source_buffer = source.get_framebuffer();
         let framebuffer = target.get_framebuffer();
 
-        if source_buffer.extent == framebuffer.extent {
+        // Both paths leave the shared clear color, even when blitting does not clear
+        self.apply_clear_color();
+
+        // Tone mapping needs a shader, therefore we can not just blit
+        if self.tonemap == ToneMap::None && source_buffer.extent == framebuffer.extent {
             source_buffer.bind_read();
             framebuffer.bind_draw();
             unsafe {
@@ -353,21 +1141,29 @@ impl Renderer {
                     framebuffer.extent.width as _,
                     framebuffer.extent.height as _,
                 );
-                gl::ClearColor(0.0, 0.0, 0.0, 0.0);
                 gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
             }
 
             let color_texture = &source.color_textures[0];
 
-            let read_color_program = if color_texture.samples > 1 {
-                &self.read_color_ms_program
-            } else {
-                &self.read_color_program
-            };
+            let read_color_program = self.get_read_color_program(color_texture.samples);
 
             // Bind color read shader
             read_color_program.enable();
 
+            if self.tonemap != ToneMap::None {
+                unsafe {
+                    gl::Uniform1i(
+                        read_color_program.get_uniform_location("tonemap"),
+                        self.tonemap as i32,
+                    );
+                    gl::Uniform1f(
+                        read_color_program.get_uniform_location("exposure"),
+                        self.exposure,
+                    );
+                }
+            }
+
             // Bind extent
             unsafe {
                 gl::Uniform2f(
@@ -398,29 +1194,231 @@ impl Renderer {
             // Draw
             self.quad_primitive.draw();
         }
+
+        if let Some(params) = bloom {
+            self.add_bloom(&params, target);
+        }
     }
 
-    /// This should be called after drawing everything to trigger the actual GL rendering.
-    pub fn render_geometry<D: DrawableOnto>(&mut self, model: &Model, target: &D) {
-        // Rendering should follow this approach
-        // foreach prog in programs:
-        //   bind(prog)
-        //   foreach mat in p.materials:
-        //     bind(mat)
-        //     foreach prim in mat.primitives:
-        //       bind(prim)
-        //       foreach node in prim.nodes:
+    /// Adds the blurred bright pixels on top of the target
+    fn add_bloom<D: DrawableOnto>(&self, params: &BloomParams, target: &D) {
+        target.get_framebuffer().bind();
+
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Enable(gl::BLEND);
+            gl::BlendEquation(gl::FUNC_ADD);
+            // Intensity is applied through the blend color
+            let intensity = params.intensity;
+            gl::BlendColor(intensity, intensity, intensity, 1.0);
+            gl::BlendFunc(gl::CONSTANT_COLOR, gl::ONE);
+        }
+
+        let read_color_program = &self.read_color_program;
+        read_color_program.enable();
+        self.bloom_chain.as_ref().unwrap().get_result().bind();
+        self.draw_screen_quad(read_color_program);
+
+        unsafe {
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+    }
+
+    /// Fills the depth buffer of the currently bound framebuffer as seen by the cameras.
+    /// Instanced nodes are drawn by the shader of their material with color writes off,
+    /// as the depth program does not know about instances.
+    fn render_depth_prepass(&mut self, model: &Model, cameras: &[(Handle<Camera>, Handle<Node>)]) {
+        for (camera_handle, camera_node_handle) in cameras.iter() {
+            let camera = model.cameras.get(*camera_handle).unwrap();
+            let camera_node = model.nodes.get(*camera_node_handle).unwrap();
+            self.camera_block
+                .upload(&CameraBlock::new(camera, camera_node));
+
+            self.draw_depth(
+                model,
+                &DEPTH_VARIANTS,
+                Some((camera, camera_node)),
+                |node| node.transforms.is_empty(),
+            );
+
+            let mut instanced = vec![];
+            for (primitive_id, node_res) in self.primitives.iter() {
+                let primitive = model.primitives.get(Handle::new(*primitive_id)).unwrap();
+                for (node_id, transform) in node_res.iter() {
+                    let node = model.nodes.get(Handle::new(*node_id)).unwrap();
+                    if !node.transforms.is_empty() {
+                        instanced.push((primitive, node, transform));
+                    }
+                }
+            }
+
+            if instanced.is_empty() {
+                continue;
+            }
+
+            unsafe { gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE) };
+            for (primitive, node, transform) in instanced {
+                let (textures, colors, material) =
+                    match primitive.material.and_then(|m| model.materials.get(m)) {
+                        Some(material) => (&model.textures, &model.colors, material),
+                        None => (
+                            &self.default_textures,
+                            &self.default_colors,
+                            &self.default_material,
+                        ),
+                    };
+                let shader_id = self.override_shader.unwrap_or(material.shader);
+                let shader = &self.custom_shaders[shader_id as usize];
+                shader.bind();
+                shader.bind_time(self.delta);
+                shader.bind_wind(&self.wind);
+                shader.bind_camera(camera, camera_node);
+                shader.bind_material(&mut self.texture_units, textures, colors, material);
+                shader.bind_primitive(primitive);
+                shader.bind_node(node, transform);
+                shader.draw(node, primitive);
+            }
+            unsafe { gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE) };
+        }
+    }
+
+    /// Sets the background color of all the passes but the shadow one,
+    /// whose depth is cleared to the farthest value so that nothing is in shadow
+    pub fn set_clear_color(&mut self, color: [f32; 4]) {
+        self.clear_color = color;
+    }
+
+    pub fn get_clear_color(&self) -> [f32; 4] {
+        self.clear_color
+    }
+
+    fn apply_clear_color(&self) {
+        let [r, g, b, a] = self.clear_color;
+        unsafe { gl::ClearColor(r, g, b, a) };
+    }
+
+    /// Restricts rendering of the geometry to a region of the target, or to the whole
+    /// target when none. Only that region is cleared.
+    pub fn set_viewport(&mut self, viewport: Option<Viewport>) {
+        self.viewport = viewport;
+    }
+
+    pub fn get_viewport(&self) -> Option<Viewport> {
+        self.viewport
+    }
+
+    /// Replaces the shaders of materials with the debug shader of the mode, through
+    /// `override_shader`. Turning it off clears the override shader.
+    pub fn set_debug_mode(&mut self, mode: DebugMode) {
+        self.debug_mode = mode;
+        self.override_shader = mode.get_shader();
+    }
+
+    pub fn get_debug_mode(&self) -> DebugMode {
+        self.debug_mode
+    }
+
+    /// This should be called after drawing everything to trigger the actual GL rendering.
+    pub fn render_geometry<D: DrawableOnto>(&mut self, model: &Model, target: &D) {
+        self.culled_count = 0;
+        self.enable_framebuffer_srgb(true);
+        self.render_view(model, target, None);
+        self.enable_framebuffer_srgb(false);
+
+        // This is the last pass using what was drawn
+        self.clear_draw_state();
+    }
+
+    /// Renders what was drawn once for each view, from its camera into its viewport,
+    /// such as for split-screen. Like `render_geometry`, this is the last pass.
+    pub fn render_views<D: DrawableOnto>(
+        &mut self,
+        model: &Model,
+        target: &D,
+        views: &[(Handle<Camera>, Viewport)],
+    ) {
+        let viewport = self.viewport;
+        self.culled_count = 0;
+        self.enable_framebuffer_srgb(true);
+
+        for &(camera, view_viewport) in views {
+            self.viewport = Some(view_viewport);
+            self.render_view(model, target, Some(camera));
+        }
+
+        self.enable_framebuffer_srgb(false);
+        self.viewport = viewport;
+        self.clear_draw_state();
+    }
+
+    /// Toggles sRGB encoding around the geometry passes, only when `framebuffer_srgb` asks for it.
+    /// GLES has no such switch, sRGB targets always encode there.
+    fn enable_framebuffer_srgb(&self, enable: bool) {
+        if !self.framebuffer_srgb || cfg!(feature = "gles") {
+            return;
+        }
+        unsafe {
+            if enable {
+                gl::Enable(gl::FRAMEBUFFER_SRGB);
+            } else {
+                gl::Disable(gl::FRAMEBUFFER_SRGB);
+            }
+        }
+    }
+
+    /// Returns the cameras drawn so far, only the one specified if any
+    fn get_view_cameras(
+        &self,
+        camera: Option<Handle<Camera>>,
+    ) -> Vec<(Handle<Camera>, Handle<Node>)> {
+        self.cameras
+            .iter()
+            .filter(|(handle, _)| camera.is_none_or(|camera| camera.id == handle.id))
+            .copied()
+            .collect()
+    }
+
+    /// Renders the geometry drawn so far without clearing draw lists
+    fn render_view<D: DrawableOnto>(
+        &mut self,
+        model: &Model,
+        target: &D,
+        camera: Option<Handle<Camera>>,
+    ) {
+        // Rendering should follow this approach
+        // foreach prog in programs:
+        //   bind(prog)
+        //   foreach mat in p.materials:
+        //     bind(mat)
+        //     foreach prim in mat.primitives:
+        //       bind(prim)
+        //       foreach node in prim.nodes:
         //         bind(node) -> draw(prim)
         let framebuffer = target.get_framebuffer();
         framebuffer.bind_draw();
 
+        let cameras = self.get_view_cameras(camera);
+
         unsafe {
-            gl::Viewport(
-                0,
-                0,
-                framebuffer.extent.width as _,
-                framebuffer.extent.height as _,
-            );
+            match self.viewport {
+                Some(viewport) => {
+                    viewport.apply();
+                    // Do not clear other viewports
+                    gl::Enable(gl::SCISSOR_TEST);
+                    gl::Scissor(
+                        viewport.x,
+                        viewport.y,
+                        viewport.width as _,
+                        viewport.height as _,
+                    );
+                }
+                None => gl::Viewport(
+                    0,
+                    0,
+                    framebuffer.extent.width as _,
+                    framebuffer.extent.height as _,
+                ),
+            }
 
             gl::Enable(gl::BLEND);
             gl::BlendEquation(gl::FUNC_ADD);
@@ -428,54 +1426,135 @@ impl Renderer {
             gl::Enable(gl::CULL_FACE);
             gl::Enable(gl::DEPTH_TEST);
             gl::DepthFunc(gl::LESS);
+
+            self.apply_clear_color();
+            gl::StencilMask(0xFF);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
             gl::Disable(gl::SCISSOR_TEST);
 
-            gl::ClearColor(0.2, 0.3, 0.5, 0.0);
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            if self.debug_mode == DebugMode::Overdraw {
+                // Occluded fragments count as well
+                gl::Disable(gl::DEPTH_TEST);
+                gl::BlendFunc(gl::ONE, gl::ONE);
+            }
         }
 
-        // Need to bind programs one at a time
-        for (&shader_id, material_ids) in self.shaders.iter() {
-            let shader_id = self.override_shader.unwrap_or(shader_id);
-            let shader = &self.custom_shaders[shader_id as usize];
-            shader.bind();
-            shader.bind_time(self.delta);
-            shader.bind_extent(
-                framebuffer.virtual_extent.width as f32,
-                framebuffer.virtual_extent.height as f32,
-            );
-            if let Some(light_node) = model.nodes.get(self.directional_light) {
-                let light = model
-                    .directional_lights
-                    .get(light_node.directional_light)
-                    .unwrap();
-                shader.bind_sun(&light.color, light_node, &self.light_space);
+        match &self.stencil {
+            Some(stencil) => stencil.apply(),
+            None => unsafe { gl::Disable(gl::STENCIL_TEST) },
+        }
+
+        // Other passes bind textures on their own
+        self.texture_units.invalidate();
+
+        if self.depth_prepass {
+            self.render_depth_prepass(model, &cameras);
+
+            // Shaders compute positions slightly differently, hence LEQUAL rather than EQUAL.
+            // It still discards fragments behind the depth written by the prepass.
+            unsafe {
+                gl::DepthFunc(gl::LEQUAL);
+                gl::DepthMask(gl::FALSE);
             }
-            shader.bind_shadow(self.shadow_map);
+        }
 
-            // Draw the scene from all the points of view
-            for (camera_handle, camera_node_handle) in self.cameras.iter() {
-                let camera = model.cameras.get(*camera_handle).unwrap();
-                let camera_node = model.nodes.get(*camera_node_handle).unwrap();
+        // Light data is uploaded once for all the shaders
+        let lights = self.get_lights_block(model);
+        self.lights_block.upload(&lights);
+
+        let spot_lights: Vec<(&SpotLight, &Node)> = self
+            .spot_lights
+            .iter()
+            .map(|&node_handle| {
+                let node = model.nodes.get(node_handle).unwrap();
+                (model.spot_lights.get(node.spot_light).unwrap(), node)
+            })
+            .collect();
+
+        // Draw the scene from all the points of view
+        for (camera_handle, camera_node_handle) in cameras.iter() {
+            let camera = model.cameras.get(*camera_handle).unwrap();
+            let camera_node = model.nodes.get(*camera_node_handle).unwrap();
+            // Camera data is uploaded once for all the shaders
+            self.camera_block
+                .upload(&CameraBlock::new(camera, camera_node));
+            let planes = camera.frustum_planes(camera_node);
+
+            // Need to bind programs one at a time
+            for (&shader_id, material_ids) in self.shaders.iter() {
+                let shader_id = self.override_shader.unwrap_or(shader_id);
+                let shader_id = match self.shadow_technique {
+                    ShadowTechnique::Variance => get_variance_shader(shader_id),
+                    ShadowTechnique::Depth => shader_id,
+                };
+                let shader = &self.custom_shaders[shader_id as usize];
+                shader.bind();
+                shader.bind_time(self.delta);
+                shader.bind_wind(&self.wind);
+                shader.bind_extent(
+                    framebuffer.virtual_extent.width as f32,
+                    framebuffer.virtual_extent.height as f32,
+                );
+                // Shaders which do not use the uniform blocks
+                shader.bind_sun(&lights);
+                shader.bind_shadow(self.shadow_map);
+                let point_light = self.point_lights.first().map(|&node_handle| {
+                    let node = model.nodes.get(node_handle).unwrap();
+                    let light = model.point_lights.get(node.point_light).unwrap();
+                    (light, node)
+                });
+                shader.bind_point_light(point_light, self.point_shadow_map);
+                shader.bind_spot_lights(&spot_lights);
+                shader.bind_fog(self.fog.as_ref());
                 shader.bind_camera(camera, camera_node);
 
+                // Materials sharing the same albedo texture are bound one after the other
+                let mut material_ids = material_ids.clone();
+                material_ids.sort_by_key(|&id| {
+                    model
+                        .materials
+                        .get(Handle::new(id))
+                        .and_then(|material| material.texture)
+                        .map(|texture| texture.id)
+                });
+
                 // Need to bind materials for a group of primitives that use the same one
                 for material_id in material_ids.iter() {
                     let primitive_ids = &self.materials[material_id];
 
-                    let material = &model.materials[*material_id];
-                    shader.bind_material(&model.textures, &model.colors, material);
+                    let (textures, colors, material) =
+                        match model.materials.get(Handle::new(*material_id)) {
+                            Some(material) => (&model.textures, &model.colors, material),
+                            None => (
+                                &self.default_textures,
+                                &self.default_colors,
+                                &self.default_material,
+                            ),
+                        };
+                    shader.bind_material(&mut self.texture_units, textures, colors, material);
 
                     for primitive_id in primitive_ids.iter() {
-                        let primitive = &model.primitives[*primitive_id];
-                        assert!(primitive.material.is_some());
+                        let primitive = model.primitives.get(Handle::new(*primitive_id)).unwrap();
 
                         // Bind the primitive, bind the nodes using that primitive, draw the primitive.
                         shader.bind_primitive(primitive);
                         let node_res = &self.primitives[primitive_id];
                         for (&node_id, transform) in node_res.iter() {
-                            let node = &model.nodes[node_id];
+                            let node = model.nodes.get(Handle::new(node_id)).unwrap();
+                            if self.frustum_culling
+                                && !Self::is_in_frustum(&planes, node, primitive, transform)
+                            {
+                                self.culled_count += 1;
+                                continue;
+                            }
+
                             shader.bind_node(node, transform);
+                            shader.bind_node_id(node_id);
+                            if let Some(skin) = model.skins.get(node.skin) {
+                                let joint_matrices =
+                                    skin.get_joint_matrices(transform, &self.node_transforms);
+                                shader.bind_joints(&joint_matrices);
+                            }
 
                             shader.draw(node, primitive);
                         }
@@ -484,21 +1563,230 @@ impl Renderer {
             }
         }
 
+        if self.depth_prepass {
+            unsafe {
+                gl::DepthFunc(gl::LESS);
+                gl::DepthMask(gl::TRUE);
+            }
+        }
+
         if self.sky.enabled {
-            let sky_shader = self.custom_shaders[Shaders::Sky as usize]
-                .as_any()
-                .downcast_ref()
-                .unwrap();
-            let (_, camera_node) = self.cameras[0];
-            let camera_node = model.nodes.get(camera_node).unwrap();
-            self.sky.draw(sky_shader as _, camera_node);
+            if let Some(&(_, camera_node)) = cameras.first() {
+                let sky_shader = self.custom_shaders[Shaders::Sky as usize]
+                    .as_any()
+                    .downcast_ref()
+                    .unwrap();
+                let camera_node = model.nodes.get(camera_node).unwrap();
+                self.sky.draw(sky_shader as _, camera_node);
+            }
         }
 
+        self.render_outlines(model, &cameras);
+
+        if self.stencil.is_some() {
+            unsafe { gl::Disable(gl::STENCIL_TEST) };
+        }
+
+        if self.debug_draw_aabbs {
+            self.render_debug(model, &cameras);
+        }
+    }
+
+    /// Requests an outline of `color` around a node drawn in this frame, such as to highlight
+    /// a selection in an editor. It is drawn after the geometry, by the geometry pass, so the
+    /// target needs a stencil buffer. Nodes without a mesh have nothing to outline.
+    pub fn outline(&mut self, model: &Model, node: Handle<Node>, color: Color, thickness: f32) {
+        match model.nodes.get(node) {
+            Some(node_ref) if node_ref.mesh.valid() => self.outlines.push(Outline {
+                node,
+                color,
+                thickness,
+            }),
+            _ => log::warn!("Node {} has no mesh to outline", node.id),
+        }
+    }
+
+    /// Returns the outlines requested so far, which are cleared by the geometry pass
+    pub fn get_outlines(&self) -> &[Outline] {
+        &self.outlines
+    }
+
+    /// Marks the outlined nodes in the stencil buffer, then draws their hulls, the nodes
+    /// scaled up, wherever they are not marked. The stencil buffer is left with 1 in the marks.
+    fn render_outlines(&mut self, model: &Model, cameras: &[(Handle<Camera>, Handle<Node>)]) {
+        if self.outlines.is_empty() {
+            return;
+        }
+
+        let shader = &self.custom_shaders[Shaders::Unlit as usize];
+        shader.bind();
+        let mut material = Material::builder().unlit().build();
+
+        for (camera_handle, camera_node_handle) in cameras.iter() {
+            let camera = model.cameras.get(*camera_handle).unwrap();
+            let camera_node = model.nodes.get(*camera_node_handle).unwrap();
+            self.camera_block
+                .upload(&CameraBlock::new(camera, camera_node));
+            shader.bind_camera(camera, camera_node);
+
+            for outline in self.outlines.iter() {
+                // Not drawn in this frame
+                let transform = match self.node_transforms.get(&outline.node.id) {
+                    Some(transform) => transform,
+                    None => continue,
+                };
+                let node = model.nodes.get(outline.node).unwrap();
+                let mesh = model.meshes.get(node.mesh).unwrap();
+                let hull = transform.prepend_scaling(1.0 + outline.thickness);
+                material.tint = outline.color;
+
+                for (pass, transform) in [transform, &hull].iter().enumerate() {
+                    let outline_pass = pass == 1;
+                    unsafe {
+                        if outline_pass {
+                            Stencil::not_equal(1).apply();
+                            gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+                            gl::Disable(gl::DEPTH_TEST);
+                        } else {
+                            // Only the stencil buffer is written
+                            Stencil::write(1).apply();
+                            gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+                            gl::Enable(gl::DEPTH_TEST);
+                            gl::DepthFunc(gl::LEQUAL);
+                            gl::DepthMask(gl::FALSE);
+                        }
+                    }
+                    shader.bind_material(
+                        &mut self.texture_units,
+                        &self.default_textures,
+                        &self.default_colors,
+                        &material,
+                    );
+
+                    for &primitive_handle in mesh.primitives.iter() {
+                        let primitive = model.primitives.get(primitive_handle).unwrap();
+                        shader.bind_primitive(primitive);
+                        shader.bind_node(node, transform);
+                        shader.draw(node, primitive);
+                    }
+                }
+            }
+        }
+
+        unsafe {
+            gl::Enable(gl::DEPTH_TEST);
+            gl::DepthFunc(gl::LESS);
+            gl::DepthMask(gl::TRUE);
+            gl::Disable(gl::STENCIL_TEST);
+        }
+    }
+
+    /// Forgets everything drawn so far. Render passes already call it at the end, but call it
+    /// at the start of a frame when a previous draw might not have been rendered, for example
+    /// while the window is minimized, so that it does not leak into the next frame.
+    pub fn clear_draw_state(&mut self) {
         self.shaders.clear();
+        self.directional_lights.clear();
         self.point_lights.clear();
+        self.spot_lights.clear();
         self.cameras.clear();
         self.materials.clear();
         self.primitives.clear();
+        self.node_transforms.clear();
+        self.debug_draw.clear();
+        self.outlines.clear();
+        self.last_scene_bounds = std::mem::take(&mut self.scene_bounds);
+    }
+
+    /// Tests the bounding sphere of a primitive first, and its box only when the sphere
+    /// is found intersecting the frustum
+    fn is_in_frustum(
+        planes: &[na::Vector4<f32>; 6],
+        node: &Node,
+        primitive: &Primitive,
+        transform: &na::Matrix4<f32>,
+    ) -> bool {
+        // Bounds of instances and of skinned vertices are not known
+        if !node.transforms.is_empty() || node.skin.valid() {
+            return true;
+        }
+
+        let (center, radius) = primitive.bounding_sphere();
+        let center = transform.transform_point(&na::Point3::from(center)).coords;
+        // With non-uniform scale, the sphere grows along the biggest axis
+        let scale = (0..3)
+            .map(|i| transform.column(i).xyz().norm())
+            .fold(0.0, f32::max);
+
+        match Camera::sphere_in_frustum(planes, &center, radius * scale) {
+            Containment::Outside => false,
+            Containment::Inside => true,
+            Containment::Intersecting => {
+                Camera::is_aabb_visible(planes, &primitive.aabb.transform(transform))
+            }
+        }
+    }
+
+    /// Returns the number of primitive draws skipped by frustum culling in the last geometry pass
+    pub fn get_culled_count(&self) -> u32 {
+        self.culled_count
+    }
+
+    /// Returns the bounds of the scene drawn before the last render, or before the last call
+    /// to `clear_draw_state`, together with the frustum of the first camera drawn
+    pub fn last_scene_bounds(&self) -> &SceneBounds {
+        &self.last_scene_bounds
+    }
+
+    /// Renders the lines collected by the debug draw as seen by the cameras
+    fn render_debug(&mut self, model: &Model, cameras: &[(Handle<Camera>, Handle<Node>)]) {
+        if self.debug_draw.lines.is_empty() {
+            return;
+        }
+
+        let shader = &self.custom_shaders[Shaders::Line as usize];
+        shader.bind();
+
+        self.debug_res.vao.bind();
+        self.debug_res.vbo.upload(&self.debug_draw.lines);
+
+        // Lines are already in world space
+        shader.bind_node(&Node::new(), &na::Matrix4::identity());
+
+        for (camera_handle, camera_node_handle) in cameras.iter() {
+            let camera = model.cameras.get(*camera_handle).unwrap();
+            let camera_node = model.nodes.get(*camera_node_handle).unwrap();
+            shader.bind_camera(camera, camera_node);
+            self.camera_block
+                .upload(&CameraBlock::new(camera, camera_node));
+
+            unsafe {
+                gl::DrawArrays(gl::LINES, 0, self.debug_draw.lines.len() as _);
+            }
+        }
+    }
+
+    /// Draws the sprites collected by the batch on top of the target
+    pub fn render_sprites<D: DrawableOnto>(
+        &mut self,
+        batch: &mut SpriteBatch,
+        model: &Model,
+        target: &D,
+    ) {
+        let shader = self.custom_shaders[Shaders::Unlit as usize]
+            .as_any()
+            .downcast_ref()
+            .unwrap();
+        batch.draw(shader, &model.textures, target);
+    }
+
+    /// Draws the text collected by the text renderer on top of the target
+    pub fn render_text<D: DrawableOnto>(&mut self, text: &mut TextRenderer, target: &D) {
+        let shader = self.custom_shaders[Shaders::Unlit as usize]
+            .as_any()
+            .downcast_ref()
+            .unwrap();
+        text.draw(shader, &self.gui_res.font_texture, target);
     }
 
     pub fn render_gui<D: DrawableOnto>(&mut self, ui: imgui::Ui, target: &D) {
@@ -548,14 +1836,10 @@ impl Renderer {
 
             self.gui_res.mesh_res.vao.bind();
 
-            self.gui_res.mesh_res.vbo.bind();
-            unsafe {
-                gl::BufferData(
-                    gl::ARRAY_BUFFER,
-                    (vtx_buffer.len() * std::mem::size_of::<imgui::DrawVert>()) as _,
-                    vtx_buffer.as_ptr() as _,
-                    gl::STREAM_DRAW,
-                );
+            // Storage grows only when needed, otherwise vertices are written in place
+            let vbo = &mut self.gui_res.mesh_res.vbo;
+            if vbo.update(0, vtx_buffer).is_err() {
+                vbo.upload(vtx_buffer);
             }
 
             self.gui_res.mesh_res.ebo.bind();
@@ -616,3 +1900,1021 @@ impl Renderer {
         }
     }
 }
+
+/// These tests need a GL context, hence they run only with the headless feature
+#[cfg(all(test, feature = "headless"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn default_material() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let mut quad = Primitive::quad(Handle::none());
+        quad.material = None;
+        let primitive = model.primitives.push(quad);
+        let mesh = model.meshes.push(Mesh::new(vec![primitive]));
+        let camera = model.cameras.push(Camera::orthographic(2, 2, 0.1, 10.0));
+        let camera_node = Node::builder()
+            .camera(camera)
+            .translation(na::Translation3::new(0.0, 0.0, 1.0))
+            .build();
+        let children = vec![
+            model.nodes.push(Node::builder().mesh(mesh).build()),
+            model.nodes.push(camera_node),
+        ];
+        let root = model.nodes.push(Node::builder().children(children).build());
+
+        let frame = gfx.next_frame();
+        let renderer = &mut gfx.renderer;
+        renderer.set_clear_color([0.0, 0.0, 0.0, 1.0]);
+        renderer.draw(&model, root, &na::Matrix4::identity());
+        let geometry_buffer = frame.get_geometry_buffer();
+        renderer.render_geometry(&model, geometry_buffer);
+
+        // Without lights, the white default material shows only the ambient term
+        let (extent, pixels) = geometry_buffer.read_color(0);
+        let center = 4 * (extent.height / 2 * extent.width + extent.width / 2) as usize;
+        for &channel in &pixels[center..center + 3] {
+            assert!((channel as i32 - 77).abs() <= 1);
+        }
+        assert_eq!(&pixels[0..3], &[0, 0, 0]);
+        gfx.present(frame);
+    }
+
+    #[test]
+    fn fog() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        model.color_texture(Color::new());
+        let material = Material::builder().shader(Shaders::LightShadow).build();
+        let material = model.materials.push(material);
+        let primitive = model.primitives.push(Primitive::quad(material));
+        let mesh = model.meshes.push(Mesh::new(vec![primitive]));
+        let camera = model.cameras.push(Camera::orthographic(2, 2, 0.1, 10.0));
+        let camera_node = Node::builder()
+            .camera(camera)
+            .translation(na::Translation3::new(0.0, 0.0, 1.0))
+            .build();
+        let children = vec![
+            model.nodes.push(Node::builder().mesh(mesh).build()),
+            model.nodes.push(camera_node),
+        ];
+        let root = model.nodes.push(Node::builder().children(children).build());
+
+        let mut render = |fog: Fog| {
+            let frame = gfx.next_frame();
+            let renderer = &mut gfx.renderer;
+            renderer.fog = Some(fog);
+            renderer.draw(&model, root, &na::Matrix4::identity());
+            let geometry_buffer = frame.get_geometry_buffer();
+            renderer.render_geometry(&model, geometry_buffer);
+            let (extent, pixels) = geometry_buffer.read_color(0);
+            let center = 4 * (extent.height / 2 * extent.width + extent.width / 2) as usize;
+            gfx.present(frame);
+            [pixels[center], pixels[center + 1], pixels[center + 2]]
+        };
+
+        // The quad is 1.0 away from the camera, beyond the end of the fog
+        let fogged = render(Fog::new([1.0, 0.0, 0.0], 0.0, 0.5));
+        // Fog color is tonemapped and gamma corrected with the rest of the scene
+        let expected = (0.5f32.powf(1.0 / 2.2) * 255.0) as i32;
+        assert!((fogged[0] as i32 - expected).abs() <= 1);
+        assert_eq!(&fogged[1..], &[0, 0]);
+
+        // Fragments before the start of the fog are not affected
+        let clear = render(Fog::new([1.0, 0.0, 0.0], 2.0, 3.0));
+        assert!(clear[1] > 0 && clear[2] > 0);
+    }
+
+    #[test]
+    fn depth_prepass() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let red = Color::rgba(255, 0, 0, 255);
+        let blue = Color::rgba(0, 0, 255, 255);
+        model.color_texture(red);
+        model.color_texture(blue);
+        // The front quad is half transparent, so the back one would show through it
+        let front = Material::builder()
+            .unlit()
+            .color(red)
+            .tint(Color::rgba(255, 255, 255, 128))
+            .build();
+        let back = Material::builder().unlit().color(blue).build();
+        let mut meshes = vec![];
+        for material in [front, back] {
+            let material = model.materials.push(material);
+            let primitive = model.primitives.push(Primitive::quad(material));
+            meshes.push(model.meshes.push(Mesh::new(vec![primitive])));
+        }
+        let camera = model.cameras.push(Camera::orthographic(2, 2, 0.1, 10.0));
+        let camera_node = Node::builder()
+            .camera(camera)
+            .translation(na::Translation3::new(0.0, 0.0, 1.0))
+            .build();
+        // Instanced nodes take part in the prepass as well
+        let mut front_node = Node::builder().mesh(meshes[0]).build();
+        front_node.transforms.push(na::Matrix4::identity());
+        let back_node = Node::builder()
+            .mesh(meshes[1])
+            .translation(na::Translation3::new(0.0, 0.0, -0.5))
+            .build();
+        let children = vec![
+            model.nodes.push(front_node),
+            model.nodes.push(back_node),
+            model.nodes.push(camera_node),
+        ];
+        let root = model.nodes.push(Node::builder().children(children).build());
+
+        let frame = gfx.next_frame();
+        let renderer = &mut gfx.renderer;
+        renderer.set_clear_color([0.0, 0.0, 0.0, 1.0]);
+        renderer.depth_prepass = true;
+        renderer.draw(&model, root, &na::Matrix4::identity());
+        let geometry_buffer = frame.get_geometry_buffer();
+        renderer.render_geometry(&model, geometry_buffer);
+
+        // Whatever the draw order, fragments of the back quad fail the depth test
+        let (extent, pixels) = geometry_buffer.read_color(0);
+        let center = 4 * (extent.height / 2 * extent.width + extent.width / 2) as usize;
+        assert!(pixels[center] > 0);
+        assert_eq!(pixels[center + 2], 0);
+        gfx.present(frame);
+    }
+
+    #[test]
+    fn two_directional_lights() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let sun = model.directional_lights.push(DirectionalLight::new());
+        let moon = model
+            .directional_lights
+            .push(DirectionalLight::color(0.5, 0.5, 1.0).with_intensity(0.5));
+        let children = vec![
+            model
+                .nodes
+                .push(Node::builder().directional_light(sun).build()),
+            model
+                .nodes
+                .push(Node::builder().directional_light(moon).build()),
+        ];
+        let root = model
+            .nodes
+            .push(Node::builder().children(children.clone()).build());
+
+        gfx.renderer.draw(&model, root, &na::Matrix4::identity());
+        let drawn: Vec<usize> = gfx
+            .renderer
+            .get_directional_lights()
+            .iter()
+            .map(|node| node.id)
+            .collect();
+        assert_eq!(drawn, vec![children[0].id, children[1].id]);
+
+        // Drawing again does not add the same lights twice
+        gfx.renderer.draw(&model, root, &na::Matrix4::identity());
+        assert_eq!(gfx.renderer.get_directional_lights().len(), 2);
+
+        let lights = gfx.renderer.get_lights_block(&model);
+        assert_eq!(lights.directional_light_count, 2);
+        assert_eq!(lights.light_colors[0], [1.0, 1.0, 1.0, 0.0]);
+        assert_eq!(lights.light_colors[1], [0.25, 0.25, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn shadow_caster_opt_out() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let lamp = model
+            .directional_lights
+            .push(DirectionalLight::color(1.0, 0.0, 0.0).with_shadow(false));
+        let sun = model.directional_lights.push(DirectionalLight::new());
+        let lamp_node = model
+            .nodes
+            .push(Node::builder().directional_light(lamp).build());
+        let sun_node = model
+            .nodes
+            .push(Node::builder().directional_light(sun).build());
+        let root = model
+            .nodes
+            .push(Node::builder().children(vec![lamp_node, sun_node]).build());
+
+        let renderer = &mut gfx.renderer;
+        renderer.draw(&model, root, &na::Matrix4::identity());
+        assert_eq!(renderer.get_shadow_light(&model).unwrap().id, sun_node.id);
+
+        // The shadow caster comes first for the shaders
+        let lights = renderer.get_lights_block(&model);
+        assert_eq!(lights.directional_light_count, 2);
+        assert_eq!(lights.light_colors[0], [1.0, 1.0, 1.0, 0.0]);
+        assert_eq!(lights.light_colors[1], [1.0, 0.0, 0.0, 0.0]);
+
+        // Without shadow casters there is no shadow light
+        let root = model
+            .nodes
+            .push(Node::builder().children(vec![lamp_node]).build());
+        renderer.directional_lights.clear();
+        renderer.draw(&model, root, &na::Matrix4::identity());
+        assert!(renderer.get_shadow_light(&model).is_none());
+    }
+
+    #[test]
+    fn render_twice() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let material = model.materials.push(Material::new());
+        let primitive = model.primitives.push(Primitive::cube(material));
+        let mesh = model.meshes.push(Mesh::new(vec![primitive]));
+        let camera = model.cameras.push(Camera::perspective(64.0, 64.0));
+        let sun = model.directional_lights.push(DirectionalLight::new());
+        let children = vec![
+            model.nodes.push(Node::builder().mesh(mesh).build()),
+            model.nodes.push(Node::builder().camera(camera).build()),
+            model
+                .nodes
+                .push(Node::builder().directional_light(sun).build()),
+        ];
+        let root = model.nodes.push(Node::builder().children(children).build());
+
+        for _ in 0..2 {
+            let frame = gfx.next_frame();
+            gfx.renderer.render(&model, root, &frame);
+
+            // Nothing is left over for the next frame
+            let renderer = &gfx.renderer;
+            assert!(renderer.shaders.is_empty());
+            assert!(renderer.materials.is_empty());
+            assert!(renderer.primitives.is_empty());
+            assert!(renderer.cameras.is_empty());
+            assert!(renderer.get_directional_lights().is_empty());
+            assert!(renderer.shadow_map != 0);
+            gfx.present(frame);
+        }
+    }
+
+    #[test]
+    fn outline() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let primitive = model.primitives.push(Primitive::cube(Handle::none()));
+        let mesh = model.meshes.push(Mesh::new(vec![primitive]));
+        let camera = model.cameras.push(Camera::perspective(64.0, 64.0));
+        let mut camera_node = Node::builder().camera(camera).build();
+        camera_node.trs.translate(0.0, 0.0, 4.0);
+        let cube = model.nodes.push(Node::builder().mesh(mesh).build());
+        let children = vec![cube, model.nodes.push(camera_node)];
+        let root = model.nodes.push(Node::builder().children(children).build());
+
+        let frame = gfx.next_frame();
+        let renderer = &mut gfx.renderer;
+        renderer.draw(&model, root, &na::Matrix4::identity());
+
+        let orange = Color::rgba(255, 128, 0, 255);
+        renderer.outline(&model, cube, orange, 0.1);
+        // The root has no mesh
+        renderer.outline(&model, root, orange, 0.1);
+        assert_eq!(renderer.get_outlines().len(), 1);
+        let outline = renderer.get_outlines()[0];
+        assert_eq!(outline.node.id, cube.id);
+        assert!(outline.color == orange);
+        assert_eq!(outline.thickness, 0.1);
+
+        renderer.render_geometry(&model, frame.get_geometry_buffer());
+        assert!(renderer.get_outlines().is_empty());
+        assert_eq!(unsafe { gl::GetError() }, gl::NO_ERROR);
+        gfx.present(frame);
+    }
+
+    #[test]
+    fn clear_draw_state() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let primitive = model.primitives.push(Primitive::cube(Handle::none()));
+        let mesh = model.meshes.push(Mesh::new(vec![primitive]));
+        let camera = model.cameras.push(Camera::perspective(64.0, 64.0));
+        let sun = model.directional_lights.push(DirectionalLight::new());
+        let mut light = Node::new();
+        light.point_light = model.point_lights.push(PointLight::new());
+        let children = vec![
+            model.nodes.push(Node::builder().mesh(mesh).build()),
+            model.nodes.push(Node::builder().camera(camera).build()),
+            model
+                .nodes
+                .push(Node::builder().directional_light(sun).build()),
+            model.nodes.push(light),
+        ];
+        let root = model.nodes.push(Node::builder().children(children).build());
+
+        // Drawn without rendering, as when skipping a frame
+        let renderer = &mut gfx.renderer;
+        renderer.draw(&model, root, &na::Matrix4::identity());
+        assert!(!renderer.primitives.is_empty());
+        assert!(!renderer.point_lights.is_empty());
+
+        renderer.clear_draw_state();
+        assert!(renderer.shaders.is_empty());
+        assert!(renderer.materials.is_empty());
+        assert!(renderer.primitives.is_empty());
+        assert!(renderer.cameras.is_empty());
+        assert!(renderer.point_lights.is_empty());
+        assert!(renderer.get_directional_lights().is_empty());
+    }
+
+    #[test]
+    fn shared_textures_bound_once() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let white = model
+            .textures
+            .push(Texture::from_rgba(1, 1, &[255; 4]).unwrap());
+        let black = model
+            .textures
+            .push(Texture::from_rgba(1, 1, &[0, 0, 0, 255]).unwrap());
+
+        // The first and the last material share the same texture
+        let mut children = vec![];
+        for &texture in [white, black, white].iter() {
+            let material = model
+                .materials
+                .push(Material::builder().texture(texture).build());
+            let primitive = model.primitives.push(Primitive::cube(material));
+            let mesh = model.meshes.push(Mesh::new(vec![primitive]));
+            children.push(model.nodes.push(Node::builder().mesh(mesh).build()));
+        }
+        for _ in 0..2 {
+            let camera = model.cameras.push(Camera::perspective(64.0, 64.0));
+            children.push(model.nodes.push(Node::builder().camera(camera).build()));
+        }
+        let root = model.nodes.push(Node::builder().children(children).build());
+
+        let frame = gfx.next_frame();
+        gfx.renderer.draw(&model, root, &na::Matrix4::identity());
+        gfx.renderer
+            .render_geometry(&model, frame.get_geometry_buffer());
+
+        // Binding each material for each camera would take 6 calls
+        assert_eq!(gfx.renderer.texture_units.get_bind_count(), 4);
+        gfx.present(frame);
+    }
+
+    #[test]
+    fn debug_mode() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        gfx.renderer.set_debug_mode(DebugMode::Normals);
+        let normals = DEBUG_VARIANTS[DebugChannelVariant::Normals as usize];
+        assert!(gfx.renderer.override_shader == Some(normals));
+
+        gfx.renderer.set_debug_mode(DebugMode::FlatColor);
+        let flat = DEBUG_VARIANTS[DebugChannelVariant::Default as usize];
+        assert!(gfx.renderer.override_shader == Some(flat));
+
+        gfx.renderer.set_debug_mode(DebugMode::Off);
+        assert!(gfx.renderer.override_shader.is_none());
+
+        // Every mode is reached while cycling
+        let mut mode = DebugMode::Off.next();
+        let mut count = 1;
+        while mode != DebugMode::Off {
+            assert!(mode.get_shader().is_some());
+            mode = mode.next();
+            count += 1;
+        }
+        assert_eq!(count, 5);
+    }
+
+    #[test]
+    fn flat_color_per_node() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let primitive = model.primitives.push(Primitive::quad(Handle::none()));
+        let mesh = model.meshes.push(Mesh::new(vec![primitive]));
+        let camera = model.cameras.push(Camera::orthographic(2, 2, 0.1, 10.0));
+        let camera_node = Node::builder()
+            .camera(camera)
+            .translation(na::Translation3::new(0.0, 0.0, 1.0))
+            .build();
+        // Nodes built in code share the same id
+        let left = Node::builder()
+            .mesh(mesh)
+            .translation(na::Translation3::new(-0.5, 0.0, 0.0))
+            .build();
+        let right = Node::builder()
+            .mesh(mesh)
+            .translation(na::Translation3::new(0.5, 0.0, 0.0))
+            .build();
+        let children = vec![
+            model.nodes.push(left),
+            model.nodes.push(right),
+            model.nodes.push(camera_node),
+        ];
+        let root = model.nodes.push(Node::builder().children(children).build());
+
+        let frame = gfx.next_frame();
+        let renderer = &mut gfx.renderer;
+        renderer.set_debug_mode(DebugMode::FlatColor);
+        renderer.draw(&model, root, &na::Matrix4::identity());
+        let geometry_buffer = frame.get_geometry_buffer();
+        renderer.render_geometry(&model, geometry_buffer);
+
+        let (extent, pixels) = geometry_buffer.read_color(0);
+        let pixel = |x: u32, y: u32| {
+            let i = 4 * (y * extent.width + x) as usize;
+            [pixels[i], pixels[i + 1], pixels[i + 2]]
+        };
+        let (y, clear) = (extent.height / 2, pixel(0, 0));
+        let left_color = pixel(extent.width / 4, y);
+        let right_color = pixel(3 * extent.width / 4, y);
+        assert_ne!(left_color, clear);
+        assert_ne!(right_color, clear);
+        assert_ne!(left_color, right_color);
+        gfx.present(frame);
+    }
+
+    #[test]
+    fn active_camera() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let first = model.cameras.push(Camera::perspective(64.0, 64.0));
+        let second = model.cameras.push(Camera::perspective(64.0, 64.0));
+        let children = vec![
+            model.nodes.push(Node::builder().camera(first).build()),
+            model.nodes.push(Node::builder().camera(second).build()),
+        ];
+        let root = model.nodes.push(Node::builder().children(children).build());
+
+        gfx.renderer.draw(&model, root, &na::Matrix4::identity());
+        assert_eq!(gfx.renderer.cameras.len(), 2);
+        gfx.renderer.cameras.clear();
+
+        gfx.renderer.active_camera = Some(second);
+        gfx.renderer.draw(&model, root, &na::Matrix4::identity());
+        assert_eq!(gfx.renderer.cameras.len(), 1);
+        assert_eq!(gfx.renderer.cameras[0].0.id, second.id);
+
+        let frame = gfx.next_frame();
+        gfx.renderer
+            .render_geometry(&model, &frame.default_framebuffer);
+        gfx.present(frame);
+    }
+
+    #[test]
+    #[cfg(not(feature = "gles"))]
+    fn framebuffer_srgb() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+        let model = Model::new();
+        let frame = gfx.next_frame();
+
+        // Without the flag, the state set by others is left as it is
+        unsafe { gl::Enable(gl::FRAMEBUFFER_SRGB) };
+        gfx.renderer
+            .render_geometry(&model, &frame.default_framebuffer);
+        assert_eq!(unsafe { gl::IsEnabled(gl::FRAMEBUFFER_SRGB) }, gl::TRUE);
+
+        // With the flag, only the geometry pass encodes to sRGB,
+        // other passes such as blitting to the screen are left untouched
+        gfx.renderer.framebuffer_srgb = true;
+        gfx.renderer
+            .render_geometry(&model, &frame.default_framebuffer);
+        assert_eq!(unsafe { gl::IsEnabled(gl::FRAMEBUFFER_SRGB) }, gl::FALSE);
+        assert_eq!(unsafe { gl::GetError() }, gl::NO_ERROR);
+        gfx.present(frame);
+    }
+
+    #[test]
+    fn split_viewports() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let first = model.cameras.push(Camera::perspective(32.0, 64.0));
+        let second = model.cameras.push(Camera::perspective(32.0, 64.0));
+        let children = vec![
+            model.nodes.push(Node::builder().camera(first).build()),
+            model.nodes.push(Node::builder().camera(second).build()),
+        ];
+        let root = model.nodes.push(Node::builder().children(children).build());
+
+        let get_rect = |name| {
+            let mut rect = [0; 4];
+            unsafe { gl::GetIntegerv(name, rect.as_mut_ptr()) };
+            rect
+        };
+
+        let (left, right) = Viewport::from_extent(Extent2D::new(64, 64)).split_horizontal();
+        let frame = gfx.next_frame();
+
+        gfx.renderer.set_viewport(Some(left));
+        gfx.renderer.draw(&model, root, &na::Matrix4::identity());
+        gfx.renderer
+            .render_geometry(&model, &frame.default_framebuffer);
+        assert_eq!(get_rect(gl::VIEWPORT), [0, 0, 32, 64]);
+        // Clearing was restricted to the viewport
+        assert_eq!(get_rect(gl::SCISSOR_BOX), [0, 0, 32, 64]);
+
+        gfx.renderer.set_viewport(None);
+        gfx.renderer.draw(&model, root, &na::Matrix4::identity());
+        gfx.renderer.render_views(
+            &model,
+            &frame.default_framebuffer,
+            &[(first, left), (second, right)],
+        );
+        assert_eq!(get_rect(gl::VIEWPORT), [32, 0, 32, 64]);
+        assert_eq!(get_rect(gl::SCISSOR_BOX), [32, 0, 32, 64]);
+        assert!(gfx.renderer.cameras.is_empty());
+        assert!(gfx.renderer.get_viewport().is_none());
+
+        gfx.present(frame);
+    }
+
+    #[test]
+    fn traverse_matrix_override() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let material = model.materials.push(Material::new());
+        let primitive = model.primitives.push(Primitive::cube(material));
+        let mesh = model.meshes.push(Mesh::new(vec![primitive]));
+
+        let mut shear = na::Matrix4::identity();
+        shear[(0, 1)] = 0.5;
+        let mut node = Node::builder().mesh(mesh).build();
+        node.trs.translate(4.0, 0.0, 0.0);
+        node.matrix_override = Some(shear);
+        let node = model.nodes.push(node);
+
+        let parent = na::Matrix4::new_translation(&na::Vector3::y());
+        gfx.renderer.draw(&model, node, &parent);
+        assert_eq!(
+            gfx.renderer.primitives[&primitive.id][&node.id],
+            parent * shear
+        );
+    }
+
+    #[test]
+    fn draw_once() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let material = model.materials.push(Material::new());
+        let primitive = model.primitives.push(Primitive::cube(material));
+        let mesh = model.meshes.push(Mesh::new(vec![primitive]));
+        let camera = model.cameras.push(Camera::perspective(64.0, 64.0));
+        let sun = model.directional_lights.push(DirectionalLight::new());
+        let mut sun_node = Node::builder().directional_light(sun).build();
+        sun_node.trs.translate(0.0, 0.0, 4.0);
+        let mut camera_node = Node::builder().camera(camera).build();
+        camera_node.trs.translate(0.0, 0.0, 4.0);
+        let children = vec![
+            model.nodes.push(Node::builder().mesh(mesh).build()),
+            model.nodes.push(camera_node),
+            model.nodes.push(sun_node),
+        ];
+        let root = model.nodes.push(Node::builder().children(children).build());
+
+        let frame = gfx.next_frame();
+        let renderer = &mut gfx.renderer;
+        renderer.draw(&model, root, &na::Matrix4::identity());
+        renderer.render_shadow(&model, frame.get_shadow_buffer());
+
+        // The shadow pass saw the light, and left what was drawn for the geometry pass
+        assert!(renderer.light_space != na::Matrix4::identity());
+        assert_eq!(renderer.primitives.len(), 1);
+        assert_eq!(renderer.cameras.len(), 1);
+        assert_eq!(renderer.get_directional_lights().len(), 1);
+
+        renderer.render_geometry(&model, frame.get_geometry_buffer());
+        assert!(renderer.primitives.is_empty());
+        assert!(renderer.cameras.is_empty());
+
+        // The cube in front of the camera does not look like the background in the corner
+        let (extent, pixels) = frame.get_geometry_buffer().read_color(0);
+        let center = ((extent.height / 2 * extent.width + extent.width / 2) * 4) as usize;
+        assert_ne!(&pixels[center..center + 4], &pixels[0..4]);
+        gfx.present(frame);
+    }
+
+    #[test]
+    fn shadow_depth_range() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let material = model.materials.push(Material::new());
+        let primitive = model.primitives.push(Primitive::cube(material));
+        let mesh = model.meshes.push(Mesh::new(vec![primitive]));
+        let sun = model.directional_lights.push(DirectionalLight::new());
+        let mut sun_node = Node::builder().directional_light(sun).build();
+        sun_node.trs.translate(0.0, 0.0, 16.0);
+        let view = sun_node.trs.get_view();
+        let children = vec![
+            model.nodes.push(Node::builder().mesh(mesh).build()),
+            model.nodes.push(sun_node),
+        ];
+        let root = model.nodes.push(Node::builder().children(children).build());
+
+        let frame = gfx.next_frame();
+        let renderer = &mut gfx.renderer;
+
+        renderer.set_shadow_depth_range(0.5, 32.0);
+        renderer.draw(&model, root, &na::Matrix4::identity());
+        renderer.render_shadow(&model, frame.get_shadow_buffer());
+        let expected = Camera::orthographic(8, 8, 0.5, 32.0).proj * view;
+        assert!(renderer.light_space == expected);
+
+        // The unit cube is 16 units away from the light
+        renderer.fit_shadow_depth_range();
+        renderer.render_shadow(&model, frame.get_shadow_buffer());
+        let expected = Camera::orthographic(8, 8, 15.4, 16.6).proj * view;
+        assert!((renderer.light_space - expected).abs().max() < 0.001);
+
+        renderer.render_geometry(&model, frame.get_geometry_buffer());
+        gfx.present(frame);
+    }
+
+    #[test]
+    fn shadow_atlas() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let primitive = model.primitives.push(Primitive::cube(Handle::none()));
+        let mesh = model.meshes.push(Mesh::new(vec![primitive]));
+        let sun = model.directional_lights.push(DirectionalLight::new());
+        let mut sun_node = Node::builder().directional_light(sun).build();
+        sun_node.trs.translate(0.0, 0.0, 16.0);
+        let sun_node = model.nodes.push(sun_node);
+        let children = vec![
+            model.nodes.push(Node::builder().mesh(mesh).build()),
+            sun_node,
+        ];
+        let root = model.nodes.push(Node::builder().children(children).build());
+
+        let atlas = CustomFramebuffer::shadow();
+        let (left, right) = Viewport::from_extent(atlas.framebuffer.extent).split_horizontal();
+        atlas.framebuffer.bind();
+        unsafe { gl::Clear(gl::DEPTH_BUFFER_BIT) };
+
+        // Depth at the center of a region, where the cube is
+        let read_depth = |region: Viewport| {
+            let mut depth = 0.0f32;
+            atlas.framebuffer.bind();
+            unsafe {
+                gl::ReadPixels(
+                    region.x + region.width as i32 / 2,
+                    region.y + region.height as i32 / 2,
+                    1,
+                    1,
+                    gl::DEPTH_COMPONENT,
+                    gl::FLOAT,
+                    &mut depth as *mut f32 as _,
+                );
+            }
+            depth
+        };
+
+        let renderer = &mut gfx.renderer;
+        renderer.draw(&model, root, &na::Matrix4::identity());
+        renderer.render_shadow_region(&model, &atlas, left, sun_node);
+        assert!(read_depth(left) < 1.0);
+        assert_eq!(read_depth(right), 1.0);
+
+        // Nothing to render without a directional light
+        assert!(renderer
+            .render_shadow_region(&model, &atlas, right, root)
+            .is_none());
+        assert_eq!(read_depth(right), 1.0);
+
+        let light_space = renderer
+            .render_shadow_region(&model, &atlas, right, sun_node)
+            .unwrap();
+        assert!(read_depth(left) < 1.0);
+        assert!(read_depth(right) < 1.0);
+        let view = model.nodes.get(sun_node).unwrap().trs.get_view();
+        assert!((light_space - Camera::orthographic(8, 8, 15.4, 16.6).proj * view).norm() < 0.001);
+    }
+
+    #[test]
+    fn point_shadow_viewport() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let light = model.add_point_light(na::Vector3::new(0.0, 0.0, 2.0), Color::new());
+        let primitive = model.primitives.push(Primitive::cube(Handle::none()));
+        let mesh = model.meshes.push(Mesh::new(vec![primitive]));
+        let cube = model.nodes.push(Node::builder().mesh(mesh).build());
+        let root = model
+            .nodes
+            .push(Node::builder().children(vec![light, cube]).build());
+
+        let shadow_cube = CustomFramebuffer::shadow_cube(Extent2D::new(16, 16)).unwrap();
+        let get_viewport = || {
+            let mut viewport = [0; 4];
+            unsafe { gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr()) };
+            viewport
+        };
+        unsafe { gl::Viewport(1, 2, 30, 40) };
+
+        let renderer = &mut gfx.renderer;
+        renderer.draw(&model, root, &na::Matrix4::identity());
+        renderer.render_point_shadow(&model, &shadow_cube);
+        assert_eq!(
+            renderer.point_shadow_map,
+            shadow_cube.depth_texture.as_ref().unwrap().handle
+        );
+        // Faces are rendered with the extent of the cube, then the viewport is restored
+        assert_eq!(get_viewport(), [1, 2, 30, 40]);
+        renderer.clear_draw_state();
+    }
+
+    #[test]
+    fn depth_view_range() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let frame = gfx.next_frame();
+        gfx.renderer.depth_view_range = (0.9, 1.0);
+        gfx.renderer
+            .blit_depth(frame.get_shadow_buffer(), &frame.default_framebuffer);
+
+        let program = &gfx.renderer.read_depth_program;
+        let location = program.get_uniform_location("depth_range");
+        assert!(location >= 0);
+        // The program is still in use after blitting
+        let mut handle = 0;
+        let mut range = [0.0f32; 2];
+        unsafe {
+            gl::GetIntegerv(gl::CURRENT_PROGRAM, &mut handle);
+            gl::GetUniformfv(handle as _, location, range.as_mut_ptr());
+        }
+        assert_eq!(range, [0.9, 1.0]);
+
+        // An empty range is widened, as the shader divides by its length
+        gfx.renderer.depth_view_range = (0.5, 0.5);
+        gfx.renderer
+            .blit_depth(frame.get_shadow_buffer(), &frame.default_framebuffer);
+        unsafe {
+            gl::GetUniformfv(handle as _, location, range.as_mut_ptr());
+        }
+        assert!(range[1] - range[0] > 0.0);
+        gfx.present(frame);
+    }
+
+    #[test]
+    fn shadow_without_light() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let material = model.materials.push(Material::new());
+        let primitive = model.primitives.push(Primitive::cube(material));
+        let mesh = model.meshes.push(Mesh::new(vec![primitive]));
+        let camera = model.cameras.push(Camera::perspective(64.0, 64.0));
+        let point_light = model.point_lights.push(PointLight::new());
+        let mut light_node = Node::new();
+        light_node.point_light = point_light;
+        let children = vec![
+            model.nodes.push(Node::builder().mesh(mesh).build()),
+            model.nodes.push(Node::builder().camera(camera).build()),
+            model.nodes.push(light_node),
+        ];
+        let root = model.nodes.push(Node::builder().children(children).build());
+
+        let frame = gfx.next_frame();
+        let renderer = &mut gfx.renderer;
+        renderer.draw(&model, root, &na::Matrix4::identity());
+        renderer.render_shadow(&model, frame.get_shadow_buffer());
+        assert!(renderer.light_space == na::Matrix4::identity());
+        renderer.render_geometry(&model, frame.get_geometry_buffer());
+        gfx.present(frame);
+    }
+
+    #[test]
+    fn variance_shadow() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let primitive = model.primitives.push(Primitive::cube(Handle::none()));
+        let mesh = model.meshes.push(Mesh::new(vec![primitive]));
+        let camera = model.cameras.push(Camera::perspective(64.0, 64.0));
+        let sun = model.directional_lights.push(DirectionalLight::new());
+        let children = vec![
+            model.nodes.push(Node::builder().mesh(mesh).build()),
+            model.nodes.push(Node::builder().camera(camera).build()),
+            model
+                .nodes
+                .push(Node::builder().directional_light(sun).build()),
+        ];
+        let root = model.nodes.push(Node::builder().children(children).build());
+
+        let frame = gfx.next_frame();
+        let renderer = &mut gfx.renderer;
+        renderer.shadow_technique = ShadowTechnique::Variance;
+        renderer.draw(&model, root, &na::Matrix4::identity());
+        renderer.render_shadow(&model, frame.get_shadow_buffer());
+
+        // Moments are rendered with the same extent of the shadow buffer
+        let shadow = renderer.variance_shadow.as_ref().unwrap();
+        assert_eq!(
+            shadow.get_extent(),
+            frame.get_shadow_buffer().framebuffer.extent
+        );
+        assert_eq!(renderer.shadow_map, shadow.get_result().handle);
+
+        // Blurring must not wrap moments around the edges
+        let mut wrap = 0;
+        shadow.get_result().bind();
+        unsafe {
+            gl::GetTexParameteriv(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, &mut wrap);
+        }
+        assert_eq!(wrap, gl::CLAMP_TO_EDGE as i32);
+
+        renderer.render_geometry(&model, frame.get_geometry_buffer());
+        gfx.present(frame);
+    }
+
+    #[test]
+    fn last_scene_bounds() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let primitive = model.primitives.push(Primitive::cube(Handle::none()));
+        let mesh = model.meshes.push(Mesh::new(vec![primitive]));
+        let camera = model.cameras.push(Camera::perspective(64.0, 64.0));
+        let mut children = vec![model.nodes.push(Node::builder().camera(camera).build())];
+        for &x in [-2.0, 2.0].iter() {
+            let mut cube = Node::builder().mesh(mesh).build();
+            cube.trs.translate(x, 1.0, 0.0);
+            children.push(model.nodes.push(cube));
+        }
+        let root = model.nodes.push(Node::builder().children(children).build());
+
+        let expected = Aabb::new(
+            na::Vector3::new(-2.5, 0.5, -0.5),
+            na::Vector3::new(2.5, 1.5, 0.5),
+        );
+        assert_eq!(model.scene_aabb(root), Some(expected));
+
+        let frame = gfx.next_frame();
+        gfx.renderer.render(&model, root, &frame);
+        let bounds = gfx.renderer.last_scene_bounds();
+        assert_eq!(bounds.aabb, Some(expected));
+        assert!(bounds.frustum_corners.is_some());
+        gfx.present(frame);
+    }
+
+    #[test]
+    fn debug_draw_aabbs() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let primitive = model.primitives.push(Primitive::quad(Handle::none()));
+        let mesh = model.meshes.push(Mesh::new(vec![primitive]));
+        let left = model.nodes.push(
+            Node::builder()
+                .mesh(mesh)
+                .translation(na::Translation3::new(-1.0, 0.0, 0.0))
+                .build(),
+        );
+        let right = model.nodes.push(
+            Node::builder()
+                .mesh(mesh)
+                .translation(na::Translation3::new(1.0, 0.0, 0.0))
+                .build(),
+        );
+        let root = model
+            .nodes
+            .push(Node::builder().children(vec![left, right]).build());
+
+        let renderer = &mut gfx.renderer;
+        renderer.draw(&model, root, &na::Matrix4::identity());
+        assert!(renderer.debug_draw.lines.is_empty());
+        renderer.clear_draw_state();
+
+        // A box of 12 edges, two vertices each, for every drawn node
+        renderer.debug_draw_aabbs = true;
+        renderer.draw(&model, root, &na::Matrix4::identity());
+        assert_eq!(renderer.debug_draw.lines.len(), 2 * 12 * 2);
+        renderer.clear_draw_state();
+    }
+
+    #[test]
+    fn frustum_culling() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+
+        let mut model = Model::new();
+        let primitive = model.primitives.push(Primitive::cube(Handle::none()));
+        let mesh = model.meshes.push(Mesh::new(vec![primitive]));
+        let camera = model.cameras.push(Camera::perspective(64.0, 64.0));
+        let mut children = vec![model.nodes.push(Node::builder().camera(camera).build())];
+        // The camera looks towards -Z, hence the second cube is behind it
+        for &z in [-4.0, 4.0].iter() {
+            let mut cube = Node::builder().mesh(mesh).build();
+            cube.trs.translate(0.0, 0.0, z);
+            children.push(model.nodes.push(cube));
+        }
+        let root = model.nodes.push(Node::builder().children(children).build());
+
+        let frame = gfx.next_frame();
+        gfx.renderer.render(&model, root, &frame);
+        assert_eq!(gfx.renderer.get_culled_count(), 1);
+
+        gfx.renderer.frustum_culling = false;
+        gfx.renderer.render(&model, root, &frame);
+        assert_eq!(gfx.renderer.get_culled_count(), 0);
+        gfx.present(frame);
+    }
+
+    #[test]
+    fn tonemap_operators() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+        gfx.set_clear_color([1.0, 1.0, 1.0, 1.0]);
+
+        let model = Model::new();
+        let frame = gfx.next_frame();
+        let renderer = &mut gfx.renderer;
+        let geometry_buffer = frame.get_geometry_buffer();
+        renderer.render_geometry(&model, geometry_buffer);
+
+        let target = CustomFramebuffer::color(geometry_buffer.color_textures[0].extent);
+        let mut blit = |tonemap: ToneMap| {
+            renderer.tonemap = tonemap;
+            renderer.blit_color(geometry_buffer, &target);
+            let (_, pixels) = target.read_color(0);
+            pixels[0] as i32
+        };
+
+        // White is mapped to 0.5 by Reinhard and to about 0.8 by ACES, then gamma corrected
+        let reinhard = blit(ToneMap::Reinhard);
+        let aces = blit(ToneMap::Aces);
+        assert_ne!(reinhard, aces);
+        let expected = |mapped: f32| (mapped.powf(1.0 / 2.2) * 255.0) as i32;
+        assert!((reinhard - expected(0.5)).abs() <= 1);
+        assert!((aces - expected(2.54 / 3.16)).abs() <= 1);
+        gfx.present(frame);
+    }
+
+    #[test]
+    fn shared_clear_color() {
+        let mut context = TestContext::new();
+        let gfx = &mut context.gfx;
+        gfx.set_clear_color([1.0, 0.0, 1.0, 1.0]);
+        assert_eq!(gfx.renderer.get_clear_color(), [1.0, 0.0, 1.0, 1.0]);
+
+        let model = Model::new();
+        let frame = gfx.next_frame();
+        let renderer = &mut gfx.renderer;
+        let geometry_buffer = frame.get_geometry_buffer();
+        renderer.render_geometry(&model, geometry_buffer);
+        let (_, pixels) = geometry_buffer.read_color(0);
+        assert!(pixels.chunks(4).all(|pixel| pixel == [255, 0, 255, 255]));
+
+        // Blits cover their target, so look at the clear value they used
+        let clear_value = || {
+            let mut color = [0.0; 4];
+            unsafe { gl::GetFloatv(gl::COLOR_CLEAR_VALUE, color.as_mut_ptr()) };
+            color
+        };
+        unsafe { gl::ClearColor(0.0, 0.0, 0.0, 0.0) };
+        renderer.blit_color(geometry_buffer, &frame.default_framebuffer);
+        assert_eq!(clear_value(), [1.0, 0.0, 1.0, 1.0]);
+
+        unsafe { gl::ClearColor(0.0, 0.0, 0.0, 0.0) };
+        renderer.blit_depth(geometry_buffer, &frame.default_framebuffer);
+        assert_eq!(clear_value(), [1.0, 0.0, 1.0, 1.0]);
+        gfx.present(frame);
+    }
+}
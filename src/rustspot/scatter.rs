@@ -0,0 +1,117 @@
+// Copyright © 2021
+// Author: Antonio Caggiano <info@antoniocaggiano.eu>
+// SPDX-License-Identifier: MIT
+
+use nalgebra as na;
+use noise::{NoiseFn, Perlin, Seedable};
+use rayon::prelude::*;
+
+/// Places instances on a grid covering a square area centered at the origin, each one
+/// slightly moved from its cell by noise. The resulting transforms can be assigned to
+/// the `transforms` of any node to draw its mesh many times, such as rocks or trees.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scatter {
+    /// Number of instances, laid on a grid of `sqrt(count)` columns
+    pub count: u32,
+    /// Side of the square area covered by the grid
+    pub area: f32,
+    /// How far an instance can be moved from the center of its cell
+    pub jitter: f32,
+    /// Seed of the noise, the same seed gives the same transforms
+    pub seed: u32,
+}
+
+impl Scatter {
+    pub fn new(count: u32, area: f32, jitter: f32, seed: u32) -> Self {
+        Self {
+            count,
+            area,
+            jitter,
+            seed,
+        }
+    }
+
+    /// Returns the number of columns of the grid
+    pub fn get_stride(&self) -> u32 {
+        std::cmp::max(1, (self.count as f32).sqrt() as u32)
+    }
+
+    /// Creates transform matrices for the instances on a flat ground
+    pub fn transforms(&self) -> Vec<na::Matrix4<f32>> {
+        self.transforms_with_height(|_, _| 0.0)
+    }
+
+    /// Creates transform matrices for the instances, where `height` returns the height of
+    /// the ground at normalized coordinates of the grid, `u` along X and `v` along Z.
+    /// Height is sampled at the cell, so that jitter does not make neighbours jump up and down.
+    pub fn transforms_with_height<F>(&self, height: F) -> Vec<na::Matrix4<f32>>
+    where
+        F: Fn(f32, f32) -> f32 + Sync,
+    {
+        let stride = self.get_stride();
+        let spread = self.area / stride as f32;
+        // Center of the first cell
+        let instance_offset = na::Vector3::new(spread / 2.0, 0.0, spread / 2.0);
+        // Used to put center of grid in origin
+        let cell_offset = -na::Vector3::new(stride as f32 / 2.0, 0.0, stride as f32 / 2.0);
+
+        let perlin = Perlin::new().set_seed(self.seed);
+        let last = std::cmp::max(1, stride - 1) as f32;
+
+        (0..self.count)
+            .into_par_iter()
+            .map(|i| {
+                let column = i % stride;
+                let row = i / stride;
+
+                // [-1.0, 1.0]
+                let random_x = perlin.get([228.24 * (i as f64), 654.56 * (i as f64)]);
+                let random_z = perlin.get([310.85 * (i as f64), 142.98 * (i as f64)]);
+                let random_offset =
+                    self.jitter * na::Vector3::new(random_x as f32, 0.0, random_z as f32);
+
+                let mut translation = spread
+                    * (na::Vector3::new(column as f32, 0.0, row as f32) + cell_offset)
+                    + random_offset
+                    + instance_offset;
+                translation.y = height(column as f32 / last, row as f32 / last);
+
+                na::Matrix4::identity().append_translation(&translation)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deterministic() {
+        let scatter = Scatter::new(64, 8.0, 0.5, 42);
+        let transforms = scatter.transforms();
+        assert_eq!(transforms.len(), 64);
+        assert_eq!(transforms, scatter.transforms());
+        assert_eq!(transforms, Scatter::new(64, 8.0, 0.5, 42).transforms());
+
+        // Instances stay within their cell, with jitter
+        for transform in &transforms {
+            let translation = transform.column(3).xyz();
+            assert!(translation.x.abs() <= 4.0 + 0.5);
+            assert!(translation.z.abs() <= 4.0 + 0.5);
+            assert_eq!(translation.y, 0.0);
+        }
+
+        // Without jitter, instances are at the center of their cells
+        let grid = Scatter::new(4, 2.0, 0.0, 42).transforms();
+        assert_eq!(grid[0].column(3).xyz(), na::Vector3::new(-0.5, 0.0, -0.5));
+        assert_eq!(grid[3].column(3).xyz(), na::Vector3::new(0.5, 0.0, 0.5));
+    }
+
+    #[test]
+    fn seed_changes_jitter() {
+        let a = Scatter::new(16, 4.0, 1.0, 1).transforms();
+        let b = Scatter::new(16, 4.0, 1.0, 2).transforms();
+        assert_ne!(a, b);
+    }
+}
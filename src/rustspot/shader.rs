@@ -1,6 +1,7 @@
 use crate::*;
 use nalgebra as na;
 use std::any::Any;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::{ffi::CString, fs::File, io::Read, path::Path};
 
@@ -9,7 +10,7 @@ pub struct Shader {
 }
 
 impl Shader {
-    pub fn new(shader_type: gl::types::GLenum, src: &[u8]) -> Option<Shader> {
+    pub fn new(shader_type: gl::types::GLenum, src: &[u8]) -> Result<Shader, RustspotError> {
         unsafe {
             let version = if cfg!(feature = "gles") {
                 "#version 320 es\n"
@@ -43,11 +44,11 @@ impl Shader {
                     log.as_mut_ptr() as *mut gl::types::GLchar,
                 );
                 log.set_len(ilen as usize);
-                let message = CString::from(log);
-                println!("Compilation failed: {}", message.to_str().unwrap());
-                None
+                let message = CString::from(log).to_string_lossy().to_string();
+                log::error!("Compilation failed: {}", message);
+                Err(RustspotError::ShaderCompile(message))
             } else {
-                Some(Shader { handle })
+                Ok(Shader { handle })
             }
         }
     }
@@ -120,24 +121,84 @@ impl Loc {
     }
 }
 
+/// Uniform locations looked up by name, so that each name is queried only once
+#[derive(Default)]
+struct UniformCache {
+    locations: RefCell<HashMap<String, i32>>,
+}
+
+impl UniformCache {
+    fn get<F: FnOnce(&str) -> i32>(&self, name: &str, query: F) -> i32 {
+        if let Some(&location) = self.locations.borrow().get(name) {
+            return location;
+        }
+        let location = query(name);
+        self.locations
+            .borrow_mut()
+            .insert(name.to_string(), location);
+        location
+    }
+}
+
 pub struct ShaderProgram {
     handle: u32,
     pub loc: Loc,
+    uniform_cache: UniformCache,
 }
 
 impl ShaderProgram {
-    pub fn new(vert: Shader, frag: Shader) -> ShaderProgram {
+    pub fn new(vert: Shader, frag: Shader) -> Result<ShaderProgram, RustspotError> {
+        ShaderProgram::link(&[&vert, &frag])
+    }
+
+    /// Returns a new shader program with a geometry stage between vertex and fragment shaders.
+    /// Geometry shaders are not available on GLES.
+    #[cfg(not(feature = "gles"))]
+    pub fn with_geometry(
+        vert: Shader,
+        geom: Shader,
+        frag: Shader,
+    ) -> Result<ShaderProgram, RustspotError> {
+        ShaderProgram::link(&[&vert, &geom, &frag])
+    }
+
+    fn link(shaders: &[&Shader]) -> Result<ShaderProgram, RustspotError> {
         let handle = unsafe { gl::CreateProgram() };
 
         unsafe {
-            gl::AttachShader(handle, vert.handle);
-            gl::AttachShader(handle, frag.handle);
+            for shader in shaders {
+                gl::AttachShader(handle, shader.handle);
+            }
             gl::LinkProgram(handle);
+
+            // Check error linking program
+            let mut success = gl::FALSE as gl::types::GLint;
+            gl::GetProgramiv(handle, gl::LINK_STATUS, &mut success);
+
+            if success != gl::TRUE as gl::types::GLint {
+                let length = 512;
+                let mut log = vec![0u8; length];
+                let mut ilen = 0;
+                gl::GetProgramInfoLog(
+                    handle,
+                    length as i32 - 1,
+                    &mut ilen,
+                    log.as_mut_ptr() as *mut gl::types::GLchar,
+                );
+                log.truncate(ilen as usize);
+                let message = String::from_utf8_lossy(&log).to_string();
+                gl::DeleteProgram(handle);
+                return Err(RustspotError::ShaderLink(message));
+            }
         }
 
         let loc = Loc::new(handle);
 
-        ShaderProgram { handle, loc }
+        Ok(ShaderProgram {
+            handle,
+            loc,
+            uniform_cache: UniformCache::default(),
+        })
     }
 
     /// Returns a new shader program by loading vertex and fragment shaders files
@@ -149,24 +210,59 @@ impl ShaderProgram {
         let frag_str = frag.as_ref().to_string_lossy().to_string();
 
         File::open(vert)
-            .expect(&format!("Failed to open vertex file {}", vert_str))
+            .unwrap_or_else(|_| panic!("Failed to open vertex file {}", vert_str))
             .read_to_end(&mut vert_src)
             .expect("Failed reading vertex file");
         File::open(frag)
-            .expect(&format!("Failed to open fragment file {}", frag_str))
+            .unwrap_or_else(|_| panic!("Failed to open fragment file {}", frag_str))
             .read_to_end(&mut frag_src)
             .expect("Failed reading fragment file");
 
         let vert = Shader::new(gl::VERTEX_SHADER, &vert_src)
-            .expect(&format!("Failed creating shader {}", vert_str));
+            .unwrap_or_else(|err| panic!("Failed creating shader {}: {}", vert_str, err));
         let frag = Shader::new(gl::FRAGMENT_SHADER, &frag_src)
-            .expect(&format!("Failed creating shader {}", frag_str));
+            .unwrap_or_else(|err| panic!("Failed creating shader {}: {}", frag_str, err));
 
-        ShaderProgram::new(vert, frag)
+        ShaderProgram::new(vert, frag).unwrap_or_else(|err| {
+            panic!("Failed linking program {} {}: {}", vert_str, frag_str, err)
+        })
     }
 
+    /// Returns the location of a uniform, querying GL only the first time a name is seen.
+    /// Prefer the generated `Loc` structs for uniforms known at build time.
     pub fn get_uniform_location(&self, name: &str) -> i32 {
-        Loc::get_uniform_location(self.handle, name)
+        let handle = self.handle;
+        self.uniform_cache
+            .get(name, |name| Loc::get_uniform_location(handle, name))
+    }
+
+    /// Uploads a float uniform by name, for uniforms unknown at build time.
+    /// The program should be enabled, and missing uniforms are ignored.
+    pub fn set_uniform_f32(&self, name: &str, value: f32) {
+        unsafe { gl::Uniform1f(self.get_uniform_location(name), value) };
+    }
+
+    /// Uploads a vec3 uniform by name, see `set_uniform_f32`
+    pub fn set_uniform_vec3(&self, name: &str, value: &na::Vector3<f32>) {
+        unsafe { gl::Uniform3fv(self.get_uniform_location(name), 1, value.as_ptr()) };
+    }
+
+    /// Uploads a mat4 uniform by name, see `set_uniform_f32`
+    pub fn set_uniform_mat4(&self, name: &str, value: &na::Matrix4<f32>) {
+        let location = self.get_uniform_location(name);
+        unsafe { gl::UniformMatrix4fv(location, 1, gl::FALSE, value.as_ptr()) };
+    }
+
+    /// Associates a uniform block of this program to a binding point.
+    /// Nothing happens if the program does not use the block.
+    pub fn bind_uniform_block(&self, name: &str, binding: u32) {
+        let name = CString::new(name).expect("Failed converting Rust name to C string");
+        unsafe {
+            let index = gl::GetUniformBlockIndex(self.handle, name.as_ptr());
+            if index != gl::INVALID_INDEX {
+                gl::UniformBlockBinding(self.handle, index, binding);
+            }
+        }
     }
 
     pub fn enable(&self) {
@@ -184,20 +280,135 @@ pub trait CustomShader {
     fn as_any(&self) -> &dyn Any;
 
     fn bind(&self);
-    fn bind_time(&self, delta: f32) {}
-    fn bind_extent(&self, width: f32, height: f32) {}
-    fn bind_sun(&self, light_color: &[f32; 3], light_node: &Node, light_space: &na::Matrix4<f32>) {}
-    fn bind_shadow(&self, shadow_map: u32) {}
-    fn bind_camera(&self, camera: &Camera, camera_node: &Node) {}
+    fn bind_time(&self, _delta: f32) {}
+    fn bind_wind(&self, _wind: &Wind) {}
+    fn bind_extent(&self, _width: f32, _height: f32) {}
+    fn bind_sun(&self, _lights: &LightsBlock) {}
+    fn bind_shadow(&self, _shadow_map: u32) {}
+    fn bind_point_light(&self, _light: Option<(&PointLight, &Node)>, _shadow_map: u32) {}
+    fn bind_spot_lights(&self, _lights: &[(&SpotLight, &Node)]) {}
+    fn bind_fog(&self, _fog: Option<&Fog>) {}
+    fn bind_camera(&self, _camera: &Camera, _camera_node: &Node) {}
     fn bind_material(
         &self,
-        textures: &Pack<Texture>,
-        colors: &HashMap<Color, Texture>,
-        material: &Material,
+        _units: &mut TextureUnits,
+        _textures: &Pack<Texture>,
+        _colors: &HashMap<Color, Handle<Texture>>,
+        _material: &Material,
     ) {
     }
-    fn bind_primitive(&self, primitive: &Primitive) {}
-    fn bind_node(&self, node: &Node, transform: &na::Matrix4<f32>) {}
+    fn bind_primitive(&self, _primitive: &Primitive) {}
+    fn bind_node(&self, _node: &Node, _transform: &na::Matrix4<f32>) {}
+    fn bind_node_id(&self, _id: usize) {}
+    fn bind_joints(&self, _joint_matrices: &[na::Matrix4<f32>]) {}
 
     fn draw(&self, node: &Node, primitive: &Primitive);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cached_location() {
+        let cache = UniformCache::default();
+        let mut queries = 0;
+        let mut query = |_: &str| {
+            queries += 1;
+            3
+        };
+
+        assert_eq!(cache.get("time", &mut query), 3);
+        assert_eq!(cache.get("time", &mut query), 3);
+        assert_eq!(queries, 1);
+
+        // Locations of missing uniforms are cached as well
+        assert_eq!(cache.get("missing", |_| -1), -1);
+        assert_eq!(cache.get("missing", |_| 0), -1);
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn custom_uniforms() {
+        let _context = TestContext::new();
+
+        let vert = Shader::new(
+            gl::VERTEX_SHADER,
+            b"layout (location = 0) in vec3 in_pos;
+            uniform mat4 transform;
+            void main() { gl_Position = transform * vec4(in_pos, 1.0); }",
+        )
+        .unwrap();
+        let frag = Shader::new(
+            gl::FRAGMENT_SHADER,
+            b"precision mediump float;
+            uniform float intensity;
+            uniform vec3 tint;
+            out vec4 out_color;
+            void main() { out_color = vec4(tint * intensity, 1.0); }",
+        )
+        .unwrap();
+        let program = ShaderProgram::new(vert, frag).unwrap();
+        program.enable();
+
+        program.set_uniform_f32("intensity", 0.5);
+        program.set_uniform_vec3("tint", &na::Vector3::new(1.0, 2.0, 3.0));
+        program.set_uniform_mat4("transform", &na::Matrix4::new_scaling(2.0));
+        // Nothing happens for missing uniforms
+        program.set_uniform_f32("missing", 1.0);
+
+        let mut intensity = 0.0;
+        let mut tint = [0.0f32; 3];
+        let mut transform = [0.0f32; 16];
+        unsafe {
+            let location = program.get_uniform_location("intensity");
+            gl::GetUniformfv(program.handle, location, &mut intensity);
+            let location = program.get_uniform_location("tint");
+            gl::GetUniformfv(program.handle, location, tint.as_mut_ptr());
+            let location = program.get_uniform_location("transform");
+            gl::GetUniformfv(program.handle, location, transform.as_mut_ptr());
+            assert_eq!(gl::GetError(), gl::NO_ERROR);
+        }
+        assert_eq!(intensity, 0.5);
+        assert_eq!(tint, [1.0, 2.0, 3.0]);
+        assert_eq!(transform[0], 2.0);
+        assert_eq!(transform[15], 1.0);
+    }
+
+    #[test]
+    #[cfg(all(feature = "headless", not(feature = "gles")))]
+    fn pass_through_geometry() {
+        let _context = TestContext::new();
+
+        let vert = Shader::new(
+            gl::VERTEX_SHADER,
+            b"layout (location = 0) in vec3 in_pos;
+            void main() { gl_Position = vec4(in_pos, 1.0); }",
+        )
+        .unwrap();
+        let geom = Shader::new(
+            gl::GEOMETRY_SHADER,
+            b"layout (triangles) in;
+            layout (triangle_strip, max_vertices = 3) out;
+            void main() {
+                for (int i = 0; i < 3; ++i) {
+                    gl_Position = gl_in[i].gl_Position;
+                    EmitVertex();
+                }
+                EndPrimitive();
+            }",
+        )
+        .unwrap();
+        let frag = Shader::new(
+            gl::FRAGMENT_SHADER,
+            b"out vec4 out_color;
+            void main() { out_color = vec4(1.0); }",
+        )
+        .unwrap();
+
+        let program = ShaderProgram::with_geometry(vert, geom, frag).unwrap();
+        let mut shader_count = 0;
+        unsafe { gl::GetProgramiv(program.handle, gl::ATTACHED_SHADERS, &mut shader_count) };
+        assert_eq!(shader_count, 3);
+    }
+}
@@ -0,0 +1,178 @@
+// Copyright © 2021
+// Author: Antonio Caggiano <info@antoniocaggiano.eu>
+// SPDX-License-Identifier: MIT
+
+use nalgebra as na;
+
+use super::*;
+
+/// How the shadow map of the directional light is rendered and sampled
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ShadowTechnique {
+    /// Depth compared with a few samples around the fragment, which aliases at the edges
+    #[default]
+    Depth,
+    /// Moments of depth blurred and sampled with Chebyshev's inequality, resulting in softer edges
+    Variance,
+}
+
+/// Returns the PBR shader sampling a variance shadow map in place of the one sampling depth.
+/// Other shaders are returned as they are.
+pub fn get_variance_shader(shader: Shaders) -> Shaders {
+    for occlusion in PBR_VARIANTS.iter() {
+        for metallic_roughness in occlusion.iter() {
+            for normal in metallic_roughness.iter() {
+                if normal[PbrShadowVariant::Texture as usize] == shader {
+                    return normal[PbrShadowVariant::Variance as usize];
+                }
+            }
+        }
+    }
+    shader
+}
+
+/// Returns the variant of a depth shader, such as `DEPTH_VARIANTS`, which can draw a node
+/// whether it is skinned or not
+pub fn get_skin_variant(variants: &[Shaders; 2], skinned: bool) -> Shaders {
+    let variant = if skinned {
+        DepthSkinVariant::Joints
+    } else {
+        DepthSkinVariant::Default
+    };
+    variants[variant as usize]
+}
+
+/// Moves the projection of a light `camera` so that the world origin falls on a texel of a
+/// shadow map with `extent`, and returns the resulting light-space matrix. Moving the light
+/// by less than a texel then gives the same matrix, instead of making shadow edges shimmer.
+pub fn snap_to_texel(
+    camera: &mut Camera,
+    view: &na::Matrix4<f32>,
+    extent: Extent2D,
+) -> na::Matrix4<f32> {
+    let mut light_space = camera.proj * view;
+
+    // Where the origin is projected, orthographic projections keep w at 1
+    let half = na::Vector2::new(extent.width as f32, extent.height as f32) / 2.0;
+    let origin = na::Vector2::new(light_space[(0, 3)], light_space[(1, 3)]);
+    let snapped = origin
+        .component_mul(&half)
+        .map(f32::round)
+        .component_div(&half);
+
+    let offset = snapped - origin;
+    camera.proj[(0, 3)] += offset.x;
+    camera.proj[(1, 3)] += offset.y;
+    light_space[(0, 3)] = snapped.x;
+    light_space[(1, 3)] = snapped.y;
+    light_space
+}
+
+/// Ping-pong framebuffers of a variance shadow map. Moments of depth are rendered into
+/// the first one, then blurred through the second one and back. The result is found in the first.
+pub struct VarianceShadow {
+    pub buffers: [CustomFramebuffer; 2],
+    /// Gaussian blur with high precision, which moments need
+    pub blur_program: ShaderProgram,
+}
+
+impl VarianceShadow {
+    pub fn new(extent: Extent2D) -> Self {
+        Self {
+            buffers: [
+                CustomFramebuffer::moments(extent, true),
+                CustomFramebuffer::moments(extent, false),
+            ],
+            blur_program: ShaderProgram::open(
+                "res/shader/unlit.vert.glsl",
+                "res/shader/variance-blur.frag.glsl",
+            ),
+        }
+    }
+
+    pub fn get_extent(&self) -> Extent2D {
+        self.buffers[0].framebuffer.extent
+    }
+
+    /// Returns the texture with the blurred moments
+    pub fn get_result(&self) -> &Texture {
+        &self.buffers[0].color_textures[0]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn variance_shader() {
+        let depth = PBR_VARIANTS[0][0][0][PbrShadowVariant::Texture as usize];
+        let variance = PBR_VARIANTS[0][0][0][PbrShadowVariant::Variance as usize];
+        assert!(get_variance_shader(depth) == variance);
+        assert!(get_variance_shader(Shaders::Unlit) == Shaders::Unlit);
+    }
+
+    #[test]
+    fn skin_variant() {
+        assert!(get_skin_variant(&DEPTH_VARIANTS, false) == Shaders::DepthSkinDefault);
+        assert!(get_skin_variant(&DEPTH_VARIANTS, true) == Shaders::DepthSkinJoints);
+        let skinned = get_skin_variant(&VARIANCE_SHADOW_VARIANTS, true);
+        assert!(skinned == Shaders::VarianceShadowSkinJoints);
+
+        // Does not compile if skinned depth shaders lack the joints
+        let _ = |loc: &DepthSkinJointsLoc| loc.joint_matrices;
+        let _ = |loc: &VarianceShadowSkinJointsLoc| loc.joint_matrices;
+    }
+
+    #[test]
+    fn texel_snapping() {
+        let extent = Extent2D::new(64, 64);
+        let view = |x: f32| {
+            let mut trs = Trs::new();
+            trs.set_translation(x, 0.0, 0.0);
+            trs.get_view()
+        };
+        let light_space = |x: f32| {
+            let mut camera = Camera::orthographic(8, 8, 0.1, 100.0);
+            snap_to_texel(&mut camera, &view(x), extent)
+        };
+
+        // A texel of the shadow map covers 8 / 64 units of the world
+        assert_eq!(light_space(0.3), light_space(0.301));
+        assert_eq!(light_space(0.3), light_space(0.31));
+        assert_ne!(light_space(0.3), light_space(0.3 + 0.125));
+
+        // The projection is moved accordingly
+        let mut camera = Camera::orthographic(8, 8, 0.1, 100.0);
+        let snapped = snap_to_texel(&mut camera, &view(0.3), extent);
+        assert!((camera.proj * view(0.3) - snapped).norm() < 1e-6);
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn float_rg_attachment() {
+        let _context = TestContext::new();
+
+        let shadow = VarianceShadow::new(Extent2D::new(32, 32));
+        assert!(shadow.buffers[0].depth_texture.is_some());
+        assert!(shadow.buffers[1].depth_texture.is_none());
+
+        let texture = shadow.get_result();
+        let mut internal_format = 0;
+        texture.bind();
+        unsafe {
+            gl::GetTexLevelParameteriv(
+                gl::TEXTURE_2D,
+                0,
+                gl::TEXTURE_INTERNAL_FORMAT,
+                &mut internal_format,
+            );
+        }
+        texture.unbind();
+        assert_eq!(internal_format as u32, gl::RG32F);
+
+        shadow.buffers[0].framebuffer.bind();
+        let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+        assert_eq!(status, gl::FRAMEBUFFER_COMPLETE);
+    }
+}
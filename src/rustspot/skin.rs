@@ -0,0 +1,80 @@
+// Copyright © 2021
+// Author: Antonio Caggiano <info@antoniocaggiano.eu>
+// SPDX-License-Identifier: MIT
+
+use std::collections::HashMap;
+
+use crate::*;
+use nalgebra as na;
+
+/// Size of the joint matrix array of skinning shaders
+pub const MAX_JOINTS: usize = 32;
+
+/// Joints deforming the vertices of a mesh, which refer to them by index
+pub struct Skin {
+    pub joints: Vec<Handle<Node>>,
+    /// Transform vertices into the space of their joints, one for each joint
+    pub inverse_bind_matrices: Vec<na::Matrix4<f32>>,
+}
+
+impl Skin {
+    pub fn new(joints: Vec<Handle<Node>>) -> Self {
+        let inverse_bind_matrices = vec![na::Matrix4::identity(); joints.len()];
+        Self {
+            joints,
+            inverse_bind_matrices,
+        }
+    }
+
+    /// Returns the matrices to upload to shaders, relative to the node of the mesh so that
+    /// its own transform is still applied. `transforms` are the world transforms of the nodes
+    /// by id, and joints without one fall back to the identity. Joints beyond `MAX_JOINTS`
+    /// are ignored.
+    pub fn get_joint_matrices(
+        &self,
+        transform: &na::Matrix4<f32>,
+        transforms: &HashMap<usize, na::Matrix4<f32>>,
+    ) -> Vec<na::Matrix4<f32>> {
+        let inverse = transform
+            .try_inverse()
+            .unwrap_or_else(na::Matrix4::identity);
+
+        self.joints
+            .iter()
+            .zip(self.inverse_bind_matrices.iter())
+            .take(MAX_JOINTS)
+            .map(|(joint, inverse_bind)| {
+                let joint_transform = transforms
+                    .get(&joint.id)
+                    .copied()
+                    .unwrap_or_else(na::Matrix4::identity);
+                inverse * joint_transform * inverse_bind
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn joint_matrices() {
+        let mut skin = Skin::new(vec![Handle::new(1), Handle::new(2)]);
+        // Second joint is bound one unit up
+        skin.inverse_bind_matrices[1] = na::Matrix4::new_translation(&-na::Vector3::y());
+
+        let mesh_transform = na::Matrix4::new_translation(&na::Vector3::x());
+        let mut transforms = HashMap::new();
+        transforms.insert(1, mesh_transform);
+        // Moved one more unit up
+        transforms.insert(
+            2,
+            mesh_transform * na::Matrix4::new_translation(&na::Vector3::new(0.0, 2.0, 0.0)),
+        );
+
+        let matrices = skin.get_joint_matrices(&mesh_transform, &transforms);
+        assert_eq!(matrices[0], na::Matrix4::identity());
+        assert_eq!(matrices[1], na::Matrix4::new_translation(&na::Vector3::y()));
+    }
+}
@@ -83,3 +83,9 @@ impl Sky {
         self.primitive.draw();
     }
 }
+
+impl Default for Sky {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,253 @@
+// Copyright © 2021
+// Author: Antonio Caggiano <info@antoniocaggiano.eu>
+// SPDX-License-Identifier: MIT
+
+use super::*;
+use nalgebra as na;
+
+/// Vertices of all the sprites using the same texture
+pub(crate) struct Batch {
+    pub texture: Handle<Texture>,
+    pub vertices: Vec<Vertex>,
+}
+
+/// Pipeline state changed by drawing sprites, restored afterwards
+struct BlendState {
+    blend: bool,
+    cull_face: bool,
+    depth_test: bool,
+    scissor_test: bool,
+    /// Source and destination factors of RGB and alpha
+    func: [i32; 4],
+    equation: [i32; 2],
+}
+
+impl BlendState {
+    fn save() -> Self {
+        let mut func = [0; 4];
+        let mut equation = [0; 2];
+        unsafe {
+            gl::GetIntegerv(gl::BLEND_SRC_RGB, &mut func[0]);
+            gl::GetIntegerv(gl::BLEND_DST_RGB, &mut func[1]);
+            gl::GetIntegerv(gl::BLEND_SRC_ALPHA, &mut func[2]);
+            gl::GetIntegerv(gl::BLEND_DST_ALPHA, &mut func[3]);
+            gl::GetIntegerv(gl::BLEND_EQUATION_RGB, &mut equation[0]);
+            gl::GetIntegerv(gl::BLEND_EQUATION_ALPHA, &mut equation[1]);
+
+            Self {
+                blend: gl::IsEnabled(gl::BLEND) == gl::TRUE,
+                cull_face: gl::IsEnabled(gl::CULL_FACE) == gl::TRUE,
+                depth_test: gl::IsEnabled(gl::DEPTH_TEST) == gl::TRUE,
+                scissor_test: gl::IsEnabled(gl::SCISSOR_TEST) == gl::TRUE,
+                func,
+                equation,
+            }
+        }
+    }
+
+    fn restore(&self) {
+        let set = |capability, enabled| unsafe {
+            if enabled {
+                gl::Enable(capability);
+            } else {
+                gl::Disable(capability);
+            }
+        };
+        set(gl::BLEND, self.blend);
+        set(gl::CULL_FACE, self.cull_face);
+        set(gl::DEPTH_TEST, self.depth_test);
+        set(gl::SCISSOR_TEST, self.scissor_test);
+
+        let [src_rgb, dst_rgb, src_alpha, dst_alpha] = self.func;
+        unsafe {
+            gl::BlendFuncSeparate(src_rgb as _, dst_rgb as _, src_alpha as _, dst_alpha as _);
+            gl::BlendEquationSeparate(self.equation[0] as _, self.equation[1] as _);
+        }
+    }
+}
+
+/// Collects textured quads at pixel coordinates and draws them with an orthographic camera,
+/// one draw call for each texture. Pixel coordinates start from the top-left corner.
+pub struct SpriteBatch {
+    pub camera: Camera,
+    camera_node: Node,
+    extent: Extent2D,
+    pub(crate) batches: Vec<Batch>,
+    /// Created on first draw
+    mesh_res: Option<MeshRes>,
+}
+
+impl SpriteBatch {
+    pub fn new(extent: Extent2D) -> Self {
+        Self {
+            camera: Camera::orthographic(extent.width, extent.height, 0.1, 100.0),
+            camera_node: Node::new(),
+            extent,
+            batches: vec![],
+            mesh_res: None,
+        }
+    }
+
+    /// Call this when the target changes size, so that a pixel is still a pixel
+    pub fn set_extent(&mut self, extent: Extent2D) {
+        self.camera = Camera::orthographic(extent.width, extent.height, 0.1, 100.0);
+        self.extent = extent;
+    }
+
+    pub fn get_extent(&self) -> Extent2D {
+        self.extent
+    }
+
+    /// Adds a sprite with its top-left corner at `position` and the color multiplied by `tint`
+    pub fn sprite(
+        &mut self,
+        texture: Handle<Texture>,
+        position: [f32; 2],
+        size: [f32; 2],
+        tint: [f32; 3],
+    ) {
+        self.sprite_uv(texture, position, size, [0.0, 1.0, 1.0, 0.0], tint);
+    }
+
+    /// Adds a sprite showing only a region of the texture, such as a glyph of a font atlas.
+    /// `uv` are the texture coordinates of its top-left and bottom-right corners.
+    pub fn sprite_uv(
+        &mut self,
+        texture: Handle<Texture>,
+        position: [f32; 2],
+        size: [f32; 2],
+        uv: [f32; 4],
+        tint: [f32; 3],
+    ) {
+        let [u0, v0, u1, v1] = uv;
+
+        // From pixel coordinates to camera space, where the origin is at the center
+        let left = position[0] - self.extent.width as f32 / 2.0;
+        let top = self.extent.height as f32 / 2.0 - position[1];
+        let right = left + size[0];
+        let bottom = top - size[1];
+
+        // Same layout of `Primitive::quad` as triangles, as sprites do not share vertices
+        let mut quad = vec![Vertex::new(); 4];
+        quad[0].position = [left, bottom, -1.0];
+        quad[0].tex_coords = [u0, v1];
+        quad[1].position = [right, bottom, -1.0];
+        quad[1].tex_coords = [u1, v1];
+        quad[2].position = [right, top, -1.0];
+        quad[2].tex_coords = [u1, v0];
+        quad[3].position = [left, top, -1.0];
+        quad[3].tex_coords = [u0, v0];
+        for vertex in quad.iter_mut() {
+            vertex.color = tint;
+        }
+
+        let batch = match self.batches.iter_mut().find(|b| b.texture.id == texture.id) {
+            Some(batch) => batch,
+            None => {
+                self.batches.push(Batch {
+                    texture,
+                    vertices: vec![],
+                });
+                self.batches.last_mut().unwrap()
+            }
+        };
+
+        for &i in [0, 1, 2, 2, 3, 0].iter() {
+            batch.vertices.push(quad[i]);
+        }
+    }
+
+    /// Returns the number of draw calls needed for the sprites collected so far
+    pub fn get_batch_count(&self) -> usize {
+        self.batches.len()
+    }
+
+    /// Returns the number of sprites collected so far
+    pub fn get_sprite_count(&self) -> usize {
+        self.batches.iter().map(|b| b.vertices.len() / 6).sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.batches.clear();
+    }
+
+    /// Draws all the sprites on top of the target and clears the batch.
+    /// The blend and test state of the pipeline is left as it was.
+    pub fn draw<D: DrawableOnto>(
+        &mut self,
+        shader: &UnlitShader,
+        textures: &Pack<Texture>,
+        target: &D,
+    ) {
+        self.draw_with(shader, |handle| textures.get(handle).unwrap(), target);
+    }
+
+    /// Same as `draw`, with textures which are not in a pack, like the font of the GUI
+    pub(crate) fn draw_with<'t, D: DrawableOnto, F: Fn(Handle<Texture>) -> &'t Texture>(
+        &mut self,
+        shader: &UnlitShader,
+        get_texture: F,
+        target: &D,
+    ) {
+        target.get_framebuffer().bind();
+
+        let state = BlendState::save();
+        unsafe {
+            gl::Enable(gl::BLEND);
+            gl::BlendEquation(gl::FUNC_ADD);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            gl::Disable(gl::CULL_FACE);
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Disable(gl::SCISSOR_TEST);
+        }
+
+        if self.mesh_res.is_none() {
+            self.mesh_res = Some(MeshRes::from(&[], &vec![]));
+        }
+        let mesh_res = self.mesh_res.as_mut().unwrap();
+
+        shader.bind();
+        shader.bind_camera(&self.camera, &self.camera_node);
+        shader.bind_node(&self.camera_node, &na::Matrix4::identity());
+
+        mesh_res.vao.bind();
+
+        for batch in self.batches.iter() {
+            get_texture(batch.texture).bind();
+            mesh_res.vbo.upload(&batch.vertices);
+
+            unsafe {
+                gl::DrawArrays(gl::TRIANGLES, 0, batch.vertices.len() as _);
+            }
+        }
+
+        state.restore();
+        self.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn same_texture_one_batch() {
+        let mut batch = SpriteBatch::new(Extent2D::new(640, 480));
+        let texture = Handle::new(0);
+
+        batch.sprite(texture, [0.0, 0.0], [32.0, 32.0], [1.0, 1.0, 1.0]);
+        batch.sprite(texture, [64.0, 0.0], [32.0, 32.0], [1.0, 0.0, 0.0]);
+        assert_eq!(batch.get_batch_count(), 1);
+
+        // Top-left corner of the screen
+        assert_eq!(batch.batches[0].vertices[4].position, [-320.0, 240.0, -1.0]);
+        assert_eq!(batch.batches[0].vertices[4].tex_coords, [0.0, 1.0]);
+        assert_eq!(batch.get_sprite_count(), 2);
+
+        batch.sprite(Handle::new(1), [0.0, 0.0], [32.0, 32.0], [1.0, 1.0, 1.0]);
+        assert_eq!(batch.get_batch_count(), 2);
+
+        batch.clear();
+        assert_eq!(batch.get_batch_count(), 0);
+    }
+}
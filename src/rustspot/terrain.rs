@@ -4,10 +4,86 @@
 
 use super::*;
 use nalgebra as na;
-use noise::{NoiseFn, Perlin};
 use rayon::prelude::*;
 
 const INSTANCE_MAX: u32 = 4096 * 4096;
+/// How much the plane goes beyond the instances
+const PLANE_MARGIN: f32 = 2.0;
+/// Cells along each side of the plane, which follows the height of the terrain
+const PLANE_SEGMENTS: u16 = 32;
+
+/// Parameters of the wind swaying the blades of grass
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Wind {
+    /// How much the top of a blade bends
+    pub strength: f32,
+    /// How fast the wind pattern moves over time
+    pub frequency: f32,
+    /// Horizontal direction the wind blows towards
+    pub direction: na::Vector3<f32>,
+}
+
+impl Wind {
+    pub fn new(strength: f32, frequency: f32, direction: na::Vector3<f32>) -> Self {
+        Self {
+            strength,
+            frequency,
+            direction,
+        }
+    }
+}
+
+impl Default for Wind {
+    fn default() -> Self {
+        Self::new(1.0, 1.0 / 32.0, na::Vector3::new(1.0, 0.0, -1.0))
+    }
+}
+
+/// Grid of height values laid over the terrain, rows going along Z and columns along X
+#[derive(Clone, Debug)]
+pub struct Heightmap {
+    /// Values in `[0.0, 1.0]`, row by row
+    heights: Vec<f32>,
+    extent: Extent2D,
+    /// Height of the terrain where the value is `1.0`
+    pub max_height: f32,
+}
+
+impl Heightmap {
+    pub fn new(heights: &[f32], extent: Extent2D, max_height: f32) -> Self {
+        assert!(extent.width > 0 && extent.height > 0);
+        assert_eq!(heights.len(), (extent.width * extent.height) as usize);
+        Self {
+            heights: heights.to_vec(),
+            extent,
+            max_height,
+        }
+    }
+
+    /// Returns the height at normalized coordinates `u` along X and `v` along Z,
+    /// interpolating between the nearest values
+    pub fn sample(&self, u: f32, v: f32) -> f32 {
+        let x = u.clamp(0.0, 1.0) * (self.extent.width - 1) as f32;
+        let y = v.clamp(0.0, 1.0) * (self.extent.height - 1) as f32;
+
+        let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+        let x1 = std::cmp::min(x0 + 1, self.extent.width - 1);
+        let y1 = std::cmp::min(y0 + 1, self.extent.height - 1);
+        let (tx, ty) = (x.fract(), y.fract());
+
+        let get = |x: u32, y: u32| self.heights[(y * self.extent.width + x) as usize];
+        let top = get(x0, y0) * (1.0 - tx) + get(x1, y0) * tx;
+        let bottom = get(x0, y1) * (1.0 - tx) + get(x1, y1) * tx;
+
+        (top * (1.0 - ty) + bottom * ty) * self.max_height
+    }
+
+    /// Returns the average height of the terrain
+    pub fn get_mean_height(&self) -> f32 {
+        let sum: f32 = self.heights.iter().sum();
+        sum / self.heights.len() as f32 * self.max_height
+    }
+}
 
 pub struct Terrain {
     pub plane: Handle<Node>,
@@ -16,54 +92,32 @@ pub struct Terrain {
     // Can I calculate this from instances per unit?
     scale: f32,
     instances_per_unit: u32,
+    /// All the instances, whether visible or not
+    transforms: Vec<na::Matrix4<f32>>,
+    heightmap: Option<Heightmap>,
 }
 
 impl Terrain {
-    /// Create transform matrices for the instances
-    fn create_transforms(&mut self) -> Vec<na::Matrix4<f32>> {
+    /// Returns how instances are scattered over the terrain
+    pub fn get_scatter(&self) -> Scatter {
         let spread = 4.0 / self.instances_per_unit as f32;
-        // That is to center the instance I guess
-        let instance_offset = na::Vector3::new(spread / 2.0, 0.0, spread / 2.0);
-
-        let instance_count = self.get_instance_count();
-        let stride = (instance_count as f32).sqrt() as u32;
-        // Used to put center of grid in origin
-        let cell_offset = -na::Vector3::new(stride as f32 / 2.0, 0.0, stride as f32 / 2.0);
-
-        let perlin = Perlin::new();
-
-        let matrices = (0..instance_count)
-            .into_par_iter()
-            .map(|i| {
-                let column = i % stride;
-                let row = i / stride;
-
-                // [0.0, 1.0]
-                let random_x = perlin.get([228.24 * (i as f64), 654.56 * (i as f64)]);
-                let random_z = perlin.get([310.85 * (i as f64), 142.98 * (i as f64)]);
-
-                let random_weight = 1.0;
-                // This is to slightly offset the instance from its cell position, just for a bit of natural chaos
-                let random_offset =
-                    random_weight * na::Vector3::new(random_x as f32, 0.0, random_z as f32);
-
-                let translation = spread
-                    * (na::Vector3::new(column as f32, 0.0, row as f32) + cell_offset)
-                    + random_offset
-                    + instance_offset;
-
-                na::Matrix4::identity().append_translation(&translation)
-            })
-            .collect();
+        let count = self.get_instance_count();
+        let stride = (count as f32).sqrt() as u32;
+        Scatter::new(count, spread * stride as f32, 1.0, 0)
+    }
 
-        matrices
+    /// Create transform matrices for the instances
+    fn create_transforms(&mut self) -> Vec<na::Matrix4<f32>> {
+        let scatter = self.get_scatter();
+        match self.heightmap.as_ref() {
+            Some(heightmap) => scatter.transforms_with_height(|u, v| heightmap.sample(u, v)),
+            None => scatter.transforms(),
+        }
     }
 
     fn create_grass_blade(model: &mut Model) -> Handle<Node> {
         let color = Color::rgba(31, 100, 32, 255);
-        let texture = model
-            .textures
-            .push(Texture::builder().data(color.as_slice()).build().unwrap());
+        let texture = model.color_texture(color);
         let mut material = Material::builder().metallic(0.0).texture(texture).build();
         material.shader = Shaders::LightShadowGrass;
 
@@ -83,9 +137,7 @@ impl Terrain {
     fn create_plane(model: &mut Model) -> Handle<Node> {
         // Plane material
         let color = Color::rgba(31, 100, 32, 255);
-        let texture = model
-            .textures
-            .push(Texture::builder().data(color.as_slice()).build().unwrap());
+        let texture = model.color_texture(color);
         let material = Material::builder()
             .metallic(0.0)
             .shader(Shaders::LightShadow)
@@ -94,13 +146,15 @@ impl Terrain {
 
         let material = model.materials.push(material);
 
-        let primitives = vec![model.primitives.push(Primitive::quad(material))];
+        let primitive = Primitive::grid(material, PLANE_SEGMENTS);
+        let primitives = vec![model.primitives.push(primitive)];
         let mesh = model.meshes.push(Mesh::new(primitives));
 
         let mut plane = Node::new();
         plane.name = String::from("plane");
         plane.mesh = mesh;
-        plane.trs.scale(3.0, 3.0, 3.0);
+        let scale = 1.0 + PLANE_MARGIN;
+        plane.trs.scale(scale, scale, scale);
         plane.trs.rotate(&na::UnitQuaternion::from_axis_angle(
             &na::Vector3::x_axis(),
             -std::f32::consts::FRAC_PI_2,
@@ -118,35 +172,168 @@ impl Terrain {
         model.nodes.push(ground)
     }
 
-    pub fn new(model: &mut Model) -> Self {
-        let plane = Self::create_plane(model);
-        let grass = Self::create_grass_blade(model);
-
-        let mut ret = Self {
+    /// Creates a terrain without instances using existing nodes
+    fn with_nodes(plane: Handle<Node>, grass: Handle<Node>, root: Handle<Node>) -> Self {
+        Self {
             plane,
             grass,
-            root: Self::create_ground(model, plane, grass),
+            root,
             scale: 1.0,
             instances_per_unit: 16,
-        };
+            transforms: vec![],
+            heightmap: None,
+        }
+    }
+
+    pub fn new(model: &mut Model) -> Self {
+        let plane = Self::create_plane(model);
+        let grass = Self::create_grass_blade(model);
+        let root = Self::create_ground(model, plane, grass);
+
+        let mut ret = Self::with_nodes(plane, grass, root);
         ret.update_instance_count(model);
         ret
     }
 
+    /// Creates a terrain where instances are placed at the height sampled from `heights`.
+    /// Values are expected in `[0.0, 1.0]`, row by row, and are scaled by `max_height`.
+    pub fn from_heightmap(
+        model: &mut Model,
+        heights: &[f32],
+        extent: Extent2D,
+        max_height: f32,
+    ) -> Self {
+        let mut ret = Self::new(model);
+        ret.heightmap = Some(Heightmap::new(heights, extent, max_height));
+        ret.update_instance_count(model);
+        ret.update_plane_height(model);
+        ret
+    }
+
+    pub fn get_heightmap(&self) -> Option<&Heightmap> {
+        self.heightmap.as_ref()
+    }
+
+    /// Returns the height of the terrain at a point of the ground, with the same mapping
+    /// from the cells of the instances to the heightmap used by `create_transforms`
+    fn get_height(&self, x: f32, z: f32) -> f32 {
+        let heightmap = match self.heightmap.as_ref() {
+            Some(heightmap) => heightmap,
+            None => return 0.0,
+        };
+
+        let scatter = self.get_scatter();
+        let stride = scatter.get_stride() as f32;
+        let spread = scatter.area / stride;
+        let last = (stride - 1.0).max(1.0);
+        // Inverse of the center of a cell, from a position to its column and row
+        let cell = |p: f32| (p - spread / 2.0) / spread + stride / 2.0;
+        heightmap.sample(cell(x) / last, cell(z) / last)
+    }
+
+    /// Displaces the vertices of the plane by the height of the terrain beneath them,
+    /// so that instances stand on the plane wherever they are
+    fn update_plane_height(&mut self, model: &mut Model) {
+        let plane = model.nodes.get(self.plane).unwrap();
+        let mesh = model.meshes.get(plane.mesh).unwrap();
+        let primitive = model.primitives.get_mut(mesh.primitives[0]).unwrap();
+
+        // The plane lies on XY, rotated to XZ with Y towards -Z, and scaled uniformly
+        let scale = self.scale + PLANE_MARGIN;
+        let mut vertices = primitive.vertices.clone();
+        for vertex in vertices.iter_mut() {
+            let [x, y, _] = vertex.position;
+            vertex.position[2] = self.get_height(x * scale, -y * scale) / scale;
+        }
+
+        // Normals from the slope between neighbours, clamped at the borders
+        let side = PLANE_SEGMENTS as usize + 1;
+        let step = 2.0 / PLANE_SEGMENTS as f32;
+        let z = |row: usize, column: usize| vertices[row * side + column].position[2];
+        let normals: Vec<na::Vector3<f32>> = (0..vertices.len())
+            .map(|i| {
+                let (row, column) = (i / side, i % side);
+                let dx = z(row, (column + 1).min(side - 1)) - z(row, column.saturating_sub(1));
+                let dy = z((row + 1).min(side - 1), column) - z(row.saturating_sub(1), column);
+                na::Vector3::new(-dx / step, -dy / step, 1.0).normalize()
+            })
+            .collect();
+        for (vertex, normal) in vertices.iter_mut().zip(normals) {
+            vertex.normal = normal;
+        }
+
+        primitive.set_vertices(vertices);
+    }
+
     fn update_instance_count(&mut self, model: &mut Model) {
-        let transforms = self.create_transforms();
+        self.transforms = self.create_transforms();
+        let grass = model.nodes.get_mut(self.grass).unwrap();
+        grass.transforms = self.transforms.clone();
+    }
+
+    /// Returns the transforms of the instances within the frustum of the camera.
+    /// Instances are expected to be in world space, with the terrain at the origin.
+    pub fn visible_transforms(&self, camera: &Camera, camera_node: &Node) -> Vec<na::Matrix4<f32>> {
+        let planes = camera.frustum_planes(camera_node);
+        // Big enough to contain a blade of grass
+        let radius = 1.0;
+
+        self.transforms
+            .par_iter()
+            .filter(|transform| {
+                let translation = transform.column(3).xyz();
+                Camera::sphere_in_frustum(&planes, &translation, radius) != Containment::Outside
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Updates the grass node so that only instances seen by the camera node are drawn
+    pub fn cull(&self, model: &mut Model, camera_node: Handle<Node>) {
+        let camera_node = model.nodes.get(camera_node).unwrap();
+        let camera = model.cameras.get(camera_node.camera).unwrap();
+        let transforms = self.visible_transforms(camera, camera_node);
+
         let grass = model.nodes.get_mut(self.grass).unwrap();
         grass.transforms = transforms;
     }
 
+    /// Replaces the blade of grass with a custom primitive, such as a multi-segment blade
+    /// or a small quad. Its material should use a grass shader to support instancing.
+    pub fn set_blade_primitive(&mut self, model: &mut Model, primitive: Handle<Primitive>) {
+        let mesh = model.meshes.push(Mesh::new(vec![primitive]));
+
+        let grass = model.nodes.get_mut(self.grass).unwrap();
+        let prev_mesh = grass.mesh;
+        grass.mesh = mesh;
+
+        // The previous mesh was created for the grass node only
+        if prev_mesh.valid() {
+            model.meshes.remove(prev_mesh);
+        }
+    }
+
+    /// Sets the wind swaying the grass, which the renderer uploads to grass shaders.
+    /// A zero strength stops the blades from bending, while direction should not be zero.
+    pub fn set_wind(
+        &self,
+        renderer: &mut Renderer,
+        strength: f32,
+        frequency: f32,
+        direction: na::Vector3<f32>,
+    ) {
+        renderer.wind = Wind::new(strength, frequency, direction);
+    }
+
     fn update_plane_scale(&mut self, model: &mut Model) {
         let plane = model.nodes.get_mut(self.plane).unwrap();
-        let margin = 2.0;
-        plane.trs.set_scale(
-            self.scale + margin,
-            self.scale + margin,
-            self.scale + margin,
-        );
+        let scale = self.scale + PLANE_MARGIN;
+        plane.trs.set_scale(scale, scale, scale);
+
+        // Heights of the vertices depend on where they end up
+        if self.heightmap.is_some() {
+            self.update_plane_height(model);
+        }
     }
 
     pub fn set_scale(&mut self, model: &mut Model, scale: f32) {
@@ -188,3 +375,128 @@ impl Terrain {
         Self::instance_count(self.scale as u32, self.instances_per_unit)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn blade_primitive() {
+        let mut model = Model::new();
+        let blade = model.meshes.push(Mesh::new(vec![Handle::new(0)]));
+        let mut grass = Node::new();
+        grass.mesh = blade;
+        grass.transforms = vec![na::Matrix4::identity(); 4];
+        let grass = model.nodes.push(grass);
+
+        let mut terrain = Terrain::with_nodes(Handle::none(), grass, Handle::none());
+
+        let primitive = Handle::new(1);
+        terrain.set_blade_primitive(&mut model, primitive);
+
+        let grass = model.nodes.get(terrain.grass).unwrap();
+        assert!(grass.mesh.id != blade.id);
+        assert_eq!(grass.transforms.len(), 4);
+        let mesh = model.meshes.get(grass.mesh).unwrap();
+        assert_eq!(mesh.primitives[0].id, primitive.id);
+        // The previous mesh has been removed
+        assert_eq!(model.meshes.len(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn wind() {
+        let mut context = TestContext::new();
+        let renderer = &mut context.gfx.renderer;
+        let terrain = Terrain::with_nodes(Handle::none(), Handle::none(), Handle::none());
+
+        let direction = na::Vector3::new(0.0, 0.0, 1.0);
+        terrain.set_wind(renderer, 2.0, 0.5, direction);
+        assert_eq!(renderer.wind, Wind::new(2.0, 0.5, direction));
+
+        // Does not compile if grass shaders lack the wind uniforms
+        let _ =
+            |loc: &LightShadowGrassLoc| [loc.wind_strength, loc.wind_frequency, loc.wind_direction];
+        let _ = |loc: &LightGrassLoc| [loc.wind_strength, loc.wind_frequency, loc.wind_direction];
+    }
+
+    #[test]
+    fn ramp_heightmap() {
+        // Height grows along X
+        let heights = [0.0, 0.5, 1.0, 0.0, 0.5, 1.0];
+        let mut terrain = Terrain::with_nodes(Handle::none(), Handle::none(), Handle::none());
+        terrain.heightmap = Some(Heightmap::new(&heights, Extent2D::new(3, 2), 4.0));
+        assert_eq!(terrain.get_heightmap().unwrap().sample(0.25, 0.5), 1.0);
+        assert_eq!(terrain.get_heightmap().unwrap().get_mean_height(), 2.0);
+
+        let transforms = terrain.create_transforms();
+        let stride = (terrain.get_instance_count() as f32).sqrt() as usize;
+
+        for row in 0..stride {
+            let heights: Vec<f32> = transforms[row * stride..(row + 1) * stride]
+                .iter()
+                .map(|transform| transform[(1, 3)])
+                .collect();
+            assert_eq!(heights[0], 0.0);
+            assert_eq!(heights[stride - 1], 4.0);
+            assert!(heights.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn displaced_plane() {
+        let _context = TestContext::new();
+
+        // Height grows along X, the same for all the rows
+        let heights = [0.0, 0.5, 1.0, 0.0, 0.5, 1.0];
+        let mut model = Model::new();
+        let terrain = Terrain::from_heightmap(&mut model, &heights, Extent2D::new(3, 2), 4.0);
+
+        let plane = model.nodes.get(terrain.plane).unwrap();
+        let mesh = model.meshes.get(plane.mesh).unwrap();
+        let primitive = model.primitives.get(mesh.primitives[0]).unwrap();
+        let scale = terrain.get_scale() + PLANE_MARGIN;
+        let side = PLANE_SEGMENTS as usize + 1;
+        let height = |i: usize| primitive.vertices[i].position[2] * scale;
+
+        // Beyond the instances, the plane keeps the height of the border
+        assert_eq!(height(0), 0.0);
+        assert!((height(side - 1) - 4.0).abs() < 0.001);
+        assert!((0..side - 1).all(|i| height(i) <= height(i + 1)));
+        assert_eq!(height(side * (side - 1) + side / 2), height(side / 2));
+        assert!((primitive.aabb.max.z * scale - 4.0).abs() < 0.001);
+
+        // Instances are at the height of the plane at the center of their cell
+        let stride = terrain.get_scatter().get_stride() as usize;
+        let spread = terrain.get_scatter().area / stride as f32;
+        let center = |cell: usize| spread * (cell as f32 - stride as f32 / 2.0) + spread / 2.0;
+        for (i, transform) in terrain.transforms.iter().enumerate() {
+            let height = terrain.get_height(center(i % stride), center(i / stride));
+            assert!((height - transform[(1, 3)]).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn narrow_frustum() {
+        let mut terrain = Terrain::with_nodes(Handle::none(), Handle::none(), Handle::none());
+        terrain.scale = 8.0;
+        terrain.transforms = terrain.create_transforms();
+
+        // Far enough to see everything
+        let mut camera_node = Node::new();
+        camera_node.trs.rotate(&na::UnitQuaternion::from_axis_angle(
+            &na::Vector3::x_axis(),
+            -std::f32::consts::FRAC_PI_2,
+        ));
+        camera_node.trs.translate(0.0, 64.0, 0.0);
+        let wide = Camera::orthographic(256, 256, 0.1, 128.0);
+        let all = terrain.visible_transforms(&wide, &camera_node);
+        assert_eq!(all.len(), terrain.transforms.len());
+
+        let narrow = Camera::orthographic(2, 2, 0.1, 128.0);
+        let visible = terrain.visible_transforms(&narrow, &camera_node);
+        assert!(!visible.is_empty());
+        assert!(visible.len() < all.len());
+    }
+}
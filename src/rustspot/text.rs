@@ -0,0 +1,171 @@
+// Copyright © 2021
+// Author: Antonio Caggiano <info@antoniocaggiano.eu>
+// SPDX-License-Identifier: MIT
+
+use std::collections::HashMap;
+
+use super::*;
+
+/// Placement of a character within a line of text and within the font atlas
+#[derive(Clone, Copy, Debug)]
+pub struct Glyph {
+    /// How much to move right after this glyph
+    pub advance: f32,
+    /// Rectangle relative to the pen position, `[x0, y0, x1, y1]` in pixels, y pointing down
+    pub rect: [f32; 4],
+    /// Texture coordinates into the font atlas, `[u0, v0, u1, v1]`
+    pub uv: [f32; 4],
+}
+
+/// Glyphs of a font, as baked by imgui into its font atlas
+#[derive(Clone, Debug)]
+pub struct GlyphAtlas {
+    /// Height of a line in pixels
+    pub size: f32,
+    glyphs: HashMap<char, Glyph>,
+}
+
+impl GlyphAtlas {
+    pub fn new(size: f32) -> Self {
+        Self {
+            size,
+            glyphs: HashMap::new(),
+        }
+    }
+
+    /// Collects printable ASCII glyphs of the font. The font atlas should already be built.
+    pub fn from_imgui(font: &imgui::Font) -> Self {
+        let mut ret = Self::new(font.font_size);
+
+        let raw_font = font as *const imgui::Font as *mut imgui::sys::ImFont;
+        for c in ' '..='~' {
+            let glyph = unsafe { imgui::sys::ImFont_FindGlyphNoFallback(raw_font, c as _) };
+            if let Some(glyph) = unsafe { glyph.as_ref() } {
+                ret.insert(
+                    c,
+                    Glyph {
+                        advance: glyph.AdvanceX,
+                        rect: [glyph.X0, glyph.Y0, glyph.X1, glyph.Y1],
+                        uv: [glyph.U0, glyph.V0, glyph.U1, glyph.V1],
+                    },
+                );
+            }
+        }
+
+        ret
+    }
+
+    pub fn insert(&mut self, c: char, glyph: Glyph) {
+        self.glyphs.insert(c, glyph);
+    }
+
+    pub fn get(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&c)
+    }
+}
+
+/// Draws strings at pixel coordinates using the imgui font texture, outside imgui windows.
+/// Glyphs are sprites of a batch showing regions of the font texture, so pixel coordinates
+/// start from the top-left corner as well.
+pub struct TextRenderer {
+    glyphs: GlyphAtlas,
+    batch: SpriteBatch,
+}
+
+impl TextRenderer {
+    pub fn new(extent: Extent2D, glyphs: GlyphAtlas) -> Self {
+        Self {
+            glyphs,
+            batch: SpriteBatch::new(extent),
+        }
+    }
+
+    /// Resizes the sprite batch along with the target, see `SpriteBatch::set_extent`
+    pub fn set_extent(&mut self, extent: Extent2D) {
+        self.batch.set_extent(extent);
+    }
+
+    /// Adds a line of text with its top-left corner at `position`.
+    /// Characters without a glyph in the atlas are skipped.
+    pub fn text(&mut self, text: &str, position: [f32; 2], scale: f32, color: [f32; 3]) {
+        let mut pen = position[0];
+
+        for c in text.chars() {
+            let glyph = match self.glyphs.get(c) {
+                Some(glyph) => *glyph,
+                None => continue,
+            };
+
+            let [x0, y0, x1, y1] = glyph.rect;
+
+            // Spaces and the like only move the pen
+            if x1 > x0 && y1 > y0 {
+                // All glyphs are in the font texture, which is not part of any pack
+                self.batch.sprite_uv(
+                    Handle::none(),
+                    [pen + x0 * scale, position[1] + y0 * scale],
+                    [(x1 - x0) * scale, (y1 - y0) * scale],
+                    glyph.uv,
+                    color,
+                );
+            }
+
+            pen += glyph.advance * scale;
+        }
+    }
+
+    /// Returns the number of glyph quads collected so far
+    pub fn get_quad_count(&self) -> usize {
+        self.batch.get_sprite_count()
+    }
+
+    pub fn clear(&mut self) {
+        self.batch.clear();
+    }
+
+    /// Draws the collected text on top of the target and clears it
+    pub fn draw<D: DrawableOnto>(&mut self, shader: &UnlitShader, font: &Texture, target: &D) {
+        self.batch.draw_with(shader, |_| font, target);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn two_glyph_quads() {
+        let mut glyphs = GlyphAtlas::new(13.0);
+        let glyph = Glyph {
+            advance: 7.0,
+            rect: [0.0, 0.0, 6.0, 13.0],
+            uv: [0.0, 0.0, 0.1, 0.1],
+        };
+        glyphs.insert('H', glyph);
+        glyphs.insert('i', glyph);
+        glyphs.insert(
+            ' ',
+            Glyph {
+                advance: 7.0,
+                rect: [0.0; 4],
+                uv: [0.0; 4],
+            },
+        );
+
+        let mut text = TextRenderer::new(Extent2D::new(640, 480), glyphs);
+        text.text("Hi", [0.0, 0.0], 1.0, [1.0, 1.0, 1.0]);
+        assert_eq!(text.get_quad_count(), 2);
+
+        // Second glyph starts after the advance of the first one
+        let vertices = &text.batch.batches[0].vertices;
+        assert_eq!(vertices[6].position[0], -320.0 + 7.0);
+        // Top-left corner shows the top-left of the glyph in the atlas
+        assert_eq!(vertices[4].tex_coords, [0.0, 0.0]);
+
+        // Spaces do not generate quads
+        text.clear();
+        text.text("H i", [0.0, 0.0], 2.0, [1.0, 1.0, 1.0]);
+        assert_eq!(text.get_quad_count(), 2);
+        assert_eq!(text.batch.batches[0].vertices[6].position[0], -320.0 + 28.0);
+    }
+}
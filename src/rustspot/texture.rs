@@ -3,13 +3,61 @@
 // SPDX-License-Identifier: MIT
 
 use std::{
-    error::Error,
     fs::File,
+    io::Read,
     path::{Path, PathBuf},
 };
 
+use noise::{NoiseFn, Seedable};
+
 use super::*;
 
+/// From `GL_EXT_texture_filter_anisotropic`, not part of the generated bindings
+const TEXTURE_MAX_ANISOTROPY: gl::types::GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: gl::types::GLenum = 0x84FF;
+
+/// Anisotropic filtering is available either as an extension or in core GL 4.6
+fn has_anisotropy() -> bool {
+    Gfx::has_extension("GL_EXT_texture_filter_anisotropic")
+        || Gfx::has_extension("GL_ARB_texture_filter_anisotropic")
+}
+
+/// Returns the anisotropy level which can actually be used on this device
+fn clamp_anisotropy(level: f32, max: f32) -> f32 {
+    level.max(1.0).min(max)
+}
+
+/// Returns whether a region at `x` and `y` fits within `extent`
+fn contains_region(extent: Extent2D, x: u32, y: u32, region: Extent2D) -> bool {
+    let fits_width = x
+        .checked_add(region.width)
+        .is_some_and(|right| right <= extent.width);
+    let fits_height = y
+        .checked_add(region.height)
+        .is_some_and(|top| top <= extent.height);
+    fits_width && fits_height
+}
+
+/// Returns the size of a tightly packed pixel with `format` and `component` type
+fn bytes_per_pixel(format: gl::types::GLenum, component: gl::types::GLenum) -> usize {
+    // Packed types store all the channels of a pixel in one value
+    if component == gl::UNSIGNED_INT_24_8 {
+        return 4;
+    }
+    let channels = match format {
+        gl::RED | gl::DEPTH_COMPONENT => 1,
+        gl::RG => 2,
+        gl::RGB => 3,
+        _ => 4,
+    };
+    let component_size = match component {
+        gl::UNSIGNED_BYTE | gl::BYTE => 1,
+        gl::UNSIGNED_SHORT | gl::SHORT | gl::HALF_FLOAT => 2,
+        _ => 4,
+    };
+    channels * component_size
+}
+
 fn to_gl_format(color_type: png::ColorType) -> gl::types::GLenum {
     match color_type {
         png::ColorType::Grayscale => gl::RED,
@@ -20,46 +68,136 @@ fn to_gl_format(color_type: png::ColorType) -> gl::types::GLenum {
     }
 }
 
+/// Returns the internal format used to store texels of a certain format and component type.
+/// sRGB applies only to color formats, as other formats hold linear data.
+fn to_gl_internal_format(
+    format: gl::types::GLenum,
+    component: gl::types::GLenum,
+    srgb: bool,
+) -> gl::types::GLenum {
+    match (format, component, srgb) {
+        (gl::RGB, _, true) => gl::SRGB8,
+        (gl::RGBA, _, true) => gl::SRGB8_ALPHA8,
+        // GLES does not accept an unsized single channel format
+        (gl::RED, gl::UNSIGNED_BYTE, _) => gl::R8,
+        (gl::DEPTH_STENCIL, _, _) => gl::DEPTH24_STENCIL8,
+        _ => format,
+    }
+}
+
+/// Returns the internal format storing floating point texels of a color format
+fn to_gl_float_format(format: gl::types::GLenum) -> gl::types::GLenum {
+    match format {
+        gl::RED => gl::R32F,
+        // Two channels of 32 bits floats can not be rendered to on GLES
+        gl::RG if cfg!(feature = "gles") => gl::RG16F,
+        gl::RG => gl::RG32F,
+        gl::RGB => gl::RGB32F,
+        gl::RGBA => gl::RGBA32F,
+        _ => format,
+    }
+}
+
 fn to_gl_renderable_format(format: gl::types::GLenum) -> gl::types::GLenum {
     match format {
         gl::RGB => gl::RGB8,
         gl::RGBA => gl::RGBA8,
         gl::DEPTH_COMPONENT => gl::DEPTH_COMPONENT16,
+        gl::DEPTH_STENCIL => gl::DEPTH24_STENCIL8,
         _ => format,
     }
 }
 
 pub struct TextureBuilder<'a> {
-    id: u32,
+    pub(crate) id: u32,
     format: gl::types::GLenum,
-    extent: Extent2D,
+    pub(crate) extent: Extent2D,
     component: gl::types::GLenum,
     samples: u32,
+    anisotropy: f32,
+    lod_bias: f32,
+    max_level: Option<u32>,
+    srgb: bool,
+    flip_v: bool,
 
     data: Option<&'a [u8]>,
 
     // Data loaded from file
-    owned_data: Option<Vec<u8>>,
+    pub(crate) owned_data: Option<Vec<u8>>,
     path: Option<PathBuf>,
 }
 
-fn load_data<P: AsRef<Path>>(
-    path: P,
-) -> Result<(Extent2D, gl::types::GLenum, Vec<u8>), Box<dyn Error>> {
-    let decoder = png::Decoder::new(File::open(&path)?);
+/// Reverses the order of the rows of an image, as its data is stored top to bottom
+/// while OpenGL expects the first row to be the bottom one
+fn flip_rows(data: &mut [u8], height: u32) {
+    if height == 0 {
+        return;
+    }
+    let row_size = data.len() / height as usize;
+    let height = height as usize;
+    for y in 0..height / 2 {
+        let (top, bottom) = data.split_at_mut((height - 1 - y) * row_size);
+        top[y * row_size..(y + 1) * row_size].swap_with_slice(&mut bottom[..row_size]);
+    }
+}
+
+/// Checks that `len` bytes are exactly the pixels of an image with 8-bit `channels`
+fn check_data_len(extent: Extent2D, channels: usize, len: usize) -> Result<(), TextureError> {
+    let expected = extent.width as usize * extent.height as usize * channels;
+    if len != expected {
+        return Err(TextureError::DataLength { expected, len });
+    }
+    Ok(())
+}
+
+/// Samples Perlin noise over the extent, one byte for each texel. `scale` is
+/// how many noise cells fit along the side of the texture.
+fn perlin_data(extent: Extent2D, scale: f32, seed: u32) -> Vec<u8> {
+    let perlin = noise::Perlin::new().set_seed(seed);
+    let mut data = Vec::with_capacity(extent.width as usize * extent.height as usize);
+
+    for y in 0..extent.height {
+        for x in 0..extent.width {
+            let u = x as f64 / extent.width as f64 * scale as f64;
+            let v = y as f64 / extent.height as f64 * scale as f64;
+            // From [-1, 1] to [0, 255]
+            let value = (perlin.get([u, v]) * 0.5 + 0.5).clamp(0.0, 1.0);
+            data.push((value * 255.0).round() as u8);
+        }
+    }
+
+    data
+}
+
+fn decode_png<R: Read>(
+    reader: R,
+    flip_v: bool,
+) -> Result<(Extent2D, gl::types::GLenum, Vec<u8>, png::ColorType), RustspotError> {
+    let decoder = png::Decoder::new(reader);
     let (info, mut reader) = decoder.read_info()?;
 
     let mut data: Vec<u8> = vec![0; info.buffer_size()];
-    let mut timer = Timer::new();
     reader.next_frame(data.as_mut_slice())?;
+    if flip_v {
+        flip_rows(&mut data, info.height);
+    }
 
     let extent = Extent2D::new(info.width, info.height);
     let format = to_gl_format(info.color_type);
+    Ok((extent, format, data, info.color_type))
+}
 
-    println!(
-        "Image {} ({:?}) leaded in {}",
+fn load_data<P: AsRef<Path>>(
+    path: P,
+    flip_v: bool,
+) -> Result<(Extent2D, gl::types::GLenum, Vec<u8>), RustspotError> {
+    let mut timer = Timer::new();
+    let (extent, format, data, color_type) = decode_png(File::open(&path)?, flip_v)?;
+
+    log::debug!(
+        "Image {} ({:?}) loaded in {}",
         path.as_ref().to_string_lossy(),
-        info.color_type,
+        color_type,
         timer.get_delta().as_secs_f32()
     );
     Ok((extent, format, data))
@@ -73,6 +211,11 @@ impl<'a> TextureBuilder<'a> {
             extent: Extent2D::new(1, 1),
             component: gl::UNSIGNED_BYTE,
             samples: 1,
+            anisotropy: 1.0,
+            lod_bias: 0.0,
+            max_level: None,
+            srgb: false,
+            flip_v: false,
             data: None,
             owned_data: None,
             path: None,
@@ -105,28 +248,71 @@ impl<'a> TextureBuilder<'a> {
         self
     }
 
+    /// Level of anisotropic filtering, clamped to the maximum supported by the device.
+    /// It is ignored when anisotropic filtering is not available.
+    pub fn anisotropy(mut self, level: f32) -> Self {
+        self.anisotropy = level;
+        self
+    }
+
+    /// Added to the level of detail when selecting a mipmap, positive values making it blurrier.
+    /// It is ignored on GLES, which has no such texture parameter.
+    pub fn lod_bias(mut self, bias: f32) -> Self {
+        self.lod_bias = bias;
+        self
+    }
+
+    /// Highest mipmap level which can be sampled, lower ones having more detail
+    pub fn max_level(mut self, level: u32) -> Self {
+        self.max_level = Some(level);
+        self
+    }
+
+    /// Whether texel data is sRGB encoded, as usually color maps are, so that the GPU
+    /// converts it to linear when sampling. Normal and other data maps should stay linear.
+    /// Note that lit shaders already decode albedo, so they expect linear textures.
+    pub fn srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    /// Whether to reverse the rows of images loaded by `path`, so that the top-left
+    /// pixel ends up at the bottom-left. Call it before `path`.
+    pub fn flip_v(mut self, flip_v: bool) -> Self {
+        self.flip_v = flip_v;
+        self
+    }
+
     pub fn data(mut self, data: &'a [u8]) -> Self {
         self.data = Some(data);
         self
     }
 
     // As soon as this method is called, data from path is loaded in main memory
-    pub fn path<P: AsRef<Path>>(mut self, path: P) -> Self {
-        let (extent, format, data) = load_data(&path).expect(&format!(
-            "Failed to load image from {}",
-            path.as_ref().to_string_lossy()
-        ));
+    pub fn path<P: AsRef<Path>>(mut self, path: P) -> Result<Self, RustspotError> {
+        let (extent, format, data) = load_data(&path, self.flip_v)?;
         self.owned_data = Some(data);
         self.extent = extent;
         self.format = format;
         self.path = Some(path.as_ref().into());
 
-        self
+        Ok(self)
     }
 
-    pub fn build(self) -> Result<Texture, Box<dyn Error>> {
+    /// Decodes a PNG image already in main memory, such as one embedded in a glTF buffer
+    pub fn png(mut self, bytes: &[u8]) -> Result<Self, RustspotError> {
+        let (extent, format, data, _) = decode_png(bytes, self.flip_v)?;
+        self.owned_data = Some(data);
+        self.extent = extent;
+        self.format = format;
+
+        Ok(self)
+    }
+
+    pub fn build(self) -> Result<Texture, RustspotError> {
         let mut ret = Texture::new(self.format, self.extent, self.component, self.samples);
         ret.id = self.id;
+        ret.srgb = self.srgb;
 
         ret.bind();
 
@@ -139,6 +325,16 @@ impl<'a> TextureBuilder<'a> {
             ret.attachment();
         }
 
+        if self.anisotropy > 1.0 && has_anisotropy() {
+            ret.set_anisotropy(self.anisotropy);
+        }
+        if self.lod_bias != 0.0 {
+            ret.set_lod_bias(self.lod_bias);
+        }
+        if let Some(max_level) = self.max_level {
+            ret.set_max_level(max_level);
+        }
+
         ret.unbind();
 
         Ok(ret)
@@ -154,6 +350,8 @@ pub struct Texture {
     component: gl::types::GLenum,
     pub samples: u32,
     pub path: Option<PathBuf>,
+    /// Whether texels are stored in sRGB
+    pub srgb: bool,
 }
 
 impl Texture {
@@ -161,6 +359,16 @@ impl Texture {
         TextureBuilder::new()
     }
 
+    /// Loads a PNG image from file
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, RustspotError> {
+        Self::builder().path(path)?.build()
+    }
+
+    /// Creates a one pixel magenta texture, useful as a replacement for textures failing to load
+    pub fn missing() -> Self {
+        Self::pixel(Color::rgba(255, 0, 255, 255))
+    }
+
     fn samples_as_target(samples: u32) -> gl::types::GLenum {
         if samples > 1 {
             gl::TEXTURE_2D_MULTISAMPLE
@@ -187,6 +395,7 @@ impl Texture {
             component,
             samples,
             path: None,
+            srgb: false,
         }
     }
 
@@ -251,11 +460,139 @@ impl Texture {
             .unwrap()
     }
 
+    /// Creates a texture with two 32-bit float channels, such as the moments of a variance shadow map
+    pub fn moments(extent: Extent2D) -> Self {
+        let ret = Self::builder()
+            .extent(extent)
+            .format(gl::RG)
+            .component(gl::FLOAT)
+            .build()
+            .unwrap();
+
+        // Repeating would blur moments of the opposite edge into the shadow map
+        ret.bind();
+        unsafe {
+            gl::TexParameteri(ret.target, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(ret.target, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+        }
+        ret
+    }
+
+    /// Creates a texture with 24 bits of depth and 8 bits of stencil
+    pub fn depth_stencil(extent: Extent2D, samples: u32) -> Self {
+        Self::builder()
+            .extent(extent)
+            .samples(samples)
+            .format(gl::DEPTH_STENCIL)
+            .component(gl::UNSIGNED_INT_24_8)
+            .build()
+            .unwrap()
+    }
+
+    /// Creates a depth cubemap, for example for point light shadows
+    pub fn depth_cube(extent: Extent2D) -> Self {
+        let mut ret = Texture::new(gl::DEPTH_COMPONENT, extent, gl::FLOAT, 1);
+        ret.target = gl::TEXTURE_CUBE_MAP;
+
+        ret.bind();
+        unsafe {
+            for face in 0..6 {
+                gl::TexImage2D(
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                    0,
+                    gl::DEPTH_COMPONENT32F as i32,
+                    extent.width as i32,
+                    extent.height as i32,
+                    0,
+                    gl::DEPTH_COMPONENT,
+                    gl::FLOAT,
+                    std::ptr::null(),
+                );
+            }
+
+            gl::TexParameteri(ret.target, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(ret.target, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(ret.target, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(ret.target, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(ret.target, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as i32);
+        }
+        ret.unbind();
+
+        ret
+    }
+
+    /// Creates a texture from pixels in memory, four bytes each, with the first row at the bottom
+    pub fn from_rgba(width: u32, height: u32, data: &[u8]) -> Result<Self, RustspotError> {
+        let extent = Extent2D::new(width, height);
+        check_data_len(extent, 4, data.len())?;
+        Self::builder()
+            .format(gl::RGBA)
+            .extent(extent)
+            .data(data)
+            .build()
+    }
+
+    /// Same as `from_rgba` but without alpha, three bytes for each pixel
+    pub fn from_rgb(width: u32, height: u32, data: &[u8]) -> Result<Self, RustspotError> {
+        let extent = Extent2D::new(width, height);
+        check_data_len(extent, 3, data.len())?;
+        Self::builder()
+            .format(gl::RGB)
+            .extent(extent)
+            .data(data)
+            .build()
+    }
+
+    /// Creates a single channel texture filled with Perlin noise, useful as a heightmap,
+    /// clouds, or a detail map. The same seed always produces the same texture.
+    pub fn perlin(extent: Extent2D, scale: f32, seed: u32) -> Self {
+        let data = perlin_data(extent, scale, seed);
+        Self::builder()
+            .format(gl::RED)
+            .extent(extent)
+            .data(&data)
+            .build()
+            .unwrap()
+    }
+
     /// Creates a one pixel texture with the RGBA color passed as argument
     pub fn pixel(data: Color) -> Self {
         Self::builder().data(data.as_slice()).build().unwrap()
     }
 
+    /// Returns the maximum anisotropy level supported, 1.0 meaning no anisotropic filtering
+    pub fn get_max_anisotropy() -> f32 {
+        if !has_anisotropy() {
+            return 1.0;
+        }
+
+        let mut max = 1.0;
+        unsafe { gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max) };
+        max
+    }
+
+    /// Sets the anisotropy level of this texture, which should be bound.
+    /// Returns the level actually used after clamping.
+    pub fn set_anisotropy(&mut self, level: f32) -> f32 {
+        let level = clamp_anisotropy(level, Self::get_max_anisotropy());
+        unsafe { gl::TexParameterf(self.target, TEXTURE_MAX_ANISOTROPY, level) };
+        level
+    }
+
+    /// The texture should be bound
+    pub fn set_lod_bias(&mut self, bias: f32) {
+        if cfg!(feature = "gles") {
+            log::warn!("Texture LOD bias is not supported on GLES");
+            return;
+        }
+        unsafe { gl::TexParameterf(self.target, gl::TEXTURE_LOD_BIAS, bias) };
+    }
+
+    /// The texture should be bound
+    pub fn set_max_level(&mut self, level: u32) {
+        unsafe { gl::TexParameteri(self.target, gl::TEXTURE_MAX_LEVEL, level as i32) };
+    }
+
     pub fn bind(&self) {
         unsafe {
             gl::BindTexture(self.target, self.handle);
@@ -268,6 +605,70 @@ impl Texture {
         }
     }
 
+    /// Whether the minifying filter samples mipmaps, so they need to be regenerated on updates.
+    /// The texture should be bound
+    fn has_mipmaps(&self) -> bool {
+        let mut filter = 0;
+        unsafe { gl::GetTexParameteriv(self.target, gl::TEXTURE_MIN_FILTER, &mut filter) };
+        filter as gl::types::GLenum != gl::NEAREST && filter as gl::types::GLenum != gl::LINEAR
+    }
+
+    /// Updates part of the texture without reallocating its storage. Data should be
+    /// in the same format and component type used when creating the texture.
+    pub fn update_region<T>(
+        &mut self,
+        x: u32,
+        y: u32,
+        extent: Extent2D,
+        data: &[T],
+    ) -> Result<(), TextureError> {
+        if !contains_region(self.extent, x, y, extent) {
+            return Err(TextureError::RegionOutOfBounds {
+                x,
+                y,
+                region: extent,
+                extent: self.extent,
+            });
+        }
+        if self.samples != 1 {
+            return Err(TextureError::Multisample);
+        }
+        let expected = extent.width as usize
+            * extent.height as usize
+            * bytes_per_pixel(self.format, self.component);
+        let len = std::mem::size_of_val(data);
+        if len < expected {
+            return Err(TextureError::DataLength { expected, len });
+        }
+
+        self.bind();
+        unsafe {
+            // Rows are tightly packed
+            let mut alignment = 4;
+            gl::GetIntegerv(gl::UNPACK_ALIGNMENT, &mut alignment);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                x as _,
+                y as _,
+                extent.width as _,
+                extent.height as _,
+                self.format,
+                self.component,
+                data.as_ptr() as _,
+            );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, alignment);
+
+            if self.has_mipmaps() {
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+            }
+        }
+        self.unbind();
+
+        Ok(())
+    }
+
     fn upload<T>(&mut self, data: Option<&[T]>) {
         let data = if let Some(data) = data {
             &data[0] as *const T as _
@@ -275,11 +676,21 @@ impl Texture {
             std::ptr::null()
         };
 
+        let internal_format = if self.component == gl::FLOAT {
+            to_gl_float_format(self.format)
+        } else {
+            to_gl_internal_format(self.format, self.component, self.srgb)
+        };
+
         unsafe {
+            // Rows of single channel or RGB data are not necessarily aligned to 4 bytes
+            let mut alignment = 4;
+            gl::GetIntegerv(gl::UNPACK_ALIGNMENT, &mut alignment);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
             gl::TexImage2D(
                 gl::TEXTURE_2D,
                 0,
-                self.format as i32,
+                internal_format as i32,
                 self.extent.width as i32,
                 self.extent.height as i32,
                 0,
@@ -287,6 +698,7 @@ impl Texture {
                 self.component,
                 data,
             );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, alignment);
 
             gl::GenerateMipmap(gl::TEXTURE_2D);
 
@@ -309,3 +721,327 @@ impl Drop for Texture {
         }
     }
 }
+
+/// Number of texture units tracked by `TextureUnits`
+pub const MAX_TEXTURE_UNITS: usize = 8;
+
+/// Remembers the texture bound to each unit, so that binding the same texture
+/// again does not issue any GL call. Texture unit 0 is left active after binding.
+#[derive(Default)]
+pub struct TextureUnits {
+    /// Handle of the texture bound to each unit, 0 when unknown
+    bound: [u32; MAX_TEXTURE_UNITS],
+    /// Number of `glBindTexture` calls issued
+    bind_count: usize,
+}
+
+impl TextureUnits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds a texture to a unit, unless it is already bound there
+    pub fn bind(&mut self, unit: usize, texture: &Texture) {
+        if self.bound[unit] == texture.handle {
+            return;
+        }
+
+        unsafe {
+            if unit != 0 {
+                gl::ActiveTexture(gl::TEXTURE0 + unit as u32);
+            }
+            texture.bind();
+            if unit != 0 {
+                gl::ActiveTexture(gl::TEXTURE0);
+            }
+        }
+        self.bound[unit] = texture.handle;
+        self.bind_count += 1;
+    }
+
+    /// Forgets what is bound. Call it when textures may have been bound without this tracker.
+    pub fn invalidate(&mut self) {
+        self.bound = [0; MAX_TEXTURE_UNITS];
+    }
+
+    pub fn get_bind_count(&self) -> usize {
+        self.bind_count
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn anisotropy_clamp() {
+        assert_eq!(clamp_anisotropy(64.0, 16.0), 16.0);
+        assert_eq!(clamp_anisotropy(4.0, 16.0), 4.0);
+        assert_eq!(clamp_anisotropy(0.0, 16.0), 1.0);
+    }
+
+    #[test]
+    fn srgb_internal_format() {
+        let byte = gl::UNSIGNED_BYTE;
+        assert_eq!(
+            to_gl_internal_format(gl::RGBA, byte, true),
+            gl::SRGB8_ALPHA8
+        );
+        assert_eq!(to_gl_internal_format(gl::RGB, byte, true), gl::SRGB8);
+        assert_eq!(to_gl_internal_format(gl::RGBA, byte, false), gl::RGBA);
+
+        // Data maps stay linear
+        assert_eq!(to_gl_internal_format(gl::RED, byte, true), gl::R8);
+        assert_eq!(
+            to_gl_internal_format(gl::DEPTH_COMPONENT, gl::FLOAT, true),
+            gl::DEPTH_COMPONENT
+        );
+    }
+
+    #[test]
+    fn float_internal_format() {
+        if cfg!(feature = "gles") {
+            assert_eq!(to_gl_float_format(gl::RG), gl::RG16F);
+        } else {
+            assert_eq!(to_gl_float_format(gl::RG), gl::RG32F);
+        }
+        assert_eq!(to_gl_float_format(gl::RGBA), gl::RGBA32F);
+        // Depth is already stored as float
+        assert_eq!(to_gl_float_format(gl::DEPTH_COMPONENT), gl::DEPTH_COMPONENT);
+    }
+
+    #[test]
+    fn region_bounds() {
+        let extent = Extent2D::new(4, 4);
+        assert!(contains_region(extent, 0, 0, Extent2D::new(4, 4)));
+        assert!(contains_region(extent, 2, 3, Extent2D::new(2, 1)));
+        assert!(!contains_region(extent, 3, 0, Extent2D::new(2, 1)));
+        assert!(!contains_region(extent, 0, u32::MAX, Extent2D::new(1, 1)));
+    }
+
+    #[test]
+    fn bad_path() {
+        match Texture::builder().path("res/img/missing.png") {
+            Err(RustspotError::Io(_)) => (),
+            _ => panic!("Expected an IO error"),
+        }
+    }
+
+    #[test]
+    fn mismatched_data_len() {
+        match Texture::from_rgba(2, 2, &[0; 12]) {
+            Err(RustspotError::Texture(err)) => assert_eq!(
+                err,
+                TextureError::DataLength {
+                    expected: 16,
+                    len: 12
+                }
+            ),
+            _ => panic!("Expected a texture data length error"),
+        }
+        assert!(Texture::from_rgb(2, 2, &[0; 16]).is_err());
+        assert!(check_data_len(Extent2D::new(2, 2), 3, 12).is_ok());
+    }
+
+    #[test]
+    fn perlin_seed() {
+        let extent = Extent2D::new(16, 16);
+        let data = perlin_data(extent, 4.0, 42);
+        assert_eq!(data.len(), 256);
+        assert_eq!(data, perlin_data(extent, 4.0, 42));
+        assert_ne!(data, perlin_data(extent, 4.0, 7));
+    }
+
+    #[test]
+    fn flip_v() {
+        // Red top-left pixel of a 2x2 image
+        let path = std::env::temp_dir().join("rustspot-flip-v.png");
+        {
+            let file = File::create(&path).unwrap();
+            let mut encoder = png::Encoder::new(file, 2, 2);
+            encoder.set_color(png::ColorType::RGB);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut data = vec![0; 12];
+            data[0] = 255;
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&data).unwrap();
+        }
+
+        let builder = Texture::builder().path(&path).unwrap();
+        let data = builder.owned_data.unwrap();
+        assert_eq!(&data[..3], &[255, 0, 0]);
+
+        let builder = Texture::builder().flip_v(true).path(&path).unwrap();
+        let data = builder.owned_data.unwrap();
+        assert_eq!(&data[..3], &[0, 0, 0]);
+        // First pixel of the bottom row
+        assert_eq!(&data[6..9], &[255, 0, 0]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn png_from_memory() {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, 2, 1);
+            encoder.set_color(png::ColorType::RGBA);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer
+                .write_image_data(&[255, 0, 0, 255, 0, 0, 255, 255])
+                .unwrap();
+        }
+
+        let builder = Texture::builder().png(&bytes).unwrap();
+        assert!(builder.extent == Extent2D::new(2, 1));
+        assert_eq!(builder.format, gl::RGBA);
+        assert_eq!(
+            builder.owned_data.unwrap(),
+            [255, 0, 0, 255, 0, 0, 255, 255]
+        );
+
+        assert!(Texture::builder().png(&bytes[..8]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn missing() {
+        let _context = TestContext::new();
+
+        let texture = Texture::missing();
+        assert!(texture.handle != 0);
+        assert!(texture.extent == Extent2D::new(1, 1));
+    }
+
+    #[test]
+    fn pixel_size() {
+        assert_eq!(bytes_per_pixel(gl::RGBA, gl::UNSIGNED_BYTE), 4);
+        assert_eq!(bytes_per_pixel(gl::RGB, gl::UNSIGNED_BYTE), 3);
+        assert_eq!(bytes_per_pixel(gl::RG, gl::FLOAT), 8);
+        assert_eq!(bytes_per_pixel(gl::DEPTH_STENCIL, gl::UNSIGNED_INT_24_8), 4);
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn update_region_errors() {
+        let _context = TestContext::new();
+
+        let mut texture = Texture::builder()
+            .extent(Extent2D::new(4, 4))
+            .data(&[0u8; 4 * 4 * 4])
+            .build()
+            .unwrap();
+        // One pixel short
+        assert_eq!(
+            texture.update_region(0, 0, Extent2D::new(2, 2), &[255u8; 3 * 4]),
+            Err(TextureError::DataLength {
+                expected: 16,
+                len: 12
+            })
+        );
+
+        let region = Extent2D::new(2, 2);
+        assert_eq!(
+            texture.update_region(3, 0, region, &[255u8; 2 * 2 * 4]),
+            Err(TextureError::RegionOutOfBounds {
+                x: 3,
+                y: 0,
+                region,
+                extent: texture.extent
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn mipmaps() {
+        let _context = TestContext::new();
+
+        let texture = Texture::builder().data(&[255; 4]).build().unwrap();
+        texture.bind();
+        assert!(texture.has_mipmaps());
+
+        // Attachments are not mipmapped
+        let texture = Texture::builder()
+            .extent(Extent2D::new(4, 4))
+            .build()
+            .unwrap();
+        texture.bind();
+        assert!(!texture.has_mipmaps());
+    }
+
+    #[test]
+    #[cfg(all(feature = "headless", not(feature = "gles")))]
+    fn update_region_readback() {
+        let _context = TestContext::new();
+
+        let black = vec![0u8; 4 * 4 * 4];
+        let mut texture = Texture::builder()
+            .extent(Extent2D::new(4, 4))
+            .data(&black)
+            .build()
+            .unwrap();
+
+        let white = vec![255u8; 2 * 2 * 4];
+        texture
+            .update_region(1, 1, Extent2D::new(2, 2), &white)
+            .unwrap();
+
+        let mut pixels = vec![0u8; 4 * 4 * 4];
+        texture.bind();
+        unsafe {
+            gl::GetTexImage(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as _,
+            );
+        }
+
+        for y in 0..4 {
+            for x in 0..4 {
+                let inside = (1..3).contains(&x) && (1..3).contains(&y);
+                let expected = if inside { 255 } else { 0 };
+                assert_eq!(pixels[(y * 4 + x) * 4], expected);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn anisotropy_max() {
+        let _context = TestContext::new();
+
+        let mut texture = Texture::pixel(Color::new());
+        texture.bind();
+        assert_eq!(
+            texture.set_anisotropy(1024.0),
+            Texture::get_max_anisotropy()
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "headless", not(feature = "gles")))]
+    fn lod_parameters() {
+        let _context = TestContext::new();
+
+        let texture = Texture::builder()
+            .data(&[255; 4])
+            .lod_bias(0.5)
+            .max_level(2)
+            .build()
+            .unwrap();
+        texture.bind();
+
+        let mut bias = 0.0;
+        let mut max_level = 0;
+        unsafe {
+            gl::GetTexParameterfv(texture.target, gl::TEXTURE_LOD_BIAS, &mut bias);
+            gl::GetTexParameteriv(texture.target, gl::TEXTURE_MAX_LEVEL, &mut max_level);
+        }
+        assert_eq!(bias, 0.5);
+        assert_eq!(max_level, 2);
+    }
+}
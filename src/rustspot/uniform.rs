@@ -0,0 +1,201 @@
+// Copyright © 2021
+// Author: Antonio Caggiano <info@antoniocaggiano.eu>
+// SPDX-License-Identifier: MIT
+
+use super::*;
+use nalgebra as na;
+
+/// Binding point of the `Camera` uniform block
+pub const CAMERA_BLOCK_BINDING: u32 = 0;
+/// Binding point of the `Lights` uniform block
+pub const LIGHTS_BLOCK_BINDING: u32 = 1;
+/// Size of the directional light arrays of the `Lights` uniform block
+pub const MAX_DIRECTIONAL_LIGHTS: usize = 4;
+
+/// Mirrors the `std140` layout of the `Camera` uniform block in shaders
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct CameraBlock {
+    pub view: [f32; 16],
+    pub proj: [f32; 16],
+    /// A vec3 is aligned to 16 bytes, hence the padding
+    pub cam_pos: [f32; 4],
+}
+
+impl CameraBlock {
+    pub fn new(camera: &Camera, camera_node: &Node) -> Self {
+        let mut block = Self {
+            view: [0.0; 16],
+            proj: [0.0; 16],
+            cam_pos: [0.0; 4],
+        };
+        block
+            .view
+            .copy_from_slice(camera_node.trs.get_view().as_slice());
+        block.proj.copy_from_slice(camera.proj.as_slice());
+        block.cam_pos[..3].copy_from_slice(camera_node.trs.get_translation().as_slice());
+        block
+    }
+
+    /// Camera data for views not coming from a node, such as the faces of a cubemap
+    pub fn from_matrices(
+        view: &na::Matrix4<f32>,
+        proj: &na::Matrix4<f32>,
+        cam_pos: &na::Vector3<f32>,
+    ) -> Self {
+        let mut block = Self {
+            view: [0.0; 16],
+            proj: [0.0; 16],
+            cam_pos: [0.0; 4],
+        };
+        block.view.copy_from_slice(view.as_slice());
+        block.proj.copy_from_slice(proj.as_slice());
+        block.cam_pos[..3].copy_from_slice(cam_pos.as_slice());
+        block
+    }
+}
+
+/// Mirrors the `std140` layout of the `Lights` uniform block in shaders
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct LightsBlock {
+    /// Transform to the space of the first light, the one casting shadows
+    pub light_space: [f32; 16],
+    pub light_colors: [[f32; 4]; MAX_DIRECTIONAL_LIGHTS],
+    /// Pointing towards the light sources
+    pub light_directions: [[f32; 4]; MAX_DIRECTIONAL_LIGHTS],
+    pub directional_light_count: i32,
+    /// Block size is rounded up to a multiple of a vec4
+    _padding: [i32; 3],
+}
+
+impl LightsBlock {
+    /// Creates a block without lights
+    pub fn new(light_space: &na::Matrix4<f32>) -> Self {
+        let mut block = Self {
+            light_space: [0.0; 16],
+            light_colors: [[0.0; 4]; MAX_DIRECTIONAL_LIGHTS],
+            light_directions: [[0.0; 4]; MAX_DIRECTIONAL_LIGHTS],
+            directional_light_count: 0,
+            _padding: [0; 3],
+        };
+        block.light_space.copy_from_slice(light_space.as_slice());
+        block
+    }
+
+    /// Adds a directional light, returning false when there is no room left for it
+    pub fn push_directional_light(&mut self, light_color: &[f32; 3], light_node: &Node) -> bool {
+        let index = self.directional_light_count as usize;
+        if index >= MAX_DIRECTIONAL_LIGHTS {
+            return false;
+        }
+
+        self.light_colors[index][..3].copy_from_slice(light_color);
+        let direction = -light_node.trs.get_forward();
+        self.light_directions[index][..3].copy_from_slice(direction.as_slice());
+        self.directional_light_count += 1;
+        true
+    }
+}
+
+/// Buffer backing a uniform block. Data uploaded here is shared by all the
+/// shader programs which bound the block to the same binding point.
+pub struct UniformBuffer {
+    handle: u32,
+    binding: u32,
+}
+
+impl UniformBuffer {
+    pub fn new(binding: u32) -> Self {
+        let mut handle = 0;
+        unsafe { gl::GenBuffers(1, &mut handle) };
+        Self { handle, binding }
+    }
+
+    pub fn get_handle(&self) -> u32 {
+        self.handle
+    }
+
+    pub fn get_binding(&self) -> u32 {
+        self.binding
+    }
+
+    /// Uploads a block of data and binds the buffer to its binding point
+    pub fn upload<T>(&self, block: &T) {
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.handle);
+            gl::BufferData(
+                gl::UNIFORM_BUFFER,
+                std::mem::size_of::<T>() as _,
+                block as *const T as _,
+                gl::DYNAMIC_DRAW,
+            );
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+        self.bind();
+    }
+
+    pub fn bind(&self) {
+        unsafe { gl::BindBufferBase(gl::UNIFORM_BUFFER, self.binding, self.handle) };
+    }
+}
+
+impl Drop for UniformBuffer {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteBuffers(1, &self.handle) };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn std140_sizes() {
+        assert_eq!(std::mem::size_of::<CameraBlock>(), 144);
+        assert_eq!(std::mem::size_of::<LightsBlock>(), 208);
+
+        let mut camera_node = Node::new();
+        camera_node.trs.translate(1.0, 2.0, 3.0);
+        let block = CameraBlock::new(&Camera::orthographic(2, 2, 0.1, 1.0), &camera_node);
+        assert_eq!(block.cam_pos, [1.0, 2.0, 3.0, 0.0]);
+        // Translation of the view matrix
+        assert_eq!(&block.view[12..15], &[-1.0, -2.0, -3.0]);
+    }
+
+    #[test]
+    fn directional_light_array() {
+        let mut block = LightsBlock::new(&na::Matrix4::identity());
+        assert_eq!(block.directional_light_count, 0);
+
+        for i in 0..MAX_DIRECTIONAL_LIGHTS {
+            assert!(block.push_directional_light(&[i as f32; 3], &Node::new()));
+        }
+        assert!(!block.push_directional_light(&[1.0; 3], &Node::new()));
+        assert_eq!(block.directional_light_count, MAX_DIRECTIONAL_LIGHTS as i32);
+        assert_eq!(block.light_colors[1], [1.0, 1.0, 1.0, 0.0]);
+        // Nodes look towards -Z, hence light comes from +Z
+        assert_eq!(block.light_directions[0], [0.0, 0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    #[cfg(feature = "headless")]
+    fn upload_and_bind() {
+        let _context = TestContext::new();
+
+        let buffer = UniformBuffer::new(LIGHTS_BLOCK_BINDING);
+        let mut block = LightsBlock::new(&na::Matrix4::identity());
+        block.push_directional_light(&[1.0; 3], &Node::new());
+        buffer.upload(&block);
+
+        let mut bound = 0;
+        let mut size = 0;
+        unsafe {
+            gl::GetIntegeri_v(gl::UNIFORM_BUFFER_BINDING, LIGHTS_BLOCK_BINDING, &mut bound);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, buffer.get_handle());
+            gl::GetBufferParameteriv(gl::UNIFORM_BUFFER, gl::BUFFER_SIZE, &mut size);
+        }
+        assert_eq!(bound as u32, buffer.get_handle());
+        assert_eq!(size as usize, std::mem::size_of::<LightsBlock>());
+    }
+}
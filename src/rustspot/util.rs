@@ -30,6 +30,25 @@ impl<T> Handle<T> {
     pub fn valid(&self) -> bool {
         self.id != std::usize::MAX
     }
+
+    /// Whether this is the `none` handle, the opposite of `valid`
+    pub fn is_none(&self) -> bool {
+        !self.valid()
+    }
+
+    /// Converts the `none` handle to `None`, and a valid handle to `Some`
+    pub fn to_option(self) -> Option<Self> {
+        if self.valid() {
+            Some(self)
+        } else {
+            None
+        }
+    }
+
+    /// Converts `None` to the `none` handle
+    pub fn from_option(handle: Option<Self>) -> Self {
+        handle.unwrap_or_else(Self::none)
+    }
 }
 
 impl<'a, T> Handle<T> {
@@ -66,6 +85,21 @@ impl<T> Pack<T> {
         }
     }
 
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            vec: Vec::with_capacity(capacity),
+            indices: Vec::with_capacity(capacity),
+            free: vec![],
+        }
+    }
+
+    /// Reserves capacity for at least `additional` more elements, to avoid reallocating
+    /// while pushing many of them
+    pub fn reserve(&mut self, additional: usize) {
+        self.vec.reserve(additional);
+        self.indices.reserve(additional);
+    }
+
     pub fn push(&mut self, elem: T) -> Handle<T> {
         let index = self.vec.len();
         self.vec.push(elem);
@@ -88,6 +122,11 @@ impl<T> Pack<T> {
         vec_index
     }
 
+    /// Whether the handle refers to an element of this pack, which has not been removed
+    pub fn contains(&self, handle: Handle<T>) -> bool {
+        handle.valid() && handle.id < self.indices.len() && !self.free.contains(&handle.id)
+    }
+
     pub fn get(&self, handle: Handle<T>) -> Option<&T> {
         if !handle.valid() {
             return None;
@@ -120,6 +159,15 @@ impl<T> Pack<T> {
         // Index of the removed element can be added to free list
         self.free.push(handle.id);
     }
+
+    /// Returns handles to the elements which have not been removed. Positions of
+    /// elements in the inner vector do not match handles once something is removed.
+    pub fn handles(&self) -> Vec<Handle<T>> {
+        (0..self.indices.len())
+            .filter(|id| !self.free.contains(id))
+            .map(Handle::new)
+            .collect()
+    }
 }
 
 impl<T> From<Vec<T>> for Pack<T> {
@@ -174,6 +222,29 @@ mod test {
         let thing = pack.push(Thing { val: 2 });
         assert_eq!(thing.get(&pack).unwrap().val, 2);
         assert_eq!(pack.get(thing).unwrap().val, 2);
+
+        assert!(pack.contains(thing));
+        assert!(!pack.contains(Handle::new(1)));
+        assert!(!pack.contains(Handle::none()));
+        pack.remove(thing);
+        assert!(!pack.contains(thing));
+    }
+
+    #[test]
+    fn option_conversion() {
+        let none = Handle::<Thing>::none();
+        assert!(none.is_none());
+        assert_eq!(none.id, usize::MAX);
+        assert!(none.to_option().is_none());
+        assert!(Handle::<Thing>::from_option(None).is_none());
+
+        let handle = Handle::<Thing>::new(1);
+        assert!(!handle.is_none());
+        assert_eq!(handle.to_option().unwrap().id, 1);
+        assert_eq!(Handle::from_option(Some(handle)).id, 1);
+
+        // Back and forth
+        assert!(Handle::from_option(none.to_option()).is_none());
     }
 
     #[test]
@@ -205,27 +276,127 @@ mod test {
         assert_eq!(handle.id, 0);
         assert_eq!(pack.get(handle).unwrap().val, 1);
     }
+
+    #[test]
+    fn capacity() {
+        let mut pack = Pack::<Thing>::with_capacity(16);
+        assert!(pack.capacity() >= 16);
+        assert_eq!(pack.len(), 0);
+
+        pack.reserve(32);
+        assert!(pack.capacity() >= 32);
+        assert_eq!(pack.len(), 0);
+    }
+
+    #[test]
+    fn live_handles() {
+        let mut pack: Pack<Thing> = (0..4).map(|val| Thing { val }).collect();
+        pack.remove(Handle::new(1));
+
+        let handles = pack.handles();
+        let ids: Vec<usize> = handles.iter().map(|h| h.id).collect();
+        assert_eq!(ids, vec![0, 2, 3]);
+        for handle in handles {
+            assert_eq!(pack.get(handle).unwrap().val, handle.id as u32);
+        }
+
+        let handle = pack.push(Thing { val: 1 });
+        assert_eq!(handle.id, 1);
+        assert_eq!(pack.handles().len(), 4);
+    }
+
+    #[test]
+    fn scaled_timer() {
+        let mut timer = Timer::new();
+        let pause = Duration::from_millis(2);
+
+        timer.pause();
+        std::thread::sleep(pause);
+        assert_eq!(timer.get_delta(), Duration::from_secs(0));
+        assert!(timer.get_real_delta() >= pause);
+
+        timer.resume();
+        timer.set_scale(0.5);
+        std::thread::sleep(pause);
+        let delta = timer.get_delta();
+        assert_eq!(delta, timer.get_real_delta().mul_f32(0.5));
+        assert!(delta >= pause / 2);
+
+        // Real time keeps going while paused
+        assert!(timer.get_real_time() >= pause * 2);
+    }
 }
 
 /// Useful timer to get delta time, and previous time for ImGui
 pub struct Timer {
     prev: Instant,
     curr: Instant,
+    /// Multiplies deltas, slowing down or speeding up time
+    scale: f32,
+    paused: bool,
+    /// Delta of last `get_delta()` before scaling
+    real_delta: Duration,
+    /// Time passed since creation, whether paused or not
+    real_time: Duration,
 }
 
 impl Timer {
     pub fn new() -> Self {
         let prev = Instant::now();
         let curr = Instant::now();
-        Self { prev, curr }
+        Self {
+            prev,
+            curr,
+            scale: 1.0,
+            paused: false,
+            real_delta: Duration::from_secs(0),
+            real_time: Duration::from_secs(0),
+        }
     }
 
-    /// Returns delta time in seconds
+    /// Returns delta time since last call, scaled, or zero when paused
     pub fn get_delta(&mut self) -> Duration {
         self.curr = Instant::now();
-        let delta = self.curr - self.prev;
+        self.real_delta = self.curr - self.prev;
+        self.real_time += self.real_delta;
         self.prev = self.curr;
-        delta
+
+        if self.paused {
+            Duration::from_secs(0)
+        } else {
+            self.real_delta.mul_f32(self.scale)
+        }
+    }
+
+    /// Returns the delta of last `get_delta()`, ignoring scale and pause
+    pub fn get_real_delta(&self) -> Duration {
+        self.real_delta
+    }
+
+    /// Returns the time passed up to last `get_delta()`, ignoring scale and pause
+    pub fn get_real_time(&self) -> Duration {
+        self.real_time
+    }
+
+    /// A scale of 0.5 makes time go at half speed
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale.max(0.0);
+    }
+
+    pub fn get_scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
     }
 
     /// Returns the time of last `get_delta()`
@@ -250,7 +421,7 @@ impl<'a> ScopedTimer<'a> {
 
 impl<'a> Drop for ScopedTimer<'a> {
     fn drop(&mut self) {
-        println!(
+        log::debug!(
             "{} ({}s)",
             self.message,
             self.timer.get_delta().as_secs_f32()